@@ -1,8 +1,10 @@
 use lc3_emulator::emulator;
+use lc3_emulator::lc3_program;
 use std::error::Error;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let mut emu =
-        emulator::from_program("examples/hello_world_putsp.obj").map_err(Box::<dyn Error>::from)?;
-    emu.execute().map_err(Box::<dyn Error>::from)
+    let mut emu = emulator::from_static_image(lc3_program!("hello_world_putsp.obj"))
+        .map_err(Box::<dyn Error>::from)?;
+    emu.execute().map_err(Box::<dyn Error>::from)?;
+    Ok(())
 }
@@ -0,0 +1,33 @@
+//! Compares creating emulators by loading the object file each time against using an
+//! `ImageCache`, run with `cargo run --release --example image_cache_bench`.
+use lc3_emulator::emulator;
+use lc3_emulator::emulator::image_cache::ImageCache;
+use std::error::Error;
+use std::hint::black_box;
+use std::time::Instant;
+
+const ITERATIONS: u32 = 2_000;
+const PROGRAM: &str = "examples/rogue.obj";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(emulator::from_program(PROGRAM).map_err(Box::<dyn Error>::from)?);
+    }
+    let from_file = start.elapsed() / ITERATIONS;
+
+    let cache = ImageCache::from_program(PROGRAM).map_err(Box::<dyn Error>::from)?;
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(cache.instantiate());
+    }
+    let from_cache = start.elapsed() / ITERATIONS;
+
+    println!("from_program:            {from_file:?} per emulator");
+    println!("ImageCache::instantiate: {from_cache:?} per emulator");
+    println!(
+        "speedup: {:.1}x",
+        from_file.as_secs_f64() / from_cache.as_secs_f64()
+    );
+    Ok(())
+}
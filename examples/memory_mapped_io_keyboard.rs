@@ -6,5 +6,6 @@ fn main() -> Result<(), Box<dyn Error>> {
         .map_err(Box::<dyn Error>::from)?;
     emu.execute().map_err(Box::<dyn Error>::from)?;
     emu.reset_registers();
-    emu.execute().map_err(Box::<dyn Error>::from)
+    emu.execute().map_err(Box::<dyn Error>::from)?;
+    Ok(())
 }
@@ -0,0 +1,42 @@
+//! Stable, machine-facing API of this crate.
+//!
+//! Everything reachable through this module follows semantic versioning, while the other public
+//! modules may still change between minor versions as the emulator evolves.
+//! Prefer importing from here when embedding the emulator into other projects.
+//!
+//! It covers loading, running and inspecting programs. The types of specialised features like
+//! tracing, coverage or the debugger are available from their modules, f.e.
+//! [`emulator::trace`](crate::emulator::trace), and may still change.
+//!
+//! # Example
+//! ```
+//! use lc3_emulator::api::{self, StdoutForDocTest};
+//! use std::error::Error;
+//!
+//! fn main() -> Result<(), Box<dyn Error>> {
+//!     let mut emu = api::from_program("examples/times_ten.obj")?;
+//!     emu.execute_with_stdout(&mut StdoutForDocTest::new())?;
+//!     assert_eq!(30, emu.state().register(3).as_decimal());
+//!     Ok(())
+//! }
+//! ```
+
+pub use crate::assembler::{AssembledProgram, assemble};
+pub use crate::emulator::snapshot::MachineState;
+pub use crate::emulator::stdout_helpers::CrosstermCompatibility;
+pub use crate::emulator::{
+    CancelToken, Emulator, EmulatorBuilder, ExitStatus, HaltReason, StepResult, from_bytes,
+    from_program, from_program_with_chaos_input, from_static_image,
+};
+pub use crate::errors::{
+    AssembleError, AssembleProgramError, ExecutionError, LoadProgramError, StateAccessError,
+};
+pub use crate::hardware::keyboard::{
+    ChaosInputProvider, KeyboardInputProvider, ScriptedInputProvider,
+};
+pub use crate::hardware::memory::EmulatorConfig;
+pub use crate::hardware::registers::{ConditionFlag, Register, Registers};
+pub use crate::lc3_program;
+
+#[doc(hidden)]
+pub use crate::emulator::stdout_helpers::StdoutForDocTest;
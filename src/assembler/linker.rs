@@ -0,0 +1,476 @@
+//! Linking of several relocatable modules of assembly source into a single program image.
+//!
+//! Modules contain no `.ORIG` and are placed one after the other in the given order.
+//! Labels are local to their module unless exported with `.GLOBAL LABEL`, other modules
+//! use them after declaring `.EXTERNAL LABEL`.
+//! Besides instructions and `.FILL`, modules may contain `.BLKW`, `.STRINGZ` and `.END`.
+//! Strings may contain the escapes `\n`, `\t`, `\e`, `\0`, `\\` and `\"`.
+use crate::assembler::{AssembledProgram, assemble_line, is_label, parse_number};
+use crate::errors::{AssembleError, AssembleProgramError, LinkError};
+use crate::symbols::SymbolTable;
+use std::collections::HashMap;
+
+const MNEMONICS: [&str; 22] = [
+    "ADD", "AND", "NOT", "JMP", "RET", "JSR", "JSRR", "LD", "LDI", "LEA", "ST", "STI", "LDR",
+    "STR", "RTI", "TRAP", "GETC", "OUT", "PUTS", "IN", "PUTSP", "HALT",
+];
+
+/// Content of a module line, either an instruction to assemble or already encoded data.
+enum Item<'a> {
+    Code(&'a str),
+    Words(Vec<u16>),
+}
+
+/// A module after the first pass, addresses are offsets relative to the module start.
+#[derive(Default)]
+struct Module<'a> {
+    /// Line number, offset and content of lines occupying memory
+    items: Vec<(usize, u16, Item<'a>)>,
+    labels: HashMap<&'a str, u16>,
+    globals: Vec<(usize, &'a str)>,
+    externals: Vec<&'a str>,
+    len: u16,
+    /// Line and operand of a `.ORIG` at the start, only allowed in a program assembled alone
+    origin: Option<(usize, &'a str)>,
+}
+
+/// Links the assembly source `modules` into words starting with the `.ORIG` address `origin`
+/// like an `.obj` file, see [`from_bytes`](crate::emulator::from_bytes).
+///
+/// # Errors
+/// - See [`LinkError`]
+pub fn link(modules: &[&str], origin: u16) -> Result<Vec<u16>, LinkError> {
+    link_program(modules, origin).map(|program| program.words)
+}
+
+/// Links the assembly source `modules` like [`link`] together with the addresses of their
+/// labels.
+///
+/// A global label takes precedence over local labels of the same name in other modules, of
+/// those the one of the last module is kept.
+///
+/// # Errors
+/// - See [`LinkError`]
+pub fn link_program(modules: &[&str], origin: u16) -> Result<AssembledProgram, LinkError> {
+    let modules = (0..)
+        .zip(modules)
+        .map(|(idx, source)| parse_module(idx, source))
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some((idx, line)) = (0..)
+        .zip(&modules)
+        .find_map(|(idx, module)| module.origin.map(|(line, _)| (idx, line)))
+    {
+        return Err(LinkError::OrigInModule { module: idx, line });
+    }
+    let mut bases = Vec::with_capacity(modules.len());
+    let mut next = origin;
+    for module in &modules {
+        bases.push(next);
+        next = next
+            .checked_add(module.len)
+            .ok_or(LinkError::ImageTooLarge)?;
+    }
+    let mut globals = HashMap::new();
+    for (idx, (module, base)) in (0..).zip(modules.iter().zip(&bases)) {
+        for (line, name) in &module.globals {
+            let offset = module
+                .labels
+                .get(name)
+                .ok_or_else(|| LinkError::UndefinedGlobal {
+                    module: idx,
+                    line: *line,
+                    name: (*name).to_owned(),
+                })?;
+            if globals.insert(*name, base + offset).is_some() {
+                return Err(LinkError::DuplicateGlobal((*name).to_owned()));
+            }
+        }
+    }
+    for (idx, module) in (0..).zip(&modules) {
+        if let Some(name) = module.externals.iter().find(|n| !globals.contains_key(*n)) {
+            return Err(LinkError::UndefinedExternal {
+                module: idx,
+                name: (*name).to_owned(),
+            });
+        }
+    }
+    let mut words = vec![origin];
+    let mut symbols = SymbolTable::default();
+    for (idx, (module, base)) in (0..).zip(modules.iter().zip(bases)) {
+        module.emit(idx, base, &|label| globals.get(label).copied(), &mut words)?;
+        for (name, offset) in &module.labels {
+            symbols.insert(name, base + offset);
+        }
+    }
+    for (name, address) in &globals {
+        symbols.insert(name, *address);
+    }
+    Ok(AssembledProgram { words, symbols })
+}
+
+/// Splits off the module `error` occurred in, with its line and column in that module like
+/// [`assemble`](crate::assembler::assemble) reports them, f.e. to prefix it with the file
+/// name of the module.
+///
+/// # Errors
+/// - `error` itself if it does not refer to a line of a module
+pub fn locate_error(
+    modules: &[&str],
+    error: LinkError,
+) -> Result<(usize, AssembleProgramError), LinkError> {
+    match error {
+        LinkError::Assemble { module, .. } | LinkError::OrigInModule { module, .. } => {
+            let source = modules.get(module).copied().unwrap_or_default();
+            Ok((module, program_error(source, error)))
+        }
+        _ => Err(error),
+    }
+}
+
+/// Assembles the source of a program starting with `.ORIG`, see
+/// [`assemble`](crate::assembler::assemble).
+pub(crate) fn assemble_program(source: &str) -> Result<AssembledProgram, AssembleProgramError> {
+    let to_program_error = |error| program_error(source, error);
+    let module = parse_module(0, source).map_err(to_program_error)?;
+    let (line, operand) = module.origin.ok_or(AssembleProgramError::MissingOrig)?;
+    let origin = parse_number(operand)
+        .ok()
+        .and_then(|n| u16::try_from(n).ok())
+        .ok_or_else(|| {
+            to_program_error(LinkError::Assemble {
+                module: 0,
+                line,
+                error: AssembleError::InvalidNumber(operand.to_owned()),
+            })
+        })?;
+    if origin.checked_add(module.len).is_none() {
+        return Err(AssembleProgramError::ImageTooLarge);
+    }
+    let mut words = vec![origin];
+    module
+        .emit(0, origin, &|_| None, &mut words)
+        .map_err(to_program_error)?;
+    let mut symbols = SymbolTable::default();
+    for (name, offset) in &module.labels {
+        symbols.insert(name, origin + offset);
+    }
+    Ok(AssembledProgram { words, symbols })
+}
+
+/// Converts an error of the single module `source` adding the column of the offending token.
+fn program_error(source: &str, error: LinkError) -> AssembleProgramError {
+    match error {
+        LinkError::Assemble { line, error, .. } => {
+            let text = source.lines().nth(line - 1).unwrap_or_default();
+            AssembleProgramError::Assemble {
+                line,
+                column: column(strip_comment(text), &error),
+                error,
+            }
+        }
+        LinkError::OrigInModule { line, .. } => AssembleProgramError::MisplacedOrig { line },
+        _ => AssembleProgramError::ImageTooLarge,
+    }
+}
+
+/// 1-based column of the token `error` refers to in `code`, or of the first non-whitespace
+/// character if the token cannot be found.
+fn column(code: &str, error: &AssembleError) -> usize {
+    let token = match error {
+        AssembleError::UnknownMnemonic(token)
+        | AssembleError::InvalidOperand(token)
+        | AssembleError::InvalidNumber(token)
+        | AssembleError::RegisterExpected(token)
+        | AssembleError::NumberExpected(token)
+        | AssembleError::UnknownLabel(token)
+        | AssembleError::DuplicateLabel(token) => Some(token.as_str()),
+        _ => None,
+    };
+    let start = code.len() - code.trim_start().len();
+    let idx = token
+        .filter(|token| !token.is_empty())
+        .and_then(|token| code[start..].find(token).map(|idx| start + idx))
+        .unwrap_or(start);
+    code[..idx].chars().count() + 1
+}
+
+impl Module<'_> {
+    /// Appends the words of the module placed at `base` to `words`, resolving its external
+    /// labels via `resolve_external`.
+    fn emit(
+        &self,
+        idx: usize,
+        base: u16,
+        resolve_external: &dyn Fn(&str) -> Option<u16>,
+        words: &mut Vec<u16>,
+    ) -> Result<(), LinkError> {
+        let resolve = |label: &str| {
+            self.labels
+                .get(label)
+                .map(|offset| base + offset)
+                .or_else(|| {
+                    self.externals
+                        .contains(&label)
+                        .then(|| resolve_external(label))
+                        .flatten()
+                })
+        };
+        for (line, offset, item) in &self.items {
+            match item {
+                Item::Code(code) => words.push(
+                    assemble_line(code, base + offset, &resolve).map_err(|error| {
+                        LinkError::Assemble {
+                            module: idx,
+                            line: *line,
+                            error,
+                        }
+                    })?,
+                ),
+                Item::Words(data) => words.extend(data),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_module(idx: usize, source: &str) -> Result<Module<'_>, LinkError> {
+    let mut module = Module::default();
+    for (line, text) in (1..).zip(source.lines()) {
+        let err = |error| LinkError::Assemble {
+            module: idx,
+            line,
+            error,
+        };
+        let code = strip_comment(text).trim();
+        let (first, rest) = split_first_token(code);
+        let code = match first {
+            "" => continue,
+            _ if is_mnemonic(first) => code,
+            _ if is_label(first) => {
+                if module.labels.insert(first, module.len).is_some() {
+                    return Err(err(AssembleError::DuplicateLabel(first.to_owned())));
+                }
+                rest
+            }
+            _ => return Err(err(AssembleError::UnknownMnemonic(first.to_owned()))),
+        };
+        let (directive, operand) = split_first_token(code);
+        let item = match directive.to_ascii_uppercase().as_str() {
+            "" => continue,
+            ".END" => break,
+            ".ORIG" if module.origin.is_none() && module.labels.is_empty() && module.len == 0 => {
+                module.origin = Some((line, operand));
+                continue;
+            }
+            ".ORIG" => return Err(LinkError::OrigInModule { module: idx, line }),
+            ".GLOBAL" | ".EXTERNAL" if !is_label(operand) => {
+                return Err(err(AssembleError::InvalidOperand(operand.to_owned())));
+            }
+            ".GLOBAL" => {
+                module.globals.push((line, operand));
+                continue;
+            }
+            ".EXTERNAL" => {
+                module.externals.push(operand);
+                continue;
+            }
+            ".BLKW" => {
+                let count = parse_number(operand)
+                    .ok()
+                    .and_then(|n| usize::try_from(n).ok())
+                    .ok_or_else(|| err(AssembleError::InvalidNumber(operand.to_owned())))?;
+                Item::Words(vec![0; count])
+            }
+            ".STRINGZ" => Item::Words(parse_string(operand).map_err(err)?),
+            _ => Item::Code(code),
+        };
+        let len = match &item {
+            Item::Code(_) => Some(1),
+            Item::Words(words) => u16::try_from(words.len()).ok(),
+        };
+        module.items.push((line, module.len, item));
+        module.len = len
+            .and_then(|len| module.len.checked_add(len))
+            .ok_or(LinkError::ImageTooLarge)?;
+    }
+    Ok(module)
+}
+
+fn is_mnemonic(token: &str) -> bool {
+    let upper = token.to_ascii_uppercase();
+    upper.starts_with('.')
+        || MNEMONICS.contains(&upper.as_str())
+        || upper
+            .strip_prefix("BR")
+            .is_some_and(|conditions| conditions.chars().all(|c| "NZP".contains(c)))
+}
+
+fn split_first_token(code: &str) -> (&str, &str) {
+    code.split_once(char::is_whitespace)
+        .map_or((code, ""), |(first, rest)| (first, rest.trim()))
+}
+
+/// `line` without its comment, semicolons in strings do not start one.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (idx, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &line[..idx],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Words of the zero terminated ASCII string literal `text`.
+fn parse_string(text: &str) -> Result<Vec<u16>, AssembleError> {
+    let invalid = || AssembleError::InvalidOperand(text.to_owned());
+    let content = text
+        .strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .ok_or_else(invalid)?;
+    let mut words = Vec::with_capacity(content.len() + 1);
+    let mut chars = content.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                // like lc3as, f.e. for ANSI escape sequences
+                Some('e') => '\u{1b}',
+                Some('0') => '\0',
+                Some(c @ ('\\' | '"')) => c,
+                _ => return Err(invalid()),
+            },
+            '"' => return Err(invalid()),
+            c => c,
+        };
+        if !c.is_ascii() {
+            return Err(invalid());
+        }
+        words.push(u16::from(u8::try_from(c).map_err(|_| invalid())?));
+    }
+    words.push(0);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator;
+    use crate::emulator::test_helpers::StringWriter;
+    use googletest::prelude::*;
+
+    const MAIN: &str = r#"
+        .EXTERNAL TIMES_TEN
+        .EXTERNAL RESULT
+        LD R0, THREE
+        JSR TIMES_TEN
+        ST R3, RESULT       ; stored in the other module
+        LEA R0, DONE
+        PUTS
+        HALT
+THREE   .FILL 3
+DONE    .STRINGZ "done; \"linked\"\n"
+        .END
+"#;
+
+    const LIB: &str = "
+        .GLOBAL TIMES_TEN
+        .GLOBAL RESULT
+TIMES_TEN
+        AND R3, R3, #0
+        LD R2, TEN
+LOOP    ADD R3, R3, R0
+        ADD R2, R2, #-1
+        BRp LOOP
+        RET
+TEN     .FILL #10
+RESULT  .BLKW 1
+";
+
+    #[gtest]
+    pub fn test_link() {
+        let words = link(&[MAIN, LIB], 0x3000).unwrap();
+        let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_be_bytes()).collect();
+        let mut emu = emulator::from_bytes(&bytes).unwrap();
+        let mut stdout = StringWriter::new();
+        emu.execute_with_stdout(&mut stdout).unwrap();
+        expect_that!(stdout.get_string(), starts_with("done; \"linked\""));
+        expect_that!(emu.registers().get(3).as_decimal(), eq(30));
+        let result = 0x3000 + u16::try_from(words.len() - 2).unwrap();
+        expect_that!(emu.memory()[result], eq(30));
+        let symbols = link_program(&[MAIN, LIB], 0x3000).unwrap().symbols;
+        expect_that!(symbols.address_of("THREE"), some(eq(0x3006)));
+        expect_that!(symbols.address_of("RESULT"), some(eq(result)));
+    }
+
+    #[gtest]
+    pub fn test_link_errors() {
+        expect_that!(
+            link(&[MAIN], 0x3000),
+            err(eq(&LinkError::UndefinedExternal {
+                module: 0,
+                name: "TIMES_TEN".to_owned()
+            }))
+        );
+        expect_that!(
+            link(&[MAIN, LIB, LIB], 0x3000),
+            err(eq(&LinkError::DuplicateGlobal("TIMES_TEN".to_owned())))
+        );
+        expect_that!(
+            link(&["  .GLOBAL MISSING"], 0x3000),
+            err(eq(&LinkError::UndefinedGlobal {
+                module: 0,
+                line: 1,
+                name: "MISSING".to_owned()
+            }))
+        );
+        expect_that!(
+            link(&["HALT", ".ORIG x3000"], 0x3000),
+            err(eq(&LinkError::OrigInModule { module: 1, line: 1 }))
+        );
+        // labels of other modules are not visible without .EXTERNAL
+        expect_that!(
+            link(&["JSR TIMES_TEN", LIB], 0x3000),
+            err(eq(&LinkError::Assemble {
+                module: 0,
+                line: 1,
+                error: AssembleError::UnknownLabel("TIMES_TEN".to_owned())
+            }))
+        );
+    }
+
+    #[gtest]
+    pub fn test_parse_string_escapes() {
+        expect_that!(
+            parse_string(r#""\e[0m\n""#),
+            ok(eq(&[0x1B, 0x5B, 0x30, 0x6D, 0x0A, 0x00]))
+        );
+        expect_that!(parse_string(r#""\x""#), err(anything()));
+    }
+
+    #[gtest]
+    pub fn test_locate_error() {
+        let modules = ["HALT", "  JSR NOWHERE"];
+        expect_that!(
+            locate_error(&modules, link(&modules, 0x3000).unwrap_err()),
+            ok(eq(&(
+                1,
+                AssembleProgramError::Assemble {
+                    line: 1,
+                    column: 7,
+                    error: AssembleError::UnknownLabel("NOWHERE".to_owned())
+                }
+            )))
+        );
+        expect_that!(
+            locate_error(&modules, LinkError::ImageTooLarge),
+            err(eq(&LinkError::ImageTooLarge))
+        );
+    }
+}
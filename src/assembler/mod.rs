@@ -0,0 +1,426 @@
+//! Assembler for LC-3 assembly source.
+//!
+//! Syntax follows `lc3as`: mnemonics and registers are case-insensitive, numbers are written
+//! as `#` decimal or `x` hexadecimal, comments start with `;`.
+//! Numeric operands of PC-relative instructions like `BR` or `LD` are offsets, not addresses.
+pub mod linker;
+
+use crate::errors::{AssembleError, AssembleProgramError};
+use crate::symbols::SymbolTable;
+
+/// An operand of an instruction before encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Operand {
+    Register(u8),
+    Number(i32),
+    /// Hexadecimal number, which like in `lc3as` may also give the bits of a signed field
+    Hex(i32),
+    Label(String),
+}
+
+/// A program assembled from source, the counterpart of an `.obj` and its `.sym` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssembledProgram {
+    /// Words starting with the `.ORIG` address like an `.obj` file
+    pub words: Vec<u16>,
+    /// Addresses of all labels
+    pub symbols: SymbolTable,
+}
+
+impl AssembledProgram {
+    /// Contents of the `.obj` file, the words in big-endian byte order.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.words.iter().flat_map(|w| w.to_be_bytes()).collect()
+    }
+}
+
+/// Assembles the source of a program starting with `.ORIG`.
+///
+/// Besides instructions and `.FILL`, programs may contain `.BLKW`, `.STRINGZ` and `.END`.
+///
+/// # Errors
+/// - See [`AssembleProgramError`], errors in lines contain the line and column
+pub fn assemble(source: &str) -> Result<AssembledProgram, AssembleProgramError> {
+    linker::assemble_program(source)
+}
+
+/// Assembles a single line of assembly to the word placed at `address`.
+///
+/// Labels cannot be resolved in a single line, thus PC-relative operands must be numeric.
+///
+/// # Errors
+/// - See [`AssembleError`]
+pub fn assemble_instruction(line: &str, address: u16) -> Result<u16, AssembleError> {
+    assemble_line(line, address, &|_| None)
+}
+
+/// Assembles a single line resolving labels with `resolve_label`.
+pub(crate) fn assemble_line(
+    line: &str,
+    address: u16,
+    resolve_label: &dyn Fn(&str) -> Option<u16>,
+) -> Result<u16, AssembleError> {
+    let code = line.split(';').next().unwrap_or_default().trim();
+    let (mnemonic, rest) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+    if mnemonic.is_empty() {
+        return Err(AssembleError::EmptyLine);
+    }
+    let operands = parse_operands(rest)?;
+    let ctx = Context {
+        mnemonic,
+        operands: &operands,
+        address,
+        resolve_label,
+    };
+    ctx.encode()
+}
+
+fn parse_operands(text: &str) -> Result<Vec<Operand>, AssembleError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+    text.split(',').map(|o| parse_operand(o.trim())).collect()
+}
+
+fn parse_operand(text: &str) -> Result<Operand, AssembleError> {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some('r' | 'R'), Some(d @ '0'..='7'), None) => Ok(Operand::Register(
+            u8::try_from(d.to_digit(10).expect("matched digit")).expect("single digit"),
+        )),
+        (Some('#' | '-' | '0'..='9'), _, _) => parse_number(text).map(Operand::Number),
+        // hexadecimal number or a label starting with x
+        (Some('x' | 'X'), _, _) => parse_number(text)
+            .map(Operand::Hex)
+            .or_else(|_| parse_label(text)),
+        _ => parse_label(text),
+    }
+}
+
+fn parse_label(text: &str) -> Result<Operand, AssembleError> {
+    if is_label(text) {
+        Ok(Operand::Label(text.to_owned()))
+    } else {
+        Err(AssembleError::InvalidOperand(text.to_owned()))
+    }
+}
+
+pub(crate) fn is_label(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parses `#-12`, `x3000`, `0x3000` and plain decimal numbers.
+pub(crate) fn parse_number(text: &str) -> Result<i32, AssembleError> {
+    let invalid = || AssembleError::InvalidNumber(text.to_owned());
+    let unprefixed = text.strip_prefix('#').unwrap_or(text);
+    let (negative, digits) = unprefixed
+        .strip_prefix('-')
+        .map_or((false, unprefixed), |digits| (true, digits));
+    let value = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix(['x', 'X']))
+    {
+        i32::from_str_radix(hex, 16).map_err(|_| invalid())?
+    } else {
+        digits.parse::<i32>().map_err(|_| invalid())?
+    };
+    if value > 0xFFFF {
+        return Err(invalid());
+    }
+    Ok(if negative { -value } else { value })
+}
+
+/// Converts `value` to a word, negative values to their two's complement.
+pub(crate) fn to_word(value: i32) -> Result<u16, AssembleError> {
+    if (-0x8000..=0xFFFF).contains(&value) {
+        #[expect(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            reason = "value range checked, negative values as two's complement"
+        )]
+        Ok(value as u16)
+    } else {
+        Err(AssembleError::ValueOutOfRange { value, bits: 16 })
+    }
+}
+
+struct Context<'a> {
+    mnemonic: &'a str,
+    operands: &'a [Operand],
+    address: u16,
+    resolve_label: &'a dyn Fn(&str) -> Option<u16>,
+}
+
+impl Context<'_> {
+    fn encode(&self) -> Result<u16, AssembleError> {
+        let upper = self.mnemonic.to_ascii_uppercase();
+        match upper.as_str() {
+            "ADD" => self.arithmetic(0b0001),
+            "AND" => self.arithmetic(0b0101),
+            "NOT" => {
+                self.expect_operands(2)?;
+                Ok(0b1001 << 12 | self.reg(0)? << 9 | self.reg(1)? << 6 | 0b11_1111)
+            }
+            "JMP" => {
+                self.expect_operands(1)?;
+                Ok(0b1100 << 12 | self.reg(0)? << 6)
+            }
+            "RET" => {
+                self.expect_operands(0)?;
+                Ok(0b1100_0001_1100_0000)
+            }
+            "JSR" => {
+                self.expect_operands(1)?;
+                Ok(0b0100 << 12 | 1 << 11 | self.pc_offset(0, 11)?)
+            }
+            "JSRR" => {
+                self.expect_operands(1)?;
+                Ok(0b0100 << 12 | self.reg(0)? << 6)
+            }
+            "LD" => self.reg_pc_offset(0b0010),
+            "LDI" => self.reg_pc_offset(0b1010),
+            "LEA" => self.reg_pc_offset(0b1110),
+            "ST" => self.reg_pc_offset(0b0011),
+            "STI" => self.reg_pc_offset(0b1011),
+            "LDR" => self.base_offset(0b0110),
+            "STR" => self.base_offset(0b0111),
+            "RTI" => {
+                self.expect_operands(0)?;
+                Ok(0b1000 << 12)
+            }
+            "TRAP" => {
+                self.expect_operands(1)?;
+                Ok(0b1111 << 12 | self.unsigned(0, 8)?)
+            }
+            "GETC" => self.trap_alias(0x20),
+            "OUT" => self.trap_alias(0x21),
+            "PUTS" => self.trap_alias(0x22),
+            "IN" => self.trap_alias(0x23),
+            "PUTSP" => self.trap_alias(0x24),
+            "HALT" => self.trap_alias(0x25),
+            ".FILL" => {
+                self.expect_operands(1)?;
+                self.fill_value(0)
+            }
+            m if m.starts_with("BR") => self.br(&m[2..]),
+            _ => Err(AssembleError::UnknownMnemonic(self.mnemonic.to_owned())),
+        }
+    }
+
+    fn expect_operands(&self, expected: usize) -> Result<(), AssembleError> {
+        if self.operands.len() == expected {
+            Ok(())
+        } else {
+            Err(AssembleError::WrongOperandCount {
+                mnemonic: self.mnemonic.to_owned(),
+                expected,
+                actual: self.operands.len(),
+            })
+        }
+    }
+
+    fn reg(&self, idx: usize) -> Result<u16, AssembleError> {
+        match &self.operands[idx] {
+            Operand::Register(r) => Ok(u16::from(*r)),
+            o => Err(AssembleError::RegisterExpected(format!("{o:?}"))),
+        }
+    }
+
+    fn number(&self, idx: usize) -> Result<i32, AssembleError> {
+        match &self.operands[idx] {
+            Operand::Number(n) | Operand::Hex(n) => Ok(*n),
+            Operand::Label(l) => (self.resolve_label)(l)
+                .map(i32::from)
+                .ok_or_else(|| AssembleError::UnknownLabel(l.clone())),
+            o @ Operand::Register(_) => Err(AssembleError::NumberExpected(format!("{o:?}"))),
+        }
+    }
+
+    /// Signed value fitting into `bits`, returned as masked field.
+    fn signed(value: i32, bits: u8) -> Result<u16, AssembleError> {
+        let limit = 1i32 << (bits - 1);
+        if (-limit..limit).contains(&value) {
+            #[expect(
+                clippy::cast_possible_truncation,
+                clippy::cast_sign_loss,
+                reason = "value range checked, masking the two's complement representation"
+            )]
+            Ok((value as u16) & ((1 << bits) - 1))
+        } else {
+            Err(AssembleError::ValueOutOfRange { value, bits })
+        }
+    }
+
+    fn unsigned(&self, idx: usize, bits: u8) -> Result<u16, AssembleError> {
+        let value = self.number(idx)?;
+        if (0..(1i32 << bits)).contains(&value) {
+            Ok(u16::try_from(value).expect("range checked"))
+        } else {
+            Err(AssembleError::ValueOutOfRange { value, bits })
+        }
+    }
+
+    /// Offset to the next PC, either given numerically or as label.
+    fn pc_offset(&self, idx: usize, bits: u8) -> Result<u16, AssembleError> {
+        match &self.operands[idx] {
+            Operand::Label(_) => {
+                Self::signed(self.number(idx)? - (i32::from(self.address) + 1), bits)
+            }
+            _ => self.signed_field(idx, bits),
+        }
+    }
+
+    /// Signed numeric operand, hexadecimal numbers may also give the bits of the field, f.e.
+    /// `x1F` for `#-1` in 5 bits.
+    fn signed_field(&self, idx: usize, bits: u8) -> Result<u16, AssembleError> {
+        match &self.operands[idx] {
+            Operand::Hex(n) if (0..(1i32 << bits)).contains(n) => {
+                Ok(u16::try_from(*n).expect("range checked"))
+            }
+            _ => Self::signed(self.number(idx)?, bits),
+        }
+    }
+
+    fn fill_value(&self, idx: usize) -> Result<u16, AssembleError> {
+        to_word(self.number(idx)?)
+    }
+
+    fn arithmetic(&self, op_code: u16) -> Result<u16, AssembleError> {
+        self.expect_operands(3)?;
+        let base = op_code << 12 | self.reg(0)? << 9 | self.reg(1)? << 6;
+        match &self.operands[2] {
+            Operand::Register(r) => Ok(base | u16::from(*r)),
+            _ => Ok(base | 1 << 5 | self.signed_field(2, 5)?),
+        }
+    }
+
+    fn reg_pc_offset(&self, op_code: u16) -> Result<u16, AssembleError> {
+        self.expect_operands(2)?;
+        Ok(op_code << 12 | self.reg(0)? << 9 | self.pc_offset(1, 9)?)
+    }
+
+    fn base_offset(&self, op_code: u16) -> Result<u16, AssembleError> {
+        self.expect_operands(3)?;
+        Ok(op_code << 12 | self.reg(0)? << 9 | self.reg(1)? << 6 | self.signed_field(2, 6)?)
+    }
+
+    fn trap_alias(&self, vector: u16) -> Result<u16, AssembleError> {
+        self.expect_operands(0)?;
+        Ok(0b1111 << 12 | vector)
+    }
+
+    fn br(&self, conditions: &str) -> Result<u16, AssembleError> {
+        let mut nzp = 0u16;
+        for c in conditions.chars() {
+            let bit = match c {
+                'N' => 0b100,
+                'Z' => 0b010,
+                'P' => 0b001,
+                _ => return Err(AssembleError::UnknownMnemonic(self.mnemonic.to_owned())),
+            };
+            if nzp & bit != 0 {
+                return Err(AssembleError::UnknownMnemonic(self.mnemonic.to_owned()));
+            }
+            nzp |= bit;
+        }
+        if nzp == 0 {
+            // plain BR is unconditional
+            nzp = 0b111;
+        }
+        self.expect_operands(1)?;
+        Ok(nzp << 9 | self.pc_offset(0, 9)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+    use yare::parameterized;
+
+    #[parameterized(
+        ld = { "LD R0, #6", 0x2006 },
+        ld_hex = { "ld r3, x7", 0x2607 },
+        add_imm = { "ADD R3, R3, R0", 0x16C0 },
+        add_neg = { "ADD  R2, R2, #-1 ; decrement", 0x14BF },
+        brnp = { "BRnp #-3", 0x0BFD },
+        br = { "BR #1", 0x0E01 },
+        halt = { "HALT", 0xF025 },
+        trap = { "TRAP x22", 0xF022 },
+        lea = { "LEA R0, #2", 0xE002 },
+        not = { "NOT R1, R0", 0x923F },
+        ldr = { "LDR R4, R6, #-3", 0x69BD },
+        str = { "STR R2, R6, #1", 0x7581 },
+        jsr = { "JSR #-1", 0x4FFF },
+        jsrr = { "JSRR R6", 0x4180 },
+        ret = { "RET", 0xC1C0 },
+        jmp = { "JMP R2", 0xC080 },
+        rti = { "RTI", 0x8000 },
+        fill_neg = { ".FILL #-1", 0xFFFF },
+        fill_hex = { ".FILL xFE00", 0xFE00 },
+        and_hex_bits = { "AND R1, R1, x1F", 0x527F },
+    )]
+    #[test_macro(gtest)]
+    fn test_assemble_instruction(line: &str, expected: u16) {
+        expect_that!(assemble_instruction(line, 0x3000), ok(eq(&expected)));
+    }
+
+    #[parameterized(
+        unknown = { "MUL R1, R2, R3", AssembleError::UnknownMnemonic("MUL".into()) },
+        bad_br = { "BRnn #1", AssembleError::UnknownMnemonic("BRnn".into()) },
+        empty = { "  ; only a comment", AssembleError::EmptyLine },
+        operand_count = { "ADD R1, R2", AssembleError::WrongOperandCount {
+            mnemonic: "ADD".into(), expected: 3, actual: 2 } },
+        imm_range = { "ADD R1, R2, #16", AssembleError::ValueOutOfRange { value: 16, bits: 5 } },
+        label = { "BRz LOOP", AssembleError::UnknownLabel("LOOP".into()) },
+        register = { "NOT R1, #1", AssembleError::RegisterExpected("Number(1)".into()) },
+    )]
+    #[test_macro(gtest)]
+    fn test_assemble_instruction_errors(line: &str, expected: AssembleError) {
+        expect_that!(assemble_instruction(line, 0x3000), err(eq(&expected)));
+    }
+
+    #[parameterized(
+        times_ten = { "times_ten" },
+        hello_world_puts = { "hello_world_puts" },
+        hello_world_putsp = { "hello_world_putsp" },
+        getc_out = { "getc_out" },
+        memory_mapped_io_keyboard = { "memory_mapped_io_keyboard" },
+        game_2048 = { "2048" },
+        rogue = { "rogue" },
+    )]
+    #[test_macro(gtest)]
+    fn test_assemble_like_lc3as(example: &str) {
+        let source = std::fs::read_to_string(format!("examples/{example}.asm")).unwrap();
+        let program = assemble(&source).unwrap();
+        let obj = std::fs::read(format!("examples/{example}.obj")).unwrap();
+        let sym = SymbolTable::from_file(&format!("examples/{example}.sym")).unwrap();
+        expect_that!(program.to_bytes(), eq(&obj));
+        expect_that!(program.symbols, eq(&sym));
+    }
+
+    #[parameterized(
+        unknown_label = { ".ORIG x3000\n\tLD R0, MISSING\n", AssembleProgramError::Assemble {
+            line: 2, column: 9, error: AssembleError::UnknownLabel("MISSING".into()) } },
+        no_token = { ".ORIG x3000\n  ADD R1, R2, #16 ; x\n", AssembleProgramError::Assemble {
+            line: 2, column: 3, error: AssembleError::ValueOutOfRange { value: 16, bits: 5 } } },
+        missing_orig = { "HALT\n", AssembleProgramError::MissingOrig },
+        misplaced_orig = { "HALT\n.ORIG x3000\n", AssembleProgramError::MisplacedOrig { line: 2 } },
+    )]
+    #[test_macro(gtest)]
+    fn test_assemble_errors(source: &str, expected: AssembleProgramError) {
+        expect_that!(assemble(source), err(eq(&expected)));
+    }
+
+    #[gtest]
+    fn test_label_resolution() {
+        let resolve = |l: &str| (l == "LOOP").then_some(0x3003);
+        expect_that!(
+            assemble_line("BRnp LOOP", 0x3005, &resolve),
+            ok(eq(&0x0BFD))
+        );
+    }
+}
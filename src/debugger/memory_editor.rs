@@ -0,0 +1,126 @@
+//! Editing memory words in place with undo.
+//!
+//! Words can be entered as numbers (`x1234`, `#-5`) or as an instruction which is assembled at
+//! the edited address (`ADD R1, R1, #1`). Instructions are decoded when fetched, so edits take
+//! effect on the next execution of the edited address.
+use crate::assembler;
+use crate::emulator::Emulator;
+use crate::errors::MemoryEditError;
+
+/// A single change of a memory word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryEdit {
+    pub address: u16,
+    pub old: u16,
+    pub new: u16,
+}
+
+/// Edits memory of an [`Emulator`] and keeps the history for undo.
+#[derive(Debug, Default)]
+pub struct MemoryEditor {
+    history: Vec<MemoryEdit>,
+}
+
+impl MemoryEditor {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            history: Vec::new(),
+        }
+    }
+
+    /// Sets the word at `address` to the number or assembled instruction given in `input`.
+    ///
+    /// # Errors
+    /// - See [`MemoryEditError`]
+    pub fn edit(
+        &mut self,
+        emu: &mut Emulator,
+        address: u16,
+        input: &str,
+    ) -> Result<MemoryEdit, MemoryEditError> {
+        if !emu.memory().config().user_space.contains(&address) {
+            return Err(MemoryEditError::AddressNotEditable(address));
+        }
+        let input = input.trim();
+        let new = match assembler::parse_number(input) {
+            Ok(number) => assembler::to_word(number)?,
+            Err(_) => assembler::assemble_instruction(input, address)?,
+        };
+        let memory = emu.memory();
+        let edit = MemoryEdit {
+            address,
+            old: memory[address],
+            new,
+        };
+        memory[address] = new;
+        self.history.push(edit);
+        Ok(edit)
+    }
+
+    /// Reverts the most recent edit, returning it, `None` if there is nothing to undo.
+    pub fn undo(&mut self, emu: &mut Emulator) -> Option<MemoryEdit> {
+        let edit = self.history.pop()?;
+        emu.memory()[edit.address] = edit.old;
+        Some(edit)
+    }
+
+    /// All edits not undone, oldest first.
+    #[must_use]
+    pub fn history(&self) -> &[MemoryEdit] {
+        &self.history
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator;
+    use crate::emulator::test_helpers::StringWriter;
+    use crate::errors::AssembleError;
+    use crate::hardware::registers::from_binary;
+    use googletest::prelude::*;
+
+    #[gtest]
+    fn test_edit_and_undo() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        let mut editor = MemoryEditor::new();
+        // FACTOR
+        let edit = editor.edit(&mut emu, 0x3007, "#5").unwrap();
+        expect_that!(
+            edit,
+            eq(MemoryEdit {
+                address: 0x3007,
+                old: 3,
+                new: 5
+            })
+        );
+        // ADD R3, R3, R0 -> ADD R3, R3, #1
+        editor.edit(&mut emu, 0x3003, "ADD R3, R3, #1").unwrap();
+        expect_that!(emu.memory()[0x3003], eq(0x16E1));
+        expect_that!(editor.undo(&mut emu).map(|e| e.address), some(eq(0x3003)));
+        expect_that!(editor.history().len(), eq(1));
+
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(emu.registers().get(3), eq(from_binary(50)));
+        expect_that!(editor.undo(&mut emu), some(anything()));
+        expect_that!(emu.memory()[0x3007], eq(3));
+        expect_that!(editor.undo(&mut emu), none());
+    }
+    #[gtest]
+    fn test_edit_errors() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        let mut editor = MemoryEditor::new();
+        expect_that!(
+            editor.edit(&mut emu, 0x0100, "x0"),
+            err(eq(&MemoryEditError::AddressNotEditable(0x0100)))
+        );
+        expect_that!(
+            editor.edit(&mut emu, 0x3000, "FOO R1"),
+            err(eq(&MemoryEditError::InvalidInput(
+                AssembleError::UnknownMnemonic("FOO".into())
+            )))
+        );
+        expect_that!(editor.history(), is_empty());
+    }
+}
@@ -0,0 +1,4 @@
+//! Building blocks for interactive debugger frontends.
+pub mod memory_editor;
+#[cfg(feature = "tui")]
+pub mod tui;
@@ -0,0 +1,369 @@
+//! Full-screen terminal frontend with panes for the disassembly, registers, memory and program
+//! output and a command bar, see [`run`].
+//!
+//! Keyboard input for the program is delivered via the `input` command, so the emulator should
+//! read it from a [`ScriptedInputProvider`] without input.
+//!
+//! Available with the `tui` feature.
+use crate::assembler;
+use crate::emulator::breakpoints::Breakpoint;
+use crate::emulator::stdout_helpers::VirtualConsole;
+use crate::emulator::trace::condition_name;
+use crate::emulator::{Emulator, StepResult};
+use crate::errors::ExecutionError;
+#[cfg(doc)]
+use crate::hardware::keyboard::ScriptedInputProvider;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::Style;
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::io;
+use std::time::Duration;
+
+const HELP: &str = "s(tep) n(ext) f(inish) c(ontinue) b(reak) <ADDR> m(emory) <ADDR> \
+                    i(nput) <TEXT> q(uit), Esc pauses";
+/// Instructions executed between redraws while continuing
+const SLICE: u64 = 10_000;
+/// Words per line of the memory pane
+const WORDS_PER_LINE: u16 = 8;
+
+/// Runs the frontend for `emu` on the terminal until the user quits.
+///
+/// # Errors
+/// - Drawing to the terminal or reading keys fails
+pub fn run(emu: &mut Emulator) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    let res = Tui::new(emu).event_loop(&mut terminal);
+    ratatui::restore();
+    res
+}
+
+/// State of the frontend, which renders into any ratatui backend.
+struct Tui<'a> {
+    emu: &'a mut Emulator,
+    output: VirtualConsole<Vec<u8>>,
+    /// Text typed into the command bar
+    command: String,
+    /// Result of the last command
+    message: String,
+    /// First address of the memory pane
+    memory_start: u16,
+    /// Whether `continue` is executing slices of instructions
+    running: bool,
+    quit: bool,
+}
+
+impl<'a> Tui<'a> {
+    fn new(emu: &'a mut Emulator) -> Self {
+        let memory_start = emu.state().pc();
+        Self {
+            emu,
+            output: VirtualConsole(Vec::new()),
+            command: String::new(),
+            message: HELP.to_owned(),
+            memory_start,
+            running: false,
+            quit: false,
+        }
+    }
+
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| self.render(frame))?;
+            // while running only keys already typed are handled to keep executing
+            if (!self.running || event::poll(Duration::ZERO)?)
+                && let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                self.handle_key(key);
+            }
+            if self.running {
+                self.run_slice();
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.quit = true;
+            }
+            KeyCode::Char(c) => self.command.push(c),
+            KeyCode::Backspace => {
+                self.command.pop();
+            }
+            KeyCode::Enter => {
+                let command = std::mem::take(&mut self.command);
+                self.execute_command(command.trim());
+            }
+            KeyCode::Esc if self.running => {
+                self.running = false;
+                self.message = "Paused".into();
+            }
+            KeyCode::Esc => self.command.clear(),
+            _ => {}
+        }
+    }
+
+    /// Executes a command of the command bar, an empty one steps.
+    fn execute_command(&mut self, line: &str) {
+        let (command, argument) = line
+            .split_once(' ')
+            .map_or((line, ""), |(c, a)| (c, a.trim()));
+        self.message.clear();
+        let res = match command {
+            "" | "s" | "step" => self.emu.run_until(1, &mut self.output, |_| false),
+            "n" | "next" => self.emu.step_over(u64::MAX, &mut self.output),
+            "f" | "finish" => self.emu.step_out(u64::MAX, &mut self.output),
+            "c" | "continue" => {
+                self.running = true;
+                self.message = "Running, press Esc to pause".into();
+                return;
+            }
+            "b" | "break" => {
+                if let Some(address) = self.resolve_address(argument) {
+                    self.emu.add_breakpoint(Breakpoint::new(address));
+                    self.message = format!("Breakpoint set at x{address:04X}");
+                }
+                return;
+            }
+            "m" | "memory" => {
+                if let Some(address) = self.resolve_address(argument) {
+                    self.memory_start = address;
+                }
+                return;
+            }
+            "i" | "input" => {
+                self.emu.queue_input(&format!("{argument}\n"));
+                return;
+            }
+            "q" | "quit" => {
+                self.quit = true;
+                return;
+            }
+            "h" | "help" => {
+                self.message = HELP.into();
+                return;
+            }
+            _ => {
+                self.message =
+                    format!("Unknown command {command}, type help for a list of commands");
+                return;
+            }
+        };
+        self.report(res);
+    }
+
+    /// Executes the next instructions of a `continue`.
+    fn run_slice(&mut self) {
+        let res = self.emu.run_until(SLICE, &mut self.output, |_| false);
+        if !matches!(res, Ok(StepResult::BudgetExhausted)) {
+            self.running = false;
+            self.message.clear();
+            self.report(res);
+        }
+    }
+
+    fn report(&mut self, res: Result<StepResult, ExecutionError>) {
+        self.message = match res {
+            Ok(StepResult::Halted) => "Program halted".to_owned(),
+            Ok(StepResult::WaitingForInput) => {
+                "Program waits for keyboard input, deliver it via input <TEXT>".to_owned()
+            }
+            Ok(StepResult::Breakpoint(hit)) => format!("Breakpoint at x{:04X}", hit.address),
+            Ok(StepResult::Watchpoint(hit)) => format!(
+                "Watchpoint x{:04X} written at x{:04X}",
+                hit.access.address, hit.pc
+            ),
+            Ok(_) => String::new(),
+            Err(e) => format!("Error: {e}"),
+        };
+    }
+
+    /// The address `text` like x3004 or a label stands for.
+    fn resolve_address(&mut self, text: &str) -> Option<u16> {
+        let address = assembler::parse_number(text)
+            .ok()
+            .and_then(|n| assembler::to_word(n).ok())
+            .or_else(|| self.emu.symbols().address_of(text));
+        if address.is_none() {
+            self.message = format!("Unknown address {text}");
+        }
+        address
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let [top, memory, output, command, status] = Layout::vertical([
+            Constraint::Min(10),
+            Constraint::Length(6),
+            Constraint::Length(8),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [disassembly, registers] =
+            Layout::horizontal([Constraint::Min(30), Constraint::Length(24)]).areas(top);
+        self.render_disassembly(frame, disassembly);
+        self.render_registers(frame, registers);
+        self.render_memory(frame, memory);
+        self.render_output(frame, output);
+        frame.render_widget(
+            Paragraph::new(format!("> {}", self.command)).block(Block::bordered().title("Command")),
+            command,
+        );
+        frame.render_widget(Paragraph::new(self.message.as_str()), status);
+    }
+
+    fn render_disassembly(&self, frame: &mut Frame, area: Rect) {
+        let pc = self.emu.state().pc();
+        let start = pc.saturating_sub(3);
+        let end = start.saturating_add(area.height.saturating_sub(2));
+        let lines: Vec<Line> = self
+            .emu
+            .disassemble(start..end)
+            .into_iter()
+            .map(|line| {
+                let breakpoint = self
+                    .emu
+                    .breakpoints()
+                    .any(|(_, b)| b.address() == line.addr);
+                let text = format!("{}{line}", if breakpoint { '*' } else { ' ' });
+                if line.is_current_pc {
+                    Line::from(text).style(Style::new().reversed())
+                } else {
+                    Line::from(text)
+                }
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Disassembly")),
+            area,
+        );
+    }
+
+    fn render_registers(&self, frame: &mut Frame, area: Rect) {
+        let state = self.emu.state();
+        let mut lines: Vec<Line> = (0..8)
+            .map(|r| {
+                let value = state.register(r);
+                Line::from(format!(
+                    "R{r} x{:04X} {:>6}",
+                    value.as_binary(),
+                    value.as_decimal()
+                ))
+            })
+            .collect();
+        lines.push(Line::from(format!("PC x{:04X}", state.pc())));
+        lines.push(Line::from(format!(
+            "NZP {}",
+            condition_name(state.registers())
+        )));
+        lines.push(Line::from(format!(
+            "Executed {}",
+            state.instructions_executed()
+        )));
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Registers")),
+            area,
+        );
+    }
+
+    fn render_memory(&self, frame: &mut Frame, area: Rect) {
+        let state = self.emu.state();
+        let lines: Vec<Line> = (0..area.height.saturating_sub(2))
+            .map(|row| {
+                let address = self
+                    .memory_start
+                    .wrapping_add(row.wrapping_mul(WORDS_PER_LINE));
+                let words: Vec<String> = (0..WORDS_PER_LINE)
+                    .map(|i| format!("x{:04X}", state.memory(address.wrapping_add(i))))
+                    .collect();
+                Line::from(format!("x{address:04X}: {}", words.join(" ")))
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Memory")),
+            area,
+        );
+    }
+
+    fn render_output(&self, frame: &mut Frame, area: Rect) {
+        let output = String::from_utf8_lossy(&self.output.0);
+        let lines: Vec<&str> = output.split('\n').collect();
+        let visible = usize::from(area.height.saturating_sub(2));
+        let lines: Vec<Line> = lines[lines.len().saturating_sub(visible)..]
+            .iter()
+            .map(|line| Line::from(*line))
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Output")),
+            area,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator;
+    use crate::hardware::keyboard::ScriptedInputProvider;
+    use googletest::prelude::*;
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+
+    fn screen(tui: &Tui) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(80, 30)).unwrap();
+        terminal.draw(|frame| tui.render(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content()
+            .chunks(usize::from(buffer.area.width))
+            .map(|row| {
+                row.iter()
+                    .map(ratatui::buffer::Cell::symbol)
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[gtest]
+    pub fn test_tui_commands() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        let mut tui = Tui::new(&mut emu);
+        tui.execute_command("s");
+        expect_that!(tui.emu.state().pc(), eq(0x3001));
+        tui.execute_command("b x3006");
+        expect_that!(tui.message, eq("Breakpoint set at x3006"));
+        tui.execute_command("c");
+        while tui.running {
+            tui.run_slice();
+        }
+        expect_that!(tui.message, eq("Breakpoint at x3006"));
+        tui.execute_command("m ZERO");
+        expect_that!(tui.memory_start, eq(0x3009));
+        tui.execute_command("c");
+        tui.run_slice();
+        expect_that!(tui.message, eq("Program halted"));
+        expect_that!(tui.emu.state().register(3).as_decimal(), eq(30));
+    }
+
+    #[gtest]
+    pub fn test_tui_render() {
+        let mut emu = emulator::from_program("examples/getc_out.obj").unwrap();
+        emu.set_input_provider(ScriptedInputProvider::new(""));
+        let mut tui = Tui::new(&mut emu);
+        tui.execute_command("i a");
+        tui.execute_command("c");
+        while tui.running {
+            tui.run_slice();
+        }
+        let screen = screen(&tui);
+        expect_that!(screen, contains_substring("R0 x0061"));
+        expect_that!(screen, contains_substring("x3000: x"));
+        expect_that!(screen, contains_substring("Program halted"));
+    }
+}
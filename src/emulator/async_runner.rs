@@ -0,0 +1,88 @@
+//! Futures of the async runner, see
+//! [`Emulator::execute_async`](crate::emulator::Emulator::execute_async).
+use crate::emulator::CancelToken;
+use crate::hardware::keyboard::{InputQueue, SharedInputProvider};
+use crate::sync::lock;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+/// Handle to send keyboard input to an emulator from another task or thread, waking an
+/// [`Emulator::execute_async`](crate::emulator::Emulator::execute_async) awaiting it.
+///
+/// All senders of an emulator share its input queue.
+#[derive(Clone)]
+pub struct InputSender(pub(crate) Arc<Mutex<InputQueue>>);
+
+impl InputSender {
+    /// Delivers the characters of `input` after the ones queued before.
+    pub fn send(&self, input: &str) {
+        lock(&self.0).extend(input.chars());
+    }
+}
+
+/// Returns pending once to let the executor run other tasks.
+#[derive(Default)]
+pub(crate) struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Ready once keyboard input is available or the execution was interrupted.
+///
+/// Input sent via an [`InputSender`], a [`CancelToken`] and the keyboard input provider wake
+/// the task, see [`KeyboardInputProvider::register_waker`], so waiting needs no thread.
+///
+/// [`KeyboardInputProvider::register_waker`]: crate::hardware::keyboard::KeyboardInputProvider::register_waker
+pub(crate) struct InputReady {
+    provider: SharedInputProvider,
+    queue: Option<Arc<Mutex<InputQueue>>>,
+    cancel_token: CancelToken,
+}
+
+impl InputReady {
+    pub(crate) const fn new(
+        provider: SharedInputProvider,
+        queue: Option<Arc<Mutex<InputQueue>>>,
+        cancel_token: CancelToken,
+    ) -> Self {
+        Self {
+            provider,
+            queue,
+            cancel_token,
+        }
+    }
+}
+
+impl Future for InputReady {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // registered before checking so that input sent in between is not missed
+        self.cancel_token.register_waker(cx.waker());
+        let queued = self.queue.as_ref().is_some_and(|queue| {
+            let mut queue = lock(queue);
+            queue.register_waker(cx.waker());
+            !queue.is_empty()
+        });
+        if queued || self.cancel_token.is_cancelled() {
+            return Poll::Ready(());
+        }
+        let mut provider = lock(&self.provider);
+        if provider.check_input_available().unwrap_or(true) || provider.is_interrupted() {
+            return Poll::Ready(());
+        }
+        provider.register_waker(cx.waker());
+        Poll::Pending
+    }
+}
@@ -0,0 +1,117 @@
+//! Throughput of the interpreter measured over repeated runs of a program, see
+//! [`Emulator::bench`](crate::emulator::Emulator::bench).
+use crate::emulator::HaltReason;
+use crate::emulator::summary::halt_reason_name;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+/// Wall times of the runs of a benchmark.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchReport {
+    /// Wall time per run in the order of execution
+    pub durations: Vec<Duration>,
+    /// Number of instructions executed by all runs together
+    pub instructions_executed: u64,
+    /// How the last run ended
+    pub halt_reason: HaltReason,
+}
+
+impl BenchReport {
+    #[must_use]
+    pub fn min(&self) -> Duration {
+        self.durations.iter().min().copied().unwrap_or_default()
+    }
+    #[must_use]
+    pub fn max(&self) -> Duration {
+        self.durations.iter().max().copied().unwrap_or_default()
+    }
+    #[must_use]
+    pub fn mean(&self) -> Duration {
+        u32::try_from(self.durations.len())
+            .ok()
+            .filter(|&runs| runs > 0)
+            .map_or(Duration::ZERO, |runs| self.total() / runs)
+    }
+    /// The middle wall time, the mean of both middle ones for an even number of runs.
+    #[must_use]
+    pub fn median(&self) -> Duration {
+        let mut sorted = self.durations.clone();
+        sorted.sort_unstable();
+        match sorted.len() {
+            0 => Duration::ZERO,
+            len if len % 2 == 0 => (sorted[len / 2 - 1] + sorted[len / 2]) / 2,
+            len => sorted[len / 2],
+        }
+    }
+    /// Sum of the wall times of all runs.
+    #[must_use]
+    pub fn total(&self) -> Duration {
+        self.durations.iter().sum()
+    }
+    /// Instructions executed per second of wall time over all runs.
+    #[must_use]
+    #[expect(
+        clippy::cast_precision_loss,
+        reason = "Counts beyond 2^52 instructions are not expected"
+    )]
+    pub fn instructions_per_second(&self) -> f64 {
+        let seconds = self.total().as_secs_f64();
+        if seconds == 0.0 {
+            0.0
+        } else {
+            self.instructions_executed as f64 / seconds
+        }
+    }
+}
+
+impl Display for BenchReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let runs = self.durations.len();
+        writeln!(
+            f,
+            "Runs: {runs}, {} instructions per run, last ended by {}",
+            self.instructions_executed / u64::try_from(runs.max(1)).unwrap_or(1),
+            halt_reason_name(self.halt_reason)
+        )?;
+        writeln!(
+            f,
+            "Wall time: min {:.1?}, median {:.1?}, mean {:.1?}, max {:.1?}",
+            self.min(),
+            self.median(),
+            self.mean(),
+            self.max()
+        )?;
+        write!(
+            f,
+            "Throughput: {:.0} instructions/s",
+            self.instructions_per_second()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator;
+    use crate::emulator::HaltReason;
+    use googletest::prelude::*;
+
+    #[gtest]
+    pub fn test_bench() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        let report = emu.bench(5, "").unwrap();
+        expect_that!(report.durations, len(eq(5)));
+        expect_that!(report.instructions_executed, eq(5 * 34));
+        expect_that!(report.halt_reason, eq(HaltReason::HaltTrap));
+        expect_that!(report.min(), le(report.median()));
+        expect_that!(report.median(), le(report.max()));
+        expect_that!(report.instructions_per_second(), gt(0.0));
+        expect_that!(
+            report.to_string(),
+            starts_with("Runs: 5, 34 instructions per run, last ended by halt\nWall time: min ")
+        );
+        emu.set_start_pc(0x3002).unwrap();
+        let report = emu.bench(2, "").unwrap();
+        expect_that!(report.instructions_executed, eq(2 * 32));
+    }
+}
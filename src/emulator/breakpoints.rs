@@ -0,0 +1,151 @@
+//! Breakpoints pausing execution before an instruction is executed.
+//!
+//! See [`Emulator::add_breakpoint`](crate::emulator::Emulator::add_breakpoint) for addresses
+//! and [`Emulator::break_on_trap`](crate::emulator::Emulator::break_on_trap) for trap routines.
+
+/// Identifies a breakpoint to remove it later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BreakpointId(u32);
+
+/// A breakpoint at an instruction address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    address: u16,
+    ignore_count: u32,
+    temporary: bool,
+    hit_count: u32,
+}
+
+impl Breakpoint {
+    #[must_use]
+    pub const fn new(address: u16) -> Self {
+        Self {
+            address,
+            ignore_count: 0,
+            temporary: false,
+            hit_count: 0,
+        }
+    }
+    /// Continues the first `count` times the breakpoint is reached.
+    #[must_use]
+    pub const fn with_ignore_count(mut self, count: u32) -> Self {
+        self.ignore_count = count;
+        self
+    }
+    /// Removes the breakpoint after it paused execution once.
+    #[must_use]
+    pub const fn temporary(mut self) -> Self {
+        self.temporary = true;
+        self
+    }
+    #[must_use]
+    pub const fn address(&self) -> u16 {
+        self.address
+    }
+    #[must_use]
+    pub const fn ignore_count(&self) -> u32 {
+        self.ignore_count
+    }
+    #[must_use]
+    pub const fn is_temporary(&self) -> bool {
+        self.temporary
+    }
+    /// How often the breakpoint was reached including ignored hits.
+    #[must_use]
+    pub const fn hit_count(&self) -> u32 {
+        self.hit_count
+    }
+}
+
+/// Stop reason of a breakpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakpointHit {
+    pub id: BreakpointId,
+    pub address: u16,
+    pub hit_count: u32,
+}
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Breakpoints {
+    next_id: u32,
+    entries: Vec<(BreakpointId, Breakpoint)>,
+    /// Trap vectors to break on
+    traps: Vec<u8>,
+}
+
+impl Breakpoints {
+    pub fn add(&mut self, breakpoint: Breakpoint) -> BreakpointId {
+        let id = BreakpointId(self.next_id);
+        self.next_id += 1;
+        self.entries.push((id, breakpoint));
+        id
+    }
+    pub fn remove(&mut self, id: BreakpointId) -> Option<Breakpoint> {
+        let idx = self.entries.iter().position(|(i, _)| *i == id)?;
+        Some(self.entries.remove(idx).1)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (BreakpointId, &Breakpoint)> {
+        self.entries.iter().map(|(id, bp)| (*id, bp))
+    }
+    pub fn break_on_trap(&mut self, vector: u8) {
+        if !self.traps.contains(&vector) {
+            self.traps.push(vector);
+        }
+    }
+    pub fn clear_trap_break(&mut self, vector: u8) -> bool {
+        let len = self.traps.len();
+        self.traps.retain(|v| *v != vector);
+        self.traps.len() != len
+    }
+    pub fn breaks_on_trap(&self, vector: u8) -> bool {
+        self.traps.contains(&vector)
+    }
+    /// Counts hits of breakpoints at `pc` and returns the first one which is not ignored.
+    pub fn on_fetch(&mut self, pc: u16) -> Option<BreakpointHit> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        let mut hit = None;
+        for (id, bp) in self.entries.iter_mut().filter(|(_, bp)| bp.address == pc) {
+            bp.hit_count += 1;
+            if hit.is_none() && bp.hit_count > bp.ignore_count {
+                hit = Some(BreakpointHit {
+                    id: *id,
+                    address: pc,
+                    hit_count: bp.hit_count,
+                });
+            }
+        }
+        if let Some(hit) = hit {
+            self.entries
+                .retain(|(id, bp)| !(*id == hit.id && bp.temporary));
+        }
+        hit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+
+    #[gtest]
+    pub fn test_ignore_count_and_temporary() {
+        let mut bps = Breakpoints::default();
+        let ignoring = bps.add(Breakpoint::new(0x3003).with_ignore_count(2));
+        let temporary = bps.add(Breakpoint::new(0x3004).temporary());
+        expect_that!(bps.on_fetch(0x3003), none());
+        expect_that!(bps.on_fetch(0x3003), none());
+        expect_that!(
+            bps.on_fetch(0x3003),
+            some(eq(BreakpointHit {
+                id: ignoring,
+                address: 0x3003,
+                hit_count: 3
+            }))
+        );
+        expect_that!(bps.on_fetch(0x3004).map(|h| h.id), some(eq(temporary)));
+        expect_that!(bps.on_fetch(0x3004), none());
+        expect_that!(bps.iter().count(), eq(1));
+    }
+}
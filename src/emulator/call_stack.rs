@@ -0,0 +1,136 @@
+//! Virtual call stack reconstructed from `JSR`, `JSRR` and `RET`.
+use crate::emulator::Operation;
+use crate::emulator::instruction::Instruction;
+use crate::hardware::registers::Registers;
+use std::fmt::{Display, Formatter};
+
+/// Frames beyond this depth are dropped from the bottom, f.e. for programs calling
+/// subroutines which never return.
+const MAX_DEPTH: usize = 1024;
+
+/// A subroutine call which has not returned yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    /// Address of the `JSR` or `JSRR` instruction
+    pub call_site: u16,
+    /// Address of the called subroutine
+    pub target: u16,
+    /// Address execution continues at after `RET`
+    pub return_addr: u16,
+}
+impl Display for Frame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "x{:04X} called from x{:04X}, returns to x{:04X}",
+            self.target, self.call_site, self.return_addr
+        )
+    }
+}
+
+/// How a single instruction changed the call stack, to be able to revert it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum CallStackChange {
+    Pushed { dropped: Option<Frame> },
+    Popped(Vec<Frame>),
+}
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct CallStack {
+    frames: Vec<Frame>,
+}
+
+impl CallStack {
+    /// Innermost frame last.
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+    /// Updates the stack after `instruction` at `pc` was executed resulting in `after`.
+    pub fn on_executed(
+        &mut self,
+        pc: u16,
+        instruction: Instruction,
+        after: &Registers,
+    ) -> Option<CallStackChange> {
+        match instruction.operation() {
+            Operation::Jsr => {
+                let dropped = (self.frames.len() == MAX_DEPTH).then(|| self.frames.remove(0));
+                self.frames.push(Frame {
+                    call_site: pc,
+                    target: after.pc().as_binary(),
+                    return_addr: after.get(7).as_binary(),
+                });
+                Some(CallStackChange::Pushed { dropped })
+            }
+            Operation::JmpOrRet => {
+                let target = after.pc().as_binary();
+                // returning to an outer frame directly unwinds the inner ones as well
+                let idx = self.frames.iter().rposition(|f| f.return_addr == target)?;
+                Some(CallStackChange::Popped(self.frames.split_off(idx)))
+            }
+            Operation::Br
+            | Operation::Add
+            | Operation::Ld
+            | Operation::St
+            | Operation::And
+            | Operation::Ldr
+            | Operation::Str
+            | Operation::Rti
+            | Operation::Not
+            | Operation::Ldi
+            | Operation::Sti
+            | Operation::_Reserved
+            | Operation::Lea
+            | Operation::Trap => None,
+        }
+    }
+    /// Reverts `change` returned by [`CallStack::on_executed`].
+    pub fn revert(&mut self, change: CallStackChange) {
+        match change {
+            CallStackChange::Pushed { dropped } => {
+                self.frames.pop();
+                if let Some(frame) = dropped {
+                    self.frames.insert(0, frame);
+                }
+            }
+            CallStackChange::Popped(frames) => self.frames.extend(frames),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::registers::from_binary;
+    use googletest::prelude::*;
+
+    #[gtest]
+    pub fn test_call_and_return() {
+        let mut stack = CallStack::default();
+        let mut regs = Registers::new();
+        // JSR #4 at x3000
+        regs.set_pc(0x3005);
+        regs.set(7, from_binary(0x3001));
+        let change = stack.on_executed(0x3000, Instruction::from(0x4804), &regs);
+        assert_that!(
+            stack.frames(),
+            elements_are![eq(&Frame {
+                call_site: 0x3000,
+                target: 0x3005,
+                return_addr: 0x3001
+            })]
+        );
+        expect_that!(
+            stack.frames()[0].to_string(),
+            eq("x3005 called from x3000, returns to x3001")
+        );
+        // RET at x3005
+        regs.set_pc(0x3001);
+        let ret = stack.on_executed(0x3005, Instruction::from(0xC1C0), &regs);
+        expect_that!(stack.frames(), is_empty());
+        stack.revert(ret.unwrap());
+        expect_that!(stack.frames().len(), eq(1));
+        stack.revert(change.unwrap());
+        expect_that!(stack.frames(), is_empty());
+    }
+}
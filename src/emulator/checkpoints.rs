@@ -0,0 +1,57 @@
+//! Periodic checkpoints for coarse-grained time travel in long-running programs, see
+//! [`Emulator::set_checkpoint_interval`](crate::emulator::Emulator::set_checkpoint_interval).
+use crate::emulator::call_stack::CallStack;
+use crate::hardware::memory::MemorySnapshot;
+use crate::hardware::registers::Registers;
+use std::collections::VecDeque;
+
+/// Machine state after a number of executed instructions.
+#[derive(Clone)]
+pub struct Checkpoint {
+    pub instructions_executed: u64,
+    pub registers: Registers,
+    /// Shared copy-on-write with the emulator
+    pub memory: MemorySnapshot,
+    pub call_stack: CallStack,
+}
+
+/// Ring buffer keeping the most recent `capacity` checkpoints, taken every `interval`
+/// instructions.
+#[derive(Clone)]
+pub struct Checkpoints {
+    interval: u64,
+    capacity: usize,
+    entries: VecDeque<Checkpoint>,
+}
+
+impl Checkpoints {
+    pub fn new(interval: u64, capacity: usize) -> Self {
+        Self {
+            interval,
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+    /// `true` if a checkpoint is due after `instructions_executed` instructions.
+    pub const fn is_due(&self, instructions_executed: u64) -> bool {
+        instructions_executed.is_multiple_of(self.interval)
+    }
+    /// Adds `checkpoint` dropping the oldest one if the ring is full.
+    pub fn push(&mut self, checkpoint: Checkpoint) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(checkpoint);
+    }
+    /// Removes all checkpoints newer than the `k`-th most recent one and returns that one,
+    /// keeping it in the ring.
+    pub fn rewind(&mut self, k: usize) -> Option<&Checkpoint> {
+        let idx = self.entries.len().checked_sub(k + 1)?;
+        self.entries.truncate(idx + 1);
+        self.entries.back()
+    }
+    /// Executed instructions of all checkpoints, most recent first.
+    pub fn positions(&self) -> impl Iterator<Item = u64> {
+        self.entries.iter().rev().map(|c| c.instructions_executed)
+    }
+}
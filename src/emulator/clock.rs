@@ -0,0 +1,73 @@
+//! Throttling of the execution speed to a clock frequency, see
+//! [`Emulator::set_clock`](crate::emulator::Emulator::set_clock).
+use std::num::NonZeroU32;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Execution ahead of the clock by less than this continues without sleeping.
+const MIN_SLEEP: Duration = Duration::from_millis(1);
+/// Execution behind the clock by more than this does not catch up, f.e. after waiting for input.
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+/// Clock cycles elapsed since a point in time.
+pub struct Clock {
+    hz: NonZeroU32,
+    /// Cycles per instruction indexed by opcode
+    weights: [u32; 16],
+    start: Instant,
+    cycles: u64,
+}
+
+impl Clock {
+    pub fn new(hz: NonZeroU32, weights: [u32; 16]) -> Self {
+        Self {
+            hz,
+            weights,
+            start: Instant::now(),
+            cycles: 0,
+        }
+    }
+
+    pub const fn hz(&self) -> u32 {
+        self.hz.get()
+    }
+
+    pub const fn set_weights(&mut self, weights: [u32; 16]) {
+        self.weights = weights;
+    }
+
+    /// Counts the cycles of an instruction with `op_code` and sleeps if execution is ahead of
+    /// the clock.
+    pub fn tick(&mut self, op_code: u8) {
+        self.cycles += u64::from(self.weights[usize::from(op_code & 0xF)]);
+        let nanos = u128::from(self.cycles) * 1_000_000_000 / u128::from(self.hz.get());
+        let due = self.start + Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX));
+        let now = Instant::now();
+        if due > now + MIN_SLEEP {
+            thread::sleep(due - now);
+        } else if now > due + MAX_LAG {
+            self.start = now;
+            self.cycles = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+
+    #[gtest]
+    pub fn test_tick_throttles() {
+        let mut weights = [1; 16];
+        weights[0xF] = 10;
+        let mut clock = Clock::new(NonZeroU32::new(1000).unwrap(), weights);
+        let start = Instant::now();
+        for _ in 0..5 {
+            clock.tick(0x1);
+            clock.tick(0xF);
+        }
+        // 55 cycles at 1 kHz
+        expect_that!(start.elapsed(), ge(Duration::from_millis(54)));
+    }
+}
@@ -0,0 +1,104 @@
+//! Code coverage of the guest program, see
+//! [`Emulator::coverage`](crate::emulator::Emulator::coverage).
+use crate::emulator::instruction::Instruction;
+use crate::emulator::{BrNzpZeroBehavior, Operation, opcodes};
+use crate::hardware::memory::LoadedRegion;
+use crate::hardware::registers::Registers;
+use std::collections::BTreeMap;
+
+/// Directions a conditional branch went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BranchCoverage {
+    pub address: u16,
+    /// Branched at least once
+    pub taken: bool,
+    /// Fell through at least once
+    pub not_taken: bool,
+}
+
+impl BranchCoverage {
+    /// `true` if the branch went both ways.
+    #[must_use]
+    pub const fn is_complete(&self) -> bool {
+        self.taken && self.not_taken
+    }
+}
+
+/// Coverage of the executable loaded regions, without words known to be data.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverageReport {
+    /// Number of executable addresses executed at least once
+    pub covered: usize,
+    /// Number of executable addresses
+    pub total: usize,
+    /// Every executable address with whether it was executed, ordered by address
+    pub executed: Vec<(u16, bool)>,
+    /// Conditional branches executed at least once, ordered by address
+    pub branches: Vec<BranchCoverage>,
+}
+
+impl CoverageReport {
+    /// Addresses never executed.
+    pub fn uncovered(&self) -> impl Iterator<Item = u16> + '_ {
+        self.executed
+            .iter()
+            .filter(|(_, executed)| !executed)
+            .map(|(address, _)| *address)
+    }
+}
+
+/// Records executed addresses and branch directions.
+#[derive(Clone)]
+pub(crate) struct Coverage {
+    executed: Vec<bool>,
+    /// Taken and not taken per conditional branch address
+    branches: BTreeMap<u16, (bool, bool)>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self {
+            executed: vec![false; 1 << 16],
+            branches: BTreeMap::new(),
+        }
+    }
+    /// Records the execution of `i` at `pc`, `r` being the registers after executing it.
+    pub fn record(&mut self, pc: u16, i: Instruction, r: &Registers, no_flags: BrNzpZeroBehavior) {
+        self.executed[usize::from(pc)] = true;
+        let nzp = i.get_bit_range(9, 11);
+        if i.operation() == Operation::Br && nzp != 0 && nzp != 0b111 {
+            let (taken, not_taken) = self.branches.entry(pc).or_default();
+            if opcodes::is_branch_taken(i, r, no_flags) {
+                *taken = true;
+            } else {
+                *not_taken = true;
+            }
+        }
+    }
+    /// Coverage of the executable regions of `layout`, words never executed are left out if
+    /// `is_data` says they hold data.
+    pub fn report(&self, layout: &[LoadedRegion], is_data: impl Fn(u16) -> bool) -> CoverageReport {
+        let executed: Vec<(u16, bool)> = layout
+            .iter()
+            .filter(|region| region.executable)
+            .flat_map(|region| (region.origin..).take(usize::from(region.length)))
+            .map(|address| (address, self.executed[usize::from(address)]))
+            .filter(|(address, executed)| *executed || !is_data(*address))
+            .collect();
+        CoverageReport {
+            covered: executed.iter().filter(|(_, executed)| *executed).count(),
+            total: executed.len(),
+            executed,
+            branches: self
+                .branches
+                .iter()
+                .map(|(address, (taken, not_taken))| BranchCoverage {
+                    address: *address,
+                    taken: *taken,
+                    not_taken: *not_taken,
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,24 @@
+//! Non-fatal findings collected while loading and executing a program.
+//!
+//! Diagnostics do not stop the program but point to likely bugs in it.
+//! They are available via [`Emulator::diagnostics`](crate::emulator::Emulator::diagnostics).
+use crate::errors::SymbolTableError;
+use displaydoc::Display;
+
+/// A finding about the executed program which did not stop execution.
+#[rustfmt::skip]
+#[derive(Display, Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    /// Instruction {raw:#06X} fetched from non-executable address {pc:#06X}
+    ExecutedNonExecutable { pc: u16, raw: u16 },
+    /// Ran past the program end to {pc:#06X} without halting, last instruction {raw:#06X} at {last:#06X}
+    RanPastProgramEnd { pc: u16, last: u16, raw: u16 },
+    /// Instruction {raw:#06X} at {pc:#06X} was written by the program itself before executing it
+    ExecutedModifiedCode { pc: u16, raw: u16 },
+    /// Instruction at {pc:#06X} read R{register} before a value was written to it
+    UninitializedRegisterRead { pc: u16, register: u8 },
+    /// Instruction at {pc:#06X} read {address:#06X} before a value was written or loaded there
+    UninitializedMemoryRead { pc: u16, address: u16 },
+    /// Symbol table next to the program was not loaded: {0}
+    SymbolTableIgnored(SymbolTableError),
+}
@@ -0,0 +1,248 @@
+//! Rendering of memory as LC-3 assembly, see
+//! [`Emulator::disassemble`](crate::emulator::Emulator::disassemble), and of whole program
+//! images as source which can be assembled again, see [`disassemble`].
+use crate::assembler;
+use crate::emulator::Operation;
+use crate::emulator::instruction::Instruction;
+use crate::symbols::SymbolTable;
+use std::fmt::{Display, Formatter};
+
+/// A single disassembled memory cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisasmLine {
+    pub addr: u16,
+    pub raw: u16,
+    /// f.e. `ADD`, `BRnz` or `PUTS`
+    pub mnemonic: String,
+    /// Comma separated operands, f.e. `R1, R2, #5`, empty if there are none
+    pub operands: String,
+    pub is_current_pc: bool,
+}
+
+impl Display for DisasmLine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let marker = if self.is_current_pc { '>' } else { ' ' };
+        write!(
+            f,
+            "{marker} x{:04X}  x{:04X}  {}",
+            self.addr, self.raw, self.mnemonic
+        )?;
+        if !self.operands.is_empty() {
+            write!(f, " {}", self.operands)?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders `words` loaded at `orig` as source which `lc3as` or the
+/// [`assembler`] assemble to the same words again.
+///
+/// Targets of PC relative instructions inside the image get generated labels like `L3007`,
+/// targets outside of it are written as numeric offsets. Words which do not encode an
+/// instruction exactly, f.e. data or the reserved opcode, are written as `.FILL`.
+#[must_use]
+pub fn disassemble(words: &[u16], orig: u16) -> String {
+    let end = orig.saturating_add(u16::try_from(words.len()).unwrap_or(u16::MAX));
+    let lines: Vec<(String, String)> = (orig..)
+        .zip(words)
+        .map(|(address, &raw)| {
+            let (mnemonic, operands) = decode(raw, &|offset| {
+                let target = address.wrapping_add(1).wrapping_add_signed(offset);
+                if (orig..end).contains(&target) {
+                    label(target)
+                } else {
+                    format!("#{offset}")
+                }
+            });
+            let line = format!("{mnemonic} {operands}");
+            if assembler::assemble_line(&line, address, &label_address) == Ok(raw) {
+                (mnemonic, operands)
+            } else {
+                (".FILL".to_owned(), format!("x{raw:04X}"))
+            }
+        })
+        .collect();
+    // only PC relative operands are labels
+    let labels: Vec<u16> = lines
+        .iter()
+        .filter_map(|(_, operands)| label_address(operands.rsplit(", ").next()?))
+        .collect();
+    let mut res = format!(".ORIG x{orig:04X}\n");
+    for (address, (mnemonic, operands)) in (orig..).zip(lines) {
+        let label = if labels.contains(&address) {
+            label(address)
+        } else {
+            String::new()
+        };
+        let line = format!("{label:<8}{mnemonic} {operands}");
+        res.push_str(line.trim_end());
+        res.push('\n');
+    }
+    res.push_str(".END\n");
+    res
+}
+
+fn label(address: u16) -> String {
+    format!("L{address:04X}")
+}
+
+fn label_address(label: &str) -> Option<u16> {
+    u16::from_str_radix(label.strip_prefix('L')?, 16).ok()
+}
+
+/// Mnemonic and operands of `raw` stored at `address`.
+///
+/// PC relative targets are rendered as label if `symbols` contains one for them.
+/// Cells with the reserved opcode or `BR` without condition codes are rendered as `.FILL`.
+pub(crate) fn disassemble_word(address: u16, raw: u16, symbols: &SymbolTable) -> (String, String) {
+    decode(raw, &|offset| {
+        let target = address.wrapping_add(1).wrapping_add_signed(offset);
+        symbols
+            .name_at(target)
+            .map_or_else(|| format!("x{target:04X}"), str::to_owned)
+    })
+}
+
+/// Mnemonic and operands of `raw`, PC relative targets rendered by `render_target` from
+/// their offset to the incremented PC.
+pub(crate) fn decode(raw: u16, render_target: &dyn Fn(i16) -> String) -> (String, String) {
+    let i = Instruction::from(raw);
+    let dr = i.dr_number();
+    let sr1 = i.sr1_number();
+    let target = |len| render_target(i.pc_offset(len));
+    let (mnemonic, operands) = match i.operation() {
+        Operation::Add => ("ADD", format!("R{dr}, R{sr1}, {}", operand2(i))),
+        Operation::And => ("AND", format!("R{dr}, R{sr1}, {}", operand2(i))),
+        Operation::Not => ("NOT", format!("R{dr}, R{sr1}")),
+        // without condition codes assemblers read BR as BRnzp, these are most likely data
+        Operation::Br if dr == 0 => (".FILL", format!("x{raw:04X}")),
+        Operation::Br => {
+            let mut mnemonic = String::from("BR");
+            for (bit, c) in [(11, 'n'), (10, 'z'), (9, 'p')] {
+                if i.get_bit(bit) {
+                    mnemonic.push(c);
+                }
+            }
+            return (mnemonic, target(9));
+        }
+        Operation::JmpOrRet if sr1 == 7 => ("RET", String::new()),
+        Operation::JmpOrRet => ("JMP", format!("R{sr1}")),
+        Operation::Jsr if i.get_bit(11) => ("JSR", target(11)),
+        Operation::Jsr => ("JSRR", format!("R{sr1}")),
+        Operation::Ld => ("LD", format!("R{dr}, {}", target(9))),
+        Operation::Ldi => ("LDI", format!("R{dr}, {}", target(9))),
+        Operation::Lea => ("LEA", format!("R{dr}, {}", target(9))),
+        Operation::St => ("ST", format!("R{dr}, {}", target(9))),
+        Operation::Sti => ("STI", format!("R{dr}, {}", target(9))),
+        Operation::Ldr => ("LDR", format!("R{dr}, R{sr1}, #{}", i.pc_offset(6))),
+        Operation::Str => ("STR", format!("R{dr}, R{sr1}, #{}", i.pc_offset(6))),
+        Operation::Trap => match i.get_bit_range(0, 7) {
+            0x20 => ("GETC", String::new()),
+            0x21 => ("OUT", String::new()),
+            0x22 => ("PUTS", String::new()),
+            0x23 => ("IN", String::new()),
+            0x24 => ("PUTSP", String::new()),
+            0x25 => ("HALT", String::new()),
+            vector => ("TRAP", format!("x{vector:02X}")),
+        },
+        Operation::Rti => ("RTI", String::new()),
+        Operation::_Reserved => (".FILL", format!("x{raw:04X}")),
+    };
+    (mnemonic.to_owned(), operands)
+}
+
+fn operand2(i: Instruction) -> String {
+    if i.is_immediate() {
+        format!("#{}", i.pc_offset(5))
+    } else {
+        format!("R{}", i.sr2_number())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::read_program_file;
+    use googletest::prelude::*;
+    use yare::parameterized;
+
+    #[parameterized(
+        add_immediate = { 0x1265, "ADD", "R1, R1, #5" },
+        add_register = { 0x1642, "ADD", "R3, R1, R2" },
+        and_negative = { 0x5B7F, "AND", "R5, R5, #-1" },
+        not = { 0x907F, "NOT", "R0, R1" },
+        br_label = { 0x0A02, "BRnp", "LOOP" },
+        br_address = { 0x0FFD, "BRnzp", "x2FFE" },
+        br_never = { 0x0003, ".FILL", "x0003" },
+        ldr = { 0x6283, "LDR", "R1, R2, #3" },
+        ret = { 0xC1C0, "RET", "" },
+        jsrr = { 0x4080, "JSRR", "R2" },
+        puts = { 0xF022, "PUTS", "" },
+        unknown_trap = { 0xF030, "TRAP", "x30" },
+        reserved = { 0xD123, ".FILL", "xD123" },
+    )]
+    #[test_macro(gtest)]
+    fn test_disassemble_word(raw: u16, mnemonic: &str, operands: &str) {
+        let mut symbols = SymbolTable::default();
+        symbols.insert("LOOP", 0x3003);
+        let res = disassemble_word(0x3000, raw, &symbols);
+        expect_that!(res.0, eq(mnemonic));
+        expect_that!(res.1, eq(operands));
+    }
+
+    /// Assembles the output of [`disassemble`] line by line.
+    fn reassemble(source: &str) -> Vec<u16> {
+        let lines: Vec<&str> = source
+            .lines()
+            .filter(|l| !l.starts_with(".ORIG") && !l.starts_with(".END"))
+            .collect();
+        let mut labels = Vec::new();
+        let code: Vec<&str> = (0x3000u16..)
+            .zip(&lines)
+            .map(|(address, line)| match line.split_once(' ') {
+                Some((label, code)) if !label.is_empty() => {
+                    labels.push((label.to_owned(), address));
+                    code
+                }
+                _ => line,
+            })
+            .collect();
+        let resolve = |l: &str| labels.iter().find(|(n, _)| n == l).map(|(_, a)| *a);
+        (0x3000u16..)
+            .zip(code)
+            .map(|(address, line)| assembler::assemble_line(line, address, &resolve).unwrap())
+            .collect()
+    }
+
+    #[gtest]
+    fn test_disassemble_times_ten() {
+        let words = read_program_file("examples/times_ten.obj").unwrap();
+        let source = disassemble(&words[1..], words[0]);
+        expect_that!(
+            source,
+            eq(".ORIG x3000
+        LD R0, L3007
+        LD R3, L3009
+        LD R2, L3008
+L3003   ADD R3, R3, R0
+        ADD R2, R2, #-1
+        BRnp L3003
+        HALT
+L3007   .FILL x0003
+L3008   .FILL x000A
+L3009   .FILL x0000
+.END
+")
+        );
+        expect_that!(reassemble(&source), eq(&words[1..]));
+    }
+
+    #[gtest]
+    fn test_disassemble_round_trip() {
+        for program in ["examples/rogue.obj", "examples/2048.obj"] {
+            let words = read_program_file(program).unwrap();
+            let source = disassemble(&words[1..], words[0]);
+            expect_that!(reassemble(&source), eq(&words[1..]), "{program}");
+        }
+    }
+}
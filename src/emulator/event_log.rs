@@ -0,0 +1,259 @@
+//! Anonymized, structured session events for analytics in courseware, disabled by default.
+//!
+//! Events never contain file names or program contents, programs are only identified by a
+//! hash of their words. Attach a sink via
+//! [`Emulator::set_event_log`](crate::emulator::Emulator::set_event_log).
+use crate::json;
+use crate::random::SplitMix64;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A structured event of an emulator session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionEvent {
+    /// A program was loaded, identified by a hash of its contents only.
+    ProgramLoaded { program_hash: u64, words: u16 },
+    /// Execution started.
+    RunStarted,
+    /// Execution stopped at a breakpoint.
+    BreakpointHit { address: u16 },
+    /// Execution ended without error.
+    RunFinished { instructions_executed: u64 },
+    /// Execution ended with an error.
+    ErrorEncountered {
+        error: String,
+        pc: u16,
+        instructions_executed: u64,
+    },
+}
+
+impl SessionEvent {
+    /// Serializes the event as a single line JSON object.
+    #[must_use]
+    pub fn to_json(&self, session_id: u64, timestamp_ms: u128) -> String {
+        let obj = json::Object::new()
+            .str("session", &format!("{session_id:016x}"))
+            .num("ts_ms", timestamp_ms);
+        match self {
+            Self::ProgramLoaded {
+                program_hash,
+                words,
+            } => obj
+                .str("event", "program_loaded")
+                .str("program_hash", &format!("{program_hash:016x}"))
+                .num("words", words),
+            Self::RunStarted => obj.str("event", "run_started"),
+            Self::BreakpointHit { address } => {
+                obj.str("event", "breakpoint_hit").num("address", address)
+            }
+            Self::RunFinished {
+                instructions_executed,
+            } => obj
+                .str("event", "run_finished")
+                .num("instructions_executed", instructions_executed),
+            Self::ErrorEncountered {
+                error,
+                pc,
+                instructions_executed,
+            } => obj
+                .str("event", "error_encountered")
+                .str("error", error)
+                .num("pc", pc)
+                .num("instructions_executed", instructions_executed),
+        }
+        .finish()
+    }
+}
+
+/// Receives session events, errors are the sink's responsibility since logging must never
+/// influence the emulated program.
+pub trait EventLogSink {
+    fn record(&mut self, session_id: u64, event: &SessionEvent);
+}
+
+fn now_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis())
+}
+
+/// Writes events as JSON Lines to any writer, f.e. a file.
+pub struct JsonLinesEventSink<W: Write> {
+    writer: W,
+}
+impl<W: Write> JsonLinesEventSink<W> {
+    pub const fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+impl JsonLinesEventSink<File> {
+    /// Appends events to the file at `path`, creating it if necessary.
+    ///
+    /// # Errors
+    /// - the file cannot be opened
+    pub fn append_to(path: impl AsRef<Path>) -> io::Result<Self> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map(Self::new)
+    }
+}
+impl<W: Write> EventLogSink for JsonLinesEventSink<W> {
+    fn record(&mut self, session_id: u64, event: &SessionEvent) {
+        let _ = writeln!(self.writer, "{}", event.to_json(session_id, now_ms()));
+        let _ = self.writer.flush();
+    }
+}
+
+/// Posts every event as JSON to a plain HTTP endpoint, best-effort without retries.
+///
+/// Events are posted during execution, so connecting, sending and waiting for the response
+/// each give up after [`HttpEventSink::DEFAULT_TIMEOUT`] or the timeout set by
+/// [`HttpEventSink::with_timeout`] and the event is dropped.
+pub struct HttpEventSink {
+    host_port: String,
+    path: String,
+    timeout: Duration,
+}
+impl HttpEventSink {
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(500);
+
+    /// `host_port` like `localhost:8080`, `path` like `/events`.
+    #[must_use]
+    pub fn new(host_port: &str, path: &str) -> Self {
+        Self {
+            host_port: host_port.to_owned(),
+            path: path.to_owned(),
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+    /// Gives up on an event once connecting, sending or receiving takes longer than `timeout`,
+    /// a zero `timeout` drops all events.
+    #[must_use]
+    pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut last_error = None;
+        for addr in self.host_port.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, self.timeout) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "host resolved to no address")
+        }))
+    }
+    fn post(&self, body: &str) -> io::Result<()> {
+        let mut stream = self.connect()?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            self.path,
+            self.host_port,
+            body.len()
+        )?;
+        stream.flush()?;
+        // wait for the response to not drop the connection before the request is processed
+        let mut response = [0u8; 64];
+        let _ = stream.read(&mut response)?;
+        Ok(())
+    }
+}
+impl EventLogSink for HttpEventSink {
+    fn record(&mut self, session_id: u64, event: &SessionEvent) {
+        let _ = self.post(&event.to_json(session_id, now_ms()));
+    }
+}
+
+/// The sink of an emulator together with its anonymous session id.
+pub(crate) struct EventLog {
+    session_id: u64,
+    sink: Box<dyn EventLogSink + Send>,
+}
+impl EventLog {
+    pub(crate) fn new(sink: Box<dyn EventLogSink + Send>) -> Self {
+        let seed = u64::try_from(now_ms() & u128::from(u64::MAX)).unwrap_or_default();
+        Self {
+            session_id: SplitMix64::new(seed ^ u64::from(std::process::id())).next_u64(),
+            sink,
+        }
+    }
+    pub(crate) fn record(&mut self, event: &SessionEvent) {
+        self.sink.record(self.session_id, event);
+    }
+}
+
+/// FNV-1a hash of the program words identifying it without revealing its contents.
+pub(crate) fn program_hash(words: &[u16]) -> u64 {
+    words
+        .iter()
+        .flat_map(|w| w.to_be_bytes())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01B3)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+    use std::io::BufRead;
+    use std::net::TcpListener;
+
+    #[gtest]
+    fn test_event_json() {
+        let event = SessionEvent::ErrorEncountered {
+            error: "Unknown trap routine found: 0x0030".into(),
+            pc: 0x3001,
+            instructions_executed: 2,
+        };
+        expect_that!(
+            event.to_json(0xAB, 17),
+            eq(
+                r#"{"session":"00000000000000ab","ts_ms":17,"event":"error_encountered","error":"Unknown trap routine found: 0x0030","pc":12289,"instructions_executed":2}"#
+            )
+        );
+    }
+    #[gtest]
+    fn test_program_hash_differs() {
+        expect_that!(program_hash(&[1, 2]), not(eq(program_hash(&[2, 1]))));
+    }
+    #[gtest]
+    fn test_http_sink_posts_event() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = io::BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .unwrap();
+            request_line
+        });
+        HttpEventSink::new(&addr, "/events").record(1, &SessionEvent::RunStarted);
+        expect_that!(server.join().unwrap(), eq("POST /events HTTP/1.1\r\n"));
+    }
+    #[gtest]
+    fn test_http_sink_gives_up_on_silent_collector() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let start = std::time::Instant::now();
+        HttpEventSink::new(&addr, "/events")
+            .with_timeout(Duration::from_millis(50))
+            .record(1, &SessionEvent::RunStarted);
+        expect_that!(start.elapsed(), lt(Duration::from_secs(5)));
+        drop(listener);
+    }
+}
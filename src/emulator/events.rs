@@ -0,0 +1,135 @@
+//! Structured events of the machine delivered to subscribers, f.e. to build tracers,
+//! profilers or GUIs without bespoke hooks, see
+//! [`Emulator::subscribe`](crate::emulator::Emulator::subscribe).
+use crate::emulator::HaltReason;
+use crate::emulator::instruction::Instruction;
+use crate::hardware::registers::Register;
+use std::ops::BitOr;
+
+/// Set of [`MachineEvent`] kinds a subscriber receives, combined via `|`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EventMask(u8);
+
+impl EventMask {
+    pub const NONE: Self = Self(0);
+    pub const INSTRUCTION_RETIRED: Self = Self(1);
+    pub const REGISTER_WRITTEN: Self = Self(1 << 1);
+    pub const MEMORY_WRITTEN: Self = Self(1 << 2);
+    pub const TRAP_ENTERED: Self = Self(1 << 3);
+    pub const INTERRUPT_TAKEN: Self = Self(1 << 4);
+    pub const HALTED: Self = Self(1 << 5);
+    pub const ALL: Self = Self(0b11_1111);
+
+    /// `true` if all kinds of `other` are in `self`.
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+    const fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl BitOr for EventMask {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Something that happened while executing the program.
+///
+/// Events of an instruction are emitted after executing it in this order: `TrapEntered`,
+/// `RegisterWritten`, `MemoryWritten`, `InstructionRetired` and finally `Halted`.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MachineEvent {
+    /// The instruction at `pc` was executed.
+    InstructionRetired { pc: u16, instruction: Instruction },
+    /// The instruction at `pc` changed a general purpose register.
+    RegisterWritten {
+        pc: u16,
+        register: u8,
+        old: Register,
+        new: Register,
+    },
+    /// The instruction at `pc` wrote a memory cell.
+    MemoryWritten {
+        pc: u16,
+        address: u16,
+        old: u16,
+        new: u16,
+    },
+    /// The `TRAP` instruction at `pc` was executed.
+    TrapEntered { pc: u16, vector: u8 },
+    /// The service routine of an interrupt was started.
+    InterruptTaken { vector: u8, priority: u8 },
+    /// The program halted.
+    Halted(HaltReason),
+}
+
+impl MachineEvent {
+    /// The mask selecting this kind of event.
+    #[must_use]
+    pub const fn kind(&self) -> EventMask {
+        match self {
+            Self::InstructionRetired { .. } => EventMask::INSTRUCTION_RETIRED,
+            Self::RegisterWritten { .. } => EventMask::REGISTER_WRITTEN,
+            Self::MemoryWritten { .. } => EventMask::MEMORY_WRITTEN,
+            Self::TrapEntered { .. } => EventMask::TRAP_ENTERED,
+            Self::InterruptTaken { .. } => EventMask::INTERRUPT_TAKEN,
+            Self::Halted(_) => EventMask::HALTED,
+        }
+    }
+}
+
+/// Identifies a subscription to end it later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SubscriptionId(u32);
+
+/// A callback receiving the [`MachineEvent`]s it subscribed to.
+pub type EventHandler = Box<dyn FnMut(&MachineEvent) + Send>;
+
+/// The subscribers of an emulator.
+#[derive(Default)]
+pub(crate) struct EventBus {
+    subscribers: Vec<(SubscriptionId, EventMask, EventHandler)>,
+    next_id: u32,
+    /// Union of the masks of all subscribers
+    mask: EventMask,
+}
+
+impl EventBus {
+    pub fn subscribe(&mut self, mask: EventMask, handler: EventHandler) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id);
+        self.next_id += 1;
+        self.subscribers.push((id, mask, handler));
+        self.mask = self.mask | mask;
+        id
+    }
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let len = self.subscribers.len();
+        self.subscribers.retain(|(i, _, _)| *i != id);
+        self.mask = self
+            .subscribers
+            .iter()
+            .fold(EventMask::NONE, |mask, (_, m, _)| mask | *m);
+        self.subscribers.len() != len
+    }
+    /// `true` if any subscriber receives one of the kinds in `mask`, to skip building events
+    /// nobody receives.
+    pub const fn wants(&self, mask: EventMask) -> bool {
+        self.mask.intersects(mask)
+    }
+    pub fn emit(&mut self, event: &MachineEvent) {
+        let kind = event.kind();
+        if !self.wants(kind) {
+            return;
+        }
+        for (_, mask, handler) in &mut self.subscribers {
+            if mask.contains(kind) {
+                handler(event);
+            }
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Plain-English descriptions of executed instructions for first-time assembly students,
+//! see [`Emulator::set_explain`](crate::emulator::Emulator::set_explain).
+use crate::emulator::instruction::Instruction;
+use crate::emulator::opcodes::is_branch_taken;
+use crate::emulator::{BrNzpZeroBehavior, Operation};
+use crate::hardware::registers::{ConditionFlag, Registers};
+use crate::symbols::SymbolTable;
+
+/// Describes what `instruction` fetched from `address` did, f.e.
+/// `x3003 ADD: R3 ← R2 + #5 = 30; flags=P`.
+///
+/// `before` is the state after incrementing the PC, `after` the state after execution.
+/// Addresses are followed by their label from `symbols` if there is one, `BR` without `nzp`
+/// bits is explained according to `no_flags`.
+pub fn explain(
+    address: u16,
+    instruction: Instruction,
+    before: &Registers,
+    after: &Registers,
+    symbols: &SymbolTable,
+    no_flags: BrNzpZeroBehavior,
+) -> String {
+    format!(
+        "x{address:04X} {}",
+        describe(instruction, before, after, symbols, no_flags)
+    )
+}
+
+fn describe(
+    i: Instruction,
+    before: &Registers,
+    after: &Registers,
+    symbols: &SymbolTable,
+    no_flags: BrNzpZeroBehavior,
+) -> String {
+    let dr = i.dr_number();
+    let result = || format!("= {}; flags={}", after.get(dr).as_decimal(), flags(after));
+    match i.operation() {
+        Operation::Add => {
+            format!(
+                "ADD: R{dr} ← R{} + {} {}",
+                i.sr1_number(),
+                operand2(i),
+                result()
+            )
+        }
+        Operation::And => {
+            format!(
+                "AND: R{dr} ← R{} & {} {}",
+                i.sr1_number(),
+                operand2(i),
+                result()
+            )
+        }
+        Operation::Not => {
+            format!("NOT: R{dr} ← NOT R{} {}", i.sr1_number(), result())
+        }
+        Operation::Br => describe_br(i, before, after, symbols, no_flags),
+        Operation::JmpOrRet => {
+            let base = i.get_bit_range_u8(6, 8, "Error in explain JMP");
+            let mnemonic = if base == 7 { "RET" } else { "JMP" };
+            format!(
+                "{mnemonic}: PC ← R{base} = {}",
+                symbols.describe(after.pc().as_binary())
+            )
+        }
+        Operation::Jsr => {
+            let mnemonic = if i.get_bit(11) { "JSR" } else { "JSRR" };
+            format!(
+                "{mnemonic}: R7 ← {}, jump to subroutine at {}",
+                symbols.describe(before.pc().as_binary()),
+                symbols.describe(after.pc().as_binary())
+            )
+        }
+        Operation::Ld => format!(
+            "LD: R{dr} ← M[{}] {}",
+            symbols.describe(pc_relative(i, before)),
+            result()
+        ),
+        Operation::Ldi => format!(
+            "LDI: R{dr} ← M[M[{}]] {}",
+            symbols.describe(pc_relative(i, before)),
+            result()
+        ),
+        Operation::Ldr => format!(
+            "LDR: R{dr} ← M[R{} + #{}] = M[{}] {}",
+            i.sr1_number(),
+            i.pc_offset(6),
+            symbols.describe(base_relative(i, before)),
+            result()
+        ),
+        Operation::Lea => format!(
+            "LEA: R{dr} ← address {}; flags={}",
+            symbols.describe(after.get(dr).as_binary()),
+            flags(after)
+        ),
+        Operation::St => format!(
+            "ST: M[{}] ← R{dr} = {}",
+            symbols.describe(pc_relative(i, before)),
+            before.get(dr).as_decimal()
+        ),
+        Operation::Sti => format!(
+            "STI: M[M[{}]] ← R{dr} = {}",
+            symbols.describe(pc_relative(i, before)),
+            before.get(dr).as_decimal()
+        ),
+        Operation::Str => format!(
+            "STR: M[R{} + #{}] = M[{}] ← R{dr} = {}",
+            i.sr1_number(),
+            i.pc_offset(6),
+            symbols.describe(base_relative(i, before)),
+            before.get(dr).as_decimal()
+        ),
+        Operation::Trap => describe_trap(i, after, symbols),
+        Operation::Rti => "RTI: return from interrupt".to_owned(),
+        Operation::_Reserved => "reserved opcode".to_owned(),
+    }
+}
+
+fn describe_br(
+    i: Instruction,
+    before: &Registers,
+    after: &Registers,
+    symbols: &SymbolTable,
+    no_flags: BrNzpZeroBehavior,
+) -> String {
+    let mut mnemonic = String::from("BR");
+    for (bit, c) in [(11, 'n'), (10, 'z'), (9, 'p')] {
+        if i.get_bit(bit) {
+            mnemonic.push(c);
+        }
+    }
+    if is_branch_taken(i, before, no_flags) {
+        format!(
+            "{mnemonic}: taken, flags={}, jump to {}",
+            flags(before),
+            symbols.describe(after.pc().as_binary())
+        )
+    } else {
+        format!(
+            "{mnemonic}: not taken, flags={}, continue at {}",
+            flags(before),
+            symbols.describe(after.pc().as_binary())
+        )
+    }
+}
+
+fn describe_trap(i: Instruction, after: &Registers, symbols: &SymbolTable) -> String {
+    let vector = i.get_bit_range(0, 7);
+    let description = match vector {
+        0x20 => format!(
+            "GETC: read a character into R0 = {}",
+            after.get(0).as_decimal()
+        ),
+        0x21 => "OUT: print the character in R0".to_owned(),
+        0x22 => format!(
+            "PUTS: print the string at {}",
+            symbols.describe(after.get(0).as_binary())
+        ),
+        0x23 => format!(
+            "IN: prompt for a character and read it into R0 = {}",
+            after.get(0).as_decimal()
+        ),
+        0x24 => format!(
+            "PUTSP: print the packed string at {}",
+            symbols.describe(after.get(0).as_binary())
+        ),
+        0x25 => "HALT: stop the program".to_owned(),
+        _ => "unknown trap routine".to_owned(),
+    };
+    format!("TRAP x{vector:02X} {description}")
+}
+
+fn operand2(i: Instruction) -> String {
+    if i.is_immediate() {
+        format!("#{}", i.pc_offset(5))
+    } else {
+        format!("R{}", i.sr2_number())
+    }
+}
+
+fn pc_relative(i: Instruction, r: &Registers) -> u16 {
+    (r.pc().as_decimal() + i.pc_offset(9)).cast_unsigned()
+}
+
+fn base_relative(i: Instruction, r: &Registers) -> u16 {
+    (r.get(i.sr1_number()).as_decimal() + i.pc_offset(6)).cast_unsigned()
+}
+
+const fn flags(r: &Registers) -> char {
+    match r.get_conditional_register() {
+        ConditionFlag::Neg => 'N',
+        ConditionFlag::Zero => 'Z',
+        ConditionFlag::Pos => 'P',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::registers::from_decimal;
+    use googletest::prelude::*;
+
+    #[gtest]
+    pub fn test_explain_add_immediate() {
+        let mut before = Registers::new();
+        before.set(2, from_decimal(25));
+        let mut after = before.clone();
+        after.set(3, from_decimal(30));
+        after.update_conditional_register(3);
+        // ADD R3, R2, #5
+        let res = explain(
+            0x3000,
+            Instruction::from(0x16A5),
+            &before,
+            &after,
+            &SymbolTable::default(),
+            BrNzpZeroBehavior::Unconditional,
+        );
+        assert_that!(res, eq("x3000 ADD: R3 ← R2 + #5 = 30; flags=P"));
+    }
+    #[gtest]
+    pub fn test_explain_br_not_taken() {
+        let mut before = Registers::new();
+        before.set_pc(0x3001);
+        let after = before.clone();
+        let mut symbols = SymbolTable::default();
+        symbols.insert("DONE", 0x3001);
+        // BRnp #-1
+        let res = explain(
+            0x3000,
+            Instruction::from(0x0BFF),
+            &before,
+            &after,
+            &symbols,
+            BrNzpZeroBehavior::Unconditional,
+        );
+        assert_that!(
+            res,
+            eq("x3000 BRnp: not taken, flags=Z, continue at x3001 (DONE)")
+        );
+    }
+    #[gtest]
+    pub fn test_explain_br_taken_to_next_instruction() {
+        let mut before = Registers::new();
+        before.set_pc(0x3001);
+        let after = before.clone();
+        // BRnzp #0
+        let res = explain(
+            0x3000,
+            Instruction::from(0x0E00),
+            &before,
+            &after,
+            &SymbolTable::default(),
+            BrNzpZeroBehavior::Unconditional,
+        );
+        assert_that!(res, eq("x3000 BRnzp: taken, flags=Z, jump to x3001"));
+    }
+}
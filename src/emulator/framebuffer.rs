@@ -0,0 +1,115 @@
+//! Memory mapped text display, f.e. for games drawing a whole screen instead of printing lines.
+//!
+//! Attach a renderer via
+//! [`Emulator::set_display_renderer`](crate::emulator::Emulator::set_display_renderer).
+use std::ops::Range;
+
+/// Layout of the character cells of a text display in memory, row by row.
+///
+/// Each cell holds an ASCII character in its low byte, `0` is shown as a blank.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Framebuffer {
+    /// Address of the top left cell
+    pub origin: u16,
+    pub columns: u16,
+    pub rows: u16,
+}
+
+impl Framebuffer {
+    /// A display of 80x25 cells starting at `origin`, f.e. `xC000`.
+    #[must_use]
+    pub const fn new(origin: u16) -> Self {
+        Self {
+            origin,
+            columns: 80,
+            rows: 25,
+        }
+    }
+    /// Addresses of all cells, [`None`] if the display does not fit into memory.
+    #[must_use]
+    pub fn range(&self) -> Option<Range<u16>> {
+        let end = self
+            .columns
+            .checked_mul(self.rows)
+            .and_then(|len| self.origin.checked_add(len))?;
+        Some(self.origin..end)
+    }
+    /// The characters of `cells` with one line per row.
+    #[must_use]
+    pub fn to_text(&self, cells: &[u16]) -> String {
+        cells
+            .chunks(usize::from(self.columns).max(1))
+            .map(|row| {
+                row.iter()
+                    .map(|cell| match cell.to_le_bytes()[0] {
+                        0 => ' ',
+                        c => char::from(c),
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Draws the framebuffer in a frontend, f.e. a terminal UI, a GUI or a canvas.
+pub trait DisplayRenderer {
+    /// Called after an instruction wrote to the framebuffer with the contents of all `cells`.
+    fn render(&mut self, framebuffer: &Framebuffer, cells: &[u16]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::EmulatorBuilder;
+    use crate::emulator::test_helpers::StringWriter;
+    use crate::errors::StateAccessError;
+    use crate::sync::lock;
+    use googletest::prelude::*;
+    use std::sync::{Arc, Mutex};
+
+    struct TextRenderer(Arc<Mutex<Vec<String>>>);
+    impl DisplayRenderer for TextRenderer {
+        fn render(&mut self, framebuffer: &Framebuffer, cells: &[u16]) {
+            lock(&self.0).push(framebuffer.to_text(cells));
+        }
+    }
+
+    #[gtest]
+    pub fn test_render() {
+        let program = [
+            0x3000, // .ORIG x3000
+            0x2203, // LD R1, DISPLAY
+            0x2003, // LD R0, CHAR
+            0x7041, // STR R0, R1, #1
+            0xF025, // HALT
+            0xC000, // DISPLAY
+            0x0048, // CHAR: 'H'
+        ];
+        let mut emu = EmulatorBuilder::new(&program).build().unwrap();
+        let frames = Arc::new(Mutex::new(Vec::new()));
+        let framebuffer = Framebuffer {
+            origin: 0xC000,
+            columns: 3,
+            rows: 2,
+        };
+        emu.set_display_renderer(framebuffer, TextRenderer(frames.clone()))
+            .unwrap();
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(*lock(&frames), elements_are![eq(" H \n   ")]);
+    }
+    #[gtest]
+    pub fn test_framebuffer_must_fit_into_memory() {
+        let mut emu = EmulatorBuilder::new(&[0x3000, 0xF025]).build().unwrap();
+        let framebuffer = Framebuffer::new(0xFF00);
+        expect_that!(framebuffer.range(), none());
+        expect_that!(
+            emu.set_display_renderer(framebuffer, TextRenderer(Arc::default())),
+            err(eq(&StateAccessError::FramebufferOutOfRange {
+                origin: 0xFF00,
+                columns: 80,
+                rows: 25
+            }))
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! Bounded history of reversible state changes for stepping back in a debugger.
+use crate::emulator::call_stack::CallStackChange;
+use crate::hardware::memory::MemoryAccess;
+use crate::hardware::registers::Registers;
+use std::collections::VecDeque;
+
+/// State changed by a single executed instruction.
+#[derive(Clone)]
+pub struct HistoryEntry {
+    /// All registers including PC before the instruction was fetched
+    pub registers: Registers,
+    /// Memory cells written by the instruction and its instruction hooks in order
+    pub memory_writes: Vec<MemoryAccess>,
+    pub call_stack_change: Option<CallStackChange>,
+}
+
+/// Ring buffer keeping the most recent `depth` entries.
+#[derive(Clone)]
+pub struct History {
+    depth: usize,
+    entries: VecDeque<HistoryEntry>,
+}
+
+impl History {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            entries: VecDeque::with_capacity(depth),
+        }
+    }
+    /// Adds `entry` dropping the oldest one if the history is full.
+    pub fn push(&mut self, entry: HistoryEntry) {
+        if self.entries.len() == self.depth {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+    pub fn pop(&mut self) -> Option<HistoryEntry> {
+        self.entries.pop_back()
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::registers::from_binary;
+    use googletest::prelude::*;
+
+    fn entry(r0: u16) -> HistoryEntry {
+        let mut registers = Registers::new();
+        registers.set(0, from_binary(r0));
+        HistoryEntry {
+            registers,
+            memory_writes: Vec::new(),
+            call_stack_change: None,
+        }
+    }
+
+    #[gtest]
+    pub fn test_history_drops_oldest() {
+        let mut history = History::new(2);
+        for r0 in 1..=3 {
+            history.push(entry(r0));
+        }
+        assert_that!(history.len(), eq(2));
+        expect_that!(history.pop().unwrap().registers.get(0), eq(from_binary(3)));
+        expect_that!(history.pop().unwrap().registers.get(0), eq(from_binary(2)));
+        expect_that!(history.pop().is_none(), eq(true));
+    }
+}
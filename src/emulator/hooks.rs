@@ -0,0 +1,82 @@
+//! Callbacks around every executed instruction, f.e. for profilers, coverage tools or poking
+//! memory, see [`Emulator::set_pre_instruction_hook`](crate::emulator::Emulator::set_pre_instruction_hook).
+use crate::emulator::instruction::Instruction;
+use crate::hardware::memory::{AccessKind, Memory, MemoryAccess};
+use crate::hardware::registers::Registers;
+
+/// Limited access to the machine state passed to instruction hooks.
+pub struct HookContext<'a> {
+    pc: u16,
+    instruction: Instruction,
+    registers: &'a mut Registers,
+    memory: &'a mut Memory,
+    /// Writes of [`HookContext::poke`] in order, to be able to revert them
+    writes: Vec<MemoryAccess>,
+}
+
+/// A callback receiving a [`HookContext`].
+pub type InstructionHook = Box<dyn FnMut(&mut HookContext) + Send>;
+
+impl<'a> HookContext<'a> {
+    pub(crate) const fn new(
+        pc: u16,
+        instruction: Instruction,
+        registers: &'a mut Registers,
+        memory: &'a mut Memory,
+    ) -> Self {
+        Self {
+            pc,
+            instruction,
+            registers,
+            memory,
+            writes: Vec::new(),
+        }
+    }
+    /// Address the instruction was fetched from.
+    #[must_use]
+    pub const fn pc(&self) -> u16 {
+        self.pc
+    }
+    #[must_use]
+    pub const fn instruction(&self) -> Instruction {
+        self.instruction
+    }
+    #[must_use]
+    pub const fn registers(&self) -> &Registers {
+        self.registers
+    }
+    pub const fn registers_mut(&mut self) -> &mut Registers {
+        self.registers
+    }
+    /// Content of `address` without side effects of memory mapped IO.
+    #[must_use]
+    pub fn peek(&self, address: u16) -> u16 {
+        self.memory.peek(address)
+    }
+    /// Sets `address` anywhere in memory to `value` without triggering watchpoints, device
+    /// registers keep working as before since they are not backed by memory.
+    ///
+    /// The write is undone together with the instruction by
+    /// [`Emulator::step_back`](crate::emulator::Emulator::step_back).
+    pub fn poke(&mut self, address: u16, value: u16) {
+        self.writes.push(MemoryAccess {
+            address,
+            kind: AccessKind::Write,
+            old: self.memory.peek(address),
+            new: value,
+        });
+        self.memory.restore(address, value);
+    }
+}
+
+/// Calls `hook` if there is one and returns the memory it wrote.
+pub(crate) fn call(
+    hook: Option<&mut InstructionHook>,
+    mut context: HookContext,
+) -> Vec<MemoryAccess> {
+    hook.map(|hook| {
+        hook(&mut context);
+        context.writes
+    })
+    .unwrap_or_default()
+}
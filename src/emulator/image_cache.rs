@@ -0,0 +1,91 @@
+//! Cache for loaded program images to create many emulators from, f.e. in grading services.
+//!
+//! Reading and validating an object file is done once, afterward every
+//! [`ImageCache::instantiate`] only shares the prepared memory which is copied lazily
+//! on the first write of the respective emulator.
+use crate::emulator::{Emulator, from_program_bytes, read_program_file};
+use crate::errors::LoadProgramError;
+use crate::hardware::keyboard::{KeyboardInputProvider, TerminalInputProvider};
+use crate::hardware::memory::{Memory, MemorySnapshot};
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Mutex};
+
+/// A loaded and validated program image to create fresh [`Emulator`]s from.
+#[derive(Clone)]
+pub struct ImageCache {
+    snapshot: MemorySnapshot,
+}
+
+impl ImageCache {
+    /// Loads and validates the LC-3 object file at `path` once.
+    ///
+    /// # Errors
+    /// - See [`LoadProgramError`]
+    pub fn from_program(path: &str) -> Result<Self, LoadProgramError> {
+        Self::load(read_program_file(path)?.as_slice(), Some(path))
+    }
+
+    fn load(data: &[u16], source: Option<&str>) -> Result<Self, LoadProgramError> {
+        let mut emu = from_program_bytes(data)?;
+        if let Some(source) = source {
+            emu.memory.set_source_of_last_region(source);
+        }
+        Ok(Self {
+            snapshot: emu.memory.snapshot(),
+        })
+    }
+
+    /// Creates an emulator in initial state reading input from the terminal.
+    #[must_use]
+    pub fn instantiate(&self) -> Emulator {
+        self.instantiate_with_input_provider(TerminalInputProvider::new())
+    }
+
+    /// Creates an emulator in initial state reading input from `keyboard_input_provider`.
+    #[must_use]
+    pub fn instantiate_with_input_provider(
+        &self,
+        keyboard_input_provider: impl KeyboardInputProvider + Send + 'static,
+    ) -> Emulator {
+        let kpi = Arc::new(Mutex::new(keyboard_input_provider));
+        let memory = Memory::from_snapshot(&self.snapshot, kpi.clone());
+        Emulator::with_memory(memory, kpi)
+    }
+}
+
+impl Debug for ImageCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ImageCache")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::test_helpers::{FakeKeyboardInputProvider, StringWriter};
+    use crate::hardware::registers::from_binary;
+    use googletest::prelude::*;
+
+    #[gtest]
+    fn test_instances_are_independent() {
+        let cache = ImageCache::from_program("examples/times_ten.obj").unwrap();
+        let mut first = cache.instantiate_with_input_provider(FakeKeyboardInputProvider::new(""));
+        first.memory[0x3007] = 5; // FACTOR
+        first.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(first.registers.get(3), eq(from_binary(50)));
+
+        let mut second = cache.instantiate_with_input_provider(FakeKeyboardInputProvider::new(""));
+        expect_that!(second.memory[0x3007], eq(3));
+        second
+            .execute_with_stdout(&mut StringWriter::new())
+            .unwrap();
+        expect_that!(second.registers.get(3), eq(from_binary(30)));
+    }
+    #[gtest]
+    fn test_load_errors_are_reported() {
+        expect_that!(
+            ImageCache::load(&[0x3000], None),
+            err(eq(&LoadProgramError::ProgramEmpty))
+        );
+    }
+}
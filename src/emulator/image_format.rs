@@ -0,0 +1,243 @@
+//! Container formats of program images besides the `.obj` files written by `lc3as`, see
+//! [`from_program_with_format`](crate::emulator::from_program_with_format).
+use crate::emulator::{lc3tools, words_from_bytes};
+use crate::errors::LoadProgramError;
+use crate::hardware::memory::PROGRAM_SECTION_START;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// How the bytes of a program image are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Words starting with the `.ORIG` address as written by `lc3as`, which writes big-endian
+    /// words unlike some third-party assemblers
+    Obj { byte_order: ByteOrder },
+    /// Intel HEX records of a big-endian image, record addresses are byte addresses, so
+    /// word `x3000` is at byte address `x6000`
+    IntelHex,
+    /// Big-endian words without `.ORIG` address, which is given explicitly
+    RawBinary { origin: u16 },
+    /// Object files of the `lc3tools` suite, which contain the source line of each word
+    Lc3Tools,
+}
+
+/// Order of the two bytes of each word in an object file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// Detect the byte order from the `.ORIG` address, which is `x3000` for programs.
+    ///
+    /// Object files of the `lc3tools` suite are detected as well and read as
+    /// [`ImageFormat::Lc3Tools`].
+    #[default]
+    Auto,
+    BigEndian,
+    LittleEndian,
+}
+
+impl ByteOrder {
+    /// Swaps the bytes of all `words` read as big-endian if they are little-endian.
+    pub(crate) fn normalize(self, mut words: Vec<u16>) -> Vec<u16> {
+        let swap = match self {
+            Self::BigEndian => false,
+            Self::LittleEndian => true,
+            Self::Auto => words.first().is_some_and(|orig| {
+                *orig != PROGRAM_SECTION_START && orig.swap_bytes() == PROGRAM_SECTION_START
+            }),
+        };
+        if swap {
+            for word in &mut words {
+                *word = word.swap_bytes();
+            }
+        }
+        words
+    }
+}
+
+impl ImageFormat {
+    /// An object file with detected byte order.
+    pub const OBJ: Self = Self::Obj {
+        byte_order: ByteOrder::Auto,
+    };
+    /// [`ImageFormat::IntelHex`] for files ending with `.hex` or `.ihex`,
+    /// [`ImageFormat::OBJ`] otherwise.
+    #[must_use]
+    pub fn from_extension(path: &str) -> Self {
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("hex") || e.eq_ignore_ascii_case("ihex") => {
+                Self::IntelHex
+            }
+            _ => Self::OBJ,
+        }
+    }
+    /// Converts `data` to words starting with the `.ORIG` address like an `.obj` file.
+    ///
+    /// # Errors
+    /// - See [`LoadProgramError`]
+    pub fn to_obj_words(self, data: &[u8]) -> Result<Vec<u16>, LoadProgramError> {
+        match self {
+            Self::Obj {
+                byte_order: ByteOrder::Auto,
+            } if data.starts_with(&lc3tools::HEADER[..5]) => lc3tools::parse(data),
+            Self::Obj { byte_order } => Ok(byte_order.normalize(words_from_bytes(data)?)),
+            Self::IntelHex => parse_intel_hex(data),
+            Self::RawBinary { origin } => {
+                let mut words = vec![origin];
+                words.extend(words_from_bytes(data)?);
+                Ok(words)
+            }
+            Self::Lc3Tools => lc3tools::parse(data),
+        }
+    }
+}
+
+fn invalid_hex(line: usize, message: &str) -> LoadProgramError {
+    LoadProgramError::InvalidIntelHex {
+        line,
+        message: message.to_owned(),
+    }
+}
+
+fn parse_intel_hex(data: &[u8]) -> Result<Vec<u16>, LoadProgramError> {
+    let text = str::from_utf8(data).map_err(|_| invalid_hex(1, "not ASCII text"))?;
+    let mut bytes = BTreeMap::new();
+    let mut base = 0u32;
+    let mut ended = false;
+    for (line, record) in (1..).zip(text.lines()) {
+        let record = record.trim();
+        if record.is_empty() {
+            continue;
+        }
+        if ended {
+            return Err(invalid_hex(line, "record after end of file record"));
+        }
+        let raw = record
+            .strip_prefix(':')
+            .and_then(decode_hex)
+            .ok_or_else(|| invalid_hex(line, "expected ':' followed by hex digits"))?;
+        let [len, address_high, address_low, kind, rest @ ..] = raw.as_slice() else {
+            return Err(invalid_hex(line, "record too short"));
+        };
+        if rest.len() != usize::from(*len) + 1 {
+            return Err(invalid_hex(line, "length does not match the record"));
+        }
+        if raw.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0 {
+            return Err(invalid_hex(line, "checksum mismatch"));
+        }
+        let payload = &rest[..usize::from(*len)];
+        let address = base + u32::from(u16::from_be_bytes([*address_high, *address_low]));
+        match (kind, payload) {
+            (0x00, _) => bytes.extend((address..).zip(payload.iter().copied())),
+            (0x01, _) => ended = true,
+            (0x02, [high, low]) => base = u32::from(u16::from_be_bytes([*high, *low])) << 4,
+            (0x04, [high, low]) => base = u32::from(u16::from_be_bytes([*high, *low])) << 16,
+            // start addresses are irrelevant, execution starts at the origin
+            (0x03 | 0x05, _) => {}
+            _ => return Err(invalid_hex(line, "unsupported record")),
+        }
+    }
+    if !ended {
+        return Err(invalid_hex(
+            text.lines().count(),
+            "missing end of file record",
+        ));
+    }
+    let (Some((&first, _)), Some((&last, _))) = (bytes.first_key_value(), bytes.last_key_value())
+    else {
+        return Err(LoadProgramError::ProgramEmpty);
+    };
+    let origin = u16::try_from(first / 2)
+        .ok()
+        .filter(|_| first.is_multiple_of(2))
+        .ok_or_else(|| invalid_hex(1, "image must start at an even byte address below x20000"))?;
+    let mut words = vec![origin];
+    words.extend((first..=last).step_by(2).map(|address| {
+        let byte = |a| bytes.get(&a).copied().unwrap_or_default();
+        u16::from_be_bytes([byte(address), byte(address + 1)])
+    }));
+    Ok(words)
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::read_program_file;
+    use googletest::prelude::*;
+    use yare::parameterized;
+
+    const TIMES_TEN_HEX: &str = "\
+:020000040000FA
+:0860000020062607240516C046
+:0C60080014BF0BFDF0250003000A00008F
+:00000001FF
+";
+
+    #[gtest]
+    fn test_intel_hex() {
+        let expected = read_program_file("examples/times_ten.obj").unwrap();
+        expect_that!(
+            ImageFormat::IntelHex.to_obj_words(TIMES_TEN_HEX.as_bytes()),
+            ok(eq(&expected))
+        );
+    }
+
+    #[parameterized(
+        checksum = { ":0860000020062607240516C047\n:00000001FF", 1, "checksum mismatch" },
+        missing_end = { ":0860000020062607240516C046\n", 1, "missing end of file record" },
+        no_colon = { "0860000020062607240516C046", 1, "expected ':' followed by hex digits" },
+        length = { ":0960000020062607240516C046\n:00000001FF", 1,
+            "length does not match the record" },
+        after_end = { ":00000001FF\n:00000001FF", 2, "record after end of file record" },
+    )]
+    #[test_macro(gtest)]
+    fn test_intel_hex_errors(hex: &str, line: usize, message: &str) {
+        expect_that!(
+            ImageFormat::IntelHex.to_obj_words(hex.as_bytes()),
+            err(eq(&invalid_hex(line, message)))
+        );
+    }
+
+    #[gtest]
+    fn test_little_endian_obj() {
+        let expected = read_program_file("examples/times_ten.obj").unwrap();
+        let little_endian: Vec<u8> = expected.iter().flat_map(|w| w.to_le_bytes()).collect();
+        expect_that!(
+            ImageFormat::OBJ.to_obj_words(&little_endian),
+            ok(eq(&expected))
+        );
+        let explicit = ImageFormat::Obj {
+            byte_order: ByteOrder::LittleEndian,
+        };
+        expect_that!(explicit.to_obj_words(&little_endian), ok(eq(&expected)));
+        let wrong = ImageFormat::Obj {
+            byte_order: ByteOrder::BigEndian,
+        };
+        expect_that!(
+            wrong.to_obj_words(&little_endian).unwrap().first(),
+            some(eq(&0x0030))
+        );
+    }
+
+    #[gtest]
+    fn test_raw_binary() {
+        let res = ImageFormat::RawBinary { origin: 0x3000 }.to_obj_words(&[0xF0, 0x25]);
+        expect_that!(res, ok(elements_are![eq(&0x3000), eq(&0xF025)]));
+        expect_that!(
+            ImageFormat::from_extension("prog.HEX"),
+            eq(ImageFormat::IntelHex)
+        );
+        expect_that!(
+            ImageFormat::from_extension("prog.obj"),
+            eq(ImageFormat::OBJ)
+        );
+    }
+}
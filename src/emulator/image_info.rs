@@ -0,0 +1,139 @@
+//! Metadata of a loaded program image like its address range, likely data and embedded
+//! strings, see [`Emulator::image_info`](crate::emulator::Emulator::image_info).
+//!
+//! Data words are indistinguishable from instructions, so data is only detected where it is
+//! obvious: words marked non-executable, `.STRINGZ` strings and words decoding to `BR` without
+//! condition codes or the reserved opcode, which is what `.FILL` of small numbers and `.BLKW`
+//! produce.
+use crate::emulator::Operation;
+use crate::emulator::instruction::Instruction;
+use crate::hardware::memory::Memory;
+use std::ops::Range;
+
+/// Strings shorter than this are not reported to avoid mistaking small numbers for text.
+const MIN_STRING_LENGTH: usize = 2;
+
+/// A zero-terminated string as emitted by `.STRINGZ`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedString {
+    /// Address of the first character
+    pub address: u16,
+    pub text: String,
+}
+
+/// Overview of the loaded program.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageInfo {
+    /// Address the program is loaded at, its `.ORIG`
+    pub origin: u16,
+    /// Number of words of the program
+    pub length: u16,
+    /// Ranges of the program which obviously hold data instead of instructions
+    pub data_regions: Vec<Range<u16>>,
+    /// Strings found in the program
+    pub strings: Vec<EmbeddedString>,
+}
+
+impl ImageInfo {
+    /// Addresses of the program.
+    #[must_use]
+    pub const fn address_range(&self) -> Range<u16> {
+        self.origin..self.origin + self.length
+    }
+}
+
+pub(crate) fn image_info(memory: &Memory) -> ImageInfo {
+    let origin = memory.program_start();
+    let image = memory.program_slice();
+    let strings = strings(image, origin);
+    let mut data_regions: Vec<Range<u16>> = Vec::new();
+    for (address, &raw) in (origin..).zip(image) {
+        let in_string = strings.iter().any(|s| {
+            let len = u16::try_from(s.text.chars().count()).unwrap_or(u16::MAX);
+            // includes the terminating zero
+            (s.address..=s.address.saturating_add(len)).contains(&address)
+        });
+        if !(in_string || !memory.is_executable(address) || is_obviously_data(raw)) {
+            continue;
+        }
+        match data_regions.last_mut() {
+            Some(region) if region.end == address => region.end += 1,
+            _ => data_regions.push(address..address + 1),
+        }
+    }
+    ImageInfo {
+        origin,
+        length: u16::try_from(image.len()).unwrap_or(u16::MAX),
+        data_regions,
+        strings,
+    }
+}
+
+/// `BR` without condition codes never branches and is what small numbers decode to.
+fn is_obviously_data(raw: u16) -> bool {
+    let i = Instruction::from(raw);
+    match i.operation() {
+        Operation::_Reserved => true,
+        Operation::Br => i.dr_number() == 0,
+        _ => false,
+    }
+}
+
+/// Printable ASCII, whitespace and the escape character starting ANSI control sequences.
+fn is_text(raw: u16) -> bool {
+    u8::try_from(raw)
+        .is_ok_and(|c| c.is_ascii_graphic() || matches!(c, b' ' | b'\n' | b'\r' | b'\t' | 0x1B))
+}
+
+/// Runs of printable characters followed by a zero word.
+fn strings(image: &[u16], origin: u16) -> Vec<EmbeddedString> {
+    let mut res = Vec::new();
+    let mut start = 0;
+    for (idx, &raw) in image.iter().enumerate() {
+        if is_text(raw) {
+            continue;
+        }
+        if raw == 0 && idx - start >= MIN_STRING_LENGTH {
+            res.push(EmbeddedString {
+                address: origin + u16::try_from(start).unwrap_or(u16::MAX),
+                text: String::from_utf16_lossy(&image[start..idx]),
+            });
+        }
+        start = idx + 1;
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator;
+    use crate::emulator::image_info::EmbeddedString;
+    use googletest::prelude::*;
+
+    #[gtest]
+    pub fn test_image_info() {
+        let info = emulator::from_program("examples/hello_world_puts.obj")
+            .unwrap()
+            .image_info();
+        expect_that!(info.address_range(), eq(&(0x3000..0x300F)));
+        expect_that!(info.data_regions, elements_are![eq(&(0x3003..0x300F))]);
+        expect_that!(
+            info.strings,
+            elements_are![eq(&EmbeddedString {
+                address: 0x3003,
+                text: "HelloWorld!".to_owned()
+            })]
+        );
+    }
+    #[gtest]
+    pub fn test_image_info_without_strings() {
+        let info = emulator::from_program("examples/times_ten.obj")
+            .unwrap()
+            .image_info();
+        expect_that!(info.origin, eq(0x3000));
+        expect_that!(info.length, eq(10));
+        expect_that!(info.data_regions, elements_are![eq(&(0x3007..0x300A))]);
+        expect_that!(info.strings, is_empty());
+    }
+}
@@ -0,0 +1,166 @@
+//! Shadow state telling which registers and memory cells hold a value the program wrote or
+//! loaded, see
+//! [`Emulator::set_uninitialized_read_policy`](crate::emulator::Emulator::set_uninitialized_read_policy).
+use crate::emulator::instruction::Instruction;
+use crate::emulator::{Operation, opcodes};
+use crate::hardware::memory::Memory;
+use crate::hardware::registers::Registers;
+
+/// A value read before anything was written to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UninitializedRead {
+    Register(u8),
+    Memory(u16),
+}
+
+#[derive(Clone)]
+pub struct InitTracker {
+    registers: [bool; 8],
+    memory: Vec<bool>,
+}
+
+impl InitTracker {
+    /// All registers are uninitialized, memory is initialized where something was loaded and
+    /// in the device region.
+    pub fn new(memory: &Memory) -> Self {
+        let mut initialized = vec![false; 1 << 16];
+        for region in memory.layout() {
+            initialized[usize::from(region.origin)..region.end() as usize].fill(true);
+        }
+        let devices = &memory.config().device_region;
+        initialized[usize::from(devices.start)..usize::from(devices.end)].fill(true);
+        Self {
+            registers: [false; 8],
+            memory: initialized,
+        }
+    }
+
+    /// Checks the values `i` reads and marks the ones it writes as initialized, the PC being
+    /// already incremented.
+    ///
+    /// Returns the first uninitialized value read, which counts as initialized afterward, so
+    /// that it is only reported once.
+    pub fn on_execute(
+        &mut self,
+        i: Instruction,
+        r: &Registers,
+        memory: &Memory,
+    ) -> Option<UninitializedRead> {
+        let (reads, writes) = register_accesses(i);
+        let [first, second] = opcodes::data_addresses(i, r, memory);
+        let is_store = matches!(
+            i.operation(),
+            Operation::St | Operation::Sti | Operation::Str
+        );
+        let (memory_reads, memory_write) = if is_store {
+            // STI reads the target address from the first one
+            second.map_or(([None, None], first), |target| {
+                ([first, None], Some(target))
+            })
+        } else {
+            ([first, second], None)
+        };
+        let uninitialized = reads
+            .into_iter()
+            .flatten()
+            .find(|reg| !self.registers[usize::from(*reg)])
+            .map(UninitializedRead::Register)
+            .or_else(|| {
+                memory_reads
+                    .into_iter()
+                    .flatten()
+                    .find(|address| !self.memory[usize::from(*address)])
+                    .map(UninitializedRead::Memory)
+            });
+        match uninitialized {
+            Some(UninitializedRead::Register(reg)) => self.registers[usize::from(reg)] = true,
+            Some(UninitializedRead::Memory(address)) => self.memory[usize::from(address)] = true,
+            None => {}
+        }
+        for reg in writes.into_iter().flatten() {
+            self.registers[usize::from(reg)] = true;
+        }
+        if let Some(address) = memory_write {
+            self.memory[usize::from(address)] = true;
+        }
+        uninitialized
+    }
+}
+
+/// Registers read and written by `i`.
+fn register_accesses(i: Instruction) -> ([Option<u8>; 2], [Option<u8>; 2]) {
+    let dr = i.dr_number();
+    let sr1 = i.sr1_number();
+    let second_operand = (!i.is_immediate()).then(|| i.sr2_number());
+    match i.operation() {
+        // AND with #0 clears DR independent of SR1
+        Operation::And if i.is_immediate() && i.get_immediate() == 0 => {
+            ([None, None], [Some(dr), None])
+        }
+        Operation::Add | Operation::And => ([Some(sr1), second_operand], [Some(dr), None]),
+        Operation::Not | Operation::Ldr => ([Some(sr1), None], [Some(dr), None]),
+        Operation::Ld | Operation::Ldi | Operation::Lea => ([None, None], [Some(dr), None]),
+        Operation::St | Operation::Sti => ([Some(dr), None], [None; 2]),
+        Operation::Str => ([Some(dr), Some(sr1)], [None, None]),
+        Operation::JmpOrRet => ([Some(sr1), None], [None, None]),
+        Operation::Jsr if i.get_bit(11) => ([None, None], [Some(7), None]),
+        Operation::Jsr => ([Some(sr1), None], [Some(7), None]),
+        Operation::Trap => match i.get_bit_range(0, 7) {
+            // GETC and IN
+            0x20 | 0x23 => ([None, None], [Some(0), Some(7)]),
+            // OUT, PUTS and PUTSP
+            0x21 | 0x22 | 0x24 => ([Some(0), None], [Some(7), None]),
+            _ => ([None, None], [Some(7), None]),
+        },
+        Operation::Br | Operation::Rti | Operation::_Reserved => ([None, None], [None, None]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::test_helpers::FakeKeyboardInputProvider;
+    use googletest::prelude::*;
+    use std::sync::{Arc, Mutex};
+
+    #[gtest]
+    pub fn test_on_execute() {
+        let mut memory = Memory::new(Arc::new(Mutex::new(FakeKeyboardInputProvider::new(""))));
+        memory.load_program(&[0; 4]).unwrap();
+        let mut tracker = InitTracker::new(&memory);
+        let mut r = Registers::new();
+        r.set_pc(0x3001);
+        // AND R0, R0, #0
+        expect_that!(
+            tracker.on_execute(Instruction::from(0x5020), &r, &memory),
+            none()
+        );
+        // ADD R1, R0, R2
+        expect_that!(
+            tracker.on_execute(Instruction::from(0x1202), &r, &memory),
+            some(eq(UninitializedRead::Register(2)))
+        );
+        expect_that!(
+            tracker.on_execute(Instruction::from(0x1202), &r, &memory),
+            none()
+        );
+        // LD R3, #2 reads the loaded program, LD R3, #3 after it
+        expect_that!(
+            tracker.on_execute(Instruction::from(0x2602), &r, &memory),
+            none()
+        );
+        expect_that!(
+            tracker.on_execute(Instruction::from(0x2603), &r, &memory),
+            some(eq(UninitializedRead::Memory(0x3004)))
+        );
+        // ST R3, #4 initializes the memory read afterward
+        expect_that!(
+            tracker.on_execute(Instruction::from(0x3604), &r, &memory),
+            none()
+        );
+        expect_that!(
+            tracker.on_execute(Instruction::from(0x2604), &r, &memory),
+            none()
+        );
+    }
+}
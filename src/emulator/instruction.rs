@@ -1,5 +1,7 @@
+use crate::emulator::{Operation, disassembler};
 use crate::numbers;
-use std::fmt::{Debug, Formatter};
+use crate::symbols::SymbolTable;
+use std::fmt::{Debug, Display, Formatter};
 
 /// Wrapper for LC-3 u16 instruction.
 /// format is: `OOOO_DDD_P_PPPP_PPPP`
@@ -44,6 +46,12 @@ impl Instruction {
     pub fn op_code(self) -> u8 {
         self.get_bit_range_u8(12, 15, "Error parsing op_code")
     }
+    /// The operation of the opcode, every one of the 4 bit values is one.
+    #[must_use]
+    pub(super) fn operation(self) -> Operation {
+        #[allow(clippy::cast_possible_truncation)]
+        Operation::from((self.0 >> 12) as u8)
+    }
     #[must_use]
     pub fn dr_number(self) -> u8 {
         self.get_bit_range_u8(9, 11, "Error parsing dr")
@@ -60,9 +68,21 @@ impl Instruction {
     pub fn is_immediate(self) -> bool {
         self.get_bit_range(5, 5) == 1
     }
+    #[must_use]
     pub fn get_immediate(self) -> u16 {
         numbers::sign_extend(self.get_bit_range(0, 4), 5)
     }
+    /// Canonical LC-3 assembly of the instruction stored at `address`, f.e. `BRnp LOOP`.
+    ///
+    /// PC relative targets are rendered as label if `symbols` contains one for them,
+    /// otherwise as address like `x3003`.
+    #[must_use]
+    pub fn to_assembly(self, address: u16, symbols: Option<&SymbolTable>) -> String {
+        let no_symbols = SymbolTable::default();
+        let (mnemonic, operands) =
+            disassembler::disassemble_word(address, self.0, symbols.unwrap_or(&no_symbols));
+        join(&mnemonic, &operands)
+    }
     /// Offset to add to program counter PC.
     /// Can be positive or negative.
     #[must_use]
@@ -80,15 +100,49 @@ impl Instruction {
     }
 }
 
+/// Writes the raw value and the assembly, f.e. `x1642 (ADD R3, R1, R2)`.
 impl Debug for Instruction {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Op: {:04b}, DR: {:03b}, PC_Off: {:09b}",
-            self.op_code(),
-            self.dr_number(),
-            self.pc_offset(9)
-        )
+        write!(f, "x{:04X} ({self})", self.0)
+    }
+}
+
+/// Writes canonical LC-3 assembly like `ADD R2, R0, R1` or `LDR R4, R6, #-3`.
+///
+/// Without an address PC relative targets are written as offset, f.e. `BRnp #-3`,
+/// see [`Instruction::to_assembly`] for targets as address or label.
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (mnemonic, operands) = disassembler::decode(self.0, &|offset| format!("#{offset}"));
+        f.write_str(&join(&mnemonic, &operands))
+    }
+}
+
+fn join(mnemonic: &str, operands: &str) -> String {
+    if operands.is_empty() {
+        mnemonic.to_owned()
+    } else {
+        format!("{mnemonic} {operands}")
+    }
+}
+
+/// A word of a loaded region, see
+/// [`Emulator::instructions`](crate::emulator::Emulator::instructions).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadedWord {
+    pub address: u16,
+    pub raw: u16,
+    /// `raw` decoded, which is meaningless for data
+    pub instruction: Instruction,
+}
+
+impl LoadedWord {
+    pub(crate) const fn new(address: u16, raw: u16) -> Self {
+        Self {
+            address,
+            raw,
+            instruction: Instruction(raw),
+        }
     }
 }
 
@@ -98,12 +152,163 @@ impl From<u16> for Instruction {
     }
 }
 
+impl From<Instruction> for u16 {
+    fn from(instruction: Instruction) -> Self {
+        instruction.0
+    }
+}
+
+/// Encoding of instructions, f.e. to construct programs in tests or code generators.
+///
+/// Registers are given by number, offsets are relative to the incremented PC.
+///
+/// # Panics
+/// - a register number is greater than 7
+/// - an immediate value or offset does not fit into its field
+impl Instruction {
+    /// `ADD DR, SR1, SR2`
+    #[must_use]
+    pub const fn add_reg(dr: u8, sr1: u8, sr2: u8) -> Self {
+        Self::arithmetic(0b0001, dr, sr1, reg(sr2))
+    }
+    /// `ADD DR, SR1, #imm5`
+    #[must_use]
+    pub const fn add_imm(dr: u8, sr1: u8, imm5: i16) -> Self {
+        Self::arithmetic(0b0001, dr, sr1, 1 << 5 | field(imm5, 5))
+    }
+    /// `AND DR, SR1, SR2`
+    #[must_use]
+    pub const fn and_reg(dr: u8, sr1: u8, sr2: u8) -> Self {
+        Self::arithmetic(0b0101, dr, sr1, reg(sr2))
+    }
+    /// `AND DR, SR1, #imm5`
+    #[must_use]
+    pub const fn and_imm(dr: u8, sr1: u8, imm5: i16) -> Self {
+        Self::arithmetic(0b0101, dr, sr1, 1 << 5 | field(imm5, 5))
+    }
+    /// `NOT DR, SR`
+    #[must_use]
+    pub const fn not(dr: u8, sr: u8) -> Self {
+        Self(0b1001 << 12 | reg(dr) << 9 | reg(sr) << 6 | 0b11_1111)
+    }
+    /// `BRnzp PCoffset9` branching if one of the set condition codes matches.
+    #[must_use]
+    pub const fn br(n: bool, z: bool, p: bool, offset: i16) -> Self {
+        Self(bit(n, 11) | bit(z, 10) | bit(p, 9) | field(offset, 9))
+    }
+    /// `JMP BaseR`
+    #[must_use]
+    pub const fn jmp(base: u8) -> Self {
+        Self(0b1100 << 12 | reg(base) << 6)
+    }
+    /// `RET`, which is `JMP R7`
+    #[must_use]
+    pub const fn ret() -> Self {
+        Self::jmp(7)
+    }
+    /// `JSR PCoffset11`
+    #[must_use]
+    pub const fn jsr(offset: i16) -> Self {
+        Self(0b0100 << 12 | 1 << 11 | field(offset, 11))
+    }
+    /// `JSRR BaseR`
+    #[must_use]
+    pub const fn jsrr(base: u8) -> Self {
+        Self(0b0100 << 12 | reg(base) << 6)
+    }
+    /// `LD DR, PCoffset9`
+    #[must_use]
+    pub const fn ld(dr: u8, offset: i16) -> Self {
+        Self::pc_relative(0b0010, dr, offset)
+    }
+    /// `LDI DR, PCoffset9`
+    #[must_use]
+    pub const fn ldi(dr: u8, offset: i16) -> Self {
+        Self::pc_relative(0b1010, dr, offset)
+    }
+    /// `LEA DR, PCoffset9`
+    #[must_use]
+    pub const fn lea(dr: u8, offset: i16) -> Self {
+        Self::pc_relative(0b1110, dr, offset)
+    }
+    /// `ST SR, PCoffset9`
+    #[must_use]
+    pub const fn st(sr: u8, offset: i16) -> Self {
+        Self::pc_relative(0b0011, sr, offset)
+    }
+    /// `STI SR, PCoffset9`
+    #[must_use]
+    pub const fn sti(sr: u8, offset: i16) -> Self {
+        Self::pc_relative(0b1011, sr, offset)
+    }
+    /// `LDR DR, BaseR, offset6`
+    #[must_use]
+    pub const fn ldr(dr: u8, base: u8, offset: i16) -> Self {
+        Self::base_relative(0b0110, dr, base, offset)
+    }
+    /// `STR SR, BaseR, offset6`
+    #[must_use]
+    pub const fn str(sr: u8, base: u8, offset: i16) -> Self {
+        Self::base_relative(0b0111, sr, base, offset)
+    }
+    /// `RTI`
+    #[must_use]
+    pub const fn rti() -> Self {
+        Self(0b1000 << 12)
+    }
+    /// `TRAP trapvect8`
+    #[must_use]
+    pub const fn trap(vector: u8) -> Self {
+        Self(0b1111 << 12 | vector as u16)
+    }
+    const fn arithmetic(op_code: u16, dr: u8, sr1: u8, operand: u16) -> Self {
+        Self(op_code << 12 | reg(dr) << 9 | reg(sr1) << 6 | operand)
+    }
+    const fn pc_relative(op_code: u16, r: u8, offset: i16) -> Self {
+        Self(op_code << 12 | reg(r) << 9 | field(offset, 9))
+    }
+    const fn base_relative(op_code: u16, r: u8, base: u8, offset: i16) -> Self {
+        Self(op_code << 12 | reg(r) << 9 | reg(base) << 6 | field(offset, 6))
+    }
+}
+
+/// Register number as field.
+const fn reg(r: u8) -> u16 {
+    assert!(r < 8, "register number greater than 7");
+    r as u16
+}
+
+/// `value` as two's complement in the lowest `bits` bits.
+#[expect(
+    clippy::cast_sign_loss,
+    reason = "range checked, masking the two's complement representation"
+)]
+const fn field(value: i16, bits: u32) -> u16 {
+    let limit = 1 << (bits - 1);
+    assert!(
+        -limit <= value && value < limit,
+        "immediate value or offset does not fit into its field"
+    );
+    (value as u16) & ((1 << bits) - 1)
+}
+
+const fn bit(set: bool, index: u32) -> u16 {
+    if set { 1 << index } else { 0 }
+}
+
 #[expect(clippy::unusual_byte_groupings)]
 #[cfg(test)]
 mod tests {
     use super::*;
     use googletest::prelude::*;
 
+    #[gtest]
+    pub fn test_operation_of_every_opcode() {
+        for op_code in 0..16u8 {
+            let i = Instruction::from(u16::from(op_code) << 12 | 0x0FFF);
+            expect_that!(i.operation() as u8, eq(op_code));
+        }
+    }
     #[gtest]
     pub fn test_instr_get_bit_range_valid() {
         let sut = Instruction::from(0b1010_101_001010101);
@@ -129,6 +334,79 @@ mod tests {
         expect_that!(sut.get_immediate(), eq(14));
     }
     #[gtest]
+    pub fn test_operation() {
+        for op_code in 0..16_u16 {
+            let sut = Instruction::from(op_code << 12 | 0x0ABC);
+            expect_that!(sut.operation() as u8, eq(sut.op_code()));
+        }
+        expect_that!(Instruction::from(0xF025).operation(), eq(Operation::Trap));
+    }
+    #[gtest]
+    pub fn test_encode() {
+        expect_that!(
+            Instruction::add_reg(3, 2, 1),
+            eq(Instruction::from(0b0001_011_010_0_00_001))
+        );
+        expect_that!(
+            Instruction::add_imm(7, 0, 14),
+            eq(Instruction::from(0b0001_111_000_1_01110))
+        );
+        expect_that!(u16::from(Instruction::and_imm(2, 0, -11)), eq(0x5435));
+        expect_that!(u16::from(Instruction::not(1, 0)), eq(0x923F));
+        expect_that!(u16::from(Instruction::br(true, true, true, -1)), eq(0x0FFF));
+        expect_that!(
+            u16::from(Instruction::br(false, true, false, 255)),
+            eq(0x04FF)
+        );
+        expect_that!(u16::from(Instruction::ret()), eq(0xC1C0));
+        expect_that!(u16::from(Instruction::jsr(-1024)), eq(0x4C00));
+        expect_that!(u16::from(Instruction::jsrr(3)), eq(0x40C0));
+        expect_that!(u16::from(Instruction::ld(4, -68)), eq(0x29BC));
+        expect_that!(u16::from(Instruction::ldi(1, 1)), eq(0xA201));
+        expect_that!(u16::from(Instruction::lea(0, 2)), eq(0xE002));
+        expect_that!(u16::from(Instruction::st(3, 4)), eq(0x3604));
+        expect_that!(u16::from(Instruction::sti(0, 0)), eq(0xB000));
+        expect_that!(u16::from(Instruction::ldr(2, 6, -32)), eq(0x65A0));
+        expect_that!(u16::from(Instruction::str(2, 6, 1)), eq(0x7581));
+        expect_that!(u16::from(Instruction::rti()), eq(0x8000));
+        expect_that!(u16::from(Instruction::trap(0x25)), eq(0xF025));
+    }
+    #[gtest]
+    pub fn test_display() {
+        expect_that!(
+            Instruction::add_reg(2, 0, 1).to_string(),
+            eq("ADD R2, R0, R1")
+        );
+        expect_that!(
+            Instruction::ldr(4, 6, -3).to_string(),
+            eq("LDR R4, R6, #-3")
+        );
+        expect_that!(
+            Instruction::br(true, false, true, -3).to_string(),
+            eq("BRnp #-3")
+        );
+        expect_that!(Instruction::trap(0x25).to_string(), eq("HALT"));
+        expect_that!(
+            format!("{:?}", Instruction::add_reg(3, 1, 2)),
+            eq("x1642 (ADD R3, R1, R2)")
+        );
+        let br = Instruction::br(true, false, true, -3);
+        expect_that!(br.to_assembly(0x3006, None), eq("BRnp x3004"));
+        let mut symbols = SymbolTable::default();
+        symbols.insert("LOOP", 0x3004);
+        expect_that!(br.to_assembly(0x3006, Some(&symbols)), eq("BRnp LOOP"));
+    }
+    #[gtest]
+    #[should_panic(expected = "immediate value or offset does not fit into its field")]
+    pub fn test_encode_offset_too_large() {
+        let _ = Instruction::ldr(0, 0, 32);
+    }
+    #[gtest]
+    #[should_panic(expected = "register number greater than 7")]
+    pub fn test_encode_invalid_register() {
+        let _ = Instruction::add_reg(8, 0, 0);
+    }
+    #[gtest]
     #[should_panic(expected = "wrong direction of from: 2 and to: 1")]
     pub fn test_instr_get_bit_range_wrong_order() {
         let sut = Instruction::from(0b1010_101_101010101);
@@ -0,0 +1,96 @@
+//! Interrupt controller collecting the interrupt requests of all devices.
+//!
+//! An interrupt is only taken if its priority is higher than the priority of the running
+//! program, see [`Registers::priority`](crate::hardware::registers::Registers::priority).
+
+/// Start of the interrupt vector table, an interrupt with vector `v` runs the routine whose
+/// address is stored at `INTERRUPT_VECTOR_TABLE + v`.
+pub const INTERRUPT_VECTOR_TABLE: u16 = 0x0100;
+
+/// Interrupt request of a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interrupt {
+    /// Index into the interrupt vector table
+    pub vector: u8,
+    /// Priority level `PL0` to `PL7`
+    pub priority: u8,
+}
+
+/// The keyboard interrupts with `PL4` when a key is ready and interrupts are enabled in `KBSR`.
+pub const KEYBOARD_INTERRUPT: Interrupt = Interrupt {
+    vector: 0x80,
+    priority: 4,
+};
+
+/// The serial line interrupts with `PL4` when a byte arrived and interrupts are enabled in the
+/// serial receive status register.
+pub const SERIAL_INTERRUPT: Interrupt = Interrupt {
+    vector: 0x82,
+    priority: 4,
+};
+
+/// Pending interrupt requests in the order they were raised.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct InterruptController {
+    pending: Vec<Interrupt>,
+}
+
+impl InterruptController {
+    /// Marks `interrupt` pending until it is taken, raising it again meanwhile has no effect.
+    pub fn raise(&mut self, interrupt: Interrupt) {
+        if !self.pending.contains(&interrupt) {
+            self.pending.push(interrupt);
+        }
+    }
+    /// Sets whether a device signalling its interrupt as long as a condition holds, like the
+    /// keyboard, currently requests `interrupt`.
+    pub fn set_level(&mut self, interrupt: Interrupt, requested: bool) {
+        if requested {
+            self.raise(interrupt);
+        } else {
+            self.pending.retain(|pending| *pending != interrupt);
+        }
+    }
+    /// Removes and returns the pending interrupt with the highest priority if it is higher than
+    /// `current_priority`, the earliest raised one of equal priorities.
+    pub fn take(&mut self, current_priority: u8) -> Option<Interrupt> {
+        let (idx, _) = self
+            .pending
+            .iter()
+            .enumerate()
+            .filter(|(_, interrupt)| interrupt.priority > current_priority)
+            .min_by_key(|(idx, interrupt)| (std::cmp::Reverse(interrupt.priority), *idx))?;
+        Some(self.pending.remove(idx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+
+    const TIMER: Interrupt = Interrupt {
+        vector: 0x81,
+        priority: 6,
+    };
+
+    #[gtest]
+    pub fn test_take_by_priority() {
+        let mut controller = InterruptController::default();
+        controller.raise(KEYBOARD_INTERRUPT);
+        controller.raise(TIMER);
+        controller.raise(TIMER);
+        expect_that!(controller.take(6), none());
+        expect_that!(controller.take(0), some(eq(TIMER)));
+        expect_that!(controller.take(0), some(eq(KEYBOARD_INTERRUPT)));
+        expect_that!(controller.take(0), none());
+    }
+
+    #[gtest]
+    pub fn test_set_level() {
+        let mut controller = InterruptController::default();
+        controller.set_level(KEYBOARD_INTERRUPT, true);
+        controller.set_level(KEYBOARD_INTERRUPT, false);
+        expect_that!(controller.take(0), none());
+    }
+}
@@ -0,0 +1,261 @@
+//! Instructions of the LC-3b, the byte-addressable variant of the LC-3.
+//!
+//! Memory cells keep holding 16 bit words, but only those at even addresses are used: the word at
+//! address `a` contains byte `a` in its low and byte `a + 1` in its high half.
+//! The device registers stay at their LC-3 addresses this way.
+use crate::emulator::instruction::Instruction;
+use crate::emulator::{BrNzpZeroBehavior, Operation, opcodes};
+use crate::errors::ExecutionError;
+use crate::hardware::memory::Memory;
+use crate::hardware::registers::{Registers, from_binary};
+
+/// Difference of the addresses of consecutive words.
+pub const WORD_SIZE: u16 = 2;
+
+/// Spreads `words` to even addresses by following each one with an unused cell.
+pub fn spread_words(words: &[u16]) -> Vec<u16> {
+    words.iter().flat_map(|word| [*word, 0]).collect()
+}
+
+/// Byte at `address` of the `word` stored at the even address next to it.
+const fn byte_of(word: u16, address: u16) -> u8 {
+    let [low, high] = word.to_le_bytes();
+    if address & 1 == 0 { low } else { high }
+}
+
+/// Byte at `address` without notifying memory observers.
+pub fn read_byte(memory: &Memory, address: u16) -> u8 {
+    byte_of(memory[address & !1], address)
+}
+
+fn write_byte(memory: &mut Memory, address: u16, value: u8) {
+    let [low, high] = memory[address & !1].to_le_bytes();
+    let word = if address & 1 == 0 {
+        [value, high]
+    } else {
+        [low, value]
+    };
+    memory.write(address & !1, u16::from_le_bytes(word));
+}
+
+/// Executes `i` if it differs from the LC-3 instruction with the same opcode.
+///
+/// # Result
+/// - [`None`] if the instruction is executed like on the LC-3, f.e. `ADD` or `TRAP`
+pub fn execute(
+    i: Instruction,
+    r: &mut Registers,
+    memory: &mut Memory,
+    no_flags: BrNzpZeroBehavior,
+) -> Option<Result<(), ExecutionError>> {
+    match i.operation() {
+        Operation::Br => br(i, r, no_flags),
+        Operation::Jsr => jsr(i, r),
+        Operation::Ld => ldb(i, r, memory),
+        Operation::St => stb(i, r, memory),
+        Operation::Ldr => ldw(i, r, memory),
+        Operation::Str => stw(i, r, memory),
+        Operation::Not => xor(i, r),
+        Operation::_Reserved => shf(i, r),
+        Operation::Lea => lea(i, r),
+        // the entries of the Supervisor stack are words of two bytes
+        Operation::Rti => return Some(opcodes::rti(i, r, memory, WORD_SIZE)),
+        Operation::Ldi | Operation::Sti => {
+            return Some(Err(ExecutionError::ReservedInstructionFound(i.op_code())));
+        }
+        Operation::Add | Operation::And | Operation::JmpOrRet | Operation::Trap => return None,
+    }
+    Some(Ok(()))
+}
+
+/// `PC` plus the sign extended word offset in the lowest `len` bits.
+fn address_by_pc_offset(i: Instruction, r: &Registers, len: u8) -> u16 {
+    r.pc()
+        .as_binary()
+        .wrapping_add_signed(i.pc_offset(len) << 1)
+}
+
+fn base_address(i: Instruction, r: &Registers) -> u16 {
+    r.get(i.get_bit_range_u8(6, 8, "Error in base_address"))
+        .as_binary()
+}
+
+/// BR: Conditional Branch to `PC` plus the word offset `PCoffset9`.
+fn br(i: Instruction, r: &mut Registers, no_flags: BrNzpZeroBehavior) {
+    if opcodes::is_branch_taken(i, r, no_flags) {
+        r.set_pc(address_by_pc_offset(i, r, 9));
+    }
+}
+
+/// JSR with the word offset `PCoffset11`, JSRR like on the LC-3.
+fn jsr(i: Instruction, r: &mut Registers) {
+    let target = if i.get_bit(11) {
+        address_by_pc_offset(i, r, 11)
+    } else {
+        base_address(i, r)
+    };
+    r.set(7, r.pc());
+    r.set_pc(target);
+}
+
+/// LDB: Load the sign extended byte at `BaseR` plus the byte offset `boffset6`.
+/// ```text
+///  15__12__11_9__8___6____5____0_
+/// | 0010 |  DR | BaseR | boffset6 |
+///  ------------------------------
+/// ```
+fn ldb(i: Instruction, r: &mut Registers, memory: &Memory) {
+    let address = base_address(i, r).wrapping_add_signed(i.pc_offset(6));
+    let byte = byte_of(memory.read(address & !1), address);
+    r.set(
+        i.dr_number(),
+        from_binary(i16::from(byte.cast_signed()).cast_unsigned()),
+    );
+    r.update_conditional_register(i.dr_number());
+}
+
+/// STB: Store the low byte of SR at `BaseR` plus the byte offset `boffset6`.
+/// ```text
+///  15__12__11_9__8___6____5____0_
+/// | 0011 |  SR | BaseR | boffset6 |
+///  ------------------------------
+/// ```
+fn stb(i: Instruction, r: &Registers, memory: &mut Memory) {
+    let address = base_address(i, r).wrapping_add_signed(i.pc_offset(6));
+    let [low, _] = r.get(i.dr_number()).as_binary().to_le_bytes();
+    write_byte(memory, address, low);
+}
+
+/// LDW: Load the word at `BaseR` plus the word offset `offset6`.
+/// ```text
+///  15__12__11_9__8___6____5____0_
+/// | 0110 |  DR | BaseR | offset6 |
+///  ------------------------------
+/// ```
+fn ldw(i: Instruction, r: &mut Registers, memory: &Memory) {
+    let address = base_address(i, r).wrapping_add_signed(i.pc_offset(6) << 1);
+    r.set(i.dr_number(), from_binary(memory.read(address & !1)));
+    r.update_conditional_register(i.dr_number());
+}
+
+/// STW: Store SR at `BaseR` plus the word offset `offset6`.
+/// ```text
+///  15__12__11_9__8___6____5____0_
+/// | 0111 |  SR | BaseR | offset6 |
+///  ------------------------------
+/// ```
+fn stw(i: Instruction, r: &Registers, memory: &mut Memory) {
+    let address = base_address(i, r).wrapping_add_signed(i.pc_offset(6) << 1);
+    memory.write(address & !1, r.get(i.dr_number()).as_binary());
+}
+
+/// XOR: bit-wise exclusive or of SR1 with SR2 or the sign extended `imm5`, `NOT` is
+/// `XOR DR, SR, #-1`.
+/// ```text
+///  15__12__11_9__8_6___5___4_3__2_0_
+/// | 1001 |  DR | SR1 | 0 | 00 | SR2 |
+///  ---------------------------------
+/// ```
+fn xor(i: Instruction, r: &mut Registers) {
+    let operand = if i.is_immediate() {
+        i.get_immediate()
+    } else {
+        r.get(i.sr2_number()).as_binary()
+    };
+    r.set(
+        i.dr_number(),
+        from_binary(r.get(i.sr1_number()).as_binary() ^ operand),
+    );
+    r.update_conditional_register(i.dr_number());
+}
+
+/// SHF: Shift SR by `amount4` bits, left if bit 4 is clear, otherwise right filling with zeros
+/// or, if bit 5 is set, with the sign bit.
+/// ```text
+///  15__12__11_9__8_6___5___4___3____0_
+/// | 1101 |  DR |  SR | A | D | amount4 |
+///  -----------------------------------
+/// ```
+fn shf(i: Instruction, r: &mut Registers) {
+    let value = r.get(i.sr1_number()).as_binary();
+    let amount = i.get_bit_range(0, 3);
+    let result = match (i.get_bit(4), i.get_bit(5)) {
+        (false, _) => value << amount,
+        (true, false) => value >> amount,
+        (true, true) => (value.cast_signed() >> amount).cast_unsigned(),
+    };
+    r.set(i.dr_number(), from_binary(result));
+    r.update_conditional_register(i.dr_number());
+}
+
+/// LEA: Load `PC` plus the word offset `PCoffset9` into DR without setting the condition codes.
+fn lea(i: Instruction, r: &mut Registers) {
+    r.set(i.dr_number(), from_binary(address_by_pc_offset(i, r, 9)));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator::test_helpers::StringWriter;
+    use crate::emulator::{EmulatorBuilder, IsaVariant, StepResult};
+    use crate::errors::ExecutionError;
+    use crate::hardware::registers::from_binary;
+    use googletest::prelude::*;
+
+    #[gtest]
+    pub fn test_lc3b_program() {
+        let program = [
+            0x3000, // .ORIG x3000
+            0xE208, // LEA R1, DATA
+            0x2441, // LDB R2, R1, #1
+            0xD682, // LSHF R3, R2, #2
+            0x98FF, // XOR R4, R3, #-1
+            0x3442, // STB R2, R1, #2
+            0x6A41, // LDW R5, R1, #1
+            0xE002, // LEA R0, DATA
+            0xF022, // PUTS
+            0xF025, // HALT
+            0x4241, // DATA: "AB"
+            0x0000,
+        ];
+        let mut emu = EmulatorBuilder::new(&program)
+            .with_isa_variant(IsaVariant::Lc3b)
+            .build()
+            .unwrap();
+        let mut stdout = StringWriter::new();
+        emu.execute_with_stdout(&mut stdout).unwrap();
+        expect_that!(stdout.get_string(), starts_with("ABB"));
+        expect_that!(emu.registers().get(1), eq(from_binary(0x3012)));
+        expect_that!(emu.registers().get(2), eq(from_binary(0x0042)));
+        expect_that!(emu.registers().get(3), eq(from_binary(0x0108)));
+        expect_that!(emu.registers().get(4), eq(from_binary(0xFEF7)));
+        expect_that!(emu.registers().get(5), eq(from_binary(0x0042)));
+        expect_that!(emu.registers().pc(), eq(from_binary(0x3012)));
+    }
+    #[gtest]
+    pub fn test_lc3b_reports_address_of_current_instruction() {
+        let program = [
+            0x3000, // .ORIG x3000
+            0x1020, // ADD R0, R0, #0
+            0xF025, // HALT
+            0x8000, // RTI
+        ];
+        let mut emu = EmulatorBuilder::new(&program)
+            .with_isa_variant(IsaVariant::Lc3b)
+            .build()
+            .unwrap();
+        emu.break_on_trap(0x25);
+        let mut stdout = StringWriter::new();
+        expect_that!(
+            emu.run_budgeted(10, &mut stdout),
+            ok(eq(&StepResult::TrapEntered {
+                pc: 0x3002,
+                vector: 0x25
+            }))
+        );
+        emu.set_pc(0x3004).unwrap();
+        expect_that!(
+            emu.run_budgeted(10, &mut stdout),
+            err(eq(&ExecutionError::PrivilegeViolation(0x3004)))
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! Object files written by the assembler of the `lc3tools` suite.
+//!
+//! After a magic header and a version each memory cell is stored as little-endian word, a flag
+//! telling whether the word is an `.ORIG` address, and the source line it was assembled from.
+use crate::errors::LoadProgramError;
+
+/// Magic bytes followed by the supported version `1.1`.
+pub const HEADER: [u8; 7] = [0x1C, 0x30, 0x15, 0xC0, 0x01, 0x01, 0x01];
+
+fn invalid(offset: usize, message: &str) -> LoadProgramError {
+    LoadProgramError::InvalidLc3ToolsObject {
+        offset,
+        message: message.to_owned(),
+    }
+}
+
+/// Converts an `lc3tools` object file to words starting with the `.ORIG` address.
+///
+/// Several `.ORIG` sections are only supported if each one starts right after the previous one.
+pub fn parse(data: &[u8]) -> Result<Vec<u16>, LoadProgramError> {
+    let mut rest = data
+        .strip_prefix(HEADER.as_slice())
+        .ok_or_else(|| invalid(0, "missing lc3tools header of version 1.1"))?;
+    let mut words = Vec::new();
+    let mut next_address: Option<u16> = None;
+    while !rest.is_empty() {
+        let offset = data.len() - rest.len();
+        let Some(([low, high, is_orig, len @ ..], tail)) = rest.split_first_chunk::<7>() else {
+            return Err(invalid(offset, "truncated memory cell"));
+        };
+        let value = u16::from_le_bytes([*low, *high]);
+        let line_len = usize::try_from(u32::from_le_bytes(*len))
+            .map_err(|_| invalid(offset, "source line too long"))?;
+        rest = tail
+            .get(line_len..)
+            .ok_or_else(|| invalid(offset, "truncated source line"))?;
+        match (*is_orig != 0, next_address) {
+            (true, None) | (false, Some(_)) => words.push(value),
+            (true, Some(address)) if address == value => {}
+            (true, Some(_)) => return Err(invalid(offset, "non-contiguous .ORIG sections")),
+            (false, None) => return Err(invalid(offset, "memory cell before .ORIG")),
+        }
+        next_address = Some(if *is_orig != 0 {
+            value
+        } else {
+            next_address.unwrap_or_default().wrapping_add(1)
+        });
+    }
+    if words.is_empty() {
+        return Err(LoadProgramError::ProgramEmpty);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::image_format::ImageFormat;
+    use crate::emulator::read_program_file;
+    use googletest::prelude::*;
+
+    /// Encodes `words` like `lc3tools` does, the first one being the `.ORIG` address.
+    fn encode(words: &[u16]) -> Vec<u8> {
+        let mut data = HEADER.to_vec();
+        for (i, word) in words.iter().enumerate() {
+            let line = format!("line {i}");
+            data.extend(word.to_le_bytes());
+            data.push(u8::from(i == 0));
+            data.extend(u32::try_from(line.len()).unwrap().to_le_bytes());
+            data.extend(line.as_bytes());
+        }
+        data
+    }
+
+    #[gtest]
+    fn test_parse() {
+        let expected = read_program_file("examples/times_ten.obj").unwrap();
+        expect_that!(parse(&encode(&expected)), ok(eq(&expected)));
+        expect_that!(
+            ImageFormat::OBJ.to_obj_words(&encode(&expected)),
+            ok(eq(&expected))
+        );
+    }
+
+    #[gtest]
+    fn test_parse_errors() {
+        let data = encode(&[0x3000, 0xF025]);
+        expect_that!(
+            parse(&data[1..]),
+            err(eq(&invalid(0, "missing lc3tools header of version 1.1")))
+        );
+        expect_that!(
+            parse(&data[..data.len() - 1]),
+            err(eq(&invalid(20, "truncated source line")))
+        );
+        let mut two_sections = data;
+        two_sections.extend(&encode(&[0x4000])[HEADER.len()..]);
+        expect_that!(
+            parse(&two_sections),
+            err(eq(&invalid(33, "non-contiguous .ORIG sections")))
+        );
+        expect_that!(parse(&HEADER), err(eq(&LoadProgramError::ProgramEmpty)));
+    }
+}
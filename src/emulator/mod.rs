@@ -1,29 +1,107 @@
+#[cfg(feature = "async")]
+pub mod async_runner;
+pub mod bench;
+pub mod breakpoints;
+pub mod call_stack;
+mod checkpoints;
+mod clock;
+pub mod coverage;
+pub mod diagnostics;
+pub mod disassembler;
+pub mod event_log;
+pub mod events;
+mod explain;
+pub mod framebuffer;
+mod history;
+pub mod hooks;
+pub mod image_cache;
+pub mod image_format;
+pub mod image_info;
+mod init_tracking;
 mod instruction;
+pub mod interrupts;
+mod lc3b;
+mod lc3tools;
 mod opcodes;
+pub mod profiler;
+pub mod replay;
+pub mod snapshot;
+pub mod stats;
 pub mod stdout_helpers;
+pub mod summary;
 #[cfg(test)]
-mod test_helpers;
+pub(crate) mod test_helpers;
+pub mod trace;
 mod trap_routines;
+pub mod validation;
+pub mod vcd;
 
-use crate::emulator::stdout_helpers::CrosstermCompatibility;
-use crate::errors::{ExecutionError, LoadProgramError};
-use crate::hardware::keyboard::{KeyboardInputProvider, TerminalInputProvider};
-use crate::hardware::memory::{Memory, PROGRAM_SECTION_START};
-use crate::hardware::registers::{Registers, from_binary};
+use crate::emulator::bench::BenchReport;
+use crate::emulator::breakpoints::{Breakpoint, BreakpointHit, BreakpointId, Breakpoints};
+use crate::emulator::call_stack::{CallStack, CallStackChange, Frame};
+use crate::emulator::checkpoints::{Checkpoint, Checkpoints};
+use crate::emulator::clock::Clock;
+use crate::emulator::coverage::{Coverage, CoverageReport};
+use crate::emulator::diagnostics::Diagnostic;
+use crate::emulator::disassembler::DisasmLine;
+use crate::emulator::event_log::{EventLog, EventLogSink, SessionEvent};
+use crate::emulator::events::{EventBus, EventMask, MachineEvent, SubscriptionId};
+use crate::emulator::framebuffer::{DisplayRenderer, Framebuffer};
+use crate::emulator::history::{History, HistoryEntry};
+use crate::emulator::hooks::{HookContext, InstructionHook};
+use crate::emulator::image_format::{ByteOrder, ImageFormat};
+use crate::emulator::image_info::ImageInfo;
+use crate::emulator::init_tracking::{InitTracker, UninitializedRead};
+use crate::emulator::interrupts::{
+    INTERRUPT_VECTOR_TABLE, Interrupt, InterruptController, KEYBOARD_INTERRUPT, SERIAL_INTERRUPT,
+};
+use crate::emulator::profiler::{HotAddress, Profiler};
+use crate::emulator::replay::{InputRecording, RecordingInputProvider, ReplayInputProvider};
+use crate::emulator::snapshot::{MachineSnapshot, MachineState};
+use crate::emulator::stats::ExecutionStats;
+use crate::emulator::stdout_helpers::{
+    CrosstermCompatibility, OutputErrorPolicy, PolicyWriter, VirtualConsole,
+};
+use crate::emulator::trace::{TraceEvent, TraceSink};
+use crate::emulator::validation::LoadWarning;
+use crate::errors::{
+    ExecutionError, LoadProgramError, ReplayError, StateAccessError, SymbolTableError,
+};
+use crate::hardware::keyboard::{
+    ChaosInputProvider, InputQueue, KeyboardInputProvider, QueuedInputProvider,
+    ScriptedInputProvider, SharedInputProvider, TerminalInputProvider,
+};
+use crate::hardware::memory::{
+    EmulatorConfig, LoadedRegion, Memory, MemoryAccess, MemoryObserver, MemorySnapshot,
+    PROGRAM_SECTION_START, WatchKind,
+};
+use crate::hardware::registers::{Privilege, Registers, from_binary};
+use crate::hardware::serial::SerialDevice;
+use crate::symbols::SymbolTable;
+use crate::sync::lock;
 use crate::terminal;
-use instruction::Instruction;
-use std::cell::RefCell;
+pub use instruction::{Instruction, LoadedWord};
+use std::collections::BTreeSet;
 use std::fmt::{Debug, Formatter};
 use std::fs::File;
 use std::io;
 use std::io::{BufReader, Read, Write};
-use std::ops::ControlFlow;
-use std::rc::Rc;
+use std::num::NonZeroU32;
+use std::ops::{ControlFlow, Range, RangeInclusive};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::Waker;
+use std::time::{Duration, Instant};
+use trap_routines::InputMode;
 
 const ORIG_HEADER: u16 = PROGRAM_SECTION_START;
+/// Address in the interrupt vector table holding the access control violation handler address
+const ACV_EXCEPTION_VECTOR: u16 = 0x0102;
 
 #[rustfmt::skip]
-#[derive(Debug)]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy)]
 #[derive(PartialEq, Eq)]
 enum Operation {
     Br   = 0b0000,
@@ -44,54 +122,761 @@ enum Operation {
     Trap = 0b1111,
 }
 
+impl From<u8> for Operation {
+    /// The operation of the opcode in the lowest 4 bits of `op_code`, higher bits are ignored.
+    fn from(op_code: u8) -> Self {
+        match op_code & 0xF {
+            0b0000 => Self::Br,
+            0b0001 => Self::Add,
+            0b0010 => Self::Ld,
+            0b0011 => Self::St,
+            0b0100 => Self::Jsr,
+            0b0101 => Self::And,
+            0b0110 => Self::Ldr,
+            0b0111 => Self::Str,
+            0b1000 => Self::Rti,
+            0b1001 => Self::Not,
+            0b1010 => Self::Ldi,
+            0b1011 => Self::Sti,
+            0b1100 => Self::JmpOrRet,
+            0b1101 => Self::_Reserved,
+            0b1110 => Self::Lea,
+            _ => Self::Trap,
+        }
+    }
+}
+
+/// What happens when an instruction is fetched from memory marked as non-executable.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NxPolicy {
+    /// Record a [`Diagnostic`] and continue execution.
+    #[default]
+    Warn,
+    /// Stop execution with [`ExecutionError::NonExecutableMemory`].
+    Error,
+}
+
+/// What happens when a program running in User mode accesses memory outside the user space,
+/// which includes the device registers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AcvPolicy {
+    /// Allow the access, programs polling the device registers in User mode rely on this.
+    #[default]
+    Unchecked,
+    /// Stop execution with [`ExecutionError::MemoryAccessViolation`].
+    Error,
+    /// Raise the access control violation exception: the instruction is skipped and the handler
+    /// stored at `x0102` of the interrupt vector table runs in Supervisor mode.
+    Exception,
+}
+
+/// Instruction set architecture executed by the emulator, see [`EmulatorBuilder::with_isa_variant`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum IsaVariant {
+    /// The LC-3 with word-addressable memory.
+    #[default]
+    Lc3,
+    /// The LC-3b with byte-addressable memory, `LDB`/`STB`, `LDW`/`STW`, `XOR` and `SHF`.
+    ///
+    /// Program words are placed at even addresses, so the `.obj` words are the LC-3b words in
+    /// order. `LDI`/`STI` do not exist, access control checks and tools decoding instructions
+    /// like the disassembler use the LC-3 encoding.
+    Lc3b,
+}
+
+impl IsaVariant {
+    /// Difference of the addresses of consecutive words.
+    const fn word_size(self) -> u16 {
+        match self {
+            Self::Lc3 => 1,
+            Self::Lc3b => lc3b::WORD_SIZE,
+        }
+    }
+}
+
+/// What happens when an instruction of the loaded program is executed after the program wrote
+/// to it, see [`Emulator::modified_instructions`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SelfModifyingCodePolicy {
+    /// Execute modified instructions without tracking them.
+    #[default]
+    Allow,
+    /// Record a [`Diagnostic`] and continue execution.
+    Warn,
+    /// Stop execution with [`ExecutionError::SelfModifyingCode`].
+    Deny,
+}
+
+/// What happens when an instruction reads a register or memory cell before the program wrote a
+/// value to it or loaded one there, which usually means a missing initialization.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UninitializedReadPolicy {
+    /// Do not track initialization.
+    #[default]
+    Allow,
+    /// Record a [`Diagnostic`] for the first read of each value and continue execution.
+    Warn,
+    /// Stop execution with [`ExecutionError::UninitializedRegisterRead`] or
+    /// [`ExecutionError::UninitializedMemoryRead`].
+    Error,
+}
+
+/// How `TRAP` instructions are executed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TrapMode {
+    /// Run the trap routines built into the emulator.
+    #[default]
+    Native,
+    /// Jump to the address in the trap vector table of the operating system image loaded by
+    /// [`Emulator::load_os`], saving the return address in `R7`.
+    Vectored,
+}
+
+/// How time passes for the program.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TimeMode {
+    /// Wall-clock time, f.e. for the clock set by [`Emulator::set_clock`] and
+    /// [`Emulator::run_for_duration`], with keyboard input arriving whenever it is typed.
+    #[default]
+    RealTime,
+    /// Virtual time advanced by the cycles of every executed instruction, so that runs with the
+    /// same scripted input are identical, f.e. for golden trace tests or record and replay.
+    ///
+    /// The clock does not sleep, durations are measured in virtual time, see
+    /// [`Emulator::virtual_time`], and keyboard input only comes from
+    /// [`Emulator::queue_input`]. The random number register is deterministic anyway for a
+    /// given [`EmulatorConfig::random_seed`].
+    Deterministic,
+}
+
+/// Behavior of `BR` if none of the `nzp` bits is set, which differs between course materials.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BrNzpZeroBehavior {
+    /// Always branch as specified in the 2nd edition of the ISA.
+    #[default]
+    Unconditional,
+    /// Never branch, `BR` without condition codes is a no-operation.
+    Nop,
+}
+
+/// Why a call to [`Emulator::run_budgeted`] or [`Emulator::run_for_duration`] returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepResult {
+    /// The instruction budget or time slice is used up, the program can be resumed.
+    BudgetExhausted,
+    /// The program halted or ran past its end.
+    Halted,
+    /// The program waits for keyboard input and can be resumed once it is available.
+    WaitingForInput,
+    /// An instruction accessed a watched memory cell, the program can be resumed.
+    Watchpoint(WatchpointHit),
+    /// The condition of [`Emulator::run_until`] is true, the program can be resumed.
+    ConditionMet,
+    /// A breakpoint was reached before executing the instruction there, the program can be
+    /// resumed.
+    Breakpoint(BreakpointHit),
+    /// [`Emulator::step_over`] or [`Emulator::step_out`] completed, the program can be
+    /// resumed.
+    StepCompleted,
+    /// A `TRAP` instruction for a routine registered via [`Emulator::break_on_trap`] is
+    /// about to be executed, the program can be resumed.
+    TrapEntered {
+        /// Address of the `TRAP` instruction
+        pc: u16,
+        vector: u8,
+    },
+}
+
+/// Handle to stop a running [`Emulator`] from another thread, see [`Emulator::cancel_token`].
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    requested: Arc<AtomicBool>,
+    /// Woken on cancellation, registered by an async run waiting for input
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+impl CancelToken {
+    /// Stops the execution before its next instruction with [`ExecutionError::Interrupted`],
+    /// or the next execution if none is running. A read waiting for keyboard input is
+    /// stopped as well.
+    pub fn cancel(&self) {
+        self.requested.store(true, Ordering::Relaxed);
+        let waker = lock(&self.waker).take();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+    /// `true` if cancellation was requested but not yet performed.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.requested.load(Ordering::Relaxed)
+    }
+    /// Returns whether cancellation was requested and resets the request.
+    fn take(&self) -> bool {
+        self.requested.swap(false, Ordering::Relaxed)
+    }
+    #[cfg(feature = "async")]
+    pub(crate) fn register_waker(&self, waker: &Waker) {
+        *lock(&self.waker) = Some(waker.clone());
+    }
+}
+
+/// Stop reason of a watchpoint, see [`Emulator::add_watchpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchpointHit {
+    /// Address of the instruction which accessed the watched cell
+    pub pc: u16,
+    /// The access including old and new value
+    pub access: MemoryAccess,
+}
+
+/// Where execution stopped the last time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunState {
+    Running,
+    WaitingForInput,
+    /// Stopped at a breakpoint which must not stop again when resuming
+    AtBreakpoint,
+    Halted,
+}
+
+/// End of a run limited in time.
+#[derive(Clone, Copy)]
+enum Deadline {
+    WallClock(Instant),
+    /// Number of elapsed cycles, see [`TimeMode::Deterministic`]
+    Cycles(u64),
+}
+
+/// When a run returns control to the caller.
+struct RunLimits<'a> {
+    input_mode: InputMode,
+    max_instructions: Option<u64>,
+    deadline: Option<Deadline>,
+    stop_condition: Option<&'a mut dyn FnMut(&MachineState) -> bool>,
+    /// Stop once the call stack is at most this deep after an instruction
+    return_depth: Option<usize>,
+}
+impl RunLimits<'_> {
+    const fn new(input_mode: InputMode) -> Self {
+        Self {
+            input_mode,
+            max_instructions: None,
+            deadline: None,
+            stop_condition: None,
+            return_depth: None,
+        }
+    }
+}
+
+/// Control flow of a single instruction, breaking when execution has to stop.
+pub(crate) type StepFlow = ControlFlow<Result<StepResult, ExecutionError>>;
+
+/// Instructions executed between checks of the deadline in [`Emulator::run_for_duration`].
+const DEADLINE_CHECK_INTERVAL: u64 = 256;
+/// Clock frequency of the virtual time without a clock set via [`Emulator::set_clock`]
+const VIRTUAL_CLOCK_HZ: u32 = 1_000_000;
+
+/// What happens when the PC reaches the end of the program without a `HALT`,
+/// which usually means a missing `HALT` or a wrong branch.
+///
+/// Addresses behind the program inside other loaded code, f.e. a library loaded via
+/// [`Emulator::load_image`], are not the end of the program.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ProgramEndPolicy {
+    /// Record a [`Diagnostic`] and set [`ExecutionSummary::ran_past_program_end`].
+    #[default]
+    Warn,
+    /// Stop execution with [`ExecutionError::RanPastProgramEnd`].
+    Error,
+    /// End execution silently like a `HALT`.
+    Silent,
+}
+
+/// Summary of the execution so far.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutionSummary {
+    /// Number of instructions executed
+    pub instructions_executed: u64,
+    /// Semantics used for `BR` without condition codes
+    pub br_nzp_zero_behavior: BrNzpZeroBehavior,
+    /// Whether execution ended by reaching the program end instead of a `HALT`
+    pub ran_past_program_end: bool,
+}
+
+/// Why a run of the program ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaltReason {
+    /// The program executed the `HALT` trap.
+    HaltTrap,
+    /// The program cleared the clock enable bit of the MCR itself.
+    McrCleared,
+    /// The PC reached the end of the program, see [`ProgramEndPolicy`].
+    RanPastProgramEnd,
+    /// The program waits for more keyboard input than was given.
+    InputExhausted,
+    /// Execution stopped at a breakpoint.
+    Breakpoint(BreakpointHit),
+    /// Execution stopped after an instruction accessed a watched address.
+    Watchpoint(WatchpointHit),
+    /// Execution was interrupted by CTRL-C or a [`CancelToken`].
+    Cancelled,
+    /// More instructions than allowed were executed, see [`Emulator::set_max_instructions`].
+    InstructionLimit,
+    /// Execution failed with another [`ExecutionError`].
+    Error,
+    /// Execution stopped before the program ended for another reason, f.e. when a stop
+    /// condition was met.
+    Stopped(StepResult),
+}
+
+impl HaltReason {
+    /// Reason of the clock being stopped by executing `i`.
+    fn stopped_by(i: Instruction) -> Self {
+        if i.operation() == Operation::Trap && i.get_bit_range(0, 7) == 0x25 {
+            Self::HaltTrap
+        } else {
+            Self::McrCleared
+        }
+    }
+}
+
+impl From<&ExecutionError> for HaltReason {
+    fn from(error: &ExecutionError) -> Self {
+        match error {
+            ExecutionError::Interrupted(_) => Self::Cancelled,
+            ExecutionError::InstructionLimitExceeded { .. } => Self::InstructionLimit,
+            ExecutionError::RanPastProgramEnd { .. } => Self::RanPastProgramEnd,
+            ExecutionError::ChaosRunFailed { error, .. } => Self::from(error.as_ref()),
+            _ => Self::Error,
+        }
+    }
+}
+
+/// How a run of [`Emulator::execute`] and its variants ended.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitStatus {
+    pub halt_reason: HaltReason,
+    /// PC after the run
+    pub pc: u16,
+    /// Number of instructions executed in total
+    pub instructions_executed: u64,
+}
+
+/// Result of [`Emulator::execute_captured`].
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub struct RunOutcome {
+    /// Everything the program wrote to the console
+    pub output: String,
+    /// Registers after the run
+    pub registers: Registers,
+    /// Number of instructions executed in total
+    pub instructions_executed: u64,
+    pub halt_reason: HaltReason,
+}
+
 /// The public facing emulator used to run LC-3 programs.
 pub struct Emulator {
     memory: Memory,
     registers: Registers,
-    keyboard_input_provider: Rc<RefCell<dyn KeyboardInputProvider>>,
+    keyboard_input_provider: SharedInputProvider,
+    /// Characters delivered before the keyboard input provider, see [`Emulator::queue_input`]
+    input_queue: Option<Arc<Mutex<InputQueue>>>,
+    cancel_token: CancelToken,
+    chaos_seed: Option<u64>,
+    nx_policy: NxPolicy,
+    acv_policy: AcvPolicy,
+    smc_policy: SelfModifyingCodePolicy,
+    uninitialized_read_policy: UninitializedReadPolicy,
+    init_tracker: Option<InitTracker>,
+    /// Addresses of executed instructions modified by the program
+    modified_instructions: BTreeSet<u16>,
+    /// Memory as loaded, restored by [`Emulator::reset_memory`]
+    initial_memory: MemorySnapshot,
+    /// PC the program starts at, restored by [`Emulator::reset_registers`]
+    start_pc: u16,
+    isa_variant: IsaVariant,
+    trap_mode: TrapMode,
+    time_mode: TimeMode,
+    /// Keyboard input provider and chaos seed detached by [`TimeMode::Deterministic`], restored
+    /// when switching back to [`TimeMode::RealTime`]
+    real_time_input: Option<(SharedInputProvider, Option<u64>)>,
+    output_error_policy: OutputErrorPolicy,
+    br_nzp_zero_behavior: BrNzpZeroBehavior,
+    program_end_policy: ProgramEndPolicy,
+    max_instructions: Option<u64>,
+    interrupts: InterruptController,
+    stack_bounds: Option<RangeInclusive<u16>>,
+    display: Option<(Framebuffer, Box<dyn DisplayRenderer + Send>)>,
+    clock: Option<Clock>,
+    cycle_weights: [u32; 16],
+    /// Clock cycles of the executed instructions
+    cycles: u64,
+    instructions_executed: u64,
+    /// Address and raw value of the most recently executed instruction
+    last_executed: Option<(u16, u16)>,
+    ran_past_program_end: bool,
+    /// Why the program halted the last time
+    halted_by: Option<HaltReason>,
+    /// Receives the plain-English descriptions of executed instructions
+    explain: Option<Box<dyn Write + Send>>,
+    /// Suppresses the messages of the built-in trap routines
+    quiet: bool,
+    run_state: RunState,
+    watchpoint_hit: Option<WatchpointHit>,
+    diagnostics: Vec<Diagnostic>,
+    stats: ExecutionStats,
+    event_log: Option<EventLog>,
+    trace_sinks: Vec<Box<dyn TraceSink + Send>>,
+    history: Option<History>,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
+    checkpoints: Option<Checkpoints>,
+    symbols: SymbolTable,
+    call_stack: CallStack,
+    /// Memory writes of the last executed instruction if logging them is enabled
+    last_memory_writes: Vec<MemoryAccess>,
+    pre_instruction_hook: Option<InstructionHook>,
+    post_instruction_hook: Option<InstructionHook>,
+    events: EventBus,
+    breakpoints: Breakpoints,
 }
 
-pub(crate) fn from_program_bytes(data: &[u16]) -> Result<Emulator, LoadProgramError> {
-    let tip = TerminalInputProvider::new();
-    from_program_bytes_with_kbd_input_provider(data, tip)
+/// Builds an [`Emulator`] from a program and further memory regions loaded next to it,
+/// optionally with another keyboard input provider, attached devices or start address.
+///
+/// Program output is not configured here but passed to the methods running the program, f.e.
+/// [`Emulator::execute_with_stdout`].
+///
+/// # Example
+/// ```
+/// use lc3_emulator::api::EmulatorBuilder;
+///
+/// // LD R0, #1; HALT; .FILL x4000; with a lookup table at x4000
+/// let mut emu = EmulatorBuilder::new(&[0x3000, 0x2001, 0xF025, 0x4000])
+///     .with_memory_region(0x4000, &[1, 2, 4, 8])
+///     .build()
+///     .unwrap();
+/// assert_eq!(8, emu.memory()[0x4003]);
+/// ```
+pub struct EmulatorBuilder {
+    program: Vec<u16>,
+    regions: Vec<(u16, Vec<u16>)>,
+    config: EmulatorConfig,
+    isa_variant: IsaVariant,
+    keyboard_input_provider: Option<SharedInputProvider>,
+    chaos_seed: Option<u64>,
+    serial: Option<Box<dyn SerialDevice + Send>>,
+    start_pc: Option<u16>,
 }
 
-pub(crate) fn from_program_bytes_with_kbd_input_provider(
-    data: &[u16],
-    keyboard_input_provider: impl KeyboardInputProvider + 'static,
-) -> Result<Emulator, LoadProgramError> {
-    let [header, program @ ..] = data else {
-        return Err(LoadProgramError::ProgramMissingOrigHeader);
-    };
-    if *header != ORIG_HEADER {
-        return Err(LoadProgramError::ProgramLoadedAtWrongAddress {
-            actual_address: *header,
-            expected_address: PROGRAM_SECTION_START,
-        });
+impl Debug for EmulatorBuilder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EmulatorBuilder")
+            .field("program", &self.program)
+            .field("regions", &self.regions)
+            .field("config", &self.config)
+            .field("isa_variant", &self.isa_variant)
+            .field("chaos_seed", &self.chaos_seed)
+            .field("start_pc", &self.start_pc)
+            .finish_non_exhaustive()
+    }
+}
+
+impl EmulatorBuilder {
+    /// Starts with `program` given as words starting with the `.ORIG` address like an `.obj`
+    /// file.
+    #[must_use]
+    pub fn new(program: &[u16]) -> Self {
+        Self {
+            program: program.to_vec(),
+            regions: Vec::new(),
+            config: EmulatorConfig::default(),
+            isa_variant: IsaVariant::Lc3,
+            keyboard_input_provider: None,
+            chaos_seed: None,
+            serial: None,
+            start_pc: None,
+        }
+    }
+    /// Lays out memory according to `config` instead of the standard LC-3 memory map.
+    ///
+    /// The program must then start at the start of its user space.
+    #[must_use]
+    pub const fn with_config(mut self, config: EmulatorConfig) -> Self {
+        self.config = config;
+        self
+    }
+    /// Executes the program as `variant`, f.e. [`IsaVariant::Lc3b`] instead of the LC-3.
+    #[must_use]
+    pub const fn with_isa_variant(mut self, variant: IsaVariant) -> Self {
+        self.isa_variant = variant;
+        self
+    }
+    /// Preloads `data` at `addr`, f.e. a lookup table or the input buffer of a test.
+    ///
+    /// The region is not executable and must neither overlap the program nor other regions.
+    #[must_use]
+    pub fn with_memory_region(mut self, addr: u16, data: &[u16]) -> Self {
+        self.regions.push((addr, data.to_vec()));
+        self
+    }
+    /// Reads keyboard input from `provider` instead of the terminal, f.e. scripted input of a
+    /// test harness.
+    #[must_use]
+    pub fn with_input_provider(
+        mut self,
+        provider: impl KeyboardInputProvider + Send + 'static,
+    ) -> Self {
+        self.keyboard_input_provider = Some(Arc::new(Mutex::new(provider)));
+        self.chaos_seed = None;
+        self
+    }
+    /// Delivers the characters of `input` after a random number of executed instructions,
+    /// see [`from_program_with_chaos_input`].
+    #[must_use]
+    pub fn with_chaos_input(mut self, input: &str, seed: u64) -> Self {
+        self.keyboard_input_provider =
+            Some(Arc::new(Mutex::new(ChaosInputProvider::new(input, seed))));
+        self.chaos_seed = Some(seed);
+        self
+    }
+    /// Attaches `device` to the serial line registers, see [`Emulator::attach_serial`].
+    #[must_use]
+    pub fn with_serial(mut self, device: impl SerialDevice + Send + 'static) -> Self {
+        self.serial = Some(Box::new(device));
+        self
+    }
+    /// Starts execution at `pc` instead of the start of the program, f.e. at an entry point
+    /// behind a data section.
+    #[must_use]
+    pub const fn with_start_pc(mut self, pc: u16) -> Self {
+        self.start_pc = Some(pc);
+        self
     }
-    if program.is_empty() {
-        return Err(LoadProgramError::ProgramEmpty);
+    /// Loads the program and all memory regions.
+    ///
+    /// #  Errors
+    /// - See [`LoadProgramError`]
+    pub fn build(self) -> Result<Emulator, LoadProgramError> {
+        let spread = |words: &[u16]| match self.isa_variant {
+            IsaVariant::Lc3 => words.to_vec(),
+            IsaVariant::Lc3b => lc3b::spread_words(words),
+        };
+        let [header, program @ ..] = self.program.as_slice() else {
+            return Err(LoadProgramError::ProgramMissingOrigHeader);
+        };
+        if *header != self.config.user_space.start {
+            return Err(LoadProgramError::ProgramLoadedAtWrongAddress {
+                actual_address: *header,
+                expected_address: self.config.user_space.start,
+            });
+        }
+        if program.is_empty() {
+            return Err(LoadProgramError::ProgramEmpty);
+        }
+        let rc_kpi = self
+            .keyboard_input_provider
+            .unwrap_or_else(|| Arc::new(Mutex::new(TerminalInputProvider::new())));
+        let mut memory = Memory::with_config(rc_kpi.clone(), self.config);
+        memory.load_program(&spread(program))?;
+        for (origin, data) in &self.regions {
+            memory.load_region(*origin, &spread(data))?;
+        }
+        if let Some(device) = self.serial {
+            memory.attach_serial(device);
+        }
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            origin = header,
+            words = program.len(),
+            regions = self.regions.len(),
+            "program loaded"
+        );
+        let mut emu = Emulator::with_memory(memory, rc_kpi);
+        emu.isa_variant = self.isa_variant;
+        emu.chaos_seed = self.chaos_seed;
+        if let Some(pc) = self.start_pc {
+            emu.start_pc = pc;
+            emu.registers.set_pc(pc);
+        }
+        Ok(emu)
     }
-    let rc_kpi = Rc::new(RefCell::new(keyboard_input_provider));
-    let mut memory = Memory::new(rc_kpi.clone());
-    memory.load_program(program)?;
-    Ok(Emulator {
-        memory,
-        registers: Registers::new(),
-        keyboard_input_provider: rc_kpi,
-    })
+}
+
+pub(crate) fn from_program_bytes(data: &[u16]) -> Result<Emulator, LoadProgramError> {
+    EmulatorBuilder::new(data).build()
 }
 
 /// Loads a program from disk into the memory section starting from
 /// address `_PROGRAM_SECTION_START_BYTES`
 /// and returns an iterator over the loaded instructions.
 ///
+/// A symbol table written by `lc3as` next to the program, f.e. `prog.sym` for `prog.obj`,
+/// is loaded as well, see [`Emulator::symbols`]. If it cannot be read the program is loaded
+/// without symbols and [`Diagnostic::SymbolTableIgnored`] is reported instead.
+/// Object files with little-endian words are detected, see [`ByteOrder::Auto`].
+///
 /// # Parameters
 /// - `path` defines the location of the LC-3 object file to execute
 ///
 /// #  Errors
 /// - See [`LoadProgramError`]
 pub fn from_program(path: &str) -> Result<Emulator, LoadProgramError> {
+    from_program_with_format(path, ImageFormat::OBJ)
+}
+
+/// Loads a program stored in `format` from disk, otherwise like [`from_program`].
+///
+/// #  Errors
+/// - See [`LoadProgramError`]
+pub fn from_program_with_format(
+    path: &str,
+    format: ImageFormat,
+) -> Result<Emulator, LoadProgramError> {
+    let mut emu = from_program_bytes(&read_image(path, format)?)?;
+    emu.memory.set_source_of_last_region(path);
+    emu.load_sibling_symbols(path);
+    Ok(emu)
+}
+
+/// Loads a program like [`from_program`] but instead of reading from the keyboard the
+/// characters of `input` are delivered after a random number of executed instructions.
+///
+/// This exercises keyboard polling loops with varying timing, see [`ChaosInputProvider`].
+/// If execution fails the returned error is an [`ExecutionError::ChaosRunFailed`] containing
+/// the `seed` to reproduce the run.
+///
+/// #  Errors
+/// - See [`LoadProgramError`]
+pub fn from_program_with_chaos_input(
+    path: &str,
+    input: &str,
+    seed: u64,
+) -> Result<Emulator, LoadProgramError> {
+    let mut emu = EmulatorBuilder::new(&read_program_file(path)?)
+        .with_chaos_input(input, seed)
+        .build()?;
+    emu.memory.set_source_of_last_region(path);
+    emu.load_sibling_symbols(path);
+    Ok(emu)
+}
+
+/// Loads a program from the contents of an LC-3 object file like [`from_program`], f.e.
+/// embedded via `include_bytes!` or fetched from the network.
+///
+/// #  Errors
+/// - See [`LoadProgramError`]
+pub fn from_bytes(data: &[u8]) -> Result<Emulator, LoadProgramError> {
+    from_bytes_with_format(data, ImageFormat::OBJ)
+}
+
+/// Loads a program embedded into the binary, see [`lc3_program!`](crate::lc3_program).
+///
+/// #  Errors
+/// - See [`LoadProgramError`]
+pub fn from_static_image(image: &'static [u16]) -> Result<Emulator, LoadProgramError> {
+    from_program_bytes(image)
+}
+
+/// Converts the contents of an `.obj` file to words at compile time, see
+/// [`lc3_program!`](crate::lc3_program).
+///
+/// # Panics
+/// - If `bytes` are not `N` words starting with the `.ORIG` address `x3000` and containing at
+///   least one instruction, which fails compilation in const contexts
+#[must_use]
+pub const fn obj_words<const N: usize>(bytes: &[u8]) -> [u16; N] {
+    assert!(
+        bytes.len() == 2 * N,
+        "object file does not contain N whole words"
+    );
+    assert!(
+        N >= 2,
+        "object file must contain the .ORIG address and a program"
+    );
+    let mut words = [0; N];
+    let mut i = 0;
+    while i < N {
+        words[i] = u16::from_be_bytes([bytes[2 * i], bytes[2 * i + 1]]);
+        i += 1;
+    }
+    assert!(words[0] == ORIG_HEADER, "program must be loaded at x3000");
+    words
+}
+
+/// Embeds an LC-3 object file into the binary and validates it at compile time, which
+/// results in a `&'static [u16]` for [`from_static_image`](crate::emulator::from_static_image).
+///
+/// The path is relative to the file containing the invocation like for `include_bytes!`.
+/// Embedded programs need no file I/O at runtime, f.e. in demo binaries or WASM builds.
+///
+/// # Example
+/// ```
+/// use lc3_emulator::api::{self, StdoutForDocTest};
+/// use lc3_emulator::lc3_program;
+///
+/// let mut emu = api::from_static_image(lc3_program!("../../examples/times_ten.obj")).unwrap();
+/// emu.execute_with_stdout(&mut StdoutForDocTest::new()).unwrap();
+/// assert_eq!(30, emu.registers().get(3).as_decimal());
+/// ```
+#[macro_export]
+macro_rules! lc3_program {
+    ($path:expr) => {{
+        const BYTES: &[u8] = include_bytes!($path);
+        static WORDS: [u16; BYTES.len() / 2] = $crate::emulator::obj_words(BYTES);
+        WORDS.as_slice()
+    }};
+}
+
+/// Loads a program from the contents of a file in `format`, otherwise like [`from_bytes`].
+///
+/// #  Errors
+/// - See [`LoadProgramError`]
+pub fn from_bytes_with_format(
+    data: &[u8],
+    format: ImageFormat,
+) -> Result<Emulator, LoadProgramError> {
+    from_program_bytes(&format.to_obj_words(data)?)
+}
+
+/// Loads a program by reading an LC-3 object file from `reader` until its end, otherwise like
+/// [`from_bytes`].
+///
+/// #  Errors
+/// - See [`LoadProgramError`]
+pub fn from_reader(mut reader: impl Read) -> Result<Emulator, LoadProgramError> {
+    let mut data = Vec::new();
+    reader
+        .read_to_end(&mut data)
+        .map_err(|e| LoadProgramError::ProgramNotReadable(e.to_string()))?;
+    from_bytes(&data)
+}
+
+/// Converts the big-endian contents of an object file to words.
+fn words_from_bytes(data: &[u8]) -> Result<Vec<u16>, LoadProgramError> {
+    let (words, []) = data.as_chunks::<2>() else {
+        return Err(LoadProgramError::ProgramNotEvenSize(data.len() as u64));
+    };
+    Ok(words.iter().map(|w| u16::from_be_bytes(*w)).collect())
+}
+
+/// Reads the image stored in `format` at `path` as words starting with the `.ORIG` address.
+fn read_image(path: &str, format: ImageFormat) -> Result<Vec<u16>, LoadProgramError> {
+    match format {
+        ImageFormat::Obj { byte_order } if byte_order != ByteOrder::Auto => {
+            Ok(byte_order.normalize(read_program_file(path)?))
+        }
+        _ => format.to_obj_words(
+            &std::fs::read(path).map_err(|e| map_err_program_not_loadable(path, e.to_string()))?,
+        ),
+    }
+}
+
+fn read_program_file(path: &str) -> Result<Vec<u16>, LoadProgramError> {
     let (file, file_size) =
         get_file_with_size(path).map_err(|e| map_err_program_not_loadable(path, e.to_string()))?;
     if file_size % 2 == 1 {
@@ -110,7 +895,7 @@ pub fn from_program(path: &str) -> Result<Emulator, LoadProgramError> {
         file_data.push((u16::from(buf[0]) << 8) | u16::from(buf[1]));
         read_total += 2;
     }
-    from_program_bytes(file_data.as_slice())
+    Ok(file_data)
 }
 
 fn map_err_program_not_loadable(path: &str, message: String) -> LoadProgramError {
@@ -126,6 +911,145 @@ fn get_file_with_size(path: &str) -> Result<(File, u64), io::Error> {
 }
 
 impl Emulator {
+    fn with_memory(mut memory: Memory, keyboard_input_provider: SharedInputProvider) -> Self {
+        let cancel_token = CancelToken::default();
+        memory.set_cancel_flag(cancel_token.requested.clone());
+        Self {
+            registers: Registers::with_config(memory.config()),
+            initial_memory: memory.snapshot(),
+            start_pc: memory.config().user_space.start,
+            memory,
+            keyboard_input_provider,
+            input_queue: None,
+            cancel_token,
+            chaos_seed: None,
+            nx_policy: NxPolicy::default(),
+            acv_policy: AcvPolicy::default(),
+            smc_policy: SelfModifyingCodePolicy::default(),
+            uninitialized_read_policy: UninitializedReadPolicy::default(),
+            init_tracker: None,
+            modified_instructions: BTreeSet::new(),
+            isa_variant: IsaVariant::default(),
+            trap_mode: TrapMode::default(),
+            time_mode: TimeMode::default(),
+            real_time_input: None,
+            output_error_policy: OutputErrorPolicy::default(),
+            br_nzp_zero_behavior: BrNzpZeroBehavior::default(),
+            program_end_policy: ProgramEndPolicy::default(),
+            max_instructions: None,
+            interrupts: InterruptController::default(),
+            stack_bounds: None,
+            display: None,
+            clock: None,
+            cycle_weights: [1; 16],
+            cycles: 0,
+            instructions_executed: 0,
+            last_executed: None,
+            ran_past_program_end: false,
+            halted_by: None,
+            explain: None,
+            quiet: false,
+            run_state: RunState::Running,
+            watchpoint_hit: None,
+            diagnostics: Vec::new(),
+            stats: ExecutionStats::default(),
+            event_log: None,
+            trace_sinks: Vec::new(),
+            history: None,
+            profiler: None,
+            coverage: None,
+            checkpoints: None,
+            symbols: SymbolTable::default(),
+            call_stack: CallStack::default(),
+            last_memory_writes: Vec::new(),
+            pre_instruction_hook: None,
+            post_instruction_hook: None,
+            events: EventBus::default(),
+            breakpoints: Breakpoints::default(),
+        }
+    }
+    /// Loads the symbol table next to the program, a broken one is only a diagnostic since
+    /// symbols are a debugging aid.
+    fn load_sibling_symbols(&mut self, program_path: &str) {
+        let sym_path = Path::new(program_path).with_extension("sym");
+        if sym_path.is_file()
+            && let Err(e) = self.load_symbols(&sym_path.to_string_lossy())
+        {
+            self.diagnostics.push(Diagnostic::SymbolTableIgnored(e));
+        }
+    }
+    /// Captures registers and memory to compare them with a later state via
+    /// [`MachineSnapshot::diff`].
+    #[must_use]
+    pub fn snapshot(&self) -> MachineSnapshot {
+        MachineSnapshot::new(self.registers.clone(), self.memory.snapshot())
+    }
+    /// Read-only view of registers, memory and symbols, f.e. for test harnesses, debuggers
+    /// or UIs observing the machine between runs.
+    #[must_use]
+    pub fn state(&self) -> MachineState<'_> {
+        MachineState::new(
+            &self.registers,
+            &self.memory,
+            &self.symbols,
+            &self.last_memory_writes,
+            self.instructions_executed,
+        )
+    }
+    /// Renders the memory cells in `range` as LC-3 assembly, using labels of the loaded
+    /// symbols as branch targets.
+    #[must_use]
+    pub fn disassemble(&self, range: Range<u16>) -> Vec<DisasmLine> {
+        let pc = self.registers.pc().as_binary();
+        range
+            .map(|addr| {
+                let raw = self.memory.peek(addr);
+                let (mnemonic, operands) = disassembler::disassemble_word(addr, raw, &self.symbols);
+                DisasmLine {
+                    addr,
+                    raw,
+                    mnemonic,
+                    operands,
+                    is_current_pc: addr == pc,
+                }
+            })
+            .collect()
+    }
+    /// Writes the memory cells in `range` to an object file at `path` which `lc3as` would
+    /// have written for them, f.e. to persist a patched or self-modified program.
+    ///
+    /// # Errors
+    /// - creating or writing the file fails
+    pub fn save_obj(&self, path: impl AsRef<Path>, range: Range<u16>) -> io::Result<()> {
+        let mut data = Vec::with_capacity(2 * (range.len() + 1));
+        data.extend(range.start.to_be_bytes());
+        for address in range {
+            data.extend(self.memory.peek(address).to_be_bytes());
+        }
+        std::fs::write(path, data)
+    }
+    /// Subroutines called via `JSR` or `JSRR` which have not returned yet, innermost last.
+    #[must_use]
+    pub fn call_stack(&self) -> Vec<Frame> {
+        self.call_stack.frames().to_vec()
+    }
+    /// Labels of the loaded program used to print addresses symbolically.
+    #[must_use]
+    pub const fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+    /// Replaces the symbol table with the one in the `.sym` file at `path`.
+    ///
+    /// # Errors
+    /// - See [`SymbolTableError`]
+    pub fn load_symbols(&mut self, path: &str) -> Result<(), SymbolTableError> {
+        self.symbols = SymbolTable::from_file(path)?;
+        Ok(())
+    }
+    /// Replaces the symbol table, f.e. with the one of a program assembled in memory.
+    pub fn set_symbols(&mut self, symbols: SymbolTable) {
+        self.symbols = symbols;
+    }
     /// Access registers to set them before execution or query values afterward.
     #[must_use]
     pub const fn registers(&mut self) -> &mut Registers {
@@ -136,203 +1060,3590 @@ impl Emulator {
     pub const fn memory(&mut self) -> &mut Memory {
         &mut self.memory
     }
+    /// Content of `addr` without side effects, f.e. for test harnesses or debugger frontends.
+    ///
+    /// # Errors
+    /// - [`StateAccessError::DeviceRegister`] since reading them has side effects like
+    ///   consuming keyboard input
+    pub fn read_mem(&self, addr: u16) -> Result<u16, StateAccessError> {
+        self.check_not_device(addr)?;
+        Ok(self.memory.peek(addr))
+    }
+    /// Sets `addr` to `val` without notifying watchpoints or memory observers.
+    ///
+    /// # Errors
+    /// - [`StateAccessError::DeviceRegister`] since writing them has side effects like output
+    pub fn write_mem(&mut self, addr: u16, val: u16) -> Result<(), StateAccessError> {
+        self.check_not_device(addr)?;
+        self.memory.restore(addr, val);
+        Ok(())
+    }
+    fn check_not_device(&self, addr: u16) -> Result<(), StateAccessError> {
+        if self.memory.config().device_region.contains(&addr) {
+            return Err(StateAccessError::DeviceRegister(addr));
+        }
+        Ok(())
+    }
+    /// Sets general purpose register `r` to `val`, the condition codes are left unchanged.
+    ///
+    /// # Errors
+    /// - [`StateAccessError::InvalidRegister`] if `r` is greater than 7
+    pub fn set_register(&mut self, r: u8, val: u16) -> Result<(), StateAccessError> {
+        if r > 7 {
+            return Err(StateAccessError::InvalidRegister(r));
+        }
+        self.registers.set(r, from_binary(val));
+        Ok(())
+    }
+    /// Continues execution at `addr`, also after the program halted.
+    ///
+    /// # Errors
+    /// - [`StateAccessError::PcOutOfRange`] if `addr` is behind the user space
+    pub const fn set_pc(&mut self, addr: u16) -> Result<(), StateAccessError> {
+        if addr >= self.memory.config().user_space.end {
+            return Err(StateAccessError::PcOutOfRange(addr));
+        }
+        self.registers.set_pc(addr);
+        self.run_state = RunState::Running;
+        Ok(())
+    }
+    /// Continues execution at `addr` like [`Emulator::set_pc`] and also starts there again after
+    /// [`Emulator::reset`], like [`EmulatorBuilder::with_start_pc`].
+    ///
+    /// # Errors
+    /// - [`StateAccessError::PcOutOfRange`] if `addr` is behind the user space
+    pub fn set_start_pc(&mut self, addr: u16) -> Result<(), StateAccessError> {
+        self.set_pc(addr)?;
+        self.start_pc = addr;
+        Ok(())
+    }
     /// Executes the loaded program.
     /// # Errors
     /// - See [`ExecutionError`]
-    pub fn execute(&mut self) -> Result<(), ExecutionError> {
+    pub fn execute(&mut self) -> Result<ExitStatus, ExecutionError> {
         let mut stdout = io::stdout();
         let _lock = terminal::set_terminal_raw(&mut stdout);
         self.execute_with_stdout(&mut stdout)
     }
-
-    /// Resets all registers to initial values including PC to provide a clean slate for another execution.
-    pub const fn reset_registers(&mut self) {
-        self.registers = Registers::new();
-    }
-
-    /// Return instructions parsed from loaded program.
-    #[must_use]
-    pub fn instructions(&self) -> impl ExactSizeIterator<Item = Instruction> + Debug {
-        self.memory
-            .program_slice()
-            .iter()
-            .map(|bits| Instruction::from(*bits))
-    }
-
-    /// Executes the loaded program.
+    /// Executes the loaded program writing its output unchanged to stdout, without raw mode
+    /// and cursor movement of the terminal, f.e. to redirect output into a file.
+    ///
+    /// Keyboard input typed into a terminal is then only delivered after pressing enter.
+    ///
     /// # Errors
     /// - See [`ExecutionError`]
-    pub fn execute_with_stdout(
-        &mut self,
-        stdout: &mut (impl Write + CrosstermCompatibility),
-    ) -> Result<(), ExecutionError> {
-        while self.registers.pc() < from_binary(self.memory.program_end()) {
-            let data = self.memory[self.registers.pc().as_binary()];
-            let i = Instruction::from(data);
-            // println!("{i:?}");
-            self.registers.inc_pc();
-            if let Some(res) = self.execute_instruction(i, stdout).break_value() {
-                return res;
-            }
-        }
-        // stdout.flush().map_err(|e| {
-        //     ExecutionError::IOInputOutputError(format!("Error flushing stdout: {e}"))
-        // })?;
-        Ok(())
+    pub fn execute_plain(&mut self) -> Result<ExitStatus, ExecutionError> {
+        self.execute_with_stdout(&mut VirtualConsole(io::stdout()))
     }
 
-    #[expect(
-        clippy::unnecessary_mut_passed,
-        reason = "Needed for all opcodes thus if this fails this expect can be removed"
-    )]
-    fn execute_instruction(
-        &mut self,
-        instruction: Instruction,
-        stdout: &mut (impl Write + CrosstermCompatibility),
-    ) -> ControlFlow<Result<(), ExecutionError>, ()> {
-        if self.keyboard_input_provider.borrow().is_interrupted() {
-            return ControlFlow::Break(Ok(()));
+    /// Resets all registers to initial values including PC to provide a clean slate for another execution.
+    pub fn reset_registers(&mut self) {
+        self.registers = Registers::with_config(self.memory.config());
+        self.registers.set_pc(self.start_pc);
+        self.run_state = RunState::Running;
+        self.last_executed = None;
+        self.call_stack = CallStack::default();
+    }
+    /// Restores the memory to the program image as loaded, including an operating system
+    /// loaded via [`Emulator::load_os`], undoing all writes of previous executions.
+    ///
+    /// Watchpoints, observers and attached devices are kept.
+    pub fn reset_memory(&mut self) {
+        self.memory.restore_snapshot(&self.initial_memory);
+        self.modified_instructions.clear();
+        self.last_memory_writes.clear();
+    }
+    /// Resets memory and registers including PC and condition codes as well as the execution
+    /// state, so that the program can be run again as if freshly loaded.
+    ///
+    /// Configuration like policies, breakpoints and the keyboard input provider is kept.
+    pub fn reset(&mut self) {
+        self.reset_memory();
+        self.reset_registers();
+        self.instructions_executed = 0;
+        self.cycles = 0;
+        self.ran_past_program_end = false;
+        self.halted_by = None;
+        self.watchpoint_hit = None;
+        if let Some(history) = self.history.as_mut() {
+            history.clear();
         }
-        match instruction.op_code() {
-            o if o == Operation::Add as u8 => opcodes::add(instruction, &mut self.registers),
-            o if o == Operation::And as u8 => opcodes::and(instruction, &mut self.registers),
-            o if o == Operation::Not as u8 => opcodes::not(instruction, &mut self.registers),
-            o if o == Operation::Br as u8 => opcodes::br(instruction, &mut self.registers),
-            o if o == Operation::JmpOrRet as u8 => {
-                opcodes::jmp_or_ret(instruction, &mut self.registers);
-            }
-            o if o == Operation::Jsr as u8 => opcodes::jsr(instruction, &mut self.registers),
-            o if o == Operation::Ld as u8 => {
-                opcodes::ld(instruction, &mut self.registers, &self.memory);
-            }
-            o if o == Operation::Ldi as u8 => {
-                opcodes::ldi(instruction, &mut self.registers, &mut self.memory);
-            }
-            o if o == Operation::Ldr as u8 => {
-                opcodes::ldr(instruction, &mut self.registers, &mut self.memory);
-            }
-            o if o == Operation::Lea as u8 => opcodes::lea(instruction, &mut self.registers),
-            o if o == Operation::St as u8 => {
-                opcodes::st(instruction, &self.registers, &mut self.memory);
-            }
-            o if o == Operation::Sti as u8 => {
-                opcodes::sti(instruction, &self.registers, &mut self.memory);
-            }
-            o if o == Operation::Str as u8 => {
-                opcodes::str(instruction, &self.registers, &mut self.memory);
-            }
-            o if o == Operation::Trap as u8 => return self.trap(instruction, stdout),
-            o if o == Operation::Rti as u8 => opcodes::rti(instruction, &mut self.registers),
-            o if o == Operation::_Reserved as u8 => {
-                return ControlFlow::Break(Err(ExecutionError::ReservedInstructionFound(o)));
-            }
-            _ => unreachable!("All variants of 4 bit opcodes checked"),
+        if self.init_tracker.is_some() {
+            self.init_tracker = Some(InitTracker::new(&self.memory));
         }
-        ControlFlow::Continue(())
+    }
+    /// Creates an independent copy of the machine to explore another course of execution from
+    /// the current state, f.e. with different input in search-based tools.
+    ///
+    /// Memory is shared copy-on-write, so forking is cheap. The copy is headless: it reads
+    /// keyboard input only from its input queue, which starts with the characters still queued
+    /// here, see [`Emulator::queue_input`]. Hooks, sinks, memory observers, the display, the
+    /// clock, the serial device and event subscriptions are not copied and the copy has its own [`CancelToken`].
+    #[must_use]
+    pub fn fork(&self) -> Self {
+        let provider: SharedInputProvider = Arc::new(Mutex::new(ScriptedInputProvider::new("")));
+        let mut fork = Self {
+            memory: self.memory.fork(provider.clone()),
+            registers: self.registers.clone(),
+            keyboard_input_provider: provider,
+            input_queue: None,
+            cancel_token: CancelToken::default(),
+            chaos_seed: None,
+            nx_policy: self.nx_policy,
+            acv_policy: self.acv_policy,
+            smc_policy: self.smc_policy,
+            uninitialized_read_policy: self.uninitialized_read_policy,
+            init_tracker: self.init_tracker.clone(),
+            modified_instructions: self.modified_instructions.clone(),
+            initial_memory: self.initial_memory.clone(),
+            start_pc: self.start_pc,
+            isa_variant: self.isa_variant,
+            trap_mode: self.trap_mode,
+            time_mode: self.time_mode,
+            real_time_input: None,
+            output_error_policy: self.output_error_policy,
+            br_nzp_zero_behavior: self.br_nzp_zero_behavior,
+            program_end_policy: self.program_end_policy,
+            max_instructions: self.max_instructions,
+            interrupts: self.interrupts.clone(),
+            stack_bounds: self.stack_bounds.clone(),
+            display: None,
+            clock: None,
+            cycle_weights: self.cycle_weights,
+            cycles: self.cycles,
+            instructions_executed: self.instructions_executed,
+            last_executed: self.last_executed,
+            ran_past_program_end: self.ran_past_program_end,
+            halted_by: self.halted_by,
+            explain: None,
+            quiet: self.quiet,
+            run_state: self.run_state,
+            watchpoint_hit: self.watchpoint_hit,
+            diagnostics: self.diagnostics.clone(),
+            stats: self.stats.clone(),
+            event_log: None,
+            trace_sinks: Vec::new(),
+            history: self.history.clone(),
+            profiler: self.profiler.clone(),
+            coverage: self.coverage.clone(),
+            checkpoints: self.checkpoints.clone(),
+            symbols: self.symbols.clone(),
+            call_stack: self.call_stack.clone(),
+            last_memory_writes: self.last_memory_writes.clone(),
+            pre_instruction_hook: None,
+            post_instruction_hook: None,
+            events: EventBus::default(),
+            breakpoints: self.breakpoints.clone(),
+        };
+        fork.memory
+            .set_cancel_flag(fork.cancel_token.requested.clone());
+        let queued = self.input_queue.as_ref().map(|queue| lock(queue).pending());
+        fork.queue_input(&queued.unwrap_or_default());
+        fork
     }
 
-    /// Handles Trap Routines.
+    /// Marks the addresses in `range` as data which must not be executed.
     ///
-    /// # Result
-    /// - [`ControlFlow::Continue`] when the program should continue as normal
-    /// - [`ControlFlow::Break`] with a [`Result`] when the program should end
+    /// Fetching an instruction from there is handled according to [`Emulator::set_nx_policy`],
+    /// which catches programs falling through into their data, f.e. a missing `HALT` before a
+    /// `.STRINGZ`.
+    pub fn mark_non_executable(&mut self, range: Range<u16>) {
+        self.memory.set_non_executable(range);
+    }
+    /// Defines how fetching from non-executable memory is handled, default is [`NxPolicy::Warn`].
+    pub const fn set_nx_policy(&mut self, policy: NxPolicy) {
+        self.nx_policy = policy;
+    }
+    /// Defines how executing instructions the program wrote to itself is handled, default is
+    /// [`SelfModifyingCodePolicy::Allow`].
+    pub fn set_self_modifying_code_policy(&mut self, policy: SelfModifyingCodePolicy) {
+        self.smc_policy = policy;
+        self.memory
+            .set_code_write_tracking(policy != SelfModifyingCodePolicy::Allow);
+    }
+    /// Addresses of the executed instructions which the program modified before, in ascending
+    /// order. Only tracked if the policy is not [`SelfModifyingCodePolicy::Allow`].
+    pub fn modified_instructions(&self) -> impl Iterator<Item = u16> + '_ {
+        self.modified_instructions.iter().copied()
+    }
+    /// Defines how reading registers and memory never written or loaded is handled, default is
+    /// [`UninitializedReadPolicy::Allow`].
+    ///
+    /// Tracking starts with all registers uninitialized and memory initialized where something
+    /// is loaded and in the device region. `AND Rx, Rx, #0` initializes Rx without reading it.
+    pub fn set_uninitialized_read_policy(&mut self, policy: UninitializedReadPolicy) {
+        self.uninitialized_read_policy = policy;
+        self.init_tracker =
+            (policy != UninitializedReadPolicy::Allow).then(|| InitTracker::new(&self.memory));
+    }
+    /// Defines how accesses of User mode programs outside the user space are handled, default is
+    /// [`AcvPolicy::Unchecked`].
+    pub const fn set_acv_policy(&mut self, policy: AcvPolicy) {
+        self.acv_policy = policy;
+    }
+    /// Defines whether `TRAP` runs the built-in routines or the ones of the operating system
+    /// image, default is [`TrapMode::Native`].
+    pub const fn set_trap_mode(&mut self, mode: TrapMode) {
+        self.trap_mode = mode;
+    }
+    /// Defines whether time passes in real or virtual time, default is [`TimeMode::RealTime`].
+    ///
+    /// Switching to [`TimeMode::Deterministic`] detaches the keyboard input provider, so that
+    /// only input passed to [`Emulator::queue_input`] reaches the program. Switching back to
+    /// [`TimeMode::RealTime`] reattaches it, unless another one was set in the meantime via
+    /// [`Emulator::set_input_provider`].
+    pub fn set_time_mode(&mut self, mode: TimeMode) {
+        match (self.time_mode, mode) {
+            (TimeMode::RealTime, TimeMode::Deterministic) => {
+                let detached = (self.keyboard_input_provider.clone(), self.chaos_seed);
+                self.set_input_provider(ScriptedInputProvider::new(""));
+                self.real_time_input = Some(detached);
+            }
+            (TimeMode::Deterministic, TimeMode::RealTime) => {
+                if let Some((provider, chaos_seed)) = self.real_time_input.take() {
+                    self.memory.set_keyboard_input_provider(provider.clone());
+                    self.keyboard_input_provider = provider;
+                    self.chaos_seed = chaos_seed;
+                }
+            }
+            _ => {}
+        }
+        self.time_mode = mode;
+    }
+    /// Time the executed instructions took at the frequency of [`Emulator::set_clock`], or
+    /// at 1 MHz without a clock, counting the cycles set by [`Emulator::set_cycle_weights`].
+    #[must_use]
+    pub fn virtual_time(&self) -> Duration {
+        let nanos = u128::from(self.cycles) * 1_000_000_000 / u128::from(self.clock_hz());
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+    fn clock_hz(&self) -> u32 {
+        self.clock.as_ref().map_or(VIRTUAL_CLOCK_HZ, Clock::hz)
+    }
+    /// Loads an operating system object file like `lc3os.obj` into the system space below
+    /// `x3000`, see [`TrapMode::Vectored`].
+    ///
+    /// Its trap routines have to return via `RET`, `RTI` is not supported. Output written to
+    /// the display data register and clearing the machine control register to halt work for
+    /// programs as well.
+    ///
+    /// #  Errors
+    /// - See [`LoadProgramError`]
+    pub fn load_os(&mut self, path: &str) -> Result<(), LoadProgramError> {
+        self.load_os_words(&read_program_file(path)?)?;
+        self.memory.set_source_of_last_region(path);
+        Ok(())
+    }
+    /// Loads an operating system image from the contents of an object file, otherwise like
+    /// [`Emulator::load_os`].
+    ///
+    /// #  Errors
+    /// - See [`LoadProgramError`]
+    pub fn load_os_bytes(&mut self, data: &[u8]) -> Result<(), LoadProgramError> {
+        self.load_os_words(&words_from_bytes(data)?)
+    }
+    fn load_os_words(&mut self, words: &[u16]) -> Result<(), LoadProgramError> {
+        let [origin, image @ ..] = words else {
+            return Err(LoadProgramError::ProgramMissingOrigHeader);
+        };
+        if image.is_empty() {
+            return Err(LoadProgramError::ProgramEmpty);
+        }
+        self.memory.load_os(*origin, image)?;
+        #[cfg(feature = "tracing")]
+        tracing::info!(origin, words = image.len(), "operating system loaded");
+        self.initial_memory = self.memory.snapshot();
+        Ok(())
+    }
+    /// Loads another image stored in `format` at its `.ORIG`, f.e. an operating system or a
+    /// library of subroutines the program calls. Images below the user space are loaded like
+    /// [`Emulator::load_os`], all others as further code the PC may reach.
+    ///
+    /// #  Errors
+    /// - [`LoadProgramError::RegionOverlaps`] if the image overlaps an already loaded one
+    /// - See [`LoadProgramError`]
+    pub fn load_image(&mut self, path: &str, format: ImageFormat) -> Result<(), LoadProgramError> {
+        self.load_image_words(&read_image(path, format)?, true)?;
+        self.memory.set_source_of_last_region(path);
+        Ok(())
+    }
+    /// Loads an image from the contents of an object file, otherwise like
+    /// [`Emulator::load_image`].
+    ///
+    /// #  Errors
+    /// - See [`LoadProgramError`]
+    pub fn load_image_bytes(&mut self, data: &[u8]) -> Result<(), LoadProgramError> {
+        self.load_image_words(&words_from_bytes(data)?, true)
+    }
+    /// Loads an image stored in `format` at its `.ORIG` in the user space as data which is not
+    /// executed, like the regions of [`EmulatorBuilder::with_memory_region`].
+    ///
+    /// #  Errors
+    /// - [`LoadProgramError::RegionOutOfRange`] if the image is not in the user space
+    /// - See [`LoadProgramError`]
+    pub fn load_data_image(
+        &mut self,
+        path: &str,
+        format: ImageFormat,
+    ) -> Result<(), LoadProgramError> {
+        self.load_image_words(&read_image(path, format)?, false)?;
+        self.memory.set_source_of_last_region(path);
+        Ok(())
+    }
+    /// Loads a data image from the contents of an object file, otherwise like
+    /// [`Emulator::load_data_image`].
+    ///
+    /// #  Errors
+    /// - See [`LoadProgramError`]
+    pub fn load_data_image_bytes(&mut self, data: &[u8]) -> Result<(), LoadProgramError> {
+        self.load_image_words(&words_from_bytes(data)?, false)
+    }
+    fn load_image_words(&mut self, words: &[u16], code: bool) -> Result<(), LoadProgramError> {
+        let [origin, image @ ..] = words else {
+            return Err(LoadProgramError::ProgramMissingOrigHeader);
+        };
+        if code && *origin < self.memory.config().user_space.start {
+            return self.load_os_words(words);
+        }
+        if image.is_empty() {
+            return Err(LoadProgramError::ProgramEmpty);
+        }
+        if code {
+            self.memory.load_code_region(*origin, image)?;
+        } else {
+            self.memory.load_region(*origin, image)?;
+        }
+        self.initial_memory = self.memory.snapshot();
+        Ok(())
+    }
+    /// Defines how errors writing program output are handled,
+    /// see [`OutputErrorPolicy::default`] for the default.
+    pub const fn set_output_error_policy(&mut self, policy: OutputErrorPolicy) {
+        self.output_error_policy = policy;
+    }
+    /// Defines whether `BR` without condition codes always branches or never.
+    pub const fn set_br_nzp_zero_behavior(&mut self, behavior: BrNzpZeroBehavior) {
+        self.br_nzp_zero_behavior = behavior;
+    }
+    /// Defines how reaching the program end without `HALT` is handled,
+    /// default is [`ProgramEndPolicy::Warn`].
+    pub const fn set_program_end_policy(&mut self, policy: ProgramEndPolicy) {
+        self.program_end_policy = policy;
+    }
+    /// Writes a plain-English description of every executed instruction to `out`, f.e.
+    /// `x3003 ADD: R3 ← R2 + #5 = 30; flags=P`, aimed at students.
+    ///
+    /// The descriptions are kept apart from the program output, pass [`io::stderr`] to show
+    /// them next to it in a terminal. Forks do not explain their instructions.
+    pub fn set_explain(&mut self, out: impl Write + Send + 'static) {
+        self.explain = Some(Box::new(out));
+    }
+    /// Suppresses the messages the built-in trap routines add to the program output, the
+    /// `Input: ` prompt of `IN` and `Program halted` of `HALT`, f.e. to compare the output of a
+    /// program with expected output. Disabled by default.
+    pub const fn set_quiet(&mut self, quiet: bool) {
+        self.quiet = quiet;
+    }
+
+    /// Pauses execution before the instruction at the address of `breakpoint` is executed.
+    ///
+    /// [`Emulator::run_budgeted`] and the other non-blocking runs then return
+    /// [`StepResult::Breakpoint`], executing again resumes the program.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> BreakpointId {
+        self.breakpoints.add(breakpoint)
+    }
+    /// Removes the breakpoint with `id` and returns it if it still existed.
+    pub fn remove_breakpoint(&mut self, id: BreakpointId) -> Option<Breakpoint> {
+        self.breakpoints.remove(id)
+    }
+    /// All breakpoints including their hit counts.
+    pub fn breakpoints(&self) -> impl Iterator<Item = (BreakpointId, &Breakpoint)> {
+        self.breakpoints.iter()
+    }
+    /// Pauses execution whenever the program is about to call the trap routine `vector`,
+    /// f.e. `0x23` to stop each time it requests input.
+    ///
+    /// Non-blocking runs then return [`StepResult::TrapEntered`] before the `TRAP` is
+    /// executed.
+    pub fn break_on_trap(&mut self, vector: u8) {
+        self.breakpoints.break_on_trap(vector);
+    }
+    /// Stops breaking on the trap routine `vector`, returns `false` if there was no such break.
+    pub fn clear_trap_break(&mut self, vector: u8) -> bool {
+        self.breakpoints.clear_trap_break(vector)
+    }
+    /// `true` if execution stopped before the instruction at the PC and must not stop there
+    /// again.
+    const fn is_resuming_instruction(&self) -> bool {
+        matches!(
+            self.run_state,
+            RunState::AtBreakpoint | RunState::WaitingForInput
+        )
+    }
+    /// Pauses execution after an instruction accesses `address` in a way matching `kind`.
+    ///
+    /// [`Emulator::run_budgeted`] and [`Emulator::run_for_duration`] then return
+    /// [`StepResult::Watchpoint`], [`Emulator::execute`] returns [`HaltReason::Watchpoint`]
+    /// and the hit is available via [`Emulator::watchpoint_hit`] as well. Executing again
+    /// resumes the program.
+    pub fn add_watchpoint(&mut self, address: u16, kind: WatchKind) {
+        self.memory.add_watchpoint(address, kind);
+    }
+    /// Notifies `observer` about every memory read and write of the executed instructions.
+    pub fn add_memory_observer(&mut self, observer: impl MemoryObserver + Send + 'static) {
+        self.memory.add_observer(observer);
+    }
+    /// Removes the watchpoint at `address` if there is one.
+    pub fn remove_watchpoint(&mut self, address: u16) {
+        self.memory.remove_watchpoint(address);
+    }
+    /// The watchpoint which paused the most recent execution, if any.
+    #[must_use]
+    pub const fn watchpoint_hit(&self) -> Option<WatchpointHit> {
+        self.watchpoint_hit
+    }
+    /// Stops execution with [`ExecutionError::InstructionLimitExceeded`] before executing more
+    /// than `max` instructions in total, f.e. to guard against infinite loops of untrusted
+    /// programs. Unlimited by default.
+    pub const fn set_max_instructions(&mut self, max: u64) {
+        self.max_instructions = Some(max);
+    }
+    /// Requests `interrupt` of a device attached by the caller, f.e. a timer.
+    ///
+    /// It is taken before the next instruction once its priority is higher than the one of the
+    /// running program, see [`Registers::priority`].
+    ///
+    /// # Panics
+    /// - the priority is higher than 7
+    pub fn raise_interrupt(&mut self, interrupt: Interrupt) {
+        assert!(
+            interrupt.priority <= 7,
+            "interrupt priority must be at most 7"
+        );
+        self.interrupts.raise(interrupt);
+    }
+    /// Throttles execution to `hz` clock cycles per second, so interactive programs run at a human
+    /// pace. Each instruction takes one cycle unless changed by
+    /// [`Emulator::set_cycle_weights`]. Unthrottled by default.
+    pub fn set_clock(&mut self, hz: NonZeroU32) {
+        self.clock = Some(Clock::new(hz, self.cycle_weights));
+    }
+    /// Sets the clock cycles each instruction takes indexed by its opcode, f.e. to make memory
+    /// accesses slower than `ADD`.
+    pub const fn set_cycle_weights(&mut self, weights: [u32; 16]) {
+        self.cycle_weights = weights;
+        if let Some(clock) = self.clock.as_mut() {
+            clock.set_weights(weights);
+        }
+    }
+    /// Calls `handler` with every [`MachineEvent`] whose kind is in `mask`, until the returned
+    /// id is passed to [`Emulator::unsubscribe`].
+    pub fn subscribe(
+        &mut self,
+        mask: EventMask,
+        handler: impl FnMut(&MachineEvent) + Send + 'static,
+    ) -> SubscriptionId {
+        let id = self.events.subscribe(mask, Box::new(handler));
+        self.update_write_logging();
+        id
+    }
+    /// Ends the subscription `id`, returns `false` if it did not exist.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let removed = self.events.unsubscribe(id);
+        self.update_write_logging();
+        removed
+    }
+    /// Calls `hook` after fetching and before executing every instruction.
+    pub fn set_pre_instruction_hook(
+        &mut self,
+        hook: impl FnMut(&mut HookContext) + Send + 'static,
+    ) {
+        self.pre_instruction_hook = Some(Box::new(hook));
+    }
+    /// Calls `hook` after every executed instruction.
+    pub fn set_post_instruction_hook(
+        &mut self,
+        hook: impl FnMut(&mut HookContext) + Send + 'static,
+    ) {
+        self.post_instruction_hook = Some(Box::new(hook));
+    }
+    /// Summary of the execution so far including the semantics chosen.
+    #[must_use]
+    pub const fn execution_summary(&self) -> ExecutionSummary {
+        ExecutionSummary {
+            instructions_executed: self.instructions_executed,
+            br_nzp_zero_behavior: self.br_nzp_zero_behavior,
+            ran_past_program_end: self.ran_past_program_end,
+        }
+    }
+    /// Counts of the executed instructions per opcode and trap vector and of taken branches,
+    /// unlike [`Emulator::execution_summary`] not rewound by [`Emulator::step_back`].
+    #[must_use]
+    pub const fn stats(&self) -> &ExecutionStats {
+        &self.stats
+    }
+    /// Restarts collecting [`Emulator::stats`] from zero.
+    pub fn reset_stats(&mut self) {
+        self.stats = ExecutionStats::default();
+    }
+    /// Counts executions per address for [`Emulator::profile`], disabled by default.
+    ///
+    /// Enabling it again restarts counting from zero.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profiler = enabled.then(Profiler::new);
+    }
+    /// The at most `limit` most executed addresses since profiling was enabled, hottest first,
+    /// with their symbols and disassembly.
+    #[must_use]
+    pub fn profile(&self, limit: usize) -> Vec<HotAddress> {
+        self.profiler.as_ref().map_or_else(Vec::new, |profiler| {
+            profiler
+                .hottest(limit)
+                .into_iter()
+                .map(|(address, count)| {
+                    let (mnemonic, operands) = disassembler::disassemble_word(
+                        address,
+                        self.memory.peek(address),
+                        &self.symbols,
+                    );
+                    HotAddress {
+                        address,
+                        count,
+                        label: self.symbols.name_at(address).map(str::to_owned),
+                        instruction: format!("{mnemonic} {operands}").trim_end().to_owned(),
+                    }
+                })
+                .collect()
+        })
+    }
+    /// Records executed addresses and directions of conditional branches for
+    /// [`Emulator::coverage`], disabled by default.
+    ///
+    /// Enabling it again restarts recording.
+    pub fn set_coverage(&mut self, enabled: bool) {
+        self.coverage = enabled.then(Coverage::new);
+    }
+    /// Coverage of the executable loaded regions since recording was enabled, [`None`] if it
+    /// is disabled.
+    ///
+    /// Words never executed which are data, i.e. not executable or obviously data of the
+    /// program like its strings, see [`Emulator::image_info`], are left out. Other data, f.e.
+    /// a `.FILL` of a large number, still counts as uncovered.
+    #[must_use]
+    pub fn coverage(&self) -> Option<CoverageReport> {
+        let coverage = self.coverage.as_ref()?;
+        let data_regions = self.image_info().data_regions;
+        let is_data = |address: u16| {
+            !self.memory.is_executable(address)
+                || data_regions.iter().any(|region| region.contains(&address))
+        };
+        Some(coverage.report(&self.memory.layout(), is_data))
+    }
+    /// Describes every occupied memory region with its permissions and source file,
+    /// ordered by address.
+    #[must_use]
+    pub fn layout(&self) -> Vec<LoadedRegion> {
+        self.memory.layout()
+    }
+    /// Enables export of anonymized session events to `sink`, which is disabled by default.
+    ///
+    /// Records a [`SessionEvent::ProgramLoaded`] for the currently loaded program immediately.
+    pub fn set_event_log(&mut self, sink: impl EventLogSink + Send + 'static) {
+        let mut log = EventLog::new(Box::new(sink));
+        let program = self.memory.program_slice();
+        log.record(&SessionEvent::ProgramLoaded {
+            program_hash: event_log::program_hash(program),
+            words: u16::try_from(program.len()).unwrap_or(u16::MAX),
+        });
+        self.event_log = Some(log);
+    }
+    /// Sends a [`TraceEvent`] for every executed instruction to `sink` instead of the sinks
+    /// attached before.
+    pub fn set_trace_sink(&mut self, sink: impl TraceSink + Send + 'static) {
+        self.trace_sinks.clear();
+        self.add_trace_sink(sink);
+    }
+    /// Sends a [`TraceEvent`] for every executed instruction to `sink` as well, f.e. to write
+    /// a text trace and a VCD file of the same run.
+    pub fn add_trace_sink(&mut self, sink: impl TraceSink + Send + 'static) {
+        self.trace_sinks.push(Box::new(sink));
+        self.update_write_logging();
+    }
+    /// Keeps the state changes of the last `depth` executed instructions to allow
+    /// [`Emulator::step_back`], `0` disables the history which is the default.
+    pub fn set_history_depth(&mut self, depth: usize) {
+        self.history = (depth > 0).then(|| History::new(depth));
+        self.update_write_logging();
+    }
+    /// Treats R6 as stack pointer of a stack growing down from the stack base `bounds.end()` to
+    /// at most `bounds.start()`.
+    ///
+    /// R6 is not changed, the program initializes it with the stack base as usual. Until then
+    /// R6 may be outside the bounds, only instructions changing R6 are checked.
+    ///
+    /// Instructions of User mode programs changing R6 outside the bounds stop execution with
+    /// [`ExecutionError::StackOverflow`] or [`ExecutionError::StackUnderflow`].
+    pub const fn set_stack_bounds(&mut self, bounds: RangeInclusive<u16>) {
+        self.stack_bounds = Some(bounds);
+    }
+    /// The pushed values from the top of the stack at R6 up to the stack base, `None` if no
+    /// stack bounds are set or R6 is outside of them.
+    #[must_use]
+    pub fn stack_slice(&self) -> Option<&[u16]> {
+        let bounds = self.stack_bounds.as_ref()?;
+        let sp = self.registers.get(6).as_binary();
+        bounds
+            .contains(&sp)
+            .then(|| self.memory.cells(sp..*bounds.end()))
+    }
+    fn check_stack(&self, sp_before: u16) -> Result<(), ExecutionError> {
+        let sp = self.registers.get(6).as_binary();
+        let Some(bounds) = self.stack_bounds.as_ref() else {
+            return Ok(());
+        };
+        if sp == sp_before || bounds.contains(&sp) || self.registers.privilege() != Privilege::User
+        {
+            return Ok(());
+        }
+        let pc = self.last_executed.map_or(0, |(pc, _)| pc);
+        Err(if sp < *bounds.start() {
+            ExecutionError::StackOverflow { pc, sp }
+        } else {
+            ExecutionError::StackUnderflow { pc, sp }
+        })
+    }
+    /// Maps the serial line registers `xFE0A` to `xFE10` to `device`, f.e. one end of a
+    /// [`serial::link`](crate::hardware::serial::link) to another emulator.
+    pub fn attach_serial(&mut self, device: impl SerialDevice + Send + 'static) {
+        self.memory.attach_serial(Box::new(device));
+    }
+    /// Calls `renderer` whenever an instruction writes to the cells of `framebuffer`.
+    ///
+    /// # Errors
+    /// - [`StateAccessError::FramebufferOutOfRange`] if `framebuffer` does not fit into memory
+    pub fn set_display_renderer(
+        &mut self,
+        framebuffer: Framebuffer,
+        renderer: impl DisplayRenderer + Send + 'static,
+    ) -> Result<(), StateAccessError> {
+        if framebuffer.range().is_none() {
+            return Err(StateAccessError::FramebufferOutOfRange {
+                origin: framebuffer.origin,
+                columns: framebuffer.columns,
+                rows: framebuffer.rows,
+            });
+        }
+        self.display = Some((framebuffer, Box::new(renderer)));
+        self.update_write_logging();
+        Ok(())
+    }
+    fn update_write_logging(&mut self) {
+        self.memory.set_write_logging(
+            !self.trace_sinks.is_empty()
+                || self.history.is_some()
+                || self.display.is_some()
+                || self.events.wants(EventMask::MEMORY_WRITTEN),
+        );
+    }
+    fn render_display(&mut self) {
+        let Some((framebuffer, renderer)) = self.display.as_mut() else {
+            return;
+        };
+        // validated by `set_display_renderer`
+        let Some(range) = framebuffer.range() else {
+            return;
+        };
+        if self
+            .last_memory_writes
+            .iter()
+            .any(|access| range.contains(&access.address))
+        {
+            renderer.render(framebuffer, self.memory.cells(range));
+        }
+    }
+    /// Number of instructions [`Emulator::step_back`] can currently revert.
+    #[must_use]
+    pub fn steps_back_available(&self) -> usize {
+        self.history.as_ref().map_or(0, History::len)
+    }
+    /// Reverts the most recently executed instruction including its register and memory writes.
+    ///
+    /// Program output and consumed keyboard input are not reverted.
+    /// Returns `false` if the history is empty, see [`Emulator::set_history_depth`].
+    pub fn step_back(&mut self) -> bool {
+        let Some(entry) = self.history.as_mut().and_then(History::pop) else {
+            return false;
+        };
+        for write in entry.memory_writes.iter().rev() {
+            self.memory.restore(write.address, write.old);
+        }
+        self.registers = entry.registers;
+        if let Some(change) = entry.call_stack_change {
+            self.call_stack.revert(change);
+        }
+        self.instructions_executed -= 1;
+        self.run_state = RunState::Running;
+        self.last_executed = None;
+        true
+    }
+    /// Takes a checkpoint every `interval` executed instructions, keeping the most recent
+    /// `capacity` ones for [`Emulator::rewind_to_checkpoint`].
+    ///
+    /// Unlike [`Emulator::set_history_depth`] this does not record every instruction, so it
+    /// is cheap enough for programs running millions of instructions. Memory is shared
+    /// copy-on-write between checkpoints. An `interval` or `capacity` of 0 disables
+    /// checkpoints.
+    pub fn set_checkpoint_interval(&mut self, interval: u64, capacity: usize) {
+        self.checkpoints =
+            (interval > 0 && capacity > 0).then(|| Checkpoints::new(interval, capacity));
+    }
+    /// Number of executed instructions of each available checkpoint, most recent first.
+    #[must_use]
+    pub fn checkpoints(&self) -> Vec<u64> {
+        self.checkpoints
+            .as_ref()
+            .map_or_else(Vec::new, |c| c.positions().collect())
+    }
+    /// Restores the `k`-th most recent checkpoint, 0 being the most recent one, and discards
+    /// all newer checkpoints as well as the step back history.
+    ///
+    /// Program output and consumed keyboard input are not reverted.
+    /// Returns `false` if there are not enough checkpoints.
+    pub fn rewind_to_checkpoint(&mut self, k: usize) -> bool {
+        let Some(checkpoint) = self.checkpoints.as_mut().and_then(|c| c.rewind(k)) else {
+            return false;
+        };
+        self.memory.restore_snapshot(&checkpoint.memory);
+        self.registers = checkpoint.registers.clone();
+        self.call_stack = checkpoint.call_stack.clone();
+        self.instructions_executed = checkpoint.instructions_executed;
+        if let Some(history) = self.history.as_mut() {
+            history.clear();
+        }
+        self.run_state = RunState::Running;
+        self.last_executed = None;
+        true
+    }
+    fn record_checkpoint(&mut self) {
+        if let Some(checkpoints) = self.checkpoints.as_mut()
+            && checkpoints.is_due(self.instructions_executed)
+        {
+            checkpoints.push(Checkpoint {
+                instructions_executed: self.instructions_executed,
+                registers: self.registers.clone(),
+                memory: self.memory.snapshot(),
+                call_stack: self.call_stack.clone(),
+            });
+        }
+    }
+    fn record_event(&mut self, event: impl FnOnce(&Self) -> SessionEvent) {
+        if self.event_log.is_some() {
+            let event = event(self);
+            if let Some(log) = self.event_log.as_mut() {
+                log.record(&event);
+            }
+        }
+    }
+    /// Non-fatal findings collected while loading and executing the program.
+    #[must_use]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Scans the loaded program for likely assembler mistakes like reserved opcodes, unknown
+    /// trap vectors, branches leaving the program or `RET` before any `JSR`.
+    ///
+    /// Data marked via [`Emulator::mark_non_executable`] is skipped.
+    #[must_use]
+    pub fn validate(&self) -> Vec<LoadWarning> {
+        validation::validate(&self.memory)
+    }
+    /// Origin, length, obvious data regions and embedded strings of the loaded program.
+    #[must_use]
+    pub fn image_info(&self) -> ImageInfo {
+        image_info::image_info(&self.memory)
+    }
+
+    /// Words of all loaded regions ordered by address, including the OS and regions loaded
+    /// via [`EmulatorBuilder::with_memory_region`], with their current content.
+    #[must_use]
+    pub fn instructions(&self) -> impl ExactSizeIterator<Item = LoadedWord> + Debug {
+        self.memory
+            .layout()
+            .iter()
+            .flat_map(|region| (region.origin..).take(usize::from(region.length)))
+            .map(|address| LoadedWord::new(address, self.memory.peek(address)))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Executes the loaded program.
+    /// # Errors
+    /// - See [`ExecutionError`]
+    pub fn execute_with_stdout(
+        &mut self,
+        stdout: &mut (impl Write + CrosstermCompatibility),
+    ) -> Result<ExitStatus, ExecutionError> {
+        let res = self.run_observed(stdout, RunLimits::new(InputMode::Blocking))?;
+        Ok(self.exit_status(res))
+    }
+
+    /// Resumes execution where the previous one stopped, f.e. at a breakpoint, after
+    /// [`ExecutionError::Interrupted`] or in a `GETC` waiting for input, which is not repeated.
+    ///
+    /// After [`ExecutionError::InstructionLimitExceeded`] the limit has to be raised via
+    /// [`Emulator::set_max_instructions`] first. A halted program stays halted.
+    ///
+    /// # Errors
+    /// - See [`ExecutionError`]
+    pub fn continue_execution(
+        &mut self,
+        stdout: &mut (impl Write + CrosstermCompatibility),
+    ) -> Result<ExitStatus, ExecutionError> {
+        self.execute_with_stdout(stdout)
+    }
+
+    /// Executes the loaded program reading keyboard input from `input` and writing program output
+    /// to `output`, neither of them involving the terminal.
+    ///
+    /// This fully virtualizes the console, f.e. for GUIs, web frontends or grading servers.
+    /// `input` replaces the keyboard input provider for all later executions as well.
+    ///
+    /// # Errors
+    /// - See [`ExecutionError`]
+    pub fn execute_with_io(
+        &mut self,
+        input: impl KeyboardInputProvider + Send + 'static,
+        output: impl Write,
+    ) -> Result<ExitStatus, ExecutionError> {
+        self.set_input_provider(input);
+        self.execute_with_stdout(&mut VirtualConsole(output))
+    }
+    /// Executes the loaded program with `input` as keyboard input and captures its output, f.e.
+    /// for tests.
+    ///
+    /// Unlike [`Emulator::execute_with_io`] a `GETC` or `IN` after all of `input` was read
+    /// ends the run with [`HaltReason::InputExhausted`] instead of waiting.
+    ///
+    /// # Errors
+    /// - See [`ExecutionError`]
+    pub fn execute_captured(&mut self, input: &str) -> Result<RunOutcome, ExecutionError> {
+        let mut output = Vec::new();
+        let status = self.execute_scripted(input, &mut output)?;
+        Ok(RunOutcome {
+            output: String::from_utf8_lossy(&output).into_owned(),
+            registers: self.registers.clone(),
+            instructions_executed: status.instructions_executed,
+            halt_reason: status.halt_reason,
+        })
+    }
+    /// Executes the loaded program with `input` as keyboard input and writes its output to
+    /// `output`, f.e. for batch runs of interactive programs with input from a file.
+    ///
+    /// Like [`Emulator::execute_captured`] a `GETC` or `IN` after all of `input` was read ends
+    /// the run with [`HaltReason::InputExhausted`] instead of waiting.
+    ///
+    /// # Errors
+    /// - See [`ExecutionError`]
+    pub fn execute_scripted(
+        &mut self,
+        input: &str,
+        output: impl Write,
+    ) -> Result<ExitStatus, ExecutionError> {
+        self.set_input_provider(ScriptedInputProvider::new(input));
+        self.continue_scripted(output)
+    }
+    /// Resumes an execution started via [`Emulator::execute_scripted`] where it stopped, f.e.
+    /// at a breakpoint, with the part of the input not read yet.
+    ///
+    /// # Errors
+    /// - See [`ExecutionError`]
+    pub fn continue_scripted(&mut self, output: impl Write) -> Result<ExitStatus, ExecutionError> {
+        let res = self.run_observed(
+            &mut VirtualConsole(output),
+            RunLimits::new(InputMode::NonBlocking),
+        )?;
+        Ok(self.exit_status(res))
+    }
+    /// Runs the program `iterations` times from a fresh machine like
+    /// [`Emulator::execute_scripted`] with `input` as keyboard input and discards the output,
+    /// measuring the wall time of every run.
+    ///
+    /// Memory and registers are reset before every run, so the measurement excludes loading the
+    /// program but covers the same dispatch as other executions including attached trace sinks
+    /// and breakpoints.
+    ///
+    /// # Errors
+    /// - A run fails, see [`ExecutionError`]
+    pub fn bench(&mut self, iterations: u32, input: &str) -> Result<BenchReport, ExecutionError> {
+        let mut durations = Vec::new();
+        let mut instructions_executed = 0;
+        let mut halt_reason = HaltReason::HaltTrap;
+        for _ in 0..iterations {
+            self.reset();
+            let start = Instant::now();
+            let status = self.execute_scripted(input, io::sink())?;
+            durations.push(start.elapsed());
+            instructions_executed += status.instructions_executed;
+            halt_reason = status.halt_reason;
+        }
+        Ok(BenchReport {
+            durations,
+            instructions_executed,
+            halt_reason,
+        })
+    }
+    /// Status after a run which ended with `res`.
+    fn exit_status(&self, res: StepResult) -> ExitStatus {
+        let halt_reason = match res {
+            StepResult::Halted => self.halted_by.unwrap_or(HaltReason::HaltTrap),
+            StepResult::WaitingForInput => HaltReason::InputExhausted,
+            StepResult::Breakpoint(hit) => HaltReason::Breakpoint(hit),
+            StepResult::Watchpoint(hit) => HaltReason::Watchpoint(hit),
+            stopped => HaltReason::Stopped(stopped),
+        };
+        ExitStatus {
+            halt_reason,
+            pc: self.registers.pc().as_binary(),
+            instructions_executed: self.instructions_executed,
+        }
+    }
+    /// Handle to cancel the execution from another thread, f.e. to stop a runaway program
+    /// without killing the process.
+    ///
+    /// All tokens of an emulator share the same state.
+    #[must_use]
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel_token.clone()
+    }
+    /// Writes every keyboard character delivered to the program to a new file at `path`
+    /// together with the number of instructions executed before it became available, until the
+    /// input provider is replaced. See [`Emulator::replay`].
+    ///
+    /// Characters passed to [`Emulator::queue_input`] are recorded as well.
+    ///
+    /// # Errors
+    /// - the file cannot be created
+    pub fn record_input(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        writeln!(file, "{}", replay::HEADER)?;
+        self.shared_input_queue();
+        let provider: SharedInputProvider = Arc::new(Mutex::new(RecordingInputProvider::new(
+            self.keyboard_input_provider.clone(),
+            Box::new(file),
+        )));
+        self.memory.set_keyboard_input_provider(provider.clone());
+        self.keyboard_input_provider = provider;
+        Ok(())
+    }
+    /// Feeds the keyboard input recorded via [`Emulator::record_input`] to the program, each
+    /// character once as many instructions were executed as when it was recorded.
+    ///
+    /// Replaying reproduces the recorded session when started in the same state, f.e. right
+    /// after loading the program like the recording.
+    ///
+    /// # Errors
+    /// - See [`ReplayError`]
+    pub fn replay(&mut self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        let recording = InputRecording::from_file(path)?;
+        self.set_input_provider(ReplayInputProvider::new(recording));
+        Ok(())
+    }
+    /// Reads keyboard input from `provider` instead of the one the emulator was built with.
+    ///
+    /// Characters still queued via [`Emulator::queue_input`] are delivered first.
+    pub fn set_input_provider(&mut self, provider: impl KeyboardInputProvider + Send + 'static) {
+        self.chaos_seed = None;
+        self.real_time_input = None;
+        self.install_input_provider(Arc::new(Mutex::new(provider)));
+    }
+    /// Delivers the characters of `input` to the program before reading from the keyboard
+    /// input provider again, f.e. to preload answers of an otherwise interactive run.
+    pub fn queue_input(&mut self, input: &str) {
+        self.input_queue_mut().extend(input.chars());
+    }
+    /// Delivers `key` to the program after the characters queued before, see
+    /// [`Emulator::queue_input`].
+    pub fn queue_key(&mut self, key: char) {
+        self.input_queue_mut().extend([key]);
+    }
+    fn input_queue_mut(&mut self) -> MutexGuard<'_, InputQueue> {
+        lock(self.shared_input_queue())
+    }
+    /// The input queue, created and installed in front of the keyboard input provider on
+    /// first use.
+    fn shared_input_queue(&mut self) -> &Arc<Mutex<InputQueue>> {
+        if self.input_queue.is_none() {
+            let queue: Arc<Mutex<InputQueue>> = Arc::default();
+            // the detached provider is reattached behind the queue as well
+            if let Some((provider, _)) = &mut self.real_time_input {
+                *provider = Arc::new(Mutex::new(QueuedInputProvider::new(
+                    queue.clone(),
+                    provider.clone(),
+                )));
+            }
+            self.input_queue = Some(queue);
+            self.install_input_provider(self.keyboard_input_provider.clone());
+        }
+        self.input_queue
+            .as_ref()
+            .expect("input queue was just created")
+    }
+    /// Uses `provider` for the keyboard registers and traps, behind the input queue if any.
+    fn install_input_provider(&mut self, provider: SharedInputProvider) {
+        let provider: SharedInputProvider = match &self.input_queue {
+            Some(queue) => Arc::new(Mutex::new(QueuedInputProvider::new(
+                queue.clone(),
+                provider,
+            ))),
+            None => provider,
+        };
+        self.memory.set_keyboard_input_provider(provider.clone());
+        self.keyboard_input_provider = provider;
+    }
+
+    /// Executes at most `max_instructions` instructions and returns control to the caller,
+    /// for embedders driving the emulator from their own single-threaded loop.
+    ///
+    /// Never blocks waiting for keyboard input but returns [`StepResult::WaitingForInput`],
+    /// calling again resumes the pending `GETC` or `IN` without repeating its prompt.
+    ///
+    /// # Errors
+    /// - See [`ExecutionError`]
+    pub fn run_budgeted(
+        &mut self,
+        max_instructions: u64,
+        stdout: &mut (impl Write + CrosstermCompatibility),
+    ) -> Result<StepResult, ExecutionError> {
+        let limits = RunLimits {
+            max_instructions: Some(max_instructions),
+            ..RunLimits::new(InputMode::NonBlocking)
+        };
+        self.run_observed(stdout, limits)
+    }
+
+    /// Executes the loaded program as a future writing program output to `output`, f.e. in
+    /// async servers or WASM event loops.
+    ///
+    /// Yields to the executor after every `yield_interval` instructions and, instead of
+    /// blocking, while a `GETC` or `IN` waits for input, which is best sent via an
+    /// [`InputSender`](async_runner::InputSender) to wake the task.
+    ///
+    /// Returns how the program halted or why execution stopped otherwise, f.e. at a breakpoint.
+    /// A [`CancelToken`] wakes the task while it waits for input.
+    ///
+    /// # Errors
+    /// - See [`ExecutionError`]
+    #[cfg(feature = "async")]
+    pub async fn execute_async(
+        &mut self,
+        yield_interval: u64,
+        output: impl Write + Send,
+    ) -> Result<ExitStatus, ExecutionError> {
+        let mut output = VirtualConsole(output);
+        loop {
+            match self.run_budgeted(yield_interval, &mut output)? {
+                StepResult::BudgetExhausted => async_runner::YieldNow::default().await,
+                StepResult::WaitingForInput => {
+                    async_runner::InputReady::new(
+                        self.keyboard_input_provider.clone(),
+                        self.input_queue.clone(),
+                        self.cancel_token.clone(),
+                    )
+                    .await;
+                }
+                res => return Ok(self.exit_status(res)),
+            }
+        }
+    }
+    /// Handle to send keyboard input from another task or thread, delivered like
+    /// [`Emulator::queue_input`].
+    #[cfg(feature = "async")]
+    pub fn input_sender(&mut self) -> async_runner::InputSender {
+        async_runner::InputSender(self.shared_input_queue().clone())
+    }
+
+    /// Executes instructions until roughly `duration` has passed and returns control to the caller,
+    /// otherwise like [`Emulator::run_budgeted`].
+    ///
+    /// # Errors
+    /// - See [`ExecutionError`]
+    pub fn run_for_duration(
+        &mut self,
+        duration: Duration,
+        stdout: &mut (impl Write + CrosstermCompatibility),
+    ) -> Result<StepResult, ExecutionError> {
+        let deadline = match self.time_mode {
+            TimeMode::RealTime => Deadline::WallClock(Instant::now() + duration),
+            TimeMode::Deterministic => {
+                let cycles = duration.as_nanos() * u128::from(self.clock_hz()) / 1_000_000_000;
+                Deadline::Cycles(
+                    self.cycles
+                        .saturating_add(u64::try_from(cycles).unwrap_or(u64::MAX)),
+                )
+            }
+        };
+        let limits = RunLimits {
+            deadline: Some(deadline),
+            ..RunLimits::new(InputMode::NonBlocking)
+        };
+        self.run_observed(stdout, limits)
+    }
+
+    /// Executes until `condition` is true after an instruction, the program halts or
+    /// `max_instructions` were executed, otherwise like [`Emulator::run_budgeted`].
+    ///
+    /// This covers f.e. running to an address, until `R0` is 10 or until a memory cell is
+    /// written, see [`MachineState`].
+    ///
+    /// # Errors
+    /// - See [`ExecutionError`]
+    pub fn run_until(
+        &mut self,
+        max_instructions: u64,
+        stdout: &mut (impl Write + CrosstermCompatibility),
+        mut condition: impl FnMut(&MachineState) -> bool,
+    ) -> Result<StepResult, ExecutionError> {
+        self.memory.set_write_logging(true);
+        let limits = RunLimits {
+            max_instructions: Some(max_instructions),
+            stop_condition: Some(&mut condition),
+            ..RunLimits::new(InputMode::NonBlocking)
+        };
+        let res = self.run_observed(stdout, limits);
+        self.update_write_logging();
+        res
+    }
+
+    /// Executes the next instruction, treating a subroutine call via `JSR` or `JSRR` as a
+    /// single instruction by running until the subroutine returns.
+    ///
+    /// Returns [`StepResult::StepCompleted`] unless execution stopped earlier, f.e. because
+    /// more than `max_instructions` were executed or a breakpoint was reached.
+    ///
+    /// # Errors
+    /// - See [`ExecutionError`]
+    pub fn step_over(
+        &mut self,
+        max_instructions: u64,
+        stdout: &mut (impl Write + CrosstermCompatibility),
+    ) -> Result<StepResult, ExecutionError> {
+        let depth = self.call_stack.frames().len();
+        self.run_to_depth(depth, max_instructions, stdout)
+    }
+
+    /// Executes until the current subroutine returns to its caller, otherwise like
+    /// [`Emulator::step_over`].
+    ///
+    /// Outside of a subroutine this is the same as [`Emulator::step_over`].
+    ///
+    /// # Errors
+    /// - See [`ExecutionError`]
+    pub fn step_out(
+        &mut self,
+        max_instructions: u64,
+        stdout: &mut (impl Write + CrosstermCompatibility),
+    ) -> Result<StepResult, ExecutionError> {
+        let depth = self.call_stack.frames().len().saturating_sub(1);
+        self.run_to_depth(depth, max_instructions, stdout)
+    }
+
+    fn run_to_depth(
+        &mut self,
+        depth: usize,
+        max_instructions: u64,
+        stdout: &mut (impl Write + CrosstermCompatibility),
+    ) -> Result<StepResult, ExecutionError> {
+        let limits = RunLimits {
+            max_instructions: Some(max_instructions),
+            return_depth: Some(depth),
+            ..RunLimits::new(InputMode::NonBlocking)
+        };
+        self.run_observed(stdout, limits)
+    }
+
+    /// Runs with output error policy, event logging and chaos seed reporting applied.
+    fn run_observed(
+        &mut self,
+        stdout: &mut (impl Write + CrosstermCompatibility),
+        limits: RunLimits,
+    ) -> Result<StepResult, ExecutionError> {
+        if self.instructions_executed == 0 && self.run_state == RunState::Running {
+            self.record_event(|_| SessionEvent::RunStarted);
+        }
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("run", pc = self.registers.pc().as_binary()).entered();
+        let mut stdout = PolicyWriter::new(stdout, self.output_error_policy);
+        let res = self.run(&mut stdout, limits);
+        #[cfg(feature = "tracing")]
+        self.trace_run_end(&res);
+        self.watchpoint_hit = match res {
+            Ok(StepResult::Watchpoint(hit)) => Some(hit),
+            _ => None,
+        };
+        match &res {
+            Ok(StepResult::Halted) => self.record_event(|emu| SessionEvent::RunFinished {
+                instructions_executed: emu.instructions_executed,
+            }),
+            Err(e) => self.record_event(|emu| SessionEvent::ErrorEncountered {
+                error: e.to_string(),
+                pc: emu.registers.pc().as_binary(),
+                instructions_executed: emu.instructions_executed,
+            }),
+            Ok(StepResult::Breakpoint(hit)) => {
+                let address = hit.address;
+                self.record_event(|_| SessionEvent::BreakpointHit { address });
+            }
+            Ok(
+                StepResult::BudgetExhausted
+                | StepResult::WaitingForInput
+                | StepResult::Watchpoint(_)
+                | StepResult::ConditionMet
+                | StepResult::StepCompleted
+                | StepResult::TrapEntered { .. },
+            ) => {}
+        }
+        match self.chaos_seed {
+            Some(seed) => res.map_err(|e| ExecutionError::ChaosRunFailed {
+                seed,
+                error: Box::new(e),
+            }),
+            None => res,
+        }
+    }
+
+    /// Counts the cycles of an instruction with `op_code`, throttled by the clock in real time.
+    fn tick(&mut self, op_code: u8) {
+        self.cycles += u64::from(self.cycle_weights[usize::from(op_code & 0xF)]);
+        if self.time_mode == TimeMode::RealTime
+            && let Some(clock) = self.clock.as_mut()
+        {
+            clock.tick(op_code);
+        }
+    }
+
+    /// Halts and errors are logged at INFO level, runs stopped for other reasons at DEBUG.
+    #[cfg(feature = "tracing")]
+    fn trace_run_end(&self, res: &Result<StepResult, ExecutionError>) {
+        let pc = self.registers.pc().as_binary();
+        let instructions_executed = self.instructions_executed;
+        match res {
+            Ok(StepResult::Halted) => tracing::info!(
+                pc,
+                instructions_executed,
+                halt_reason = ?self.halted_by,
+                "program halted"
+            ),
+            Ok(result) => tracing::debug!(pc, instructions_executed, ?result, "run stopped"),
+            Err(error) => tracing::info!(pc, instructions_executed, %error, "run failed"),
+        }
+    }
+
+    fn run(
+        &mut self,
+        stdout: &mut (impl Write + CrosstermCompatibility),
+        mut limits: RunLimits,
+    ) -> Result<StepResult, ExecutionError> {
+        let mut executed = 0u64;
+        loop {
+            if self.run_state == RunState::Halted {
+                return Ok(StepResult::Halted);
+            }
+            let pc = self.registers.pc().as_binary();
+            if pc >= self.memory.program_end() && !self.memory.in_code_region(pc) {
+                self.halt(HaltReason::RanPastProgramEnd);
+                self.handle_program_end()?;
+                return Ok(StepResult::Halted);
+            }
+            if self
+                .max_instructions
+                .is_some_and(|max| self.instructions_executed >= max)
+            {
+                return Err(ExecutionError::InstructionLimitExceeded {
+                    executed: self.instructions_executed,
+                    pc: self.registers.pc().as_binary(),
+                });
+            }
+            if self.cancel_token.take() || self.memory.take_keyboard_interrupt() {
+                return Err(ExecutionError::Interrupted(self.registers.pc().as_binary()));
+            }
+            if limits.max_instructions.is_some_and(|max| executed >= max)
+                || limits.deadline.is_some_and(|deadline| match deadline {
+                    Deadline::WallClock(deadline) => {
+                        executed.is_multiple_of(DEADLINE_CHECK_INTERVAL)
+                            && executed > 0
+                            && Instant::now() >= deadline
+                    }
+                    Deadline::Cycles(cycles) => self.cycles >= cycles,
+                })
+            {
+                return Ok(StepResult::BudgetExhausted);
+            }
+            if !self.is_resuming_instruction() {
+                self.take_interrupt();
+            }
+            if !self.is_resuming_instruction()
+                && let Some(hit) = self.breakpoints.on_fetch(self.registers.pc().as_binary())
+            {
+                self.run_state = RunState::AtBreakpoint;
+                return Ok(StepResult::Breakpoint(hit));
+            }
+            let sp = self.registers.get(6).as_binary();
+            match self.step(stdout, limits.input_mode) {
+                // blocking reads only return when interrupted, which is reported above
+                ControlFlow::Break(Ok(StepResult::WaitingForInput))
+                    if limits.input_mode == InputMode::Blocking =>
+                {
+                    continue;
+                }
+                ControlFlow::Break(res) => return res,
+                ControlFlow::Continue(()) => {}
+            }
+            executed += 1;
+            self.check_stack(sp)?;
+            self.render_display();
+            if let Some((_, raw)) = self.last_executed {
+                self.tick(Instruction::from(raw).op_code());
+            }
+            if let Some(condition) = limits.stop_condition.as_mut()
+                && condition(&self.state())
+            {
+                return Ok(StepResult::ConditionMet);
+            }
+            if limits
+                .return_depth
+                .is_some_and(|depth| self.call_stack.frames().len() <= depth)
+            {
+                return Ok(StepResult::StepCompleted);
+            }
+        }
+    }
+
+    /// Fetches and executes a single instruction.
+    ///
+    /// If the instruction has to wait for input it is not counted and the PC is rewound to it,
+    /// so that it is executed again when resuming.
+    fn step(
+        &mut self,
+        stdout: &mut (impl Write + CrosstermCompatibility),
+        input_mode: InputMode,
+    ) -> StepFlow {
+        let pc = self.registers.pc().as_binary();
+        // only accesses of this instruction count, not those of an earlier one that stopped
+        let _ = self.memory.take_watchpoint_hit();
+        let data = self.memory[pc];
+        if let Err(e) = self.check_executable(pc, data) {
+            return ControlFlow::Break(Err(e));
+        }
+        let i = Instruction::from(data);
+        let registers_before_fetch = self.history.is_some().then(|| self.registers.clone());
+        let pre_hook_writes = hooks::call(
+            self.pre_instruction_hook.as_mut(),
+            HookContext::new(pc, i, &mut self.registers, &mut self.memory),
+        );
+        self.registers
+            .set_pc(pc.wrapping_add(self.isa_variant.word_size()));
+        self.instructions_executed += 1;
+        let before = (self.explain.is_some() || self.events.wants(EventMask::REGISTER_WRITTEN))
+            .then(|| self.registers.clone());
+        let flow = match self.execute_instruction(i, stdout, input_mode) {
+            ControlFlow::Continue(()) => self.check_jump_target(pc, i),
+            flow @ ControlFlow::Break(_) => flow,
+        };
+        let executed = matches!(
+            flow,
+            ControlFlow::Continue(()) | ControlFlow::Break(Ok(StepResult::Halted))
+        );
+        self.last_memory_writes = self.memory.take_writes();
+        let post_hook_writes = hooks::call(
+            self.post_instruction_hook.as_mut().filter(|_| executed),
+            HookContext::new(pc, i, &mut self.registers, &mut self.memory),
+        );
+        let call_stack_change = executed.then(|| self.record_executed(pc, i)).flatten();
+        if executed {
+            self.trace_executed(pc, data, i);
+        }
+        if executed
+            && let Some(history) = self.history.as_mut()
+            && let Some(registers) = registers_before_fetch
+        {
+            history.push(HistoryEntry {
+                registers,
+                memory_writes: [
+                    pre_hook_writes,
+                    self.last_memory_writes.clone(),
+                    post_hook_writes,
+                ]
+                .concat(),
+                call_stack_change,
+            });
+        }
+        if executed {
+            self.emit_instruction_events(pc, i, before.as_ref());
+        }
+        if executed
+            && let Some(before) = before
+            && let Err(e) = self.explain_executed(pc, i, &before)
+        {
+            return ControlFlow::Break(Err(e));
+        }
+        match flow {
+            ControlFlow::Break(Ok(StepResult::WaitingForInput)) => {
+                self.registers.set_pc(pc);
+                self.instructions_executed -= 1;
+                self.run_state = RunState::WaitingForInput;
+                ControlFlow::Break(Ok(StepResult::WaitingForInput))
+            }
+            ControlFlow::Break(Ok(StepResult::TrapEntered { vector, .. })) => {
+                self.registers.set_pc(pc);
+                self.instructions_executed -= 1;
+                self.run_state = RunState::AtBreakpoint;
+                ControlFlow::Break(Ok(StepResult::TrapEntered { pc, vector }))
+            }
+            ControlFlow::Break(Ok(StepResult::Halted)) => {
+                self.halt(HaltReason::stopped_by(i));
+                ControlFlow::Break(Ok(StepResult::Halted))
+            }
+            ControlFlow::Break(res) => ControlFlow::Break(res),
+            ControlFlow::Continue(()) => {
+                self.run_state = RunState::Running;
+                self.last_executed = Some((pc, data));
+                self.record_checkpoint();
+                lock(&self.keyboard_input_provider).on_instruction_executed();
+                self.memory
+                    .take_watchpoint_hit()
+                    .map_or(ControlFlow::Continue(()), |access| {
+                        ControlFlow::Break(Ok(StepResult::Watchpoint(WatchpointHit { pc, access })))
+                    })
+            }
+        }
+    }
+
+    /// Passes the executed instruction `i` fetched from `pc` to the trace sinks.
+    fn trace_executed(&mut self, pc: u16, raw: u16, i: Instruction) {
+        if self.trace_sinks.is_empty() {
+            return;
+        }
+        let event = TraceEvent {
+            pc,
+            label: self.symbols.name_at(pc).map(str::to_owned),
+            raw,
+            decoded: i,
+            registers_after: self.registers.clone(),
+            memory_writes: self.last_memory_writes.clone(),
+        };
+        for sink in &mut self.trace_sinks {
+            sink.record(&event);
+        }
+    }
+
+    /// Writes the explanation of the executed instruction `i` fetched from `pc` if enabled.
+    fn explain_executed(
+        &mut self,
+        pc: u16,
+        i: Instruction,
+        before: &Registers,
+    ) -> Result<(), ExecutionError> {
+        let Some(out) = self.explain.as_mut() else {
+            return Ok(());
+        };
+        let line = explain::explain(
+            pc,
+            i,
+            before,
+            &self.registers,
+            &self.symbols,
+            self.br_nzp_zero_behavior,
+        );
+        writeln!(out, "{line}").map_err(|e| ExecutionError::IOInputOutputError(e.to_string()))
+    }
+
+    fn halt(&mut self, reason: HaltReason) {
+        self.run_state = RunState::Halted;
+        self.halted_by = Some(reason);
+        self.events.emit(&MachineEvent::Halted(reason));
+    }
+
+    /// Emits the events of the executed instruction `i` fetched from `pc`, `before` being the
+    /// registers before executing it if register events are subscribed.
+    fn emit_instruction_events(&mut self, pc: u16, i: Instruction, before: Option<&Registers>) {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(pc, instruction = %i, "instruction executed");
+        if i.operation() == Operation::Trap {
+            let vector = i.get_bit_range_u8(0, 7, "Error parsing trap vector");
+            self.events.emit(&MachineEvent::TrapEntered { pc, vector });
+        }
+        if let Some(before) = before {
+            for register in 0..8 {
+                let (old, new) = (before.get(register), self.registers.get(register));
+                if old != new {
+                    self.events.emit(&MachineEvent::RegisterWritten {
+                        pc,
+                        register,
+                        old,
+                        new,
+                    });
+                }
+            }
+        }
+        if self.events.wants(EventMask::MEMORY_WRITTEN) {
+            for write in &self.last_memory_writes {
+                self.events.emit(&MachineEvent::MemoryWritten {
+                    pc,
+                    address: write.address,
+                    old: write.old,
+                    new: write.new,
+                });
+            }
+        }
+        self.events
+            .emit(&MachineEvent::InstructionRetired { pc, instruction: i });
+    }
+
+    /// Interrupts the program with the pending interrupt of the highest priority if it is
+    /// higher than the priority the processor runs with.
+    ///
+    /// PSR and PC are pushed onto the supervisor stack and the service routine whose address is
+    /// stored in the interrupt vector table is started in Supervisor mode with the priority of
+    /// the interrupt.
+    fn take_interrupt(&mut self) {
+        self.interrupts.set_level(
+            KEYBOARD_INTERRUPT,
+            self.memory.is_keyboard_interrupt_enabled() && self.memory.is_keyboard_ready(),
+        );
+        self.interrupts.set_level(
+            SERIAL_INTERRUPT,
+            self.memory.is_serial_interrupt_enabled() && self.memory.is_serial_ready(),
+        );
+        if let Some(interrupt) = self.interrupts.take(self.registers.priority()) {
+            self.enter_service_routine(
+                INTERRUPT_VECTOR_TABLE + u16::from(interrupt.vector),
+                interrupt.priority,
+            );
+            self.events.emit(&MachineEvent::InterruptTaken {
+                vector: interrupt.vector,
+                priority: interrupt.priority,
+            });
+        }
+    }
+
+    /// Pushes PC and PSR onto the Supervisor stack and jumps to the routine stored at `vector`
+    /// running in Supervisor mode with `priority`.
+    fn enter_service_routine(&mut self, vector: u16, priority: u8) {
+        let psr = self.registers.psr();
+        let pc = self.registers.pc().as_binary();
+        self.registers.switch_privilege(Privilege::Supervisor);
+        let word_size = self.isa_variant.word_size();
+        let sp = self
+            .registers
+            .get(6)
+            .as_binary()
+            .wrapping_sub(2 * word_size);
+        self.registers.set(6, from_binary(sp));
+        self.memory[sp] = pc;
+        self.memory[sp.wrapping_add(word_size)] = psr;
+        self.registers
+            .set_psr(u16::from(priority) << 8 | psr & 0b111);
+        self.registers.set_pc(self.memory[vector]);
+    }
+
+    /// First address outside the user space accessed by `instruction` in User mode if
+    /// accesses are checked.
+    fn access_violation(&self, instruction: Instruction) -> Option<u16> {
+        if self.acv_policy == AcvPolicy::Unchecked
+            || self.registers.privilege() != Privilege::User
+            || self.isa_variant != IsaVariant::Lc3
+        {
+            return None;
+        }
+        opcodes::data_addresses(instruction, &self.registers, &self.memory)
+            .into_iter()
+            .flatten()
+            .find(|address| !self.memory.config().user_space.contains(address))
+    }
+
+    /// Address of the instruction being executed, the PC was already incremented past it.
+    const fn instruction_address(&self) -> u16 {
+        self.registers
+            .pc()
+            .as_binary()
+            .wrapping_sub(self.isa_variant.word_size())
+    }
+
+    fn raise_access_violation(&mut self, address: u16) -> StepFlow {
+        if self.acv_policy == AcvPolicy::Error {
+            let pc = self.instruction_address();
+            return ControlFlow::Break(Err(ExecutionError::MemoryAccessViolation { pc, address }));
+        }
+        self.enter_service_routine(ACV_EXCEPTION_VECTOR, self.registers.priority());
+        ControlFlow::Continue(())
+    }
+
+    fn handle_program_end(&mut self) -> Result<(), ExecutionError> {
+        let Some((last, raw)) = self.last_executed else {
+            return Ok(());
+        };
+        let pc = self.registers.pc().as_binary();
+        match self.program_end_policy {
+            ProgramEndPolicy::Warn => {
+                self.ran_past_program_end = true;
+                self.diagnostics
+                    .push(Diagnostic::RanPastProgramEnd { pc, last, raw });
+                Ok(())
+            }
+            ProgramEndPolicy::Error => {
+                self.ran_past_program_end = true;
+                Err(ExecutionError::RanPastProgramEnd { pc, last, raw })
+            }
+            ProgramEndPolicy::Silent => Ok(()),
+        }
+    }
+
+    fn check_executable(&mut self, pc: u16, raw: u16) -> Result<(), ExecutionError> {
+        self.check_modified_code(pc, raw)?;
+        if self.memory.is_executable(pc) {
+            return Ok(());
+        }
+        match self.nx_policy {
+            NxPolicy::Warn => {
+                let diagnostic = Diagnostic::ExecutedNonExecutable { pc, raw };
+                if !self.diagnostics.contains(&diagnostic) {
+                    self.diagnostics.push(diagnostic);
+                }
+                Ok(())
+            }
+            NxPolicy::Error => Err(ExecutionError::NonExecutableMemory(pc)),
+        }
+    }
+
+    /// Stops a BR, JMP, RET, JSR or JSRR at `pc` that jumped behind the user space, into the
+    /// device registers for example.
+    fn check_jump_target(&self, pc: u16, instruction: Instruction) -> StepFlow {
+        let target = self.registers.pc().as_binary();
+        match instruction.operation() {
+            Operation::Br | Operation::JmpOrRet | Operation::Jsr
+                if target >= self.memory.config().user_space.end =>
+            {
+                ControlFlow::Break(Err(ExecutionError::JumpOutOfRange { pc, target }))
+            }
+            _ => ControlFlow::Continue(()),
+        }
+    }
+
+    fn check_initialized(&mut self, instruction: Instruction) -> Result<(), ExecutionError> {
+        let Some(read) = self
+            .init_tracker
+            .as_mut()
+            .and_then(|tracker| tracker.on_execute(instruction, &self.registers, &self.memory))
+        else {
+            return Ok(());
+        };
+        let pc = self.instruction_address();
+        match (self.uninitialized_read_policy, read) {
+            (UninitializedReadPolicy::Allow, _) => Ok(()),
+            (UninitializedReadPolicy::Warn, UninitializedRead::Register(register)) => {
+                self.diagnostics
+                    .push(Diagnostic::UninitializedRegisterRead { pc, register });
+                Ok(())
+            }
+            (UninitializedReadPolicy::Warn, UninitializedRead::Memory(address)) => {
+                self.diagnostics
+                    .push(Diagnostic::UninitializedMemoryRead { pc, address });
+                Ok(())
+            }
+            (UninitializedReadPolicy::Error, UninitializedRead::Register(register)) => {
+                Err(ExecutionError::UninitializedRegisterRead { pc, register })
+            }
+            (UninitializedReadPolicy::Error, UninitializedRead::Memory(address)) => {
+                Err(ExecutionError::UninitializedMemoryRead { pc, address })
+            }
+        }
+    }
+
+    fn check_modified_code(&mut self, pc: u16, raw: u16) -> Result<(), ExecutionError> {
+        if !self.memory.is_modified_code(pc) {
+            return Ok(());
+        }
+        match self.smc_policy {
+            SelfModifyingCodePolicy::Allow => {}
+            SelfModifyingCodePolicy::Warn => {
+                if self.modified_instructions.insert(pc) {
+                    self.diagnostics
+                        .push(Diagnostic::ExecutedModifiedCode { pc, raw });
+                }
+            }
+            SelfModifyingCodePolicy::Deny => {
+                self.modified_instructions.insert(pc);
+                return Err(ExecutionError::SelfModifyingCode(pc));
+            }
+        }
+        Ok(())
+    }
+
+    /// Updates statistics, profile, coverage and call stack after executing `i` at `pc`.
+    fn record_executed(&mut self, pc: u16, i: Instruction) -> Option<CallStackChange> {
+        self.stats
+            .record(i, &self.registers, self.br_nzp_zero_behavior);
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record(pc);
+        }
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record(pc, i, &self.registers, self.br_nzp_zero_behavior);
+        }
+        self.call_stack.on_executed(pc, i, &self.registers)
+    }
+
+    fn execute_instruction(
+        &mut self,
+        instruction: Instruction,
+        stdout: &mut (impl Write + CrosstermCompatibility),
+        input_mode: InputMode,
+    ) -> StepFlow {
+        if let Err(e) = self.check_initialized(instruction) {
+            return ControlFlow::Break(Err(e));
+        }
+        if let Some(address) = self.access_violation(instruction) {
+            return self.raise_access_violation(address);
+        }
+        let lc3b_result = match self.isa_variant {
+            IsaVariant::Lc3 => None,
+            IsaVariant::Lc3b => lc3b::execute(
+                instruction,
+                &mut self.registers,
+                &mut self.memory,
+                self.br_nzp_zero_behavior,
+            ),
+        };
+        let flow = lc3b_result.map_or_else(
+            || self.execute_operation(instruction, stdout, input_mode),
+            |result| result.map_or_else(|e| ControlFlow::Break(Err(e)), ControlFlow::Continue),
+        );
+        if flow.is_break() {
+            return flow;
+        }
+        let output = self.memory.take_display_output();
+        if !output.is_empty()
+            && let Err(e) = terminal::print(stdout, &output)
+        {
+            return ControlFlow::Break(Err(ExecutionError::IOInputOutputError(e.to_string())));
+        }
+        if self.memory.take_clock_stopped() {
+            return ControlFlow::Break(Ok(StepResult::Halted));
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Executes `instruction` according to its LC-3 opcode.
+    #[expect(
+        clippy::unnecessary_mut_passed,
+        reason = "Needed for all opcodes thus if this fails this expect can be removed"
+    )]
+    fn execute_operation(
+        &mut self,
+        instruction: Instruction,
+        stdout: &mut (impl Write + CrosstermCompatibility),
+        input_mode: InputMode,
+    ) -> StepFlow {
+        match instruction.operation() {
+            Operation::Add => opcodes::add(instruction, &mut self.registers),
+            Operation::And => opcodes::and(instruction, &mut self.registers),
+            Operation::Not => opcodes::not(instruction, &mut self.registers),
+            Operation::Br => {
+                opcodes::br(instruction, &mut self.registers, self.br_nzp_zero_behavior);
+            }
+            Operation::JmpOrRet => opcodes::jmp_or_ret(instruction, &mut self.registers),
+            Operation::Jsr => opcodes::jsr(instruction, &mut self.registers),
+            Operation::Ld => opcodes::ld(instruction, &mut self.registers, &self.memory),
+            Operation::Ldi => opcodes::ldi(instruction, &mut self.registers, &mut self.memory),
+            Operation::Ldr => opcodes::ldr(instruction, &mut self.registers, &mut self.memory),
+            Operation::Lea => opcodes::lea(instruction, &mut self.registers),
+            Operation::St => opcodes::st(instruction, &self.registers, &mut self.memory),
+            Operation::Sti => opcodes::sti(instruction, &self.registers, &mut self.memory),
+            Operation::Str => opcodes::str(instruction, &self.registers, &mut self.memory),
+            Operation::Trap => {
+                if let ControlFlow::Break(res) = self.trap(instruction, stdout, input_mode) {
+                    return ControlFlow::Break(res);
+                }
+            }
+            Operation::Rti => {
+                if let Err(e) = opcodes::rti(
+                    instruction,
+                    &mut self.registers,
+                    &self.memory,
+                    self.isa_variant.word_size(),
+                ) {
+                    return ControlFlow::Break(Err(e));
+                }
+            }
+            Operation::_Reserved => {
+                return ControlFlow::Break(Err(ExecutionError::ReservedInstructionFound(
+                    Operation::_Reserved as u8,
+                )));
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Handles Trap Routines.
+    ///
+    /// # Result
+    /// - [`ControlFlow::Continue`] when the program should continue as normal
+    /// - [`ControlFlow::Break`] with a [`Result`] when the program should end
     ///
     /// # Errors
     /// - see [`ExecutionError`]
-    pub fn trap(
+    fn trap(
         &mut self,
         i: Instruction,
         stdout: &mut (impl Write + CrosstermCompatibility),
-    ) -> ControlFlow<Result<(), ExecutionError>, ()> {
-        let trap_routine = i.get_bit_range(0, 7);
+        input_mode: InputMode,
+    ) -> StepFlow {
+        let trap_routine = i.get_bit_range_u8(0, 7, "Error parsing trap vector");
+        #[cfg(feature = "tracing")]
+        tracing::debug!(vector = trap_routine, mode = ?self.trap_mode, "trap");
+        if !self.is_resuming_instruction() && self.breakpoints.breaks_on_trap(trap_routine) {
+            return ControlFlow::Break(Ok(StepResult::TrapEntered {
+                pc: self.instruction_address(),
+                vector: trap_routine,
+            }));
+        }
+        if self.trap_mode == TrapMode::Vectored {
+            if !self.memory.is_os_loaded() {
+                return ControlFlow::Break(Err(ExecutionError::TrapTableNotLoaded(
+                    trap_routine.into(),
+                )));
+            }
+            let entry = u16::from(trap_routine) * self.isa_variant.word_size();
+            self.registers.set(7, self.registers.pc());
+            self.registers.set_pc(self.memory.read(entry));
+            return ControlFlow::Continue(());
+        }
         match trap_routine {
-            0x20 => trap_routines::get_c(&mut self.registers, &self.memory, stdout),
+            0x20 => trap_routines::get_c(&mut self.registers, &self.memory, stdout, input_mode),
             0x21 => trap_routines::out(&self.registers, stdout),
+            0x22 if self.isa_variant == IsaVariant::Lc3b => {
+                trap_routines::put_s_bytes(&self.registers, &self.memory, stdout)
+            }
             0x22 => trap_routines::put_s(&self.registers, &self.memory, stdout),
-            0x23 => trap_routines::in_trap(&mut self.registers, &self.memory, stdout),
+            0x23 => trap_routines::in_trap(
+                &mut self.registers,
+                &self.memory,
+                stdout,
+                input_mode,
+                !self.quiet && self.run_state != RunState::WaitingForInput,
+            ),
             0x24 => trap_routines::put_sp(&self.registers, &self.memory, stdout),
-            0x25 => trap_routines::halt(stdout),
-            tr => ControlFlow::Break(Err(ExecutionError::UnknownTrapRoutine(tr))),
+            0x25 => {
+                let flow = if self.quiet {
+                    ControlFlow::Continue(())
+                } else {
+                    trap_routines::halt(stdout)
+                };
+                // halts after the instruction like the operating system routine
+                self.memory.stop_clock();
+                flow
+            }
+            tr => ControlFlow::Break(Err(ExecutionError::UnknownTrapRoutine(tr.into()))),
+        }
+    }
+}
+
+impl Debug for Emulator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Emulator:")?;
+        writeln!(f, "{:?}", self.memory)?;
+        writeln!(f, "Registers:\n{:?}", self.registers)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emulator;
+    use crate::emulator::breakpoints::{Breakpoint, BreakpointHit};
+    use crate::emulator::call_stack::Frame;
+    use crate::emulator::coverage::BranchCoverage;
+    use crate::emulator::diagnostics::Diagnostic;
+    use crate::emulator::event_log::{EventLogSink, SessionEvent};
+    use crate::emulator::events::{EventMask, MachineEvent};
+    #[cfg(feature = "async")]
+    use crate::emulator::test_helpers::block_on;
+    use crate::emulator::test_helpers::{FakeKeyboardInputProvider, SharedWriter, StringWriter};
+    use crate::emulator::trace::{TraceEvent, TraceSink};
+    use crate::emulator::validation::LoadWarning;
+    use crate::emulator::{
+        AcvPolicy, BrNzpZeroBehavior, Emulator, EmulatorBuilder, ExitStatus, HaltReason,
+        Instruction, NxPolicy, ORIG_HEADER, Operation, ProgramEndPolicy, SelfModifyingCodePolicy,
+        StepResult, TimeMode, TrapMode, UninitializedReadPolicy, WatchpointHit,
+    };
+    use crate::errors::LoadProgramError::*;
+    use crate::errors::{ExecutionError, LoadProgramError, ReplayError, StateAccessError};
+    use crate::hardware::keyboard::{ChaosInputProvider, KeyboardInputProvider};
+    use crate::hardware::memory::PROGRAM_SECTION_MAX_INSTRUCTION_COUNT;
+    use crate::hardware::memory::{AccessKind, EmulatorConfig, MemoryAccess, WatchKind};
+    use crate::hardware::registers::{ConditionFlag, Privilege, from_binary};
+    use crate::sync::lock;
+    use googletest::prelude::*;
+    use std::error::Error;
+    use std::io;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use yare::parameterized;
+
+    const PROGRAM_SECTION_MAX_INSTRUCTION_COUNT_WITH_HEADER: usize =
+        PROGRAM_SECTION_MAX_INSTRUCTION_COUNT as usize + 1;
+
+    fn emu_with_program_from_vec_wo_kdb(
+        data: &[u16],
+    ) -> std::result::Result<Emulator, LoadProgramError> {
+        EmulatorBuilder::new(data)
+            .with_input_provider(FakeKeyboardInputProvider::new(""))
+            .build()
+    }
+
+    #[parameterized(
+        missing_header = {Vec::with_capacity(0), ProgramMissingOrigHeader },
+        wrong_header = {vec![0x3001], ProgramLoadedAtWrongAddress
+            {actual_address: 0x3001, expected_address: 0x3000 } },
+        too_large = {vec![0x3000u16; PROGRAM_SECTION_MAX_INSTRUCTION_COUNT_WITH_HEADER + 1],
+            ProgramTooLong {actual_instructions: 52737,
+            maximum_instructions: PROGRAM_SECTION_MAX_INSTRUCTION_COUNT} },
+        empty = { vec![0x3000u16; 1], ProgramEmpty }
+    )]
+    #[test_macro(gtest)]
+    pub fn test_load_program_errors(data: Vec<u16>, error: LoadProgramError) {
+        let abstract_error =
+            Box::<dyn Error>::from(emu_with_program_from_vec_wo_kdb(&data).unwrap_err());
+        let res = abstract_error.downcast_ref::<LoadProgramError>();
+        assert_that!(res.unwrap(), eq(&error));
+    }
+
+    #[gtest]
+    pub fn test_load_program_max_size() {
+        let mut program = vec![0x0u16; PROGRAM_SECTION_MAX_INSTRUCTION_COUNT_WITH_HEADER];
+        program[0] = ORIG_HEADER;
+        let emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        let ins = emu.instructions();
+        assert_that!(
+            ins.len(),
+            eq(usize::from(PROGRAM_SECTION_MAX_INSTRUCTION_COUNT))
+        );
+    }
+    #[gtest]
+    pub fn test_load_program_from_bytes() {
+        let mut sw = StringWriter::new();
+        let data = std::fs::read("examples/times_ten.obj").unwrap();
+        let mut emu = emulator::from_reader(data.as_slice()).unwrap();
+        emu.run_budgeted(1000, &mut sw).unwrap();
+        expect_that!(emu.registers.get(3), eq(from_binary(30)));
+        expect_that!(
+            emulator::from_bytes(&data[1..]).unwrap_err(),
+            eq(&LoadProgramError::ProgramNotEvenSize(21))
+        );
+    }
+    #[gtest]
+    pub fn test_config_user_space() {
+        let config = EmulatorConfig {
+            user_space: 0x4000..0x5000,
+            ..EmulatorConfig::default()
+        };
+        // ADD R1, R1, #1; HALT
+        let mut emu = EmulatorBuilder::new(&[0x4000, 0x1261, 0xF025])
+            .with_config(config.clone())
+            .build()
+            .unwrap();
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(emu.registers.get(1), eq(from_binary(1)));
+        expect_that!(emu.registers.pc(), eq(from_binary(0x4002)));
+        expect_that!(emu.memory.program_slice(), eq(&[0x1261, 0xF025]));
+        expect_that!(
+            EmulatorBuilder::new(&[ORIG_HEADER, 0xF025])
+                .with_config(config.clone())
+                .build()
+                .unwrap_err(),
+            eq(&LoadProgramError::ProgramLoadedAtWrongAddress {
+                actual_address: ORIG_HEADER,
+                expected_address: 0x4000
+            })
+        );
+        expect_that!(
+            EmulatorBuilder::new(&[0x4000, 0xF025])
+                .with_config(config)
+                .with_memory_region(0x4FFF, &[1, 2])
+                .build()
+                .unwrap_err(),
+            eq(&LoadProgramError::RegionOutOfRange {
+                origin: 0x4FFF,
+                length: 2
+            })
+        );
+    }
+    #[gtest]
+    pub fn test_reset() {
+        // LD R0, #3; ADD R0, R0, #1; ST R0, #1; HALT; DATA: 5
+        let program = [ORIG_HEADER, 0x2003, 0x1021, 0x3001, 0xF025, 5];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(emu.read_mem(0x3004), ok(eq(&6)));
+        emu.reset_memory();
+        expect_that!(emu.read_mem(0x3004), ok(eq(&5)));
+        expect_that!(emu.registers().pc().as_binary(), eq(0x3004));
+
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        emu.reset();
+        expect_that!(emu.registers().pc().as_binary(), eq(0x3000));
+        expect_that!(emu.execution_summary().instructions_executed, eq(0));
+        expect_that!(
+            emu.execute_with_stdout(&mut StringWriter::new()),
+            ok(eq(&ExitStatus {
+                halt_reason: HaltReason::HaltTrap,
+                pc: 0x3004,
+                instructions_executed: 4,
+            }))
+        );
+        expect_that!(emu.registers().get(0), eq(from_binary(6)));
+        expect_that!(emu.read_mem(0x3004), ok(eq(&6)));
+    }
+    #[gtest]
+    pub fn test_fork() {
+        // GETC; OUT; HALT
+        let program = [ORIG_HEADER, 0xF020, 0xF021, 0xF025];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        expect_that!(
+            emu.run_budgeted(10, &mut StringWriter::new()),
+            ok(eq(&StepResult::WaitingForInput))
+        );
+        let mut first = emu.fork();
+        let mut second = emu.fork();
+        first.write_mem(0x3005, 7).unwrap();
+        expect_that!(emu.read_mem(0x3005), ok(eq(&0)));
+        expect_that!(
+            first.execute_captured("a").unwrap().output,
+            starts_with("a")
+        );
+        expect_that!(
+            second.execute_captured("b").unwrap().output,
+            starts_with("b")
+        );
+        expect_that!(emu.registers().pc().as_binary(), eq(0x3000));
+        expect_that!(first.registers().get(0), eq(from_binary(u16::from(b'a'))));
+        expect_that!(second.execution_summary().instructions_executed, eq(3));
+    }
+    #[gtest]
+    pub fn test_state_access() {
+        // ADD R1, R1, R2; HALT
+        let mut emu = emu_with_program_from_vec_wo_kdb(&[ORIG_HEADER, 0x1242, 0xF025]).unwrap();
+        emu.set_register(1, 40).unwrap();
+        emu.set_register(2, 2).unwrap();
+        expect_that!(
+            emu.set_register(8, 0),
+            err(eq(&StateAccessError::InvalidRegister(8)))
+        );
+        emu.write_mem(0x4000, 7).unwrap();
+        expect_that!(emu.read_mem(0x4000), ok(eq(&7)));
+        expect_that!(
+            emu.read_mem(0xFE02),
+            err(eq(&StateAccessError::DeviceRegister(0xFE02)))
+        );
+        expect_that!(
+            emu.write_mem(0xFE06, 0),
+            err(eq(&StateAccessError::DeviceRegister(0xFE06)))
+        );
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(emu.registers.get(1), eq(from_binary(42)));
+        emu.set_pc(0x3000).unwrap();
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(emu.registers.get(1), eq(from_binary(44)));
+        expect_that!(
+            emu.set_pc(0xFE00),
+            err(eq(&StateAccessError::PcOutOfRange(0xFE00)))
+        );
+    }
+    #[gtest]
+    pub fn test_execute_with_io() {
+        // GETC; OUT; OUT; HALT
+        let program = [ORIG_HEADER, 0xF020, 0xF021, 0xF021, 0xF025];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        let mut output = Vec::new();
+        emu.execute_with_io(FakeKeyboardInputProvider::new("z"), &mut output)
+            .unwrap();
+        expect_that!(String::from_utf8_lossy(&output), starts_with("zz"));
+    }
+    #[gtest]
+    pub fn test_execute_captured() {
+        // GETC; OUT; BRnzp #-3
+        let program = [ORIG_HEADER, 0xF020, 0xF021, 0x0FFD];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        let outcome = emu.execute_captured("ab").unwrap();
+        expect_that!(outcome.output, eq("ab"));
+        expect_that!(outcome.halt_reason, eq(HaltReason::InputExhausted));
+        expect_that!(outcome.instructions_executed, eq(6));
+        expect_that!(outcome.registers.get(0), eq(from_binary(u16::from(b'b'))));
+        // ADD R1, R1, #1
+        let mut emu = emu_with_program_from_vec_wo_kdb(&[ORIG_HEADER, 0x1261]).unwrap();
+        expect_that!(
+            emu.execute_captured("").unwrap().halt_reason,
+            eq(HaltReason::RanPastProgramEnd)
+        );
+    }
+    #[gtest]
+    pub fn test_execute_scripted() {
+        // GETC; OUT; BRnzp #-3
+        let program = [ORIG_HEADER, 0xF020, 0xF021, 0x0FFD];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        let mut output = Vec::new();
+        expect_that!(
+            emu.execute_scripted("hi\n", &mut output),
+            ok(eq(&ExitStatus {
+                halt_reason: HaltReason::InputExhausted,
+                pc: 0x3000,
+                instructions_executed: 9
+            }))
+        );
+        expect_that!(output, eq(b"hi\n"));
+    }
+    #[gtest]
+    pub fn test_continue_scripted() {
+        // GETC; OUT; BRnzp #-3
+        let program = [ORIG_HEADER, 0xF020, 0xF021, 0x0FFD];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        let id = emu.add_breakpoint(Breakpoint::new(0x3001));
+        let mut output = Vec::new();
+        let status = emu.execute_scripted("hi", &mut output).unwrap();
+        expect_that!(
+            status.halt_reason,
+            matches_pattern!(HaltReason::Breakpoint(_))
+        );
+        expect_that!(output, is_empty());
+        let status = emu.continue_scripted(&mut output).unwrap();
+        expect_that!(
+            status.halt_reason,
+            matches_pattern!(HaltReason::Breakpoint(_))
+        );
+        expect_that!(output, eq(b"h"));
+        emu.remove_breakpoint(id);
+        let status = emu.continue_scripted(&mut output).unwrap();
+        expect_that!(status.halt_reason, eq(HaltReason::InputExhausted));
+        expect_that!(output, eq(b"hi"));
+    }
+    #[gtest]
+    pub fn test_quiet() {
+        // IN; HALT
+        let program = [ORIG_HEADER, 0xF023, 0xF025];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        expect_that!(
+            emu.execute_captured("a").unwrap().output,
+            eq("Input: a\nProgram halted\n")
+        );
+        emu.reset();
+        emu.set_quiet(true);
+        expect_that!(emu.execute_captured("a").unwrap().output, eq("a"));
+    }
+    #[gtest]
+    pub fn test_exit_status() {
+        // AND R0, R0, #0; STI R0, #0; MCR: xFFFE
+        let mut emu =
+            emu_with_program_from_vec_wo_kdb(&[ORIG_HEADER, 0x5020, 0xB000, 0xFFFE]).unwrap();
+        expect_that!(
+            emu.execute_with_stdout(&mut StringWriter::new()),
+            ok(eq(&ExitStatus {
+                halt_reason: HaltReason::McrCleared,
+                pc: 0x3002,
+                instructions_executed: 2,
+            }))
+        );
+        // ADD R1, R1, #1; HALT
+        let mut emu = emu_with_program_from_vec_wo_kdb(&[ORIG_HEADER, 0x1261, 0xF025]).unwrap();
+        expect_that!(
+            emu.execute_with_stdout(&mut StringWriter::new())
+                .map(|status| status.halt_reason),
+            ok(eq(&HaltReason::HaltTrap))
+        );
+        // ADD R1, R1, #1
+        let mut emu = emu_with_program_from_vec_wo_kdb(&[ORIG_HEADER, 0x1261]).unwrap();
+        expect_that!(
+            emu.execute_with_stdout(&mut StringWriter::new())
+                .map(|status| status.halt_reason),
+            ok(eq(&HaltReason::RanPastProgramEnd))
+        );
+        expect_that!(
+            HaltReason::from(&ExecutionError::Interrupted(0x3000)),
+            eq(HaltReason::Cancelled)
+        );
+    }
+    #[gtest]
+    pub fn test_queue_input() {
+        // GETC; OUT; GETC; OUT; GETC; OUT; HALT
+        let program = [
+            ORIG_HEADER,
+            0xF020,
+            0xF021,
+            0xF020,
+            0xF021,
+            0xF020,
+            0xF021,
+            0xF025,
+        ];
+        let mut emu = EmulatorBuilder::new(&program)
+            .with_input_provider(FakeKeyboardInputProvider::new("n"))
+            .build()
+            .unwrap();
+        emu.queue_input("Y");
+        emu.queue_key('!');
+        let mut stdout = StringWriter::new();
+        emu.execute_with_stdout(&mut stdout).unwrap();
+        expect_that!(stdout.get_string(), starts_with("Y!n"));
+    }
+    #[gtest]
+    pub fn test_cancel_token() {
+        // BRnzp #-1
+        let mut emu = emu_with_program_from_vec_wo_kdb(&[ORIG_HEADER, 0x0FFF]).unwrap();
+        let token = emu.cancel_token();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            token.cancel();
+        });
+        expect_that!(
+            emu.execute_with_stdout(&mut StringWriter::new()),
+            err(eq(&ExecutionError::Interrupted(0x3000)))
+        );
+        canceller.join().unwrap();
+        expect_that!(emu.cancel_token().is_cancelled(), eq(false));
+    }
+    #[gtest]
+    pub fn test_cancel_token_while_waiting_for_input() {
+        let program = [ORIG_HEADER, u16::from(Instruction::trap(0x20))];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.set_input_provider(ChaosInputProvider::new("", 0));
+        let token = emu.cancel_token();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            token.cancel();
+        });
+        expect_that!(
+            emu.execute_with_stdout(&mut StringWriter::new()),
+            err(eq(&ExecutionError::Interrupted(0x3000)))
+        );
+        canceller.join().unwrap();
+    }
+    /// Counts the checks for input, which never is available.
+    struct CountingInputProvider(Arc<AtomicU64>);
+    impl KeyboardInputProvider for CountingInputProvider {
+        fn check_input_available(&mut self) -> io::Result<bool> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Ok(false)
+        }
+        fn get_input_character(&mut self) -> char {
+            panic!("No input available");
+        }
+        fn is_interrupted(&self) -> bool {
+            false
+        }
+    }
+    #[gtest]
+    pub fn test_waiting_for_input_does_not_spin() {
+        let program = [ORIG_HEADER, u16::from(Instruction::trap(0x20))];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        let checks = Arc::new(AtomicU64::new(0));
+        emu.set_input_provider(CountingInputProvider(checks.clone()));
+        let token = emu.cancel_token();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            token.cancel();
+        });
+        expect_that!(
+            emu.execute_with_stdout(&mut StringWriter::new()),
+            err(eq(&ExecutionError::Interrupted(0x3000)))
+        );
+        canceller.join().unwrap();
+        // about one check per INPUT_WAIT_INTERVAL instead of millions
+        expect_that!(checks.load(Ordering::Relaxed), le(20));
+    }
+    /// Simulates CTRL-C pressed while waiting for input.
+    struct CtrlCInputProvider {
+        polled: bool,
+    }
+    impl KeyboardInputProvider for CtrlCInputProvider {
+        fn check_input_available(&mut self) -> io::Result<bool> {
+            self.polled = true;
+            Ok(false)
+        }
+        fn get_input_character(&mut self) -> char {
+            panic!("No input available")
+        }
+        fn is_interrupted(&self) -> bool {
+            self.polled
+        }
+        fn reset_interrupt(&mut self) {
+            self.polled = false;
+        }
+    }
+    #[gtest]
+    pub fn test_ctrl_c_interrupts_blocking_input() {
+        // ADD R1, R1, #1; GETC; HALT
+        let mut emu = EmulatorBuilder::new(&[ORIG_HEADER, 0x1261, 0xF020, 0xF025])
+            .with_input_provider(CtrlCInputProvider { polled: false })
+            .build()
+            .unwrap();
+        expect_that!(
+            emu.execute_with_stdout(&mut StringWriter::new()),
+            err(eq(&ExecutionError::Interrupted(0x3001)))
+        );
+        expect_that!(emu.execution_summary().instructions_executed, eq(1));
+    }
+    #[gtest]
+    pub fn test_continue_execution() {
+        // ADD R1, R1, #1; GETC; OUT; HALT
+        let mut emu = EmulatorBuilder::new(&[ORIG_HEADER, 0x1261, 0xF020, 0xF021, 0xF025])
+            .with_input_provider(CtrlCInputProvider { polled: false })
+            .build()
+            .unwrap();
+        emu.set_max_instructions(1);
+        let mut stdout = StringWriter::new();
+        expect_that!(
+            emu.execute_with_stdout(&mut stdout),
+            err(eq(&ExecutionError::InstructionLimitExceeded {
+                executed: 1,
+                pc: 0x3001
+            }))
+        );
+        emu.set_max_instructions(10);
+        expect_that!(
+            emu.continue_execution(&mut stdout),
+            err(eq(&ExecutionError::Interrupted(0x3001)))
+        );
+        emu.queue_input("x");
+        expect_that!(
+            emu.continue_execution(&mut stdout)
+                .map(|status| status.instructions_executed),
+            ok(eq(&4))
+        );
+        expect_that!(stdout.get_string(), starts_with("x"));
+        expect_that!(emu.registers().get(1), eq(from_binary(1)));
+        expect_that!(
+            emu.continue_execution(&mut stdout)
+                .map(|status| status.halt_reason),
+            ok(eq(&HaltReason::HaltTrap))
+        );
+    }
+    #[gtest]
+    pub fn test_builder_options() {
+        let program = [
+            ORIG_HEADER,
+            0x0000, // data skipped by the start PC
+            0xF020, // GETC
+            0xF021, // OUT
+            0xF025, // HALT
+        ];
+        let mut emu = EmulatorBuilder::new(&program)
+            .with_input_provider(FakeKeyboardInputProvider::new("x"))
+            .with_start_pc(0x3001)
+            .build()
+            .unwrap();
+        let mut stdout = StringWriter::new();
+        emu.execute_with_stdout(&mut stdout).unwrap();
+        expect_that!(stdout.get_string(), starts_with("x"));
+        expect_that!(emu.execution_summary().instructions_executed, eq(3));
+    }
+    #[gtest]
+    pub fn test_keyboard_interrupt() {
+        let program = [
+            ORIG_HEADER,
+            0x2009, // LD R0, ISR_ADDRESS
+            0xB009, // STI R0, KEYBOARD_VECTOR
+            0x2009, // LD R0, INTERRUPT_ENABLE
+            0xB009, // STI R0, KBSR
+            0x1260, // WAIT: ADD R1, R1, #0
+            0x05FE, // BRz WAIT
+            0xF025, // HALT
+            0xA206, // ISR: LDI R1, KBDR
+            0x8000, // RTI
+            0x0000,
+            0x3007, // ISR_ADDRESS
+            0x0180, // KEYBOARD_VECTOR
+            0x4000, // INTERRUPT_ENABLE
+            0xFE00, // KBSR
+            0xFE02, // KBDR
+        ];
+        let mut emu = EmulatorBuilder::new(&program)
+            .with_input_provider(FakeKeyboardInputProvider::new("a"))
+            .build()
+            .unwrap();
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(emu.registers.get(1), eq(from_binary(u16::from(b'a'))));
+        expect_that!(emu.registers.psr(), eq(0x8001));
+        expect_that!(emu.registers.pc(), eq(from_binary(0x3007)));
+        // PC and PSR of the interrupted program on the supervisor stack
+        expect_that!(emu.memory[0x2FFE], eq(0x3004));
+        expect_that!(emu.memory[0x2FFF], eq(0x8001));
+    }
+    #[gtest]
+    pub fn test_access_control_violation() {
+        let program = [
+            ORIG_HEADER,
+            0xA001, // LDI R0, POINTER
+            0xF025, // HALT
+            0xFE00, // POINTER: KBSR
+            0xF025, // ACV handler: HALT
+        ];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.set_acv_policy(AcvPolicy::Error);
+        expect_that!(
+            emu.execute_with_stdout(&mut StringWriter::new()),
+            err(eq(&ExecutionError::MemoryAccessViolation {
+                pc: 0x3000,
+                address: 0xFE00
+            }))
+        );
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.set_acv_policy(AcvPolicy::Exception);
+        emu.memory[0x0102] = 0x3003;
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(emu.registers.privilege(), eq(Privilege::Supervisor));
+        expect_that!(emu.registers.pc(), eq(from_binary(0x3004)));
+        expect_that!(emu.registers.get(0), eq(from_binary(0)));
+        // the violating instruction is not resumed
+        expect_that!(emu.memory[0x2FFE], eq(0x3001));
+    }
+    #[gtest]
+    pub fn test_stack_bounds() {
+        let program = vec![
+            ORIG_HEADER,
+            0x2C03, // LD R6, BASE
+            0x1DBF, // ADD R6, R6, #-1
+            0x1DBF, // ADD R6, R6, #-1
+            0xF025, // HALT
+            0x4000, // BASE
+        ];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.set_stack_bounds(0x3FFF..=0x4000);
+        expect_that!(
+            emu.execute_with_stdout(&mut StringWriter::new()),
+            err(eq(&ExecutionError::StackOverflow {
+                pc: 0x3002,
+                sp: 0x3FFE
+            }))
+        );
+        expect_that!(emu.stack_slice(), none());
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.set_stack_bounds(0x3F00..=0x4000);
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(emu.stack_slice(), some(eq(&[0, 0][..])));
+    }
+    #[gtest]
+    pub fn test_self_modifying_code() {
+        let program = vec![
+            ORIG_HEADER,
+            0x2003, // LD R0, NEW
+            0x3000, // ST R0, TARGET
+            0x1261, // TARGET: ADD R1, R1, #1
+            0xF025, // HALT
+            0x1262, // NEW: ADD R1, R1, #2
+        ];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.set_self_modifying_code_policy(SelfModifyingCodePolicy::Deny);
+        expect_that!(
+            emu.execute_with_stdout(&mut StringWriter::new()),
+            err(eq(&ExecutionError::SelfModifyingCode(0x3002)))
+        );
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.set_self_modifying_code_policy(SelfModifyingCodePolicy::Warn);
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(emu.registers.get(1), eq(from_binary(2)));
+        expect_that!(
+            emu.diagnostics(),
+            elements_are![eq(&Diagnostic::ExecutedModifiedCode {
+                pc: 0x3002,
+                raw: 0x1262
+            })]
+        );
+        expect_that!(
+            emu.modified_instructions().collect::<Vec<_>>(),
+            eq(&[0x3002])
+        );
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(emu.diagnostics(), is_empty());
+    }
+    #[gtest]
+    pub fn test_uninitialized_read() {
+        let program = vec![
+            ORIG_HEADER,
+            0x5020, // AND R0, R0, #0
+            0x1042, // ADD R0, R1, R2
+            0x2602, // LD R3, #2
+            0xF025, // HALT
+        ];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.set_uninitialized_read_policy(UninitializedReadPolicy::Error);
+        expect_that!(
+            emu.execute_with_stdout(&mut StringWriter::new()),
+            err(eq(&ExecutionError::UninitializedRegisterRead {
+                pc: 0x3001,
+                register: 1
+            }))
+        );
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.set_uninitialized_read_policy(UninitializedReadPolicy::Warn);
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(
+            emu.diagnostics(),
+            elements_are![
+                eq(&Diagnostic::UninitializedRegisterRead {
+                    pc: 0x3001,
+                    register: 1
+                }),
+                eq(&Diagnostic::UninitializedMemoryRead {
+                    pc: 0x3002,
+                    address: 0x3005
+                }),
+            ]
+        );
+    }
+    #[gtest]
+    pub fn test_static_image() {
+        static IMAGE: [u16; 2] = emulator::obj_words(&[0x30, 0x00, 0xF0, 0x25]);
+        let mut emu = emulator::from_static_image(&IMAGE).unwrap();
+        expect_that!(
+            emu.execute_with_stdout(&mut StringWriter::new()),
+            ok(anything())
+        );
+        expect_that!(emu.instructions().len(), eq(1));
+    }
+    #[gtest]
+    #[should_panic(expected = "program must be loaded at x3000")]
+    pub fn test_static_image_wrong_origin() {
+        let _: [u16; 2] = emulator::obj_words(&[0x40, 0x00, 0xF0, 0x25]);
+    }
+    #[gtest]
+    pub fn test_builder_with_memory_region() {
+        // LD R0, #3; LDI R2, #2; LDR R1, R0, #1; HALT; .FILL x4000
+        let program = [ORIG_HEADER, 0x2003, 0xA402, 0x6201, 0xF025, 0x4000];
+        let mut emu = EmulatorBuilder::new(&program)
+            .with_memory_region(0x4000, &[0x1234, 0x5678])
+            .build()
+            .unwrap();
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(emu.registers.get(2), eq(from_binary(0x1234)));
+        expect_that!(emu.registers.get(1), eq(from_binary(0x5678)));
+        expect_that!(emu.memory.is_executable(0x4001), eq(false));
+        expect_that!(
+            emu.instructions().map(|w| w.address).collect::<Vec<_>>(),
+            eq(&[0x3000, 0x3001, 0x3002, 0x3003, 0x3004, 0x4000, 0x4001])
+        );
+        expect_that!(emu.instructions().last().map(|w| w.raw), some(eq(0x5678)));
+        expect_that!(
+            EmulatorBuilder::new(&program)
+                .with_memory_region(0x3004, &[0])
+                .build()
+                .unwrap_err(),
+            eq(&LoadProgramError::RegionOverlaps(0x3004))
+        );
+        expect_that!(
+            EmulatorBuilder::new(&program)
+                .with_memory_region(0xFDFF, &[0, 0])
+                .build()
+                .unwrap_err(),
+            eq(&LoadProgramError::RegionOutOfRange {
+                origin: 0xFDFF,
+                length: 2
+            })
+        );
+    }
+    /// Minimal operating system with `OUT` writing to the display data register and `HALT`
+    /// clearing the machine control register.
+    fn os_image() -> Vec<u8> {
+        let mut words = vec![0u16; 0x201];
+        words[1 + 0x21] = 0x0200;
+        words[1 + 0x25] = 0x0202;
+        // x0200: STI R0, DDR; RET; AND R0, R0, #0; STI R0, MCR; DDR: xFE06; MCR: xFFFE
+        words.extend([0xB003, 0xC1C0, 0x5020, 0xB001, 0xFE06, 0xFFFE]);
+        words.iter().flat_map(|w| w.to_be_bytes()).collect()
+    }
+    #[gtest]
+    pub fn test_vectored_traps() {
+        // LD R0, #2; OUT; HALT; .FILL 'A'
+        let program = vec![ORIG_HEADER, 0x2002, 0xF021, 0xF025, 0x0041];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        let mut sw = StringWriter::new();
+        emu.set_trap_mode(TrapMode::Vectored);
+        expect_that!(
+            emu.run_budgeted(100, &mut sw),
+            err(eq(&ExecutionError::TrapTableNotLoaded(0x21)))
+        );
+
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.load_os_bytes(&os_image()).unwrap();
+        emu.set_trap_mode(TrapMode::Vectored);
+        let res = emu.run_budgeted(100, &mut sw).unwrap();
+        assert_that!(res, eq(StepResult::Halted));
+        expect_that!(sw.get_string(), eq("A"));
+        expect_that!(emu.registers.get(7), eq(from_binary(0x3003)));
+        expect_that!(emu.instructions_executed, eq(7));
+        expect_that!(
+            emu.layout().iter().map(|r| r.origin).collect::<Vec<_>>(),
+            elements_are![eq(&0x0000), eq(&0x3000)]
+        );
+    }
+    #[gtest]
+    pub fn test_load_image() {
+        // LDI R0, #2; OUT; HALT; .FILL x4000
+        let program = vec![ORIG_HEADER, 0xA002, 0xF021, 0xF025, 0x4000];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.load_image_bytes(&os_image()).unwrap();
+        emu.load_data_image_bytes(&[0x40, 0x00, 0x00, 0x42])
+            .unwrap();
+        emu.set_trap_mode(TrapMode::Vectored);
+        let mut sw = StringWriter::new();
+        emu.run_budgeted(100, &mut sw).unwrap();
+        expect_that!(sw.get_string(), eq("B"));
+        expect_that!(
+            emu.layout()
+                .iter()
+                .map(|r| (r.origin, r.executable))
+                .collect::<Vec<_>>(),
+            elements_are![
+                eq(&(0x0000, true)),
+                eq(&(0x3000, true)),
+                eq(&(0x4000, false))
+            ]
+        );
+        expect_that!(
+            emu.load_image_bytes(&[0x30, 0x03, 0x00, 0x00]),
+            err(eq(&LoadProgramError::RegionOverlaps(0x3003)))
+        );
+        expect_that!(
+            emu.load_image_bytes(&os_image()),
+            err(eq(&LoadProgramError::RegionOverlaps(0x0000)))
+        );
+    }
+    #[gtest]
+    pub fn test_call_into_second_image() {
+        // LD R1, #2; JSRR R1; HALT; .FILL x4000
+        let program = vec![ORIG_HEADER, 0x2202, 0x4040, 0xF025, 0x4000];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        // x4000: AND R0, R0, #0; ADD R0, R0, #7; RET
+        emu.load_image_bytes(&[0x40, 0x00, 0x50, 0x20, 0x10, 0x27, 0xC1, 0xC0])
+            .unwrap();
+        expect_that!(
+            emu.run_budgeted(100, &mut StringWriter::new()),
+            ok(eq(&StepResult::Halted))
+        );
+        expect_that!(emu.registers().get(0).as_binary(), eq(7));
+        expect_that!(emu.layout()[1].executable, eq(true));
+    }
+    #[gtest]
+    pub fn test_display_registers() {
+        let program = vec![
+            ORIG_HEADER,
+            0x2005, // LD R0, CHAR
+            0xA205, // POLL: LDI R1, DSR
+            0x07FE, // BRzp POLL
+            0xB004, // STI R0, DDR
+            0xF025, // HALT
+            0x0000,
+            0x0058, // CHAR: 'X'
+            0xFE04, // DSR
+            0xFE06, // DDR
+        ];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        let mut sw = StringWriter::new();
+        emu.execute_with_stdout(&mut sw).unwrap();
+        expect_that!(sw.get_string(), starts_with("X"));
+        expect_that!(emu.registers.get(1), eq(from_binary(0x8000)));
+    }
+    #[gtest]
+    pub fn test_halt_by_machine_control_register() {
+        let program = vec![
+            ORIG_HEADER,
+            0xA006, // LDI R0, MCR
+            0x2206, // LD R1, MASK
+            0x5001, // AND R0, R0, R1
+            0xB003, // STI R0, MCR
+            0x14A1, // ADD R2, R2, #1
+            0xF025, // HALT
+            0x0000,
+            0xFFFE, // MCR
+            0x7FFF, // MASK
+        ];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        let res = emu.run_budgeted(100, &mut StringWriter::new());
+        expect_that!(res, ok(eq(&StepResult::Halted)));
+        expect_that!(emu.registers.get(0), eq(from_binary(0)));
+        expect_that!(emu.registers.get(2), eq(from_binary(0)));
+        expect_that!(emu.instructions_executed, eq(4));
+        // the clock runs again for resumed execution
+        expect_that!(emu.memory[0xFFFE], eq(0x8000));
+    }
+    #[gtest]
+    pub fn test_validate() {
+        // RET, .FILL xD000, TRAP x30, BRz #5, JSR #-6, RET, HALT, .FILL 0 (BR without nzp)
+        let program = vec![
+            ORIG_HEADER,
+            0xC1C0,
+            0xD000,
+            0xF030,
+            0x0405,
+            0x4FFA,
+            0xC1C0,
+            0xF025,
+            0x0000,
+        ];
+        let emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        assert_that!(
+            emu.validate(),
+            elements_are![
+                eq(&LoadWarning::ReturnWithoutCall { address: 0x3000 }),
+                eq(&LoadWarning::ReservedOpcode {
+                    address: 0x3001,
+                    raw: 0xD000
+                }),
+                eq(&LoadWarning::UnknownTrapVector {
+                    address: 0x3002,
+                    vector: 0x30
+                }),
+                eq(&LoadWarning::BranchOutsideImage {
+                    address: 0x3003,
+                    target: 0x3009
+                }),
+                eq(&LoadWarning::BranchOutsideImage {
+                    address: 0x3004,
+                    target: 0x2FFF
+                }),
+            ]
+        );
+        let emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        expect_that!(emu.validate(), is_empty());
+    }
+    #[gtest]
+    pub fn test_save_obj() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        // FACTOR
+        emu.memory()[0x3007] = 5;
+        let path = std::env::temp_dir().join(format!("lc3-save-{}.obj", std::process::id()));
+        emu.save_obj(&path, 0x3000..0x300A).unwrap();
+        let mut saved = emulator::from_program(path.to_str().unwrap()).unwrap();
+        let _ = std::fs::remove_file(&path);
+        saved.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(saved.registers.get(3), eq(from_binary(50)));
+    }
+    #[gtest]
+    pub fn test_load_program_disk_hello() {
+        let mut sw = StringWriter::new();
+        let mut emu = emulator::from_program("examples/hello_world_puts.obj").unwrap();
+        {
+            let mut ins = emu.instructions();
+            assert_that!(ins.len(), eq(15));
+            let first = ins.next().unwrap();
+            assert_that!(first.address, eq(0x3000));
+            assert_that!(first.instruction.op_code(), eq(Operation::Lea as u8));
         }
+        emu.execute_with_stdout(&mut sw).unwrap();
+        assert_that!(sw.get_string(), eq("HelloWorld!\nProgram halted\n"));
     }
-}
+    #[gtest]
+    pub fn test_chaos_input_keyboard_polling() {
+        for seed in 0..20 {
+            let mut emu = emulator::from_program_with_chaos_input(
+                "examples/memory_mapped_io_keyboard.obj",
+                "x",
+                seed,
+            )
+            .unwrap();
+            emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+            expect_that!(
+                emu.registers.get(0),
+                eq(from_binary(u16::from(b'x'))),
+                "seed: {seed}"
+            );
+        }
+    }
+    #[gtest]
+    pub fn test_chaos_input_failure_reports_seed() {
+        let program = [ORIG_HEADER, (Operation::_Reserved as u16) << 12];
+        let mut emu = EmulatorBuilder::new(&program)
+            .with_chaos_input("", 4711)
+            .build()
+            .unwrap();
+        let err = emu
+            .execute_with_stdout(&mut StringWriter::new())
+            .unwrap_err();
+        assert_that!(
+            err,
+            eq(&ExecutionError::ChaosRunFailed {
+                seed: 4711,
+                error: Box::new(ExecutionError::ReservedInstructionFound(0b1101)),
+            })
+        );
+        expect_that!(err.to_string(), contains_substring("seed 4711"));
+    }
+    #[gtest]
+    pub fn test_non_executable_warn() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        // HALT at 0x3006 is marked together with the data to have it fetched once
+        emu.mark_non_executable(0x3006..0x300A);
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        assert_that!(
+            emu.diagnostics(),
+            elements_are![eq(&Diagnostic::ExecutedNonExecutable {
+                pc: 0x3006,
+                raw: 0xF025
+            })]
+        );
+        assert_that!(emu.registers.get(3), eq(from_binary(30)));
+    }
+    #[gtest]
+    pub fn test_non_executable_error() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        emu.mark_non_executable(0x3006..0x300A);
+        emu.set_nx_policy(NxPolicy::Error);
+        let err = emu
+            .execute_with_stdout(&mut StringWriter::new())
+            .unwrap_err();
+        assert_that!(err, eq(&ExecutionError::NonExecutableMemory(0x3006)));
+    }
+    #[parameterized(
+        // LD R0, DATA; JMP R0; DATA .FILL xFF00
+        jmp = { vec![ORIG_HEADER, 0x2001, 0xC000, 0xFF00], 0x3001, 0xFF00 },
+        // LD R7, DATA; RET; DATA .FILL xFE00
+        ret = { vec![ORIG_HEADER, 0x2E01, 0xC1C0, 0xFE00], 0x3001, 0xFE00 },
+        // LD R1, DATA; JSRR R1; DATA .FILL xFFFF
+        jsrr = { vec![ORIG_HEADER, 0x2201, 0x4040, 0xFFFF], 0x3001, 0xFFFF },
+    )]
+    #[test_macro(gtest)]
+    pub fn test_jump_into_device_registers(program: Vec<u16>, pc: u16, target: u16) {
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        let err = emu
+            .execute_with_stdout(&mut StringWriter::new())
+            .unwrap_err();
+        assert_that!(err, eq(&ExecutionError::JumpOutOfRange { pc, target }));
+    }
+    #[gtest]
+    pub fn test_br_nzp_zero_behavior_in_summary() {
+        // BR #1, HALT, ADD R1, R1, #1, HALT
+        let program = [ORIG_HEADER, 0x0001, 0xF025, 0x1261, 0xF025];
+        for (behavior, expected_r1, expected_count) in [
+            (BrNzpZeroBehavior::Unconditional, 1, 3),
+            (BrNzpZeroBehavior::Nop, 0, 2),
+        ] {
+            let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+            emu.set_br_nzp_zero_behavior(behavior);
+            emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+            expect_that!(emu.registers.get(1), eq(from_binary(expected_r1)));
+            let summary = emu.execution_summary();
+            expect_that!(summary.br_nzp_zero_behavior, eq(behavior));
+            expect_that!(summary.instructions_executed, eq(expected_count));
+        }
+    }
+    #[gtest]
+    pub fn test_program_end_policy() {
+        // ADD R1, R1, #1 without HALT
+        let program = vec![ORIG_HEADER, 0x1261];
+        let expected = Diagnostic::RanPastProgramEnd {
+            pc: 0x3001,
+            last: 0x3000,
+            raw: 0x1261,
+        };
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(emu.diagnostics(), elements_are![eq(&expected)]);
+        expect_that!(emu.execution_summary().ran_past_program_end, eq(true));
 
-impl Debug for Emulator {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "Emulator:")?;
-        writeln!(f, "{:?}", self.memory)?;
-        writeln!(f, "Registers:\n{:?}", self.registers)?;
-        Ok(())
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.set_program_end_policy(ProgramEndPolicy::Error);
+        let err = emu
+            .execute_with_stdout(&mut StringWriter::new())
+            .unwrap_err();
+        expect_that!(
+            err,
+            eq(&ExecutionError::RanPastProgramEnd {
+                pc: 0x3001,
+                last: 0x3000,
+                raw: 0x1261
+            })
+        );
+
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.set_program_end_policy(ProgramEndPolicy::Silent);
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(emu.diagnostics(), is_empty());
+        expect_that!(emu.execution_summary().ran_past_program_end, eq(false));
     }
-}
+    #[gtest]
+    pub fn test_explain() {
+        // ADD R3, R2, #5, HALT
+        let program = vec![ORIG_HEADER, 0x16A5, 0xF025];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.registers.set(2, from_binary(25));
+        let explained = SharedWriter::default();
+        emu.set_explain(explained.clone());
+        let mut sw = StringWriter::new();
+        emu.execute_with_stdout(&mut sw).unwrap();
+        assert_that!(
+            explained.get_string(),
+            eq("x3000 ADD: R3 ← R2 + #5 = 30; flags=P\nx3001 TRAP x25 HALT: stop the program\n")
+        );
+        expect_that!(sw.get_string(), not(contains_substring("ADD")));
+    }
+    #[gtest]
+    pub fn test_watchpoint() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        let mut sw = StringWriter::new();
+        // the loop counter is loaded once by LD R2, LOOP_COUNT at 0x3002
+        emu.add_watchpoint(0x3008, WatchKind::Read);
+        let res = emu.run_budgeted(1000, &mut sw).unwrap();
+        assert_that!(
+            res,
+            eq(StepResult::Watchpoint(WatchpointHit {
+                pc: 0x3002,
+                access: MemoryAccess {
+                    address: 0x3008,
+                    kind: AccessKind::Read,
+                    old: 10,
+                    new: 10
+                }
+            }))
+        );
+        emu.execute_with_stdout(&mut sw).unwrap();
+        expect_that!(emu.watchpoint_hit(), none());
+        expect_that!(emu.registers.get(3), eq(from_binary(30)));
+    }
+    #[gtest]
+    pub fn test_watchpoint_stops_execute() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        emu.add_watchpoint(0x3008, WatchKind::Read);
+        let status = emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        let HaltReason::Watchpoint(hit) = status.halt_reason else {
+            fail!("stopped with {:?}", status.halt_reason).and_log_failure();
+            return;
+        };
+        expect_that!((hit.pc, hit.access.address), eq((0x3002, 0x3008)));
+        expect_that!(emu.watchpoint_hit(), some(eq(hit)));
+    }
+    #[gtest]
+    pub fn test_watchpoint_hit_of_failed_instruction_is_not_reported_later() {
+        struct FailingWriter;
+        impl io::Write for FailingWriter {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::Other.into())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let program = [
+            ORIG_HEADER,
+            0x2002, // LD R0, #2
+            0x1261, // ADD R1, R1, #1
+            0xF025, // HALT
+            0x0005, // .FILL 5
+        ];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.add_watchpoint(0x3003, WatchKind::Read);
+        // the LD hits the watchpoint but fails explaining itself
+        emu.set_explain(FailingWriter);
+        let mut sw = StringWriter::new();
+        expect_that!(
+            emu.run_budgeted(10, &mut sw),
+            err(matches_pattern!(ExecutionError::IOInputOutputError(_)))
+        );
+        emu.set_explain(io::sink());
+        expect_that!(emu.run_budgeted(10, &mut sw), ok(eq(&StepResult::Halted)));
+    }
+    #[gtest]
+    pub fn test_trace() {
+        struct CollectingSink(Arc<Mutex<Vec<TraceEvent>>>);
+        impl TraceSink for CollectingSink {
+            fn record(&mut self, event: &TraceEvent) {
+                lock(&self.0).push(event.clone());
+            }
+        }
+        let events = Arc::new(Mutex::new(Vec::new()));
+        // ADD R1, R1, #7, ST R1, #1, HALT, .FILL 0
+        let program = vec![ORIG_HEADER, 0x1267, 0x3201, 0xF025, 0x0000];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.set_trace_sink(CollectingSink(events.clone()));
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        let events = lock(&events);
+        assert_that!(events.len(), eq(3));
+        expect_that!((events[0].pc, events[0].raw), eq((0x3000, 0x1267)));
+        expect_that!(events[0].registers_after.get(1), eq(from_binary(7)));
+        expect_that!(events[0].memory_writes, is_empty());
+        expect_that!(
+            events[1].memory_writes,
+            elements_are![eq(&MemoryAccess {
+                address: 0x3003,
+                kind: AccessKind::Write,
+                old: 0,
+                new: 7
+            })]
+        );
+        expect_that!(events[2].decoded.op_code(), eq(Operation::Trap as u8));
+    }
+    #[gtest]
+    pub fn test_step_back() {
+        // ADD R1, R1, #7, ST R1, #1, HALT, .FILL 0
+        let program = vec![ORIG_HEADER, 0x1267, 0x3201, 0xF025, 0x0000];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.set_history_depth(2);
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(emu.memory[0x3003], eq(7));
+        expect_that!(emu.steps_back_available(), eq(2));
+        // HALT and ST are reverted, ADD fell out of the history
+        expect_that!(emu.step_back(), eq(true));
+        expect_that!(emu.step_back(), eq(true));
+        expect_that!(emu.step_back(), eq(false));
+        expect_that!(emu.memory[0x3003], eq(0));
+        expect_that!(emu.registers.pc(), eq(from_binary(0x3001)));
+        expect_that!(emu.registers.get(1), eq(from_binary(7)));
+        expect_that!(emu.execution_summary().instructions_executed, eq(1));
+        // executing again resumes from there
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(emu.memory[0x3003], eq(7));
+    }
+    #[gtest]
+    pub fn test_broken_symbol_table_is_ignored() {
+        let dir = std::env::temp_dir().join(format!("lc3-sym-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let program = dir.join("times_ten.obj");
+        std::fs::copy("examples/times_ten.obj", &program).unwrap();
+        std::fs::write(dir.join("times_ten.sym"), "LOOP 3003\n").unwrap();
+        let res = emulator::from_program(&program.to_string_lossy());
+        let _ = std::fs::remove_dir_all(&dir);
+        let emu = res.unwrap();
+        expect_that!(emu.symbols().is_empty(), eq(true));
+        expect_that!(
+            emu.diagnostics(),
+            elements_are![matches_pattern!(Diagnostic::SymbolTableIgnored(_))]
+        );
+    }
+    #[gtest]
+    pub fn test_symbols_loaded_with_program() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        expect_that!(emu.symbols().address_of("LOOP_START"), some(eq(0x3003)));
+        let explained = SharedWriter::default();
+        emu.set_explain(explained.clone());
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(
+            explained.get_string(),
+            contains_substring("BRnp: taken, flags=P, jump to x3003 (LOOP_START)")
+        );
+    }
+    #[gtest]
+    pub fn test_call_stack() {
+        // JSR SUB, HALT, SUB: JSR SUB2, RET, SUB2: TRAP x30 (unknown)
+        let program = vec![ORIG_HEADER, 0x4801, 0xF025, 0x4801, 0xC1C0, 0xF030];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        let _ = emu.execute_with_stdout(&mut StringWriter::new());
+        assert_that!(
+            emu.call_stack(),
+            elements_are![
+                eq(&Frame {
+                    call_site: 0x3000,
+                    target: 0x3002,
+                    return_addr: 0x3001
+                }),
+                eq(&Frame {
+                    call_site: 0x3002,
+                    target: 0x3004,
+                    return_addr: 0x3003
+                }),
+            ]
+        );
+    }
+    #[gtest]
+    pub fn test_step_over_and_out() {
+        // JSR SUB, ADD R2, R2, #1, HALT, SUB: ADD R1, R1, #1, ADD R1, R1, #1, RET
+        let program = vec![ORIG_HEADER, 0x4802, 0x14A1, 0xF025, 0x1261, 0x1261, 0xC1C0];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        let mut sw = StringWriter::new();
+        let res = emu.step_over(100, &mut sw).unwrap();
+        assert_that!(res, eq(StepResult::StepCompleted));
+        expect_that!(emu.registers.pc(), eq(from_binary(0x3001)));
+        expect_that!(emu.registers.get(1), eq(from_binary(2)));
+        emu.step_over(100, &mut sw).unwrap();
+        expect_that!(emu.registers.pc(), eq(from_binary(0x3002)));
 
-#[cfg(test)]
-mod tests {
-    use crate::emulator;
-    use crate::emulator::test_helpers::{FakeKeyboardInputProvider, StringWriter};
-    use crate::emulator::{Emulator, ORIG_HEADER, Operation};
-    use crate::errors::LoadProgramError;
-    use crate::errors::LoadProgramError::*;
-    use crate::hardware::memory::PROGRAM_SECTION_MAX_INSTRUCTION_COUNT;
-    use crate::hardware::registers::from_binary;
-    use googletest::prelude::*;
-    use std::error::Error;
-    use yare::parameterized;
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.run_budgeted(2, &mut sw).unwrap();
+        expect_that!(
+            emu.step_over(100, &mut sw),
+            ok(eq(&StepResult::StepCompleted))
+        );
+        expect_that!(emu.registers.pc(), eq(from_binary(0x3005)));
+        let res = emu.step_out(100, &mut sw).unwrap();
+        assert_that!(res, eq(StepResult::StepCompleted));
+        expect_that!(emu.registers.pc(), eq(from_binary(0x3001)));
+        expect_that!(emu.call_stack(), is_empty());
+        emu.step_out(100, &mut sw).unwrap();
+        expect_that!(emu.registers.pc(), eq(from_binary(0x3002)));
+    }
+    #[gtest]
+    pub fn test_snapshot_diff() {
+        // ADD R1, R1, #7, ST R1, #1, HALT, .FILL 0
+        let program = vec![ORIG_HEADER, 0x1267, 0x3201, 0xF025, 0x0000];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        let before = emu.snapshot();
+        emu.run_budgeted(2, &mut StringWriter::new()).unwrap();
+        let diff = before.diff(&emu.snapshot());
+        expect_that!(diff.changed_registers(), elements_are![eq(&1)]);
+        expect_that!(diff.changed_addresses(), elements_are![eq(&0x3003)]);
+        expect_that!(diff.pc_delta, eq(2));
+        expect_that!(
+            diff.to_string(),
+            eq("PC: x3000 -> x3002 (+2)\n\
+                R1: 0 -> 7 (x0000 -> x0007)\n\
+                COND: Zero -> Pos\n\
+                M[x3003]: x0000 -> x0007\n")
+        );
+        expect_that!(emu.snapshot().diff(&emu.snapshot()).is_empty(), eq(true));
+    }
+    #[gtest]
+    pub fn test_state() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        let state = emu.state();
+        expect_that!(state.register(3), eq(from_binary(30)));
+        expect_that!(state.condition(), eq(ConditionFlag::Zero));
+        expect_that!(state.pc(), eq(0x3007));
+        expect_that!(state.address_of("LOOP_START"), some(eq(0x3003)));
+        expect_that!(state.label_at(0x3003), some(eq("LOOP_START")));
+        expect_that!(state.memory_slice(0x3000..0x3002), eq(&[0x2006, 0x2607]));
+        expect_that!(state.instructions_executed(), eq(emu.instructions_executed));
+    }
+    #[gtest]
+    pub fn test_events() {
+        // ADD R1, R1, #7, ST R1, #1, HALT, .FILL 0
+        let program = vec![ORIG_HEADER, 0x1267, 0x3201, 0xF025, 0x0000];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let all = events.clone();
+        emu.subscribe(EventMask::ALL, move |e| all.lock().unwrap().push(e.clone()));
+        let halts = Arc::new(Mutex::new(Vec::new()));
+        let only_halts = halts.clone();
+        let id = emu.subscribe(EventMask::HALTED | EventMask::TRAP_ENTERED, move |e| {
+            only_halts.lock().unwrap().push(e.clone());
+        });
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(
+            *events.lock().unwrap(),
+            elements_are![
+                eq(&MachineEvent::RegisterWritten {
+                    pc: 0x3000,
+                    register: 1,
+                    old: from_binary(0),
+                    new: from_binary(7)
+                }),
+                eq(&MachineEvent::InstructionRetired {
+                    pc: 0x3000,
+                    instruction: Instruction::add_imm(1, 1, 7)
+                }),
+                eq(&MachineEvent::MemoryWritten {
+                    pc: 0x3001,
+                    address: 0x3003,
+                    old: 0,
+                    new: 7
+                }),
+                eq(&MachineEvent::InstructionRetired {
+                    pc: 0x3001,
+                    instruction: Instruction::st(1, 1)
+                }),
+                eq(&MachineEvent::TrapEntered {
+                    pc: 0x3002,
+                    vector: 0x25
+                }),
+                eq(&MachineEvent::InstructionRetired {
+                    pc: 0x3002,
+                    instruction: Instruction::trap(0x25)
+                }),
+                eq(&MachineEvent::Halted(HaltReason::HaltTrap)),
+            ]
+        );
+        expect_that!(halts.lock().unwrap().len(), eq(2));
+        expect_that!(emu.unsubscribe(id), eq(true));
+        expect_that!(emu.unsubscribe(id), eq(false));
+        emu.reset();
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(halts.lock().unwrap().len(), eq(2));
+        expect_that!(events.lock().unwrap().len(), eq(14));
+    }
+    #[cfg(feature = "tracing")]
+    #[gtest]
+    pub fn test_tracing() {
+        use crate::emulator::test_helpers::RecordingSubscriber;
+        use tracing::Level;
+        let subscriber = RecordingSubscriber::default();
+        let events = subscriber.events.clone();
+        tracing::subscriber::with_default(subscriber, || {
+            // ADD R1, R1, #7, HALT
+            let mut emu = emu_with_program_from_vec_wo_kdb(&[ORIG_HEADER, 0x1267, 0xF025]).unwrap();
+            emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        });
+        expect_that!(
+            *events.lock().unwrap(),
+            elements_are![
+                eq(&(Level::INFO, "program loaded".to_owned())),
+                eq(&(Level::TRACE, "instruction executed".to_owned())),
+                eq(&(Level::DEBUG, "trap".to_owned())),
+                eq(&(Level::TRACE, "instruction executed".to_owned())),
+                eq(&(Level::INFO, "program halted".to_owned())),
+            ]
+        );
+    }
+    #[gtest]
+    pub fn test_deterministic_time_mode() {
+        let run = || {
+            // poll KBSR until a key is available, echo it and halt
+            let code = [
+                Instruction::ldi(1, 4),
+                Instruction::br(false, true, true, -2),
+                Instruction::ldi(0, 3),
+                Instruction::trap(0x21),
+                Instruction::trap(0x25),
+            ];
+            let mut program = vec![ORIG_HEADER];
+            program.extend(code.map(u16::from));
+            program.extend([0xFE00, 0xFE02]);
+            let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+            emu.set_time_mode(TimeMode::Deterministic);
+            let events = Arc::new(Mutex::new(Vec::new()));
+            let all = events.clone();
+            emu.subscribe(EventMask::ALL, move |e| lock(&all).push(e.clone()));
+            let mut sw = StringWriter::new();
+            let first = emu.run_for_duration(Duration::from_micros(20), &mut sw);
+            expect_that!(first, ok(eq(&StepResult::BudgetExhausted)));
+            expect_that!(emu.execution_summary().instructions_executed, eq(20));
+            expect_that!(emu.virtual_time(), eq(Duration::from_micros(20)));
+            emu.queue_input("a");
+            let second = emu.run_for_duration(Duration::from_secs(1), &mut sw);
+            expect_that!(second, ok(eq(&StepResult::Halted)));
+            expect_that!(sw.get_string(), starts_with("a"));
+            lock(&events).clone()
+        };
+        expect_that!(run(), eq(&run()));
+    }
+    #[gtest]
+    pub fn test_real_time_mode_reattaches_input_provider() {
+        // GETC; OUT; GETC; OUT; HALT
+        let program = vec![ORIG_HEADER, 0xF020, 0xF021, 0xF020, 0xF021, 0xF025];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.set_input_provider(FakeKeyboardInputProvider::new("y"));
+        emu.set_time_mode(TimeMode::Deterministic);
+        emu.queue_input("x");
+        let mut sw = StringWriter::new();
+        expect_that!(
+            emu.run_budgeted(100, &mut sw),
+            ok(eq(&StepResult::WaitingForInput))
+        );
+        emu.set_time_mode(TimeMode::RealTime);
+        expect_that!(emu.run_budgeted(100, &mut sw), ok(eq(&StepResult::Halted)));
+        expect_that!(sw.get_string(), starts_with("xy"));
+    }
+    #[gtest]
+    pub fn test_record_and_replay_input() {
+        // poll KBSR until a key is available, echo it and repeat until `q` was typed
+        let code = [
+            Instruction::ldi(1, 7),
+            Instruction::br(false, true, true, -2),
+            Instruction::ldi(0, 6),
+            Instruction::trap(0x21),
+            Instruction::ld(2, 5),
+            Instruction::add_reg(0, 0, 2),
+            Instruction::br(true, false, true, -7),
+            Instruction::trap(0x25),
+        ];
+        let mut program = vec![ORIG_HEADER];
+        program.extend(code.map(u16::from));
+        program.extend([0xFE00, 0xFE02, 0u16.wrapping_sub(u16::from(b'q'))]);
+        let path = std::env::temp_dir().join(format!("lc3-replay-{}.txt", std::process::id()));
 
-    const PROGRAM_SECTION_MAX_INSTRUCTION_COUNT_WITH_HEADER: usize =
-        PROGRAM_SECTION_MAX_INSTRUCTION_COUNT as usize + 1;
+        let mut recorded = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        recorded.set_input_provider(ChaosInputProvider::with_max_delay("hiq", 7, 50));
+        recorded.record_input(&path).unwrap();
+        recorded.queue_input("x");
+        let mut sw = StringWriter::new();
+        recorded.execute_with_stdout(&mut sw).unwrap();
+        expect_that!(sw.get_string(), starts_with("xhiq"));
 
-    fn emu_with_program_from_vec_wo_kdb(
-        data: &Vec<u16>,
-    ) -> std::result::Result<Emulator, LoadProgramError> {
-        let kip = FakeKeyboardInputProvider::new("");
-        emulator::from_program_bytes_with_kbd_input_provider(data.as_slice(), kip)
+        let mut replayed = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        let res = replayed.replay(&path);
+        let _ = std::fs::remove_file(&path);
+        expect_that!(res, ok(eq(&())));
+        let mut replayed_sw = StringWriter::new();
+        replayed.execute_with_stdout(&mut replayed_sw).unwrap();
+        expect_that!(replayed_sw.get_string(), eq(&sw.get_string()));
+        expect_that!(
+            replayed.execution_summary().instructions_executed,
+            eq(recorded.execution_summary().instructions_executed)
+        );
+        expect_that!(
+            replayed.replay(&path),
+            err(matches_pattern!(ReplayError::NotLoadable { .. }))
+        );
     }
+    #[gtest]
+    pub fn test_run_until() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        let mut sw = StringWriter::new();
+        let res = emu.run_until(1000, &mut sw, |s| s.registers().get(3).as_binary() == 12);
+        expect_that!(res, ok(eq(&StepResult::ConditionMet)));
+        expect_that!(emu.registers.get(2), eq(from_binary(7)));
+        // run to address
+        let res = emu.run_until(1000, &mut sw, |s| s.pc() == 0x3006);
+        expect_that!(res, ok(eq(&StepResult::ConditionMet)));
+        expect_that!(emu.registers.get(3), eq(from_binary(30)));
+        let res = emu.run_until(1000, &mut sw, |_| false);
+        expect_that!(res, ok(eq(&StepResult::Halted)));
 
-    #[parameterized(
-        missing_header = {Vec::with_capacity(0), ProgramMissingOrigHeader },
-        wrong_header = {vec![0x3001], ProgramLoadedAtWrongAddress
-            {actual_address: 0x3001, expected_address: 0x3000 } },
-        too_large = {vec![0x3000u16; PROGRAM_SECTION_MAX_INSTRUCTION_COUNT_WITH_HEADER + 1],
-            ProgramTooLong {actual_instructions: 52737,
-            maximum_instructions: PROGRAM_SECTION_MAX_INSTRUCTION_COUNT} },
-        empty = { vec![0x3000u16; 1], ProgramEmpty }
-    )]
-    #[test_macro(gtest)]
-    pub fn test_load_program_errors(data: Vec<u16>, error: LoadProgramError) {
-        let abstract_error =
-            Box::<dyn Error>::from(emu_with_program_from_vec_wo_kdb(&data).unwrap_err());
-        let res = abstract_error.downcast_ref::<LoadProgramError>();
-        assert_that!(res.unwrap(), eq(&error));
+        // ADD R1, R1, #7, ST R1, #1, HALT, .FILL 0
+        let program = vec![ORIG_HEADER, 0x1267, 0x3201, 0xF025, 0x0000];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        let res = emu.run_until(1000, &mut sw, |s| s.was_written(0x3003));
+        expect_that!(res, ok(eq(&StepResult::ConditionMet)));
+        expect_that!(emu.execution_summary().instructions_executed, eq(2));
+        let res = emu.run_until(1, &mut sw, |_| false);
+        expect_that!(res, ok(eq(&StepResult::Halted)));
+
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        let res = emu.run_until(5, &mut sw, |_| false);
+        expect_that!(res, ok(eq(&StepResult::BudgetExhausted)));
     }
+    #[gtest]
+    pub fn test_max_instructions() {
+        // BR #-1
+        let program = vec![ORIG_HEADER, 0x0FFF];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.set_max_instructions(100);
+        let err = emu
+            .execute_with_stdout(&mut StringWriter::new())
+            .unwrap_err();
+        assert_that!(
+            err,
+            eq(&ExecutionError::InstructionLimitExceeded {
+                executed: 100,
+                pc: 0x3000
+            })
+        );
 
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        emu.set_max_instructions(37);
+        expect_that!(
+            emu.execute_with_stdout(&mut StringWriter::new())
+                .map(|status| status.halt_reason),
+            ok(eq(&HaltReason::HaltTrap))
+        );
+        expect_that!(HaltReason::from(&err), eq(HaltReason::InstructionLimit));
+    }
     #[gtest]
-    pub fn test_load_program_max_size() {
-        let mut program = vec![0x0u16; PROGRAM_SECTION_MAX_INSTRUCTION_COUNT_WITH_HEADER];
-        program[0] = ORIG_HEADER;
-        let emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
-        let ins = emu.instructions();
+    pub fn test_instruction_hooks() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        let coverage = Arc::new(Mutex::new(std::collections::BTreeSet::new()));
+        let pcs = coverage.clone();
+        emu.set_pre_instruction_hook(move |ctx| {
+            lock(&pcs).insert(ctx.pc());
+        });
+        // poke the factor to 4 once it was loaded
+        emu.set_post_instruction_hook(|ctx| {
+            if ctx.pc() == 0x3000 {
+                ctx.registers_mut().set(0, from_binary(4));
+                ctx.poke(0x3007, 4);
+            }
+        });
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(lock(&coverage).len(), eq(7));
+        expect_that!(emu.registers.get(3), eq(from_binary(40)));
+        expect_that!(emu.memory[0x3007], eq(4));
+    }
+    #[gtest]
+    pub fn test_hook_writes_are_undone() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        emu.set_history_depth(10);
+        emu.set_pre_instruction_hook(|ctx| ctx.poke(0x4000, ctx.pc()));
+        emu.set_post_instruction_hook(|ctx| ctx.poke(0xC000, ctx.pc()));
+        emu.run_budgeted(2, &mut StringWriter::new()).unwrap();
+        expect_that!(
+            (emu.memory[0x4000], emu.memory[0xC000]),
+            eq((0x3001, 0x3001))
+        );
+        expect_that!(emu.step_back(), eq(true));
+        expect_that!(
+            (emu.memory[0x4000], emu.memory[0xC000]),
+            eq((0x3000, 0x3000))
+        );
+        expect_that!(emu.step_back(), eq(true));
+        expect_that!((emu.memory[0x4000], emu.memory[0xC000]), eq((0, 0)));
+    }
+    #[gtest]
+    pub fn test_breakpoints() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        let mut sw = StringWriter::new();
+        let in_loop = emu.add_breakpoint(Breakpoint::new(0x3003).with_ignore_count(2));
+        emu.add_breakpoint(Breakpoint::new(0x3006).temporary());
+        let res = emu.run_budgeted(1000, &mut sw).unwrap();
         assert_that!(
-            ins.len(),
-            eq(usize::from(PROGRAM_SECTION_MAX_INSTRUCTION_COUNT))
+            res,
+            eq(StepResult::Breakpoint(BreakpointHit {
+                id: in_loop,
+                address: 0x3003,
+                hit_count: 3
+            }))
         );
+        expect_that!(emu.registers.get(3), eq(from_binary(6)));
+        // resuming stops at the next loop iteration
+        let res = emu.run_budgeted(1000, &mut sw).unwrap();
+        expect_that!(res, matches_pattern!(StepResult::Breakpoint(_)));
+        expect_that!(emu.registers.get(3), eq(from_binary(9)));
+        emu.remove_breakpoint(in_loop);
+        let res = emu.run_budgeted(1000, &mut sw).unwrap();
+        expect_that!(res, matches_pattern!(StepResult::Breakpoint(_)));
+        expect_that!(emu.registers.pc(), eq(from_binary(0x3006)));
+        expect_that!(emu.breakpoints().count(), eq(0));
+        let res = emu.run_budgeted(1000, &mut sw).unwrap();
+        expect_that!(res, eq(StepResult::Halted));
     }
     #[gtest]
-    pub fn test_load_program_disk_hello() {
+    pub fn test_break_on_trap() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
         let mut sw = StringWriter::new();
-        let mut emu = emulator::from_program("examples/hello_world_puts.obj").unwrap();
-        {
-            let mut ins = emu.instructions();
-            assert_that!(ins.len(), eq(15));
-            assert_that!(ins.next().unwrap().op_code(), eq(Operation::Lea as u8));
+        emu.break_on_trap(0x25);
+        let res = emu.run_budgeted(10_000, &mut sw).unwrap();
+        assert_that!(
+            res,
+            eq(StepResult::TrapEntered {
+                pc: 0x3006,
+                vector: 0x25
+            })
+        );
+        expect_that!(emu.registers.pc(), eq(from_binary(0x3006)));
+        expect_that!(emu.instructions_executed, eq(33));
+        expect_that!(emu.clear_trap_break(0x25), eq(true));
+        expect_that!(emu.clear_trap_break(0x25), eq(false));
+        let res = emu.run_budgeted(10_000, &mut sw).unwrap();
+        expect_that!(res, eq(StepResult::Halted));
+    }
+    #[gtest]
+    pub fn test_rewind_to_checkpoint() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        let mut sw = StringWriter::new();
+        emu.set_checkpoint_interval(10, 2);
+        let res = emu.run_budgeted(1000, &mut sw).unwrap();
+        assert_that!(res, eq(StepResult::Halted));
+        assert_that!(emu.checkpoints(), elements_are![eq(&30), eq(&20)]);
+        expect_that!(emu.rewind_to_checkpoint(2), eq(false));
+        assert_that!(emu.rewind_to_checkpoint(1), eq(true));
+        expect_that!(emu.instructions_executed, eq(20));
+        expect_that!(emu.registers.get(3), eq(from_binary(18)));
+        expect_that!(emu.checkpoints(), elements_are![eq(&20)]);
+        let res = emu.run_budgeted(1000, &mut sw).unwrap();
+        expect_that!(res, eq(StepResult::Halted));
+        expect_that!(emu.registers.get(3), eq(from_binary(30)));
+        expect_that!(emu.checkpoints(), elements_are![eq(&30), eq(&20)]);
+    }
+    #[gtest]
+    pub fn test_disassemble() {
+        let emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        let lines: Vec<String> = emu
+            .disassemble(0x3000..0x3008)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_that!(
+            lines,
+            elements_are![
+                eq("> x3000  x2006  LD R0, FACTOR"),
+                eq("  x3001  x2607  LD R3, ZERO"),
+                eq("  x3002  x2405  LD R2, LOOP_COUNT"),
+                eq("  x3003  x16C0  ADD R3, R3, R0"),
+                eq("  x3004  x14BF  ADD R2, R2, #-1"),
+                eq("  x3005  x0BFD  BRnp LOOP_START"),
+                eq("  x3006  xF025  HALT"),
+                eq("  x3007  x0003  .FILL x0003"),
+            ]
+        );
+    }
+    struct CollectingSink(Arc<Mutex<Vec<SessionEvent>>>);
+    impl EventLogSink for CollectingSink {
+        fn record(&mut self, _session_id: u64, event: &SessionEvent) {
+            lock(&self.0).push(event.clone());
         }
-        emu.execute_with_stdout(&mut sw).unwrap();
-        //        assert_that!(sw.get_string(), eq("HelloWorld!\nProgram halted\n"));
+    }
+    #[gtest]
+    pub fn test_event_log() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        // TRAP x30 is unknown
+        let program = vec![ORIG_HEADER, 0xF030];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        emu.set_event_log(CollectingSink(events.clone()));
+        let _ = emu.execute_with_stdout(&mut StringWriter::new());
         assert_that!(
-            sw.get_string(),
-            matches_regex("HelloWorld!.*Program halted.*")
+            *lock(&events),
+            elements_are![
+                matches_pattern!(SessionEvent::ProgramLoaded { words: eq(&1), .. }),
+                eq(&SessionEvent::RunStarted),
+                eq(&SessionEvent::ErrorEncountered {
+                    error: "Unknown trap routine found: 0x0030".into(),
+                    pc: 0x3001,
+                    instructions_executed: 1
+                }),
+            ]
         );
-        // TODO add more assertions for further content
+    }
+    #[gtest]
+    pub fn test_event_log_breakpoint_hit() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        emu.set_event_log(CollectingSink(events.clone()));
+        emu.add_breakpoint(Breakpoint::new(0x3004));
+        let res = emu.run_budgeted(1000, &mut StringWriter::new()).unwrap();
+        expect_that!(res, matches_pattern!(StepResult::Breakpoint(_)));
+        expect_that!(
+            *lock(&events),
+            elements_are![
+                matches_pattern!(SessionEvent::ProgramLoaded { .. }),
+                eq(&SessionEvent::RunStarted),
+                eq(&SessionEvent::BreakpointHit { address: 0x3004 }),
+            ]
+        );
+    }
+    #[gtest]
+    pub fn test_run_budgeted_in_slices() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        let mut sw = StringWriter::new();
+        let mut slices = 0;
+        while emu.run_budgeted(5, &mut sw).unwrap() == StepResult::BudgetExhausted {
+            slices += 1;
+        }
+        expect_that!(slices, gt(1));
+        expect_that!(emu.registers.get(3), eq(from_binary(30)));
+        expect_that!(
+            emu.run_budgeted(5, &mut sw).unwrap(),
+            eq(StepResult::Halted)
+        );
+    }
+    #[gtest]
+    pub fn test_run_budgeted_waiting_for_input() {
+        // GETC, HALT
+        let program = vec![ORIG_HEADER, 0xF020, 0xF025];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        let mut sw = StringWriter::new();
+        for _ in 0..2 {
+            expect_that!(
+                emu.run_budgeted(100, &mut sw).unwrap(),
+                eq(StepResult::WaitingForInput)
+            );
+            expect_that!(emu.registers.pc(), eq(from_binary(0x3000)));
+            expect_that!(emu.execution_summary().instructions_executed, eq(0));
+        }
+    }
+    #[gtest]
+    pub fn test_run_for_duration() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        let res = emu
+            .run_for_duration(Duration::from_secs(10), &mut StringWriter::new())
+            .unwrap();
+        expect_that!(res, eq(StepResult::Halted));
+        expect_that!(emu.registers.get(3), eq(from_binary(30)));
     }
     #[gtest]
     pub fn test_program_add_ld_break_times_ten() {
@@ -342,4 +4653,199 @@ mod tests {
         assert_that!(emu.registers.get(3), eq(from_binary(30)));
         // TODO add more assertions for further content
     }
+    #[gtest]
+    pub fn test_stats() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        let stats = emu.stats();
+        expect_that!(stats.total(), eq(34));
+        expect_that!(
+            stats.opcode_histogram().collect::<Vec<_>>(),
+            eq(&[("BR", 10), ("ADD", 20), ("LD", 3), ("TRAP", 1)])
+        );
+        expect_that!(stats.opcode_count(Operation::Add as u8), eq(20));
+        expect_that!(
+            stats.trap_counts().iter().collect::<Vec<_>>(),
+            eq(&[(&0x25, &1)])
+        );
+        expect_that!(stats.branches_taken(), eq(9));
+        expect_that!(stats.branches_not_taken(), eq(1));
+        expect_that!(stats.branch_taken_ratio(), some(eq(0.9)));
+        expect_that!(
+            stats.to_string(),
+            contains_substring("branches taken 9 / not taken 1 (90.0% taken)")
+        );
+        emu.reset_stats();
+        expect_that!(emu.stats().total(), eq(0));
+        expect_that!(emu.stats().branch_taken_ratio(), none());
+    }
+    #[gtest]
+    pub fn test_coverage() {
+        let program = vec![
+            ORIG_HEADER,
+            0x5020, // AND R0, R0, #0
+            0x0401, // BRz SKIP
+            0x1021, // ADD R0, R0, #1
+            0x0601, // SKIP: BRzp DONE
+            0x1021, // ADD R0, R0, #1
+            0xF025, // DONE: HALT
+            0x0048, // .STRINGZ "HI"
+            0x0049,
+            0x0000,
+        ];
+        let mut emu = emu_with_program_from_vec_wo_kdb(&program).unwrap();
+        expect_that!(emu.coverage(), none());
+        emu.set_coverage(true);
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        let report = emu.coverage().unwrap();
+        expect_that!(report.covered, eq(4));
+        expect_that!(report.total, eq(6));
+        expect_that!(
+            report.uncovered().collect::<Vec<_>>(),
+            eq(&[0x3002, 0x3004])
+        );
+        expect_that!(
+            report.branches,
+            elements_are![
+                eq(&BranchCoverage {
+                    address: 0x3001,
+                    taken: true,
+                    not_taken: false
+                }),
+                eq(&BranchCoverage {
+                    address: 0x3003,
+                    taken: true,
+                    not_taken: false
+                }),
+            ]
+        );
+        expect_that!(
+            report.branches.iter().any(BranchCoverage::is_complete),
+            eq(false)
+        );
+    }
+    #[gtest]
+    pub fn test_profile() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        emu.set_profiling(true);
+        emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        let profile = emu.profile(3);
+        expect_that!(
+            profile
+                .iter()
+                .map(|hot| (hot.address, hot.count))
+                .collect::<Vec<_>>(),
+            eq(&[(0x3003, 10), (0x3004, 10), (0x3005, 10)])
+        );
+        expect_that!(profile[0].instruction, eq("ADD R3, R3, R0"));
+        expect_that!(emu.profile(100).len(), eq(7));
+        emu.set_profiling(false);
+        expect_that!(emu.profile(3), is_empty());
+    }
+    #[gtest]
+    pub fn test_run_on_worker_thread() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        emu.set_input_provider(FakeKeyboardInputProvider::new(""));
+        let emu = std::thread::spawn(move || {
+            emu.execute_with_stdout(&mut StringWriter::new()).unwrap();
+            emu
+        })
+        .join()
+        .unwrap();
+        expect_that!(emu.stats().total(), eq(34));
+    }
+    #[cfg(feature = "async")]
+    #[gtest]
+    pub fn test_execute_async() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        let (res, polls) = block_on(emu.execute_async(10, io::sink()));
+        expect_that!(
+            res.map(|status| status.halt_reason),
+            ok(eq(&HaltReason::HaltTrap))
+        );
+        // 34 instructions
+        expect_that!(polls, eq(4));
+
+        // GETC; OUT; HALT
+        let mut emu =
+            emu_with_program_from_vec_wo_kdb(&[ORIG_HEADER, 0xF020, 0xF021, 0xF025]).unwrap();
+        let sender = emu.input_sender();
+        let typist = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            sender.send("Y");
+        });
+        let mut output = Vec::new();
+        let (res, _) = block_on(emu.execute_async(100, &mut output));
+        typist.join().unwrap();
+        expect_that!(
+            res.map(|status| status.halt_reason),
+            ok(eq(&HaltReason::HaltTrap))
+        );
+        expect_that!(String::from_utf8_lossy(&output), starts_with("Y"));
+    }
+    #[cfg(feature = "async")]
+    #[gtest]
+    pub fn test_cancel_wakes_execute_async_waiting_for_input() {
+        for with_queue in [false, true] {
+            // GETC; HALT
+            let mut emu = emu_with_program_from_vec_wo_kdb(&[ORIG_HEADER, 0xF020, 0xF025]).unwrap();
+            if with_queue {
+                let _ = emu.input_sender();
+            }
+            let token = emu.cancel_token();
+            let canceller = std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(20));
+                token.cancel();
+            });
+            let (res, _) = block_on(emu.execute_async(100, io::sink()));
+            canceller.join().unwrap();
+            expect_that!(res, err(pat!(ExecutionError::Interrupted(_))));
+        }
+    }
+    #[cfg(feature = "async")]
+    #[gtest]
+    pub fn test_input_provider_wakes_execute_async() {
+        /// Receives a key from another thread and wakes the task awaiting it.
+        #[derive(Default)]
+        struct Mailbox {
+            key: Option<char>,
+            waker: Option<std::task::Waker>,
+        }
+        struct MailboxInputProvider(Arc<Mutex<Mailbox>>);
+        impl KeyboardInputProvider for MailboxInputProvider {
+            fn check_input_available(&mut self) -> io::Result<bool> {
+                Ok(lock(&self.0).key.is_some())
+            }
+            fn get_input_character(&mut self) -> char {
+                lock(&self.0).key.take().unwrap()
+            }
+            fn is_interrupted(&self) -> bool {
+                false
+            }
+            fn register_waker(&mut self, waker: &std::task::Waker) {
+                lock(&self.0).waker = Some(waker.clone());
+            }
+        }
+        // GETC; OUT; HALT
+        let mut emu =
+            emu_with_program_from_vec_wo_kdb(&[ORIG_HEADER, 0xF020, 0xF021, 0xF025]).unwrap();
+        let mailbox = Arc::new(Mutex::new(Mailbox::default()));
+        emu.set_input_provider(MailboxInputProvider(mailbox.clone()));
+        let typist = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            let mut mailbox = lock(&mailbox);
+            mailbox.key = Some('Y');
+            mailbox.waker.take().unwrap().wake();
+        });
+        let mut output = Vec::new();
+        let (res, polls) = block_on(emu.execute_async(100, &mut output));
+        typist.join().unwrap();
+        expect_that!(
+            res.map(|status| status.halt_reason),
+            ok(eq(&HaltReason::HaltTrap))
+        );
+        expect_that!(String::from_utf8_lossy(&output), starts_with("Y"));
+        // polled once waiting and once woken by the provider
+        expect_that!(polls, eq(2));
+    }
 }
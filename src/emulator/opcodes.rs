@@ -1,7 +1,9 @@
 //! Implemented operations for the LC 3.
 use crate::emulator::instruction::Instruction;
+use crate::emulator::{BrNzpZeroBehavior, Operation};
+use crate::errors::ExecutionError;
 use crate::hardware::memory::Memory;
-use crate::hardware::registers::{ConditionFlag, Register, Registers, from_binary};
+use crate::hardware::registers::{ConditionFlag, Privilege, Register, Registers, from_binary};
 
 /// ADD: Mathematical addition in 2 variants
 /// - DR is set with result of SR 1 + SR 2
@@ -77,7 +79,7 @@ pub fn not(i: Instruction, r: &mut Registers) {
 }
 /// BR: Conditional Branch
 /// This opcode adds the value of the sign extended offset to PC if
-/// - either none of the `nzp` bits are set
+/// - either none of the `nzp` bits are set and `no_flags` is [`BrNzpZeroBehavior::Unconditional`]
 /// - or the current state of the `ConditionFlag` matches a set bit of `n`, `z` or `p`.
 /// ```text
 ///  15__12__11_9___8_______0_
@@ -85,17 +87,23 @@ pub fn not(i: Instruction, r: &mut Registers) {
 ///  -------------------------
 /// ```
 /// See [`ConditionFlag`]
-pub fn br(i: Instruction, r: &mut Registers) {
-    let none_set = i.get_bit_range(9, 11) == 0;
-    let do_break = none_set
+pub fn br(i: Instruction, r: &mut Registers, no_flags: BrNzpZeroBehavior) {
+    if is_branch_taken(i, r, no_flags) {
+        r.set_pc(address_by_pc_offset(i, r));
+    }
+}
+/// Whether the conditional branch `i` jumps given the current condition codes.
+///
+/// `BR` leaves the condition codes untouched, so the decision can also be repeated after
+/// executing it.
+pub fn is_branch_taken(i: Instruction, r: &Registers, no_flags: BrNzpZeroBehavior) -> bool {
+    let none_set = i.get_bit_range(9, 11) == 0 && no_flags == BrNzpZeroBehavior::Unconditional;
+    none_set
         || match r.get_conditional_register() {
             ConditionFlag::Pos => i.get_bit(9),
             ConditionFlag::Zero => i.get_bit(10),
             ConditionFlag::Neg => i.get_bit(11),
-        };
-    if do_break {
-        r.set_pc(address_by_pc_offset(i, r));
-    }
+        }
 }
 /// JSR: Jump to Sub-Routine.
 /// Two variants:
@@ -148,7 +156,7 @@ pub fn jmp_or_ret(i: Instruction, r: &mut Registers) {
 ///  -------------------------
 /// ```
 pub fn ld(i: Instruction, r: &mut Registers, memory: &Memory) {
-    let value = memory[address_by_pc_offset(i, r)];
+    let value = memory.read(address_by_pc_offset(i, r));
     r.set(i.dr_number(), from_binary(value));
     r.update_conditional_register(i.dr_number());
 }
@@ -163,8 +171,8 @@ pub fn ld(i: Instruction, r: &mut Registers, memory: &Memory) {
 /// ```
 pub fn ldi(i: Instruction, r: &mut Registers, memory: &Memory) {
     let address_address = address_by_pc_offset(i, r);
-    let value_address = memory[address_address];
-    r.set(i.dr_number(), from_binary(memory[value_address]));
+    let value_address = memory.read(address_address);
+    r.set(i.dr_number(), from_binary(memory.read(value_address)));
     r.update_conditional_register(i.dr_number());
 }
 /// LDR: Load address from base register and adds sign extended offset to load the memory content
@@ -176,7 +184,7 @@ pub fn ldi(i: Instruction, r: &mut Registers, memory: &Memory) {
 /// ```
 pub fn ldr(i: Instruction, r: &mut Registers, memory: &Memory) {
     let value_address = address_by_baser_offset(i, r);
-    r.set(i.dr_number(), from_binary(memory[value_address]));
+    r.set(i.dr_number(), from_binary(memory.read(value_address)));
     r.update_conditional_register(i.dr_number());
 }
 
@@ -189,6 +197,29 @@ fn address_by_baser_offset(i: Instruction, r: &Registers) -> u16 {
     (r.get(base_r).as_decimal() + i.pc_offset(6)).cast_unsigned()
 }
 
+/// Addresses of the data accesses of `i` with the PC already incremented, for `LDI` and `STI`
+/// the second one is the address read from the first.
+pub fn data_addresses(i: Instruction, r: &Registers, memory: &Memory) -> [Option<u16>; 2] {
+    match i.operation() {
+        Operation::Ld | Operation::St => [Some(address_by_pc_offset(i, r)), None],
+        Operation::Ldr | Operation::Str => [Some(address_by_baser_offset(i, r)), None],
+        Operation::Ldi | Operation::Sti => {
+            let pointer = address_by_pc_offset(i, r);
+            [Some(pointer), Some(memory[pointer])]
+        }
+        Operation::Br
+        | Operation::Add
+        | Operation::Jsr
+        | Operation::And
+        | Operation::Rti
+        | Operation::Not
+        | Operation::JmpOrRet
+        | Operation::_Reserved
+        | Operation::Lea
+        | Operation::Trap => [None, None],
+    }
+}
+
 /// LEA: Load Effective Address loads PC + sign extended offset into DR.
 /// ```text
 ///  15__12__11_9___8_______0_
@@ -210,7 +241,7 @@ pub fn lea(i: Instruction, r: &mut Registers) {
 /// ```
 pub fn st(i: Instruction, r: &Registers, memory: &mut Memory) {
     let store_address = address_by_pc_offset(i, r);
-    memory[store_address] = r.get(i.dr_number()).as_binary();
+    memory.write(store_address, r.get(i.dr_number()).as_binary());
 }
 /// STI: Store Indirect. The contents of the SR are written to the address which is loaded from
 /// memory address PC + sign extended offset.
@@ -221,8 +252,8 @@ pub fn st(i: Instruction, r: &Registers, memory: &mut Memory) {
 /// ```
 pub fn sti(i: Instruction, r: &Registers, memory: &mut Memory) {
     let address_of_store_address = address_by_pc_offset(i, r);
-    let store_address = memory[address_of_store_address];
-    memory[store_address] = r.get(i.dr_number()).as_binary();
+    let store_address = memory.read(address_of_store_address);
+    memory.write(store_address, r.get(i.dr_number()).as_binary());
 }
 /// STR: Store contents of SR to memory address of base register plus sign extended offset.
 /// ```text
@@ -232,7 +263,7 @@ pub fn sti(i: Instruction, r: &Registers, memory: &mut Memory) {
 /// ```
 pub fn str(i: Instruction, r: &Registers, memory: &mut Memory) {
     let store_address = address_by_baser_offset(i, r);
-    memory[store_address] = r.get(i.dr_number()).as_binary();
+    memory.write(store_address, r.get(i.dr_number()).as_binary());
 }
 /// RTI: Return from Interrupt.
 /// If the processor is running in Supervisor mode, the top two elements on the
@@ -243,23 +274,41 @@ pub fn str(i: Instruction, r: &Registers, memory: &mut Memory) {
 /// | 1000 | 0000000000000000 |
 ///  -------------------------
 /// ```
-pub fn rti(_i: Instruction, _r: &Registers) {
-    todo!()
+///
+/// The stack entries and the instruction are `word_size` addresses apart, 2 on the LC-3b.
+///
+/// # Errors
+/// - [`ExecutionError::PrivilegeViolation`] in User mode
+pub fn rti(
+    _i: Instruction,
+    r: &mut Registers,
+    memory: &Memory,
+    word_size: u16,
+) -> Result<(), ExecutionError> {
+    if r.privilege() == Privilege::User {
+        return Err(ExecutionError::PrivilegeViolation(
+            r.pc().as_binary().wrapping_sub(word_size),
+        ));
+    }
+    let sp = r.get(6).as_binary();
+    r.set_pc(memory.read(sp));
+    let psr = memory.read(sp.wrapping_add(word_size));
+    r.set(6, from_binary(sp.wrapping_add(2 * word_size)));
+    r.set_psr(psr);
+    Ok(())
 }
 
-#[expect(clippy::unusual_byte_groupings)]
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::emulator::test_helpers::FakeKeyboardInputProvider;
     use crate::hardware::registers::{ConditionFlag, from_decimal};
     use googletest::prelude::*;
-    use std::cell::RefCell;
-    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
 
     fn create_memory(data: &[u16]) -> Memory {
         let kip = FakeKeyboardInputProvider::new("");
-        let mut mem = Memory::new(Rc::new(RefCell::new(kip)));
+        let mut mem = Memory::new(Arc::new(Mutex::new(kip)));
         mem.load_program(data).expect("Error loading program");
         mem
     }
@@ -270,9 +319,9 @@ mod tests {
         regs.set(0, from_binary(22));
         regs.set(1, from_binary(128));
         // Add: DR: 2, SR1: 0: 22, Immediate: false, SR2: 1: 128 => R2: 150
-        add(0b0001_010_000_0_00_001.into(), &mut regs);
+        add(Instruction::add_reg(2, 0, 1), &mut regs);
         // Add: DR: 3, SR1: 2: 150, Immediate: true, imm5: 14 => R3: 164
-        add(0b0001_011_010_1_01110.into(), &mut regs);
+        add(Instruction::add_imm(3, 2, 14), &mut regs);
         expect_that!(regs.get(0), eq(from_binary(22)));
         expect_that!(regs.get(1), eq(from_binary(128)));
         expect_that!(regs.get(2), eq(from_binary(150)));
@@ -285,9 +334,9 @@ mod tests {
         regs.set(0, from_binary(22));
         regs.set(1, from_decimal(-128));
         // Add: DR: 2, SR1: 0: 22, Immediate: false, SR2: 1: -128 => R2: -106
-        add(0b0001_010_000_0_00_001.into(), &mut regs);
+        add(Instruction::add_reg(2, 0, 1), &mut regs);
         // Add: DR: 3, SR1: 2: -106, Immediate: true, imm5: -2 => R3: -108
-        add(0b0001_011_010_1_11110.into(), &mut regs);
+        add(Instruction::add_imm(3, 2, -2), &mut regs);
         expect_that!(regs.get(0), eq(from_binary(22)));
         expect_that!(regs.get(1), eq(from_binary(0b1111_1111_1000_0000)));
         expect_that!(regs.get(1), eq(from_decimal(-128)));
@@ -301,7 +350,7 @@ mod tests {
         regs.set(0, from_binary(0x7FFF)); // largest positive number in 2's complement
         regs.set(1, from_binary(1));
         // Add: DR: 2, SR1: 0, Immediate: false, SR2: 1 => R2: 32768
-        add(0b0001_010_000_0_00_001.into(), &mut regs);
+        add(Instruction::add_reg(2, 0, 1), &mut regs);
         expect_that!(regs.get(0), eq(from_binary(0x7FFF)));
         expect_that!(regs.get(1), eq(from_binary(1)));
         expect_that!(regs.get(2), eq(from_binary(32768)));
@@ -314,7 +363,7 @@ mod tests {
         regs.set(1, from_binary(!0x7FFF + 1));
         regs.set(2, from_binary(1)); // to be sure opcode was executed
         // Add: DR: 2, SR1: 0, Immediate: false, SR2: 1 => R2: 0
-        add(0b0001_010_000_0_00_001.into(), &mut regs);
+        add(Instruction::add_reg(2, 0, 1), &mut regs);
         expect_that!(regs.get(0), eq(from_binary(0x7FFF)));
         expect_that!(regs.get(1), eq(from_binary(!0x7FFF + 1)));
         expect_that!(regs.get(2), eq(from_binary(0)));
@@ -326,7 +375,7 @@ mod tests {
         regs.set(0, from_binary(0b1101_1001_0111_0101));
         regs.set(1, from_binary(0b0100_1010_0010_1001));
         // Add: DR: 2, SR1: 0, Immediate: false, SR2: 1 => R2: 0
-        and(0b0101_010_000_0_00_001.into(), &mut regs);
+        and(Instruction::and_reg(2, 0, 1), &mut regs);
         expect_that!(regs.get(0), eq(from_binary(0b1101_1001_0111_0101)));
         expect_that!(regs.get(1), eq(from_binary(0b0100_1010_0010_1001)));
         expect_that!(regs.get(2), eq(from_binary(0b0100_1000_0010_0001)));
@@ -339,7 +388,7 @@ mod tests {
         // Add: DR: 2, SR1: 0, Immediate: true: 21, 0xFFF5 => R2: 0
         expect_that!(regs.get(0), eq(from_binary(0b1101_1001_0111_0101)));
         // Immediate sign extended:           0b1111_1111_1111_0101
-        and(0b0101_010_000_1_10101.into(), &mut regs);
+        and(Instruction::and_imm(2, 0, -11), &mut regs);
         expect_that!(regs.get(2), eq(from_binary(0b1101_1001_0111_0101)));
         expect_that!(regs.get_conditional_register(), eq(ConditionFlag::Neg));
     }
@@ -348,7 +397,7 @@ mod tests {
         let mut regs = Registers::new();
         regs.set(0, from_binary(0x7FFF)); // largest positive number in 2's complement
         // Add: DR: 1, SR1: 0 => R1: 0xFFFE
-        super::not(0b1001_001_000_111111.into(), &mut regs);
+        super::not(Instruction::not(1, 0), &mut regs);
         expect_that!(regs.get(0), eq(from_binary(0x7FFF)));
         expect_that!(regs.get(1), eq(from_binary(0x8000)));
         expect_that!(regs.get_conditional_register(), eq(ConditionFlag::Neg));
@@ -358,8 +407,8 @@ mod tests {
         let mut regs = Registers::new();
         regs.set_pc(0x3045);
         // Lea: DR: 3, SR1: 0 => R1: 0xFFFE
-        lea(0b1110_011_0_0101_0101.into(), &mut regs);
-        expect_that!(regs.get(3), eq(from_binary(0x3045 + 0b0_0101_0101)));
+        lea(Instruction::lea(3, 0x55), &mut regs);
+        expect_that!(regs.get(3), eq(from_binary(0x3045 + 0x55)));
         expect_that!(regs.get_conditional_register(), eq(ConditionFlag::Pos));
     }
     #[gtest]
@@ -369,12 +418,12 @@ mod tests {
         let raw = vec![4711u16, 815];
         let memory = create_memory(&raw);
         // LD - DR: 4, PC_OFFSET9: -0x44
-        ld(0b0010_100_1_1011_1100.into(), &mut regs, &memory);
+        ld(Instruction::ld(4, -0x44), &mut regs, &memory);
         expect_that!(regs.get(4), eq(from_decimal(815)));
         expect_that!(regs.get_conditional_register(), eq(ConditionFlag::Pos));
 
         // LD - DR: 4, PC_OFFSET9: -0x45
-        ld(0b0010_100_1_1011_1011.into(), &mut regs, &memory);
+        ld(Instruction::ld(4, -0x45), &mut regs, &memory);
         expect_that!(regs.get(4), eq(from_decimal(4711)));
         expect_that!(regs.get_conditional_register(), eq(ConditionFlag::Pos));
     }
@@ -387,7 +436,7 @@ mod tests {
         let memory = create_memory(&raw);
         regs.set(6, from_binary(0x3025));
         // LDR - DR: 2, - BaseR: 6, OFFSET6: -32 = -0x20
-        ldr(0b0110_010_110_100000.into(), &mut regs, &memory);
+        ldr(Instruction::ldr(2, 6, -0x20), &mut regs, &memory);
         expect_that!(regs.get(2), eq(from_binary(mem_val)));
         expect_that!(regs.get_conditional_register(), eq(ConditionFlag::Neg));
     }
@@ -401,7 +450,7 @@ mod tests {
         let memory = create_memory(&raw);
         regs.set_pc(0x3065);
         // LDR - DR: 1, - PC_OFFSET9: -96 = -0x60
-        ldi(0b1010_001_110100000.into(), &mut regs, &memory);
+        ldi(Instruction::ldi(1, -0x60), &mut regs, &memory);
         expect_that!(regs.get(1), eq(from_binary(val_to_load_in_register)));
         expect_that!(regs.get_conditional_register(), eq(ConditionFlag::Neg));
     }
@@ -413,7 +462,7 @@ mod tests {
         regs.set(5, from_decimal(4760));
         regs.set_pc(0x3065);
         // ST - SR: 5, - PC_OFFSET9: -95 = -0x5F
-        st(0b0011_101_110100001.into(), &regs, &mut memory);
+        st(Instruction::st(5, -0x5F), &regs, &mut memory);
         expect_that!(memory[0x3006], eq(4760));
     }
     #[gtest]
@@ -425,7 +474,7 @@ mod tests {
         regs.set(7, from_decimal(1234));
         regs.set_pc(0x3067);
         // STI - SR: 7, - PC_OFFSET9: -0x5D
-        sti(0b1011_111_110100011.into(), &regs, &mut memory);
+        sti(Instruction::sti(7, -0x5D), &regs, &mut memory);
         expect_that!(memory[0x3006], eq(1234));
     }
     #[gtest]
@@ -436,15 +485,55 @@ mod tests {
         regs.set(2, from_decimal(2345));
         regs.set(6, from_binary(0x3005));
         // STR - SR: 2, - BaseR: 6, offset6: 0x1
-        str(0b0111_010_110_000001.into(), &regs, &mut memory);
+        str(Instruction::str(2, 6, 1), &regs, &mut memory);
         expect_that!(memory[0x3006], eq(2345));
     }
     #[gtest]
+    pub fn test_opcode_rti() {
+        let mut regs = Registers::new();
+        let mut memory = create_memory(&[0; 0x10]);
+        regs.set_pc(0x3001);
+        expect_that!(
+            rti(Instruction::rti(), &mut regs, &memory, 1),
+            err(eq(&ExecutionError::PrivilegeViolation(0x3000)))
+        );
+        regs.set(6, from_binary(0x4000));
+        regs.switch_privilege(Privilege::Supervisor);
+        regs.set(6, from_binary(0x2FFE));
+        memory[0x2FFE] = 0x3005;
+        memory[0x2FFF] = 0x8004;
+        regs.set_pc(0x0201);
+        expect_that!(rti(Instruction::rti(), &mut regs, &memory, 1), ok(eq(&())));
+        expect_that!(regs.pc(), eq(from_binary(0x3005)));
+        expect_that!(regs.psr(), eq(0x8004));
+        expect_that!(regs.get(6), eq(from_binary(0x4000)));
+        regs.switch_privilege(Privilege::Supervisor);
+        expect_that!(regs.get(6), eq(from_binary(0x3000)));
+    }
+    #[gtest]
+    pub fn test_opcode_br_nzp_zero() {
+        let mut regs = Registers::new();
+        regs.set_pc(0x3010);
+        // BR - nzp: 000, PC_OFFSET9: 0x10
+        br(
+            Instruction::br(false, false, false, 0x10),
+            &mut regs,
+            BrNzpZeroBehavior::Nop,
+        );
+        expect_that!(regs.pc(), eq(from_binary(0x3010)));
+        br(
+            Instruction::br(false, false, false, 0x10),
+            &mut regs,
+            BrNzpZeroBehavior::Unconditional,
+        );
+        expect_that!(regs.pc(), eq(from_binary(0x3020)));
+    }
+    #[gtest]
     pub fn test_opcode_jsr() {
         let mut regs = Registers::new();
         regs.set_pc(0x3099);
         // JSR - PC_OFFSET11: 0x1A1
-        jsr(0b0100_1_00110100001.into(), &mut regs);
+        jsr(Instruction::jsr(0x1A1), &mut regs);
         expect_that!(regs.pc(), eq(from_decimal(0x323A)));
         expect_that!(regs.get(7), eq(from_decimal(0x3099)));
 
@@ -452,7 +541,7 @@ mod tests {
         regs.set_pc(0x3100);
         regs.set(6, from_decimal(0x3456));
         // JSR - BaseR: 6
-        jsr(0b0100_000_110_000000.into(), &mut regs);
+        jsr(Instruction::jsrr(6), &mut regs);
         expect_that!(regs.pc(), eq(from_decimal(0x3456)));
         expect_that!(regs.get(7), eq(from_decimal(0x3100)));
     }
@@ -462,7 +551,7 @@ mod tests {
         regs.set_pc(0x3020);
         regs.set(1, from_decimal(0x3022));
         // JMP - BaseR: 1
-        jmp_or_ret(0b1100_000_001_000000.into(), &mut regs);
+        jmp_or_ret(Instruction::jmp(1), &mut regs);
         expect_that!(regs.pc(), eq(from_decimal(0x3022)));
     }
 }
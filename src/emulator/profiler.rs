@@ -0,0 +1,54 @@
+//! Execution counts per address of the guest program, see
+//! [`Emulator::profile`](crate::emulator::Emulator::profile).
+use std::fmt::{Display, Formatter};
+
+/// An address ranked by how often the instruction at it was executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotAddress {
+    pub address: u16,
+    pub count: u64,
+    /// Symbol at the address if one is loaded
+    pub label: Option<String>,
+    /// Disassembly of the current memory content, f.e. `ADD R3, R3, R0`
+    pub instruction: String,
+}
+
+impl Display for HotAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:>10}  x{:04X}  {:<12} {}",
+            self.count,
+            self.address,
+            self.label.as_deref().unwrap_or(""),
+            self.instruction
+        )
+    }
+}
+
+/// Counts how often each address is executed.
+#[derive(Clone)]
+pub(crate) struct Profiler {
+    counts: Vec<u64>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            counts: vec![0; 1 << 16],
+        }
+    }
+    pub fn record(&mut self, pc: u16) {
+        self.counts[usize::from(pc)] += 1;
+    }
+    /// The at most `limit` most executed addresses with their counts, ties ordered by address.
+    pub fn hottest(&self, limit: usize) -> Vec<(u16, u64)> {
+        let mut executed: Vec<(u16, u64)> = (0..=u16::MAX)
+            .zip(self.counts.iter().copied())
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        executed.sort_by(|(a, count_a), (b, count_b)| count_b.cmp(count_a).then(a.cmp(b)));
+        executed.truncate(limit);
+        executed
+    }
+}
@@ -0,0 +1,221 @@
+//! Recording and deterministic replay of the keyboard input delivered to the program.
+//!
+//! Useful f.e. to reproduce a bug report for an interactive program, see
+//! [`Emulator::record_input`](crate::emulator::Emulator::record_input) and
+//! [`Emulator::replay`](crate::emulator::Emulator::replay).
+//!
+//! A recording is a text file with one line per delivered character: the virtual timestamp,
+//! which is the number of instructions executed since recording started when the character
+//! became available, and its code point, f.e. `1042 97` for `a`. Lines starting with `#` are
+//! comments.
+use crate::errors::ReplayError;
+use crate::hardware::keyboard::{KeyboardInputProvider, SharedInputProvider};
+use crate::sync::lock;
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::task::Waker;
+
+pub(crate) const HEADER: &str = "# lc3-emulator input recording: <instructions> <code point>";
+
+/// A character delivered to the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordedKey {
+    /// Instructions executed since recording started when the character became available
+    pub instructions: u64,
+    pub key: char,
+}
+
+/// Characters delivered to the program in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputRecording {
+    keys: Vec<RecordedKey>,
+}
+
+impl InputRecording {
+    /// Parses the contents of a recording.
+    ///
+    /// # Errors
+    /// - [`ReplayError::InvalidLine`] if a line is neither a comment nor a timestamp followed
+    ///   by a code point
+    pub fn parse(content: &str) -> Result<Self, ReplayError> {
+        let mut keys = Vec::new();
+        for (idx, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid_line = || ReplayError::InvalidLine {
+                line: idx + 1,
+                content: line.to_owned(),
+            };
+            let (instructions, code_point) = line.split_once(' ').ok_or_else(invalid_line)?;
+            keys.push(RecordedKey {
+                instructions: instructions.parse().map_err(|_| invalid_line())?,
+                key: code_point
+                    .trim()
+                    .parse()
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(invalid_line)?,
+            });
+        }
+        Ok(Self { keys })
+    }
+    /// Reads and parses the recording at `path`.
+    ///
+    /// # Errors
+    /// - [`ReplayError::NotLoadable`] if the file cannot be read
+    /// - see [`InputRecording::parse`]
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|e| ReplayError::NotLoadable {
+            file: path.display().to_string(),
+            message: e.to_string(),
+        })?;
+        Self::parse(&content)
+    }
+    #[must_use]
+    pub fn keys(&self) -> &[RecordedKey] {
+        &self.keys
+    }
+}
+
+impl Display for InputRecording {
+    /// Writes the recording in the format [`InputRecording::parse`] reads.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{HEADER}")?;
+        for key in &self.keys {
+            writeln!(f, "{} {}", key.instructions, u32::from(key.key))?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes every character `inner` delivers to `out`, errors are ignored since recording must
+/// never influence the emulated program.
+pub(crate) struct RecordingInputProvider {
+    inner: SharedInputProvider,
+    out: Box<dyn Write + Send>,
+    instructions: u64,
+    /// Timestamp the pending character was first reported as available
+    available_since: Option<u64>,
+}
+
+impl RecordingInputProvider {
+    pub(crate) fn new(inner: SharedInputProvider, out: Box<dyn Write + Send>) -> Self {
+        Self {
+            inner,
+            out,
+            instructions: 0,
+            available_since: None,
+        }
+    }
+}
+
+impl KeyboardInputProvider for RecordingInputProvider {
+    fn check_input_available(&mut self) -> io::Result<bool> {
+        let available = lock(&self.inner).check_input_available()?;
+        if available && self.available_since.is_none() {
+            self.available_since = Some(self.instructions);
+        }
+        Ok(available)
+    }
+    fn get_input_character(&mut self) -> char {
+        let key = lock(&self.inner).get_input_character();
+        let instructions = self.available_since.take().unwrap_or(self.instructions);
+        let _ = writeln!(self.out, "{instructions} {}", u32::from(key));
+        let _ = self.out.flush();
+        key
+    }
+    fn is_interrupted(&self) -> bool {
+        lock(&self.inner).is_interrupted()
+    }
+    fn reset_interrupt(&mut self) {
+        lock(&self.inner).reset_interrupt();
+    }
+    fn on_instruction_executed(&mut self) {
+        self.instructions += 1;
+        lock(&self.inner).on_instruction_executed();
+    }
+    fn wait_for_input(&mut self) {
+        lock(&self.inner).wait_for_input();
+    }
+    fn register_waker(&mut self, waker: &Waker) {
+        lock(&self.inner).register_waker(waker);
+    }
+}
+
+/// Delivers recorded characters once as many instructions were executed as when recording.
+pub(crate) struct ReplayInputProvider {
+    keys: VecDeque<RecordedKey>,
+    instructions: u64,
+}
+
+impl ReplayInputProvider {
+    pub(crate) fn new(recording: InputRecording) -> Self {
+        Self {
+            keys: recording.keys.into(),
+            instructions: 0,
+        }
+    }
+}
+
+impl KeyboardInputProvider for ReplayInputProvider {
+    fn check_input_available(&mut self) -> io::Result<bool> {
+        Ok(self
+            .keys
+            .front()
+            .is_some_and(|key| key.instructions <= self.instructions))
+    }
+    fn get_input_character(&mut self) -> char {
+        let Some(key) = self.keys.pop_front() else {
+            panic!("No input available");
+        };
+        key.key
+    }
+    fn is_interrupted(&self) -> bool {
+        false
+    }
+    fn on_instruction_executed(&mut self) {
+        self.instructions += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+
+    #[gtest]
+    fn test_parse_recording() {
+        let recording = InputRecording::parse(&format!("{HEADER}\n3 97\n\n10 10\n")).unwrap();
+        expect_that!(
+            recording.keys(),
+            elements_are![
+                eq(&RecordedKey {
+                    instructions: 3,
+                    key: 'a'
+                }),
+                eq(&RecordedKey {
+                    instructions: 10,
+                    key: '\n'
+                }),
+            ]
+        );
+        expect_that!(
+            InputRecording::parse(&recording.to_string()),
+            ok(eq(&recording))
+        );
+        expect_that!(
+            InputRecording::parse("1 97\n2 a\n"),
+            err(eq(&ReplayError::InvalidLine {
+                line: 2,
+                content: "2 a".to_owned()
+            }))
+        );
+    }
+}
@@ -0,0 +1,219 @@
+//! Snapshots of the machine state and differences between them, f.e. to assert in tests that
+//! only `R3` and memory `x4000` changed.
+use crate::hardware::memory::{Memory, MemoryAccess, MemorySnapshot};
+use crate::hardware::registers::{ConditionFlag, Register, Registers};
+use crate::symbols::SymbolTable;
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
+/// Registers and memory at one point of execution, see
+/// [`Emulator::snapshot`](crate::emulator::Emulator::snapshot).
+///
+/// Memory is shared copy-on-write with the emulator, so taking snapshots is cheap.
+#[derive(Clone)]
+pub struct MachineSnapshot {
+    registers: Registers,
+    memory: MemorySnapshot,
+}
+
+/// Read-only view of the machine, see [`Emulator::state`](crate::emulator::Emulator::state).
+///
+/// Also passed to the condition of
+/// [`Emulator::run_until`](crate::emulator::Emulator::run_until) after each instruction.
+pub struct MachineState<'a> {
+    registers: &'a Registers,
+    memory: &'a Memory,
+    symbols: &'a SymbolTable,
+    last_writes: &'a [MemoryAccess],
+    instructions_executed: u64,
+}
+
+impl<'a> MachineState<'a> {
+    pub(crate) const fn new(
+        registers: &'a Registers,
+        memory: &'a Memory,
+        symbols: &'a SymbolTable,
+        last_writes: &'a [MemoryAccess],
+        instructions_executed: u64,
+    ) -> Self {
+        Self {
+            registers,
+            memory,
+            symbols,
+            last_writes,
+            instructions_executed,
+        }
+    }
+    #[must_use]
+    pub const fn registers(&self) -> &Registers {
+        self.registers
+    }
+    /// Value of general purpose register `r`.
+    ///
+    /// # Panics
+    /// - `r` is greater than 7
+    #[must_use]
+    pub fn register(&self, r: u8) -> Register {
+        self.registers.get(r)
+    }
+    /// Address of the next instruction.
+    #[must_use]
+    pub const fn pc(&self) -> u16 {
+        self.registers.pc().as_binary()
+    }
+    /// Condition codes set by the last instruction writing a register.
+    #[must_use]
+    pub const fn condition(&self) -> ConditionFlag {
+        self.registers.get_conditional_register()
+    }
+    /// Content of `address` without side effects of memory mapped IO.
+    #[must_use]
+    pub fn memory(&self, address: u16) -> u16 {
+        self.memory.peek(address)
+    }
+    /// Contents of `range` without side effects of memory mapped IO.
+    #[must_use]
+    pub fn memory_slice(&self, range: Range<u16>) -> &'a [u16] {
+        self.memory.cells(range)
+    }
+    /// Labels of the loaded program.
+    #[must_use]
+    pub const fn symbols(&self) -> &'a SymbolTable {
+        self.symbols
+    }
+    /// Address of the label `name`.
+    #[must_use]
+    pub fn address_of(&self, name: &str) -> Option<u16> {
+        self.symbols.address_of(name)
+    }
+    /// Label at `address` if one is loaded.
+    #[must_use]
+    pub fn label_at(&self, address: u16) -> Option<&'a str> {
+        self.symbols.name_at(address)
+    }
+    /// Memory cells written by the last instruction.
+    #[must_use]
+    pub const fn last_writes(&self) -> &[MemoryAccess] {
+        self.last_writes
+    }
+    /// `true` if the last instruction wrote to `address`.
+    #[must_use]
+    pub fn was_written(&self, address: u16) -> bool {
+        self.last_writes.iter().any(|w| w.address == address)
+    }
+    #[must_use]
+    pub const fn instructions_executed(&self) -> u64 {
+        self.instructions_executed
+    }
+}
+
+/// A changed general purpose register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterChange {
+    pub register: u8,
+    pub old: Register,
+    pub new: Register,
+}
+
+/// A changed memory cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellChange {
+    pub address: u16,
+    pub old: u16,
+    pub new: u16,
+}
+
+/// Differences from one [`MachineSnapshot`] to a later one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateDiff {
+    /// Difference of the PC, negative if it moved backwards
+    pub pc_delta: i32,
+    pub old_pc: u16,
+    pub registers: Vec<RegisterChange>,
+    /// Old and new condition flag if it changed
+    pub condition: Option<(ConditionFlag, ConditionFlag)>,
+    /// Changed cells ordered by address
+    pub memory: Vec<CellChange>,
+}
+
+impl MachineSnapshot {
+    pub(crate) const fn new(registers: Registers, memory: MemorySnapshot) -> Self {
+        Self { registers, memory }
+    }
+    #[must_use]
+    pub const fn registers(&self) -> &Registers {
+        &self.registers
+    }
+    /// Changes from `self` to `other`.
+    #[must_use]
+    pub fn diff(&self, other: &Self) -> StateDiff {
+        let registers = (0..8)
+            .filter_map(|register| {
+                let old = self.registers.get(register);
+                let new = other.registers.get(register);
+                (old != new).then_some(RegisterChange { register, old, new })
+            })
+            .collect();
+        let old_cond = self.registers.get_conditional_register();
+        let new_cond = other.registers.get_conditional_register();
+        StateDiff {
+            pc_delta: i32::from(other.registers.pc().as_binary())
+                - i32::from(self.registers.pc().as_binary()),
+            old_pc: self.registers.pc().as_binary(),
+            registers,
+            condition: (old_cond != new_cond).then_some((old_cond, new_cond)),
+            memory: self
+                .memory
+                .changed_cells(&other.memory)
+                .map(|(address, old, new)| CellChange { address, old, new })
+                .collect(),
+        }
+    }
+}
+
+impl StateDiff {
+    /// `true` if nothing but possibly the PC changed.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.registers.is_empty() && self.condition.is_none() && self.memory.is_empty()
+    }
+    /// Numbers of the changed general purpose registers.
+    #[must_use]
+    pub fn changed_registers(&self) -> Vec<u8> {
+        self.registers.iter().map(|c| c.register).collect()
+    }
+    /// Addresses of the changed memory cells.
+    #[must_use]
+    pub fn changed_addresses(&self) -> Vec<u16> {
+        self.memory.iter().map(|c| c.address).collect()
+    }
+}
+
+impl Display for StateDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let new_pc = i32::from(self.old_pc) + self.pc_delta;
+        writeln!(
+            f,
+            "PC: x{:04X} -> x{new_pc:04X} ({:+})",
+            self.old_pc, self.pc_delta
+        )?;
+        for c in &self.registers {
+            writeln!(
+                f,
+                "R{}: {} -> {} (x{:04X} -> x{:04X})",
+                c.register,
+                c.old.as_decimal(),
+                c.new.as_decimal(),
+                c.old.as_binary(),
+                c.new.as_binary()
+            )?;
+        }
+        if let Some((old, new)) = self.condition {
+            writeln!(f, "COND: {old:?} -> {new:?}")?;
+        }
+        for c in &self.memory {
+            writeln!(f, "M[x{:04X}]: x{:04X} -> x{:04X}", c.address, c.old, c.new)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+//! Dynamic instruction mix of the executed program, see
+//! [`Emulator::stats`](crate::emulator::Emulator::stats).
+use crate::emulator::instruction::Instruction;
+use crate::emulator::{BrNzpZeroBehavior, Operation, opcodes};
+use crate::hardware::registers::Registers;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+/// Mnemonics indexed by opcode.
+const MNEMONICS: [&str; 16] = [
+    "BR", "ADD", "LD", "ST", "JSR", "AND", "LDR", "STR", "RTI", "NOT", "LDI", "STI", "JMP",
+    "reserved", "LEA", "TRAP",
+];
+
+/// Statistics of the instructions executed since the emulator was created or the statistics
+/// were reset.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ExecutionStats {
+    opcodes: [u64; 16],
+    trap_vectors: BTreeMap<u8, u64>,
+    branches_taken: u64,
+    branches_not_taken: u64,
+}
+
+impl ExecutionStats {
+    /// Counts the executed instruction `i`, `r` being the registers after executing it.
+    pub(crate) fn record(&mut self, i: Instruction, r: &Registers, no_flags: BrNzpZeroBehavior) {
+        self.opcodes[usize::from(i.op_code())] += 1;
+        match i.operation() {
+            Operation::Trap => {
+                *self
+                    .trap_vectors
+                    .entry(i.get_bit_range_u8(0, 7, "Error parsing trap vector"))
+                    .or_default() += 1;
+            }
+            Operation::Br => {
+                if opcodes::is_branch_taken(i, r, no_flags) {
+                    self.branches_taken += 1;
+                } else {
+                    self.branches_not_taken += 1;
+                }
+            }
+            Operation::Add
+            | Operation::Ld
+            | Operation::St
+            | Operation::Jsr
+            | Operation::And
+            | Operation::Ldr
+            | Operation::Str
+            | Operation::Rti
+            | Operation::Not
+            | Operation::Ldi
+            | Operation::Sti
+            | Operation::JmpOrRet
+            | Operation::_Reserved
+            | Operation::Lea => {}
+        }
+    }
+    /// Total number of instructions executed.
+    #[must_use]
+    pub fn total(&self) -> u64 {
+        self.opcodes.iter().sum()
+    }
+    /// Number of executed instructions with opcode `op_code`, which is taken modulo 16.
+    #[must_use]
+    pub const fn opcode_count(&self, op_code: u8) -> u64 {
+        self.opcodes[(op_code & 0xF) as usize]
+    }
+    /// Mnemonic and count of every opcode executed at least once, ordered by opcode.
+    pub fn opcode_histogram(&self) -> impl Iterator<Item = (&'static str, u64)> + '_ {
+        MNEMONICS
+            .iter()
+            .zip(self.opcodes)
+            .filter(|(_, count)| *count > 0)
+            .map(|(mnemonic, count)| (*mnemonic, count))
+    }
+    /// Number of executed `TRAP` instructions per trap vector.
+    #[must_use]
+    pub const fn trap_counts(&self) -> &BTreeMap<u8, u64> {
+        &self.trap_vectors
+    }
+    /// Number of executed `BR` instructions that branched.
+    #[must_use]
+    pub const fn branches_taken(&self) -> u64 {
+        self.branches_taken
+    }
+    /// Number of executed `BR` instructions that did not branch.
+    #[must_use]
+    pub const fn branches_not_taken(&self) -> u64 {
+        self.branches_not_taken
+    }
+    /// Share of executed `BR` instructions that branched, [`None`] if there were none.
+    #[must_use]
+    #[expect(
+        clippy::cast_precision_loss,
+        reason = "Counts beyond 2^52 branches are not expected"
+    )]
+    pub fn branch_taken_ratio(&self) -> Option<f64> {
+        let branches = self.branches_taken + self.branches_not_taken;
+        (branches > 0).then(|| self.branches_taken as f64 / branches as f64)
+    }
+}
+
+impl Display for ExecutionStats {
+    /// Writes the opcode histogram, trap vectors and branch counts, one entry per line.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} instructions", self.total())?;
+        for (mnemonic, count) in self.opcode_histogram() {
+            writeln!(f, "  {mnemonic:<8} {count}")?;
+        }
+        for (vector, count) in &self.trap_vectors {
+            writeln!(f, "  TRAP x{vector:02X} {count}")?;
+        }
+        if let Some(ratio) = self.branch_taken_ratio() {
+            writeln!(
+                f,
+                "branches taken {} / not taken {} ({:.1}% taken)",
+                self.branches_taken,
+                self.branches_not_taken,
+                ratio * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -1,18 +1,30 @@
+//! Writers for the program output, see
+//! [`Emulator::execute_with_stdout`](crate::emulator::Emulator::execute_with_stdout).
 use std::fmt::Arguments;
-use std::io::{IoSlice, Stdout, Write, stdout};
+use std::io::{ErrorKind, IoSlice, Stdout, Write, stdout};
+use std::thread::sleep;
+use std::time::Duration;
 
+/// Output sinks used for program output need to tell whether terminal queries are possible.
+///
+/// The emulator moves the cursor of terminals to start new lines, which requires querying
+/// them. Implement it for custom writers returning `true` unless they write to a terminal.
 pub trait CrosstermCompatibility {
+    /// `true` if querying terminal size or cursor position would block, f.e. when not
+    /// connected to a terminal.
     fn will_block_on_size_or_position_queries(&self) -> bool;
 }
 impl CrosstermCompatibility for Stdout {
     fn will_block_on_size_or_position_queries(&self) -> bool {
         #[cfg(not(test))]
-        return false;
+        return !std::io::IsTerminal::is_terminal(self);
         #[cfg(test)]
         return true;
     }
 }
 
+/// Stdout which never queries the terminal, for doc tests only.
+#[doc(hidden)]
 pub struct StdoutForDocTest(Stdout);
 impl Default for StdoutForDocTest {
     fn default() -> Self {
@@ -55,3 +67,189 @@ impl Write for StdoutForDocTest {
         self
     }
 }
+
+/// Any output which is not a terminal, f.e. a buffer of a GUI or a network connection, see
+/// [`Emulator::execute_with_io`](crate::emulator::Emulator::execute_with_io).
+pub(crate) struct VirtualConsole<W: Write>(pub W);
+impl<W: Write> Write for VirtualConsole<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+impl<W: Write> CrosstermCompatibility for VirtualConsole<W> {
+    fn will_block_on_size_or_position_queries(&self) -> bool {
+        true
+    }
+}
+
+/// Action taken when writing program output fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnWriteError {
+    /// Stop execution with an [`ExecutionError::IOInputOutputError`](crate::errors::ExecutionError::IOInputOutputError).
+    Fail,
+    /// Retry the write after a short, increasing delay up to
+    /// [`OutputErrorPolicy::max_retries`] times, then fail.
+    Retry,
+    /// Drop the output and continue execution.
+    Ignore,
+}
+
+/// Defines how errors writing program output are handled depending on their kind.
+///
+/// Errors which are neither transient nor a broken pipe always fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputErrorPolicy {
+    /// Handling of [`ErrorKind::Interrupted`] and [`ErrorKind::WouldBlock`],
+    /// f.e. on non-blocking sinks
+    pub on_transient: OnWriteError,
+    /// Handling of [`ErrorKind::BrokenPipe`], f.e. output piped through `head`
+    pub on_broken_pipe: OnWriteError,
+    /// Maximum retries for [`OnWriteError::Retry`]
+    pub max_retries: u32,
+}
+impl Default for OutputErrorPolicy {
+    fn default() -> Self {
+        Self {
+            on_transient: OnWriteError::Retry,
+            on_broken_pipe: OnWriteError::Fail,
+            max_retries: 10,
+        }
+    }
+}
+impl OutputErrorPolicy {
+    const fn action_for(self, kind: ErrorKind) -> OnWriteError {
+        match kind {
+            ErrorKind::Interrupted | ErrorKind::WouldBlock => self.on_transient,
+            ErrorKind::BrokenPipe => self.on_broken_pipe,
+            _ => OnWriteError::Fail,
+        }
+    }
+}
+
+/// Applies an [`OutputErrorPolicy`] to all writes to the wrapped output.
+pub(crate) struct PolicyWriter<'a, W: Write + CrosstermCompatibility> {
+    inner: &'a mut W,
+    policy: OutputErrorPolicy,
+}
+impl<'a, W: Write + CrosstermCompatibility> PolicyWriter<'a, W> {
+    pub(crate) const fn new(inner: &'a mut W, policy: OutputErrorPolicy) -> Self {
+        Self { inner, policy }
+    }
+    fn with_policy<T>(
+        &mut self,
+        ignored: T,
+        mut op: impl FnMut(&mut W) -> std::io::Result<T>,
+    ) -> std::io::Result<T> {
+        let mut retries = 0;
+        loop {
+            match op(self.inner) {
+                Ok(res) => return Ok(res),
+                Err(e) => match self.policy.action_for(e.kind()) {
+                    OnWriteError::Retry if retries < self.policy.max_retries => {
+                        retries += 1;
+                        sleep(Duration::from_millis(u64::from(retries)));
+                    }
+                    OnWriteError::Ignore => return Ok(ignored),
+                    OnWriteError::Retry | OnWriteError::Fail => return Err(e),
+                },
+            }
+        }
+    }
+}
+impl<W: Write + CrosstermCompatibility> Write for PolicyWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.with_policy(buf.len(), |w| w.write(buf))
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.with_policy((), Write::flush)
+    }
+}
+impl<W: Write + CrosstermCompatibility> CrosstermCompatibility for PolicyWriter<'_, W> {
+    fn will_block_on_size_or_position_queries(&self) -> bool {
+        self.inner.will_block_on_size_or_position_queries()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+
+    /// Fails with `kind` for the first `failures` writes.
+    struct FailingWriter {
+        kind: ErrorKind,
+        failures: u32,
+        written: Vec<u8>,
+    }
+    impl Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(self.kind.into());
+            }
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    impl CrosstermCompatibility for FailingWriter {
+        fn will_block_on_size_or_position_queries(&self) -> bool {
+            true
+        }
+    }
+    fn failing(kind: ErrorKind, failures: u32) -> FailingWriter {
+        FailingWriter {
+            kind,
+            failures,
+            written: Vec::new(),
+        }
+    }
+
+    #[gtest]
+    fn test_transient_errors_are_retried() {
+        let mut inner = failing(ErrorKind::WouldBlock, 3);
+        let mut sut = PolicyWriter::new(&mut inner, OutputErrorPolicy::default());
+        sut.write_all(b"abc").unwrap();
+        expect_that!(inner.written, eq(b"abc"));
+    }
+    #[gtest]
+    fn test_retries_are_limited() {
+        let mut inner = failing(ErrorKind::WouldBlock, 3);
+        let policy = OutputErrorPolicy {
+            max_retries: 2,
+            ..OutputErrorPolicy::default()
+        };
+        let res = PolicyWriter::new(&mut inner, policy).write_all(b"abc");
+        expect_that!(res.unwrap_err().kind(), eq(ErrorKind::WouldBlock));
+    }
+    #[gtest]
+    fn test_broken_pipe_ignored_or_failing() {
+        let mut inner = failing(ErrorKind::BrokenPipe, u32::MAX);
+        let policy = OutputErrorPolicy {
+            on_broken_pipe: OnWriteError::Ignore,
+            ..OutputErrorPolicy::default()
+        };
+        expect_that!(
+            PolicyWriter::new(&mut inner, policy).write_all(b"abc"),
+            ok(eq(&()))
+        );
+        let res = PolicyWriter::new(&mut inner, OutputErrorPolicy::default()).write_all(b"abc");
+        expect_that!(res.unwrap_err().kind(), eq(ErrorKind::BrokenPipe));
+    }
+    #[gtest]
+    fn test_other_errors_always_fail() {
+        let mut inner = failing(ErrorKind::PermissionDenied, 1);
+        let policy = OutputErrorPolicy {
+            on_transient: OnWriteError::Ignore,
+            on_broken_pipe: OnWriteError::Ignore,
+            max_retries: 10,
+        };
+        let res = PolicyWriter::new(&mut inner, policy).write_all(b"abc");
+        expect_that!(res.unwrap_err().kind(), eq(ErrorKind::PermissionDenied));
+    }
+}
@@ -0,0 +1,199 @@
+//! Machine-readable summary of a finished run, f.e. for grading pipelines and web backends
+//! running the emulator as a separate process.
+use crate::emulator::trace::condition_name;
+use crate::emulator::{Emulator, ExitStatus, HaltReason};
+use crate::errors::ExecutionError;
+use crate::hardware::registers::Registers;
+use crate::json;
+
+/// How a run ended including its error, unlike [`RunOutcome`](crate::emulator::RunOutcome)
+/// also for failed runs.
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub struct RunSummary {
+    pub halt_reason: HaltReason,
+    /// Registers after the run
+    pub registers: Registers,
+    /// Number of instructions executed in total
+    pub instructions_executed: u64,
+    /// Everything the program wrote to the console
+    pub output: String,
+    /// The error which ended the run
+    pub error: Option<String>,
+    /// Diagnostics of the run, see [`Emulator::diagnostics`]
+    pub warnings: Vec<String>,
+}
+
+impl RunSummary {
+    /// Summarizes the run of `emu` which ended with `result` after writing `output`.
+    #[must_use]
+    pub fn new(
+        emu: &Emulator,
+        result: &Result<ExitStatus, ExecutionError>,
+        output: String,
+    ) -> Self {
+        let (halt_reason, error) = match result {
+            Ok(status) => (status.halt_reason, None),
+            Err(e) => (HaltReason::from(e), Some(e.to_string())),
+        };
+        Self {
+            halt_reason,
+            registers: emu.state().registers().clone(),
+            instructions_executed: emu.state().instructions_executed(),
+            output,
+            error,
+            warnings: emu.diagnostics().iter().map(ToString::to_string).collect(),
+        }
+    }
+    /// The summary as a single line JSON object, f.e.
+    /// `{"halt_reason":"halt","pc":12295,"registers":[3,0,0,30,0,0,0,0],"cond":"Z",
+    /// "instructions_executed":34,"output":"","error":null,"warnings":[]}`.
+    ///
+    /// `halt_reason` is one of `halt`, `mcr_cleared`, `ran_past_program_end`,
+    /// `input_exhausted`, `breakpoint`, `watchpoint`, `cancelled`, `instruction_limit`,
+    /// `error` and `stopped`.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        json::Object::new()
+            .str("halt_reason", halt_reason_name(self.halt_reason))
+            .num("pc", self.registers.pc().as_binary())
+            .nums(
+                "registers",
+                (0..8).map(|r| self.registers.get(r).as_binary()),
+            )
+            .str("cond", condition_name(&self.registers))
+            .num("instructions_executed", self.instructions_executed)
+            .str("output", &self.output)
+            .opt_str("error", self.error.as_deref())
+            .strs("warnings", self.warnings.iter().map(String::as_str))
+            .finish()
+    }
+    /// One line per difference to the run summarized in `previous`, f.e. `R1: x0005 -> x0006`,
+    /// empty if both runs ended the same way.
+    #[must_use]
+    pub fn changes_since(&self, previous: &Self) -> Vec<String> {
+        let mut changes = Vec::new();
+        let (old, new) = (
+            halt_reason_name(previous.halt_reason),
+            halt_reason_name(self.halt_reason),
+        );
+        if old != new {
+            changes.push(format!("halt reason: {old} -> {new}"));
+        }
+        if previous.error != self.error {
+            let describe =
+                |error: &Option<String>| error.clone().unwrap_or_else(|| "none".to_owned());
+            changes.push(format!(
+                "error: {} -> {}",
+                describe(&previous.error),
+                describe(&self.error)
+            ));
+        }
+        for r in 0..8 {
+            let (old, new) = (previous.registers.get(r), self.registers.get(r));
+            if old != new {
+                changes.push(format!(
+                    "R{r}: x{:04X} -> x{:04X}",
+                    old.as_binary(),
+                    new.as_binary()
+                ));
+            }
+        }
+        let (old, new) = (previous.registers.pc(), self.registers.pc());
+        if old != new {
+            changes.push(format!(
+                "PC: x{:04X} -> x{:04X}",
+                old.as_binary(),
+                new.as_binary()
+            ));
+        }
+        let (old, new) = (
+            condition_name(&previous.registers),
+            condition_name(&self.registers),
+        );
+        if old != new {
+            changes.push(format!("NZP: {old} -> {new}"));
+        }
+        if previous.instructions_executed != self.instructions_executed {
+            changes.push(format!(
+                "instructions executed: {} -> {}",
+                previous.instructions_executed, self.instructions_executed
+            ));
+        }
+        if previous.output != self.output {
+            changes.push(format!(
+                "output: {:?} -> {:?}",
+                previous.output, self.output
+            ));
+        }
+        changes
+    }
+}
+
+pub(crate) const fn halt_reason_name(reason: HaltReason) -> &'static str {
+    match reason {
+        HaltReason::HaltTrap => "halt",
+        HaltReason::McrCleared => "mcr_cleared",
+        HaltReason::RanPastProgramEnd => "ran_past_program_end",
+        HaltReason::InputExhausted => "input_exhausted",
+        HaltReason::Breakpoint(_) => "breakpoint",
+        HaltReason::Watchpoint(_) => "watchpoint",
+        HaltReason::Cancelled => "cancelled",
+        HaltReason::InstructionLimit => "instruction_limit",
+        HaltReason::Error => "error",
+        HaltReason::Stopped(_) => "stopped",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator;
+    use crate::emulator::test_helpers::StringWriter;
+    use googletest::prelude::*;
+
+    #[gtest]
+    pub fn test_run_summary_json() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        let res = emu.execute_with_stdout(&mut StringWriter::new());
+        let summary = RunSummary::new(&emu, &res, "Program halted\n".to_owned());
+        expect_that!(
+            summary.to_json(),
+            eq(
+                r#"{"halt_reason":"halt","pc":12295,"registers":[3,0,0,30,0,0,0,0],"cond":"Z","instructions_executed":34,"output":"Program halted\n","error":null,"warnings":[]}"#
+            )
+        );
+    }
+    #[gtest]
+    pub fn test_run_summary_of_error() {
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        emu.set_max_instructions(3);
+        let res = emu.execute_with_stdout(&mut StringWriter::new());
+        let summary = RunSummary::new(&emu, &res, String::new());
+        expect_that!(summary.halt_reason, eq(HaltReason::InstructionLimit));
+        expect_that!(summary.error, some(contains_substring("Instruction limit")));
+    }
+    #[gtest]
+    pub fn test_changes_since() {
+        let run = |max_instructions| {
+            let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+            emu.set_max_instructions(max_instructions);
+            let res = emu.execute_with_stdout(&mut StringWriter::new());
+            RunSummary::new(&emu, &res, String::new())
+        };
+        let (previous, current) = (run(3), run(100));
+        expect_that!(current.changes_since(&current), is_empty());
+        expect_that!(
+            current.changes_since(&previous),
+            elements_are![
+                eq("halt reason: instruction_limit -> halt"),
+                eq("error: Instruction limit exceeded after 3 instructions at 0x3003 -> none"),
+                eq("R2: x000A -> x0000"),
+                eq("R3: x0000 -> x001E"),
+                eq("PC: x3003 -> x3007"),
+                eq("NZP: P -> Z"),
+                eq("instructions executed: 3 -> 34"),
+            ]
+        );
+    }
+}
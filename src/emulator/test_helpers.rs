@@ -1,11 +1,13 @@
-use crate::emulator;
-use crate::emulator::Emulator;
 use crate::emulator::stdout_helpers::CrosstermCompatibility;
+use crate::emulator::{Emulator, EmulatorBuilder};
 use crate::hardware::keyboard::KeyboardInputProvider;
 use crate::hardware::memory::Memory;
 use crate::hardware::registers::Registers;
+#[cfg(feature = "tracing")]
+use std::fmt::Debug;
 use std::io;
 use std::io::Write;
+use std::sync::{Arc, Mutex};
 
 pub struct StringWriter {
     vec: Vec<u8>,
@@ -33,6 +35,23 @@ impl CrosstermCompatibility for StringWriter {
     }
 }
 
+/// Collects what is written into it while the emulator owns a clone.
+#[derive(Clone, Default)]
+pub struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+impl Write for SharedWriter {
+    fn write(&mut self, data: &[u8]) -> Result<usize, io::Error> {
+        crate::sync::lock(&self.0).write(data)
+    }
+    fn flush(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+}
+impl SharedWriter {
+    pub fn get_string(&self) -> String {
+        String::from_utf8(crate::sync::lock(&self.0).clone()).unwrap()
+    }
+}
+
 pub struct FakeKeyboardInputProvider {
     input_data: String,
     index: usize,
@@ -80,12 +99,10 @@ impl FakeEmulator {
         } else {
             program.extend_from_slice(program_no_header);
         }
-        let keyboard_input_provider = FakeKeyboardInputProvider::new(input);
-        let emu = emulator::from_program_bytes_with_kbd_input_provider(
-            program.as_slice(),
-            keyboard_input_provider,
-        )
-        .unwrap();
+        let emu = EmulatorBuilder::new(&program)
+            .with_input_provider(FakeKeyboardInputProvider::new(input))
+            .build()
+            .unwrap();
         let sw = StringWriter::new();
         Self {
             inner: emu,
@@ -100,3 +117,62 @@ impl FakeEmulator {
         )
     }
 }
+
+/// Minimal executor for async runs, returns the output and how often the future was polled.
+#[cfg(feature = "async")]
+pub fn block_on<F: Future>(future: F) -> (F::Output, u32) {
+    struct ThreadWaker(std::thread::Thread);
+    impl std::task::Wake for ThreadWaker {
+        fn wake(self: std::sync::Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+    let waker = std::task::Waker::from(std::sync::Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = std::task::Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    let mut polls = 0;
+    loop {
+        polls += 1;
+        if let std::task::Poll::Ready(res) = future.as_mut().poll(&mut cx) {
+            return (res, polls);
+        }
+        std::thread::park();
+    }
+}
+
+/// Subscriber recording the level and message of every event, f.e. via
+/// [`tracing::subscriber::with_default`].
+#[cfg(feature = "tracing")]
+#[derive(Clone, Default)]
+pub struct RecordingSubscriber {
+    pub events: std::sync::Arc<std::sync::Mutex<Vec<(tracing::Level, String)>>>,
+}
+#[cfg(feature = "tracing")]
+impl tracing::Subscriber for RecordingSubscriber {
+    fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+        true
+    }
+    fn new_span(&self, _: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        tracing::span::Id::from_u64(1)
+    }
+    fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+    fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+    fn event(&self, event: &tracing::Event<'_>) {
+        struct Message(String);
+        impl tracing::field::Visit for Message {
+            fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn Debug) {
+                if field.name() == "message" {
+                    self.0 = format!("{value:?}");
+                }
+            }
+        }
+        let mut message = Message(String::new());
+        event.record(&mut message);
+        self.events
+            .lock()
+            .unwrap()
+            .push((*event.metadata().level(), message.0));
+    }
+    fn enter(&self, _: &tracing::span::Id) {}
+    fn exit(&self, _: &tracing::span::Id) {}
+}
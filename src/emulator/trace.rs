@@ -0,0 +1,217 @@
+//! Instruction-level execution trace, f.e. to compare a program against a reference trace.
+//!
+//! Attach a sink via [`Emulator::set_trace_sink`](crate::emulator::Emulator::set_trace_sink).
+use crate::emulator::disassembler;
+use crate::emulator::instruction::Instruction;
+use crate::hardware::memory::MemoryAccess;
+use crate::hardware::registers::{ConditionFlag, Registers};
+use crate::json;
+use crate::symbols::SymbolTable;
+use std::io;
+use std::io::Write;
+
+/// What a single executed instruction did.
+#[derive(Debug, Clone)]
+pub struct TraceEvent {
+    /// Address the instruction was fetched from
+    pub pc: u16,
+    /// Label of `pc` from the symbol table if there is one
+    pub label: Option<String>,
+    /// The instruction as stored in memory
+    pub raw: u16,
+    pub decoded: Instruction,
+    /// All registers including PC and condition flag after execution
+    pub registers_after: Registers,
+    /// Memory cells written by the instruction in order
+    pub memory_writes: Vec<MemoryAccess>,
+}
+
+/// Receives a [`TraceEvent`] for every executed instruction.
+pub trait TraceSink {
+    fn record(&mut self, event: &TraceEvent);
+}
+
+/// Writes one JSON object per executed instruction (JSON Lines), f.e.
+/// `{"pc":12288,"raw":8198,"opcode":"LD","operands":"R0, x3007","registers":[3,0,0,0,0,0,0,0],"cond":"P"}`.
+///
+/// Operands never contain labels so traces of programs with and without symbol table are
+/// identical. Writing stops at the first error, which is available via
+/// [`JsonTraceSink::error`].
+pub struct JsonTraceSink<W: Write> {
+    out: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> JsonTraceSink<W> {
+    pub const fn new(out: W) -> Self {
+        Self { out, error: None }
+    }
+    /// The error which stopped writing the trace if there was one.
+    #[must_use]
+    pub const fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+    fn write_event(&mut self, event: &TraceEvent) -> io::Result<()> {
+        let (opcode, operands) =
+            disassembler::disassemble_word(event.pc, event.raw, &SymbolTable::default());
+        let cond = condition_name(&event.registers_after);
+        let obj = json::Object::new()
+            .num("pc", event.pc)
+            .num("raw", event.raw)
+            .str("opcode", &opcode)
+            .str("operands", &operands)
+            .nums(
+                "registers",
+                (0..8).map(|r| event.registers_after.get(r).as_binary()),
+            )
+            .str("cond", cond);
+        writeln!(self.out, "{}", obj.finish())
+    }
+}
+
+impl<W: Write> TraceSink for JsonTraceSink<W> {
+    fn record(&mut self, event: &TraceEvent) {
+        if self.error.is_none()
+            && let Err(e) = self.write_event(event)
+        {
+            self.error = Some(e);
+        }
+    }
+}
+
+/// Writes one human-readable line per executed instruction.
+///
+/// Lines look like `x3000  x2006  START        LD R0, x3007          R0 x0003 ... NZP P`,
+/// followed by the written memory cells like `x3003: x0000 -> x0007`. Writing stops at the first error, which is available via [`TextTraceSink::error`].
+pub struct TextTraceSink<W: Write> {
+    out: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> TextTraceSink<W> {
+    pub const fn new(out: W) -> Self {
+        Self { out, error: None }
+    }
+    /// The error which stopped writing the trace if there was one.
+    #[must_use]
+    pub const fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+    fn write_event(&mut self, event: &TraceEvent) -> io::Result<()> {
+        let (opcode, operands) =
+            disassembler::disassemble_word(event.pc, event.raw, &SymbolTable::default());
+        write!(
+            self.out,
+            "x{:04X}  x{:04X}  {:<12} {:<21}",
+            event.pc,
+            event.raw,
+            event.label.as_deref().unwrap_or(""),
+            format!("{opcode} {operands}").trim_end()
+        )?;
+        for r in 0..8 {
+            write!(
+                self.out,
+                " R{r} x{:04X}",
+                event.registers_after.get(r).as_binary()
+            )?;
+        }
+        write!(self.out, " NZP {}", condition_name(&event.registers_after))?;
+        for write in &event.memory_writes {
+            write!(
+                self.out,
+                "  x{:04X}: x{:04X} -> x{:04X}",
+                write.address, write.old, write.new
+            )?;
+        }
+        writeln!(self.out)
+    }
+}
+
+impl<W: Write> TraceSink for TextTraceSink<W> {
+    fn record(&mut self, event: &TraceEvent) {
+        if self.error.is_none()
+            && let Err(e) = self.write_event(event)
+        {
+            self.error = Some(e);
+        }
+    }
+}
+
+pub(crate) const fn condition_name(registers: &Registers) -> &'static str {
+    match registers.get_conditional_register() {
+        ConditionFlag::Neg => "N",
+        ConditionFlag::Zero => "Z",
+        ConditionFlag::Pos => "P",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::memory::AccessKind;
+    use crate::hardware::registers::from_binary;
+    use googletest::prelude::*;
+
+    #[gtest]
+    pub fn test_json_trace_sink() {
+        let mut registers_after = Registers::new();
+        registers_after.set(0, from_binary(3));
+        registers_after.update_conditional_register(0);
+        registers_after.set_pc(0x3001);
+        let event = TraceEvent {
+            pc: 0x3000,
+            label: Some("START".to_owned()),
+            raw: 0x2006,
+            decoded: Instruction::from(0x2006),
+            registers_after,
+            memory_writes: Vec::new(),
+        };
+        let mut sink = JsonTraceSink::new(Vec::new());
+        sink.record(&event);
+        sink.record(&event);
+        expect_that!(sink.error().is_none(), eq(true));
+        let line = r#"{"pc":12288,"raw":8198,"opcode":"LD","operands":"R0, x3007","registers":[3,0,0,0,0,0,0,0],"cond":"P"}"#;
+        expect_that!(
+            String::from_utf8(sink.into_inner()).unwrap(),
+            eq(&format!("{line}\n{line}\n"))
+        );
+    }
+
+    #[gtest]
+    pub fn test_text_trace_sink() {
+        let mut registers_after = Registers::new();
+        registers_after.set(1, from_binary(7));
+        registers_after.update_conditional_register(1);
+        let event = TraceEvent {
+            pc: 0x3001,
+            label: Some("STORE".to_owned()),
+            raw: 0x3201,
+            decoded: Instruction::from(0x3201),
+            registers_after,
+            memory_writes: vec![MemoryAccess {
+                address: 0x3003,
+                kind: AccessKind::Write,
+                old: 0,
+                new: 7,
+            }],
+        };
+        let mut sink = TextTraceSink::new(Vec::new());
+        sink.record(&event);
+        expect_that!(sink.error().is_none(), eq(true));
+        expect_that!(
+            String::from_utf8(sink.into_inner()).unwrap(),
+            eq(
+                "x3001  x3201  STORE        ST R1, x3003          R0 x0000 R1 x0007 R2 x0000 \
+                R3 x0000 R4 x0000 R5 x0000 R6 x0000 R7 x0000 NZP P  x3003: x0000 -> x0007\n"
+            )
+        );
+    }
+}
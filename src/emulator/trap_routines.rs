@@ -3,7 +3,9 @@
 //!
 //! In the real system the code for these routines is at the target of the
 //! [Trap Vector Tables](https://cs131.info/Assembly/Instructions/TRAPRoutines.html#trap-vector-table).
+use crate::emulator::lc3b;
 use crate::emulator::stdout_helpers::CrosstermCompatibility;
+use crate::emulator::{StepFlow, StepResult};
 use crate::errors::ExecutionError;
 use crate::hardware::memory::{Memory, MemoryMappedIOLocations};
 use crate::hardware::registers::{Registers, from_binary};
@@ -12,15 +14,23 @@ use crate::terminal::EchoOptions;
 use std::io;
 use std::io::Write;
 use std::ops::ControlFlow;
-use std::thread::sleep;
-use std::time::Duration;
+
+/// Whether trap routines reading input wait for it.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum InputMode {
+    /// Wait until input is available
+    Blocking,
+    /// Stop with [`StepResult::WaitingForInput`] if no input is available
+    NonBlocking,
+}
 
 fn read_character_from_console(
     regs: &mut Registers,
     eo: EchoOptions,
     memory: &Memory,
     stdout: &mut (impl Write + CrosstermCompatibility),
-) -> ControlFlow<Result<(), ExecutionError>> {
+    input_mode: InputMode,
+) -> StepFlow {
     loop {
         if memory[MemoryMappedIOLocations::Kbsr as u16] != 0 {
             let c = memory[MemoryMappedIOLocations::Kbdr as u16];
@@ -35,7 +45,14 @@ fn read_character_from_console(
             }
             return ControlFlow::Continue(());
         }
-        sleep(Duration::from_millis(100));
+        // the run loop reports the interruption before resuming
+        if input_mode == InputMode::NonBlocking
+            || memory.is_keyboard_interrupted()
+            || memory.is_cancel_requested()
+        {
+            return ControlFlow::Break(Ok(StepResult::WaitingForInput));
+        }
+        memory.wait_for_input();
     }
 }
 
@@ -46,27 +63,30 @@ pub fn get_c(
     regs: &mut Registers,
     memory: &Memory,
     stdout: &mut (impl Write + CrosstermCompatibility),
-) -> ControlFlow<Result<(), ExecutionError>> {
-    read_character_from_console(regs, EchoOptions::EchoOff, memory, stdout)
+    input_mode: InputMode,
+) -> StepFlow {
+    read_character_from_console(regs, EchoOptions::EchoOff, memory, stdout, input_mode)
 }
 
 /// IN: Print a prompt on the screen and read a single character echoed back from the keyboard.
 ///
-/// Otherwise, like 0x20 GETC.
+/// Otherwise, like 0x20 GETC. The prompt is skipped if `show_prompt` is `false`, f.e. when
+/// resuming a non-blocking wait for input.
 pub fn in_trap(
     regs: &mut Registers,
     memory: &Memory,
     stdout: &mut (impl Write + CrosstermCompatibility),
-) -> ControlFlow<Result<(), ExecutionError>> {
-    write_str_out("Input: ", stdout)?;
-    read_character_from_console(regs, EchoOptions::EchoOn, memory, stdout)
+    input_mode: InputMode,
+    show_prompt: bool,
+) -> StepFlow {
+    if show_prompt {
+        write_str_out("Input: ", stdout)?;
+    }
+    read_character_from_console(regs, EchoOptions::EchoOn, memory, stdout, input_mode)
 }
 
 /// OUT: Write a character in R0\[7:0\] to the console display.
-pub fn out(
-    regs: &Registers,
-    stdout: &mut (impl Write + CrosstermCompatibility),
-) -> ControlFlow<Result<(), ExecutionError>> {
+pub fn out(regs: &Registers, stdout: &mut (impl Write + CrosstermCompatibility)) -> StepFlow {
     let c: char = (regs.get(0).as_binary() & 0xFF) as u8 as char;
     write_str_out(&String::from(c), stdout)
 }
@@ -98,13 +118,16 @@ fn put(
     mem: &Memory,
     stdout: &mut (impl Write + CrosstermCompatibility),
     handle_char: fn(u16, &mut String),
-) -> ControlFlow<Result<(), ExecutionError>> {
-    let address = regs.get(0).as_binary();
-    let mut end = address;
+) -> StepFlow {
+    let mut address = regs.get(0).as_binary();
     let mut s = String::with_capacity(120);
-    while mem[end] != 0 {
-        handle_char(mem[end], &mut s);
-        end += 1;
+    while mem[address] != 0 {
+        handle_char(mem[address], &mut s);
+        address = address.wrapping_add(1);
+        // a string running up to xFFFF ends there instead of continuing at x0000
+        if address == 0 {
+            break;
+        }
     }
     write_str_out(s.as_str(), stdout)
 }
@@ -114,10 +137,31 @@ pub fn put_s(
     regs: &Registers,
     mem: &Memory,
     stdout: &mut (impl Write + CrosstermCompatibility),
-) -> ControlFlow<Result<(), ExecutionError>> {
+) -> StepFlow {
     put(regs, mem, stdout, put_one_char_per_u16)
 }
 
+/// PUTS of the LC-3b: print the null-delimited bytes starting at register 0's byte address
+pub fn put_s_bytes(
+    regs: &Registers,
+    mem: &Memory,
+    stdout: &mut (impl Write + CrosstermCompatibility),
+) -> StepFlow {
+    let mut address = regs.get(0).as_binary();
+    let mut s = String::with_capacity(120);
+    loop {
+        match lc3b::read_byte(mem, address) {
+            0 => break,
+            c => s.push(char::from(c)),
+        }
+        address = address.wrapping_add(1);
+        if address == 0 {
+            break;
+        }
+    }
+    write_str_out(s.as_str(), stdout)
+}
+
 /// PUTSP: Packed version of PUTS
 ///
 /// The ASCII code contained in bits \[7:0\] of a memory location is written to the console first.
@@ -127,29 +171,23 @@ pub fn put_sp(
     regs: &Registers,
     mem: &Memory,
     stdout: &mut (impl Write + CrosstermCompatibility),
-) -> ControlFlow<Result<(), ExecutionError>> {
+) -> StepFlow {
     put(regs, mem, stdout, put_two_chars_per_u16)
 }
 
-/// HALT: End program and stdout a message
-pub fn halt(
-    stdout: &mut (impl Write + CrosstermCompatibility),
-) -> ControlFlow<Result<(), ExecutionError>> {
-    write_str_out("\nProgram halted\n", stdout)?;
-    ControlFlow::Break(Ok(()))
+/// HALT: Print a message, the caller stops the clock in the machine control register
+pub fn halt(stdout: &mut (impl Write + CrosstermCompatibility)) -> StepFlow {
+    write_str_out("\nProgram halted\n", stdout)
 }
 
-fn write_str_out(
-    message: &str,
-    stdout: &mut (impl Write + CrosstermCompatibility),
-) -> ControlFlow<Result<(), ExecutionError>> {
+fn write_str_out(message: &str, stdout: &mut (impl Write + CrosstermCompatibility)) -> StepFlow {
     match terminal::print(stdout, message) {
         Ok(()) => ControlFlow::Continue(()),
         Err(e) => wrap_io_error_in_cf(&e),
     }
 }
 
-fn wrap_io_error_in_cf(error: &io::Error) -> ControlFlow<Result<(), ExecutionError>, ()> {
+fn wrap_io_error_in_cf(error: &io::Error) -> StepFlow {
     ControlFlow::Break(Err(ExecutionError::IOInputOutputError(error.to_string())))
 }
 
@@ -172,11 +210,32 @@ mod tests {
     pub fn test_get_c() {
         let mut emu = FakeEmulator::new(&[0u16; 0], "a");
         let (regs, mem, writer) = emu.get_parts();
-        let res = get_c(regs, mem, writer);
+        let res = get_c(regs, mem, writer, InputMode::Blocking);
         check_register_value(regs, 0, u16::from(b'a'));
         assert_that!(res, eq(&ControlFlow::Continue(())));
     }
     #[gtest]
+    pub fn test_get_c_non_blocking() {
+        let mut emu = FakeEmulator::new(&[0u16; 0], "");
+        let (regs, mem, writer) = emu.get_parts();
+        let res = get_c(regs, mem, writer, InputMode::NonBlocking);
+        assert_that!(
+            res,
+            eq(&ControlFlow::Break(Ok(StepResult::WaitingForInput)))
+        );
+    }
+    #[gtest]
+    pub fn test_put_s_ends_at_end_of_memory() {
+        let mut emu = FakeEmulator::new(&[], "");
+        let (regs, mem, writer) = emu.get_parts();
+        mem[0xFFFF] = u16::from(b'A');
+        mem[0x0000] = u16::from(b'B');
+        regs.set(0, from_binary(0xFFFF));
+        let res = put_s(regs, mem, writer);
+        assert!(res.is_continue());
+        assert_that!(writer.get_string(), eq("A"));
+    }
+    #[gtest]
     pub fn test_put_sp() {
         let data = [
             0xFFFF, 0xFFFF, 0xFFFF, 0xFFFF, 0xFFFF, 0x6548u16, 0x6c6c, 0x206f, 0x6f57, 0x6c72,
@@ -194,15 +253,15 @@ mod tests {
         let mut emu = FakeEmulator::new(&[], "abc");
         let (regs, mem, writer) = emu.get_parts();
 
-        let res = in_trap(regs, mem, writer);
+        let res = in_trap(regs, mem, writer, InputMode::Blocking, true);
         assert!(res.is_continue());
         check_register_value(regs, 0, u16::from(b'a'));
 
-        let res = in_trap(regs, mem, writer);
+        let res = in_trap(regs, mem, writer, InputMode::Blocking, true);
         assert!(res.is_continue());
         check_register_value(regs, 0, u16::from(b'b'));
 
-        let res = in_trap(regs, mem, writer);
+        let res = in_trap(regs, mem, writer, InputMode::Blocking, true);
         assert!(res.is_continue());
         check_register_value(regs, 0, u16::from(b'c'));
 
@@ -0,0 +1,89 @@
+//! Static checks of a loaded program image for likely assembler mistakes, see
+//! [`Emulator::validate`](crate::emulator::Emulator::validate).
+//!
+//! Data words are indistinguishable from instructions, so data which is not marked as
+//! non-executable may cause false positives. `BR` without condition codes is skipped since
+//! it mostly is data like `.FILL` values or characters.
+use crate::emulator::Operation;
+use crate::emulator::instruction::Instruction;
+use crate::hardware::memory::Memory;
+use displaydoc::Display;
+use std::ops::Range;
+
+/// Trap vectors of the routines built into the emulator.
+const KNOWN_TRAP_VECTORS: Range<u8> = 0x20..0x26;
+
+/// A finding about the loaded program image which probably fails at runtime.
+#[rustfmt::skip]
+#[derive(Display, Debug, Clone, PartialEq, Eq)]
+pub enum LoadWarning {
+    /// Reserved opcode in instruction {raw:#06X} at {address:#06X}
+    ReservedOpcode { address: u16, raw: u16 },
+    /// Unknown trap vector {vector:#04X} at {address:#06X}
+    UnknownTrapVector { address: u16, vector: u8 },
+    /// Branch at {address:#06X} to {target:#06X} outside of the program
+    BranchOutsideImage { address: u16, target: u16 },
+    /// Return at {address:#06X} before any subroutine call
+    ReturnWithoutCall { address: u16 },
+}
+
+/// Checks the executable words of the program section.
+pub(crate) fn validate(memory: &Memory) -> Vec<LoadWarning> {
+    let image = memory.program_slice();
+    let in_image = memory.program_start()..memory.program_end();
+    let mut seen_call = false;
+    let mut res = Vec::new();
+    for (address, &raw) in in_image.clone().zip(image) {
+        if !memory.is_executable(address) {
+            continue;
+        }
+        let i = Instruction::from(raw);
+        let target = |len| {
+            address
+                .wrapping_add(1)
+                .wrapping_add_signed(i.pc_offset(len))
+        };
+        let branch_target = match i.operation() {
+            Operation::_Reserved => {
+                res.push(LoadWarning::ReservedOpcode { address, raw });
+                None
+            }
+            Operation::Trap => {
+                let vector = i.get_bit_range_u8(0, 7, "Error parsing trap vector");
+                if !KNOWN_TRAP_VECTORS.contains(&vector) {
+                    res.push(LoadWarning::UnknownTrapVector { address, vector });
+                }
+                None
+            }
+            Operation::Br if i.dr_number() != 0 => Some(target(9)),
+            Operation::Jsr => {
+                seen_call = true;
+                i.get_bit(11).then(|| target(11))
+            }
+            Operation::JmpOrRet if i.sr1_number() == 7 && !seen_call => {
+                res.push(LoadWarning::ReturnWithoutCall { address });
+                None
+            }
+            Operation::Br
+            | Operation::Add
+            | Operation::Ld
+            | Operation::St
+            | Operation::And
+            | Operation::Ldr
+            | Operation::Str
+            | Operation::Rti
+            | Operation::Not
+            | Operation::Ldi
+            | Operation::Sti
+            | Operation::JmpOrRet
+            | Operation::Lea => None,
+        };
+        if let Some(target) = branch_target
+            && !in_image.contains(&target)
+            && !memory.in_code_region(target)
+        {
+            res.push(LoadWarning::BranchOutsideImage { address, target });
+        }
+    }
+    res
+}
@@ -0,0 +1,166 @@
+//! Value Change Dump (VCD) of R0–R7, PC and NZP over instruction time, viewable in waveform
+//! viewers like `GTKWave`.
+//!
+//! Attach a [`VcdTraceSink`] via
+//! [`Emulator::set_trace_sink`](crate::emulator::Emulator::set_trace_sink).
+use crate::emulator::trace::{TraceEvent, TraceSink};
+use std::io;
+use std::io::Write;
+
+/// Name, width in bits and identifier code of every dumped signal, in order R0–R7, PC, NZP.
+const SIGNALS: [(&str, u8, char); 10] = [
+    ("R0", 16, '!'),
+    ("R1", 16, '"'),
+    ("R2", 16, '#'),
+    ("R3", 16, '$'),
+    ("R4", 16, '%'),
+    ("R5", 16, '&'),
+    ("R6", 16, '\''),
+    ("R7", 16, '('),
+    ("PC", 16, ')'),
+    ("NZP", 3, '*'),
+];
+const PC: usize = 8;
+const NZP: usize = 9;
+
+/// Writes a VCD with one time unit per executed instruction.
+///
+/// Time `0` is the state before the first traced instruction, where only the PC is known and
+/// all other signals are undefined (`x`). Time `n` is the state after the `n`-th traced
+/// instruction. Writing stops at the first error, which is available via
+/// [`VcdTraceSink::error`].
+pub struct VcdTraceSink<W: Write> {
+    out: W,
+    error: Option<io::Error>,
+    /// Number of instructions recorded
+    time: u64,
+    /// Last dumped value per signal, `None` before the header is written
+    values: Option<[u16; 10]>,
+}
+
+impl<W: Write> VcdTraceSink<W> {
+    pub const fn new(out: W) -> Self {
+        Self {
+            out,
+            error: None,
+            time: 0,
+            values: None,
+        }
+    }
+    /// The error which stopped writing the dump if there was one.
+    #[must_use]
+    pub const fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+    fn write_header(&mut self, pc: u16) -> io::Result<()> {
+        writeln!(
+            self.out,
+            "$version lc3-emulator {} $end",
+            env!("CARGO_PKG_VERSION")
+        )?;
+        writeln!(self.out, "$timescale 1 us $end")?;
+        writeln!(self.out, "$scope module lc3 $end")?;
+        for (name, width, id) in SIGNALS {
+            writeln!(self.out, "$var wire {width} {id} {name} $end")?;
+        }
+        writeln!(self.out, "$upscope $end")?;
+        writeln!(self.out, "$enddefinitions $end")?;
+        writeln!(self.out, "#0")?;
+        writeln!(self.out, "$dumpvars")?;
+        for (idx, (_, _, id)) in SIGNALS.iter().enumerate() {
+            if idx == PC {
+                writeln!(self.out, "b{pc:b} {id}")?;
+            } else {
+                writeln!(self.out, "bx {id}")?;
+            }
+        }
+        writeln!(self.out, "$end")
+    }
+    fn write_event(&mut self, event: &TraceEvent) -> io::Result<()> {
+        let previous = self.values;
+        if previous.is_none() {
+            self.write_header(event.pc)?;
+        }
+        let registers = &event.registers_after;
+        let mut current = [0; 10];
+        for (r, value) in (0..8).zip(current.iter_mut()) {
+            *value = registers.get(r).as_binary();
+        }
+        current[PC] = registers.pc().as_binary();
+        current[NZP] = registers.get_conditional_register() as u16;
+        self.time += 1;
+        let mut time_written = false;
+        for (idx, (_, _, id)) in SIGNALS.iter().enumerate() {
+            if previous.is_some_and(|values| values[idx] == current[idx]) {
+                continue;
+            }
+            if !time_written {
+                writeln!(self.out, "#{}", self.time)?;
+                time_written = true;
+            }
+            writeln!(self.out, "b{:b} {id}", current[idx])?;
+        }
+        self.values = Some(current);
+        Ok(())
+    }
+}
+
+impl<W: Write> TraceSink for VcdTraceSink<W> {
+    fn record(&mut self, event: &TraceEvent) {
+        if self.error.is_none()
+            && let Err(e) = self.write_event(event)
+        {
+            self.error = Some(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::instruction::Instruction;
+    use crate::hardware::registers::{Registers, from_binary};
+    use googletest::prelude::*;
+
+    fn event(pc: u16, r0: u16) -> TraceEvent {
+        let mut registers_after = Registers::new();
+        registers_after.set(0, from_binary(r0));
+        registers_after.update_conditional_register(0);
+        registers_after.set_pc(pc + 1);
+        TraceEvent {
+            pc,
+            label: None,
+            raw: 0x2006,
+            decoded: Instruction::from(0x2006),
+            registers_after,
+            memory_writes: Vec::new(),
+        }
+    }
+
+    #[gtest]
+    pub fn test_vcd_trace_sink() {
+        let mut sink = VcdTraceSink::new(Vec::new());
+        sink.record(&event(0x3000, 3));
+        sink.record(&event(0x3001, 3));
+        expect_that!(sink.error().is_none(), eq(true));
+        let vcd = String::from_utf8(sink.into_inner()).unwrap();
+        expect_that!(
+            vcd,
+            contains_substring("$var wire 16 ) PC $end\n$var wire 3 * NZP $end\n")
+        );
+        expect_that!(
+            vcd,
+            ends_with(
+                "$enddefinitions $end\n#0\n$dumpvars\nbx !\nbx \"\nbx #\nbx $\nbx %\nbx &\nbx '\n\
+                 bx (\nb11000000000000 )\nbx *\n$end\n\
+                 #1\nb11 !\nb0 \"\nb0 #\nb0 $\nb0 %\nb0 &\nb0 '\nb0 (\n\
+                 b11000000000001 )\nb1 *\n\
+                 #2\nb11000000000010 )\n"
+            )
+        );
+    }
+}
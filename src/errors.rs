@@ -32,6 +32,18 @@ pub enum LoadProgramError {
         file: String,
         message: String
     },
+    /// Cannot read program: {0}
+    ProgramNotReadable(String),
+    /// Invalid Intel HEX in line {line}: {message}
+    InvalidIntelHex { line: usize, message: String },
+    /// Invalid lc3tools object file at byte {offset}: {message}
+    InvalidLc3ToolsObject { offset: usize, message: String },
+    /// Operating system image at {origin:#06X} with {length} words does not fit below the program section
+    OsImageOutOfRange { origin: u16, length: usize },
+    /// Memory region at {origin:#06X} with {length} words does not fit into the program section
+    RegionOutOfRange { origin: u16, length: usize },
+    /// Memory region at {0:#06X} overlaps an already loaded region
+    RegionOverlaps(u16),
 }
 impl Debug for LoadProgramError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -52,6 +64,34 @@ pub enum ExecutionError {
     IOInputOutputError(String),
     /// Unknown trap routine found: {0:#06X}
     UnknownTrapRoutine(u16),
+    /// Instruction fetched from non-executable address {0:#06X}
+    NonExecutableMemory(u16),
+    /// Instruction at {pc:#06X} jumped to {target:#06X} outside of the address space programs are executed in
+    JumpOutOfRange { pc: u16, target: u16 },
+    /// Ran past the program end to {pc:#06X} without halting, last instruction {raw:#06X} at {last:#06X}
+    RanPastProgramEnd { pc: u16, last: u16, raw: u16 },
+    /// Instruction limit exceeded after {executed} instructions at {pc:#06X}
+    InstructionLimitExceeded { executed: u64, pc: u16 },
+    /// RTI executed in User mode at {0:#06X}
+    PrivilegeViolation(u16),
+    /// Instruction at {0:#06X} was written by the program itself before executing it
+    SelfModifyingCode(u16),
+    /// Instruction at {pc:#06X} read R{register} before a value was written to it
+    UninitializedRegisterRead { pc: u16, register: u8 },
+    /// Instruction at {pc:#06X} read {address:#06X} before a value was written or loaded there
+    UninitializedMemoryRead { pc: u16, address: u16 },
+    /// Stack overflow at {pc:#06X}, R6 is {sp:#06X} below the stack limit
+    StackOverflow { pc: u16, sp: u16 },
+    /// Stack underflow at {pc:#06X}, R6 is {sp:#06X} above the stack base
+    StackUnderflow { pc: u16, sp: u16 },
+    /// Access to {address:#06X} outside the user space in User mode at {pc:#06X}
+    MemoryAccessViolation { pc: u16, address: u16 },
+    /// Trap routine {0:#04X} cannot be dispatched through the trap vector table without an operating system image
+    TrapTableNotLoaded(u16),
+    /// Execution was interrupted by CTRL-C or a cancel token at {0:#06X}
+    Interrupted(u16),
+    /// Run with chaos input timing and seed {seed} failed: {error}
+    ChaosRunFailed { seed: u64, error: Box<Self> },
 }
 impl Debug for ExecutionError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -59,3 +99,191 @@ impl Debug for ExecutionError {
     }
 }
 impl Error for ExecutionError {}
+
+/// Possible errors assembling LC-3 assembly source.
+///
+/// `Display` and `Debug` provide all necessary details.
+#[rustfmt::skip]
+#[derive(Display, PartialEq, Eq)]
+pub enum AssembleError {
+    /// Line does not contain an instruction
+    EmptyLine,
+    /// Unknown instruction or directive '{0}'
+    UnknownMnemonic(String),
+    /// {mnemonic} expects {expected} operands but got {actual}
+    WrongOperandCount { mnemonic: String, expected: usize, actual: usize },
+    /// Invalid operand '{0}'
+    InvalidOperand(String),
+    /// Invalid number '{0}'
+    InvalidNumber(String),
+    /// Register expected instead of {0}
+    RegisterExpected(String),
+    /// Number or label expected instead of {0}
+    NumberExpected(String),
+    /// Label '{0}' is not defined
+    UnknownLabel(String),
+    /// Value {value} does not fit into {bits} bits
+    ValueOutOfRange { value: i32, bits: u8 },
+    /// Label '{0}' is defined more than once
+    DuplicateLabel(String),
+}
+impl Debug for AssembleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+impl Error for AssembleError {}
+
+/// Possible errors linking assembly modules.
+///
+/// `Display` and `Debug` provide all necessary details.
+#[rustfmt::skip]
+#[derive(Display, PartialEq, Eq)]
+pub enum LinkError {
+    /// Module {module} line {line}: {error}
+    Assemble { module: usize, line: usize, error: AssembleError },
+    /// Module {module} line {line}: relocatable modules must not contain .ORIG
+    OrigInModule { module: usize, line: usize },
+    /// Module {module} line {line}: global label '{name}' is not defined in the module
+    UndefinedGlobal { module: usize, line: usize, name: String },
+    /// Module {module}: external label '{name}' is not a global label of any module
+    UndefinedExternal { module: usize, name: String },
+    /// Global label '{0}' is defined by more than one module
+    DuplicateGlobal(String),
+    /// Linked image does not fit into memory
+    ImageTooLarge,
+}
+impl Debug for LinkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+impl Error for LinkError {}
+
+/// Possible errors assembling a program, see [`assemble`](crate::assembler::assemble).
+///
+/// `Display` and `Debug` provide all necessary details.
+#[rustfmt::skip]
+#[derive(Display, PartialEq, Eq)]
+pub enum AssembleProgramError {
+    /// {line}:{column}: {error}
+    Assemble { line: usize, column: usize, error: AssembleError },
+    /// {line}:1: .ORIG is only allowed before the first label or instruction
+    MisplacedOrig { line: usize },
+    /// Program does not start with .ORIG
+    MissingOrig,
+    /// Program does not fit into memory
+    ImageTooLarge,
+}
+impl Debug for AssembleProgramError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+impl Error for AssembleProgramError {}
+
+/// Possible errors loading a symbol table.
+///
+/// `Display` and `Debug` provide all necessary details.
+#[rustfmt::skip]
+#[derive(Display, Clone, PartialEq, Eq)]
+pub enum SymbolTableError {
+    /// Cannot read symbol table from file '{file}': {message}
+    NotLoadable { file: String, message: String },
+    /// Invalid symbol table line {line}: '{content}'
+    InvalidLine { line: usize, content: String },
+}
+impl Debug for SymbolTableError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+impl Error for SymbolTableError {}
+
+/// Possible errors reading a recording of keyboard input, see
+/// [`Emulator::replay`](crate::emulator::Emulator::replay).
+///
+/// `Display` and `Debug` provide all necessary details.
+#[rustfmt::skip]
+#[derive(Display, PartialEq, Eq)]
+pub enum ReplayError {
+    /// Cannot read input recording from file '{file}': {message}
+    NotLoadable { file: String, message: String },
+    /// Invalid input recording line {line}: '{content}'
+    InvalidLine { line: usize, content: String },
+}
+impl Debug for ReplayError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+impl Error for ReplayError {}
+
+/// Possible errors editing memory.
+///
+/// `Display` and `Debug` provide all necessary details.
+#[rustfmt::skip]
+#[derive(Display, PartialEq, Eq)]
+pub enum MemoryEditError {
+    /// Address {0:#06X} cannot be edited
+    AddressNotEditable(u16),
+    /// {0}
+    InvalidInput(AssembleError),
+}
+impl From<AssembleError> for MemoryEditError {
+    fn from(value: AssembleError) -> Self {
+        Self::InvalidInput(value)
+    }
+}
+impl Debug for MemoryEditError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+impl Error for MemoryEditError {}
+
+/// Possible errors accessing the machine state via the public [`Emulator`](crate::emulator::Emulator)
+/// methods.
+///
+/// `Display` and `Debug` provide all necessary details.
+#[rustfmt::skip]
+#[derive(Display, PartialEq, Eq)]
+pub enum StateAccessError {
+    /// Address {0:#06X} is a device register, access it via the memory instead
+    DeviceRegister(u16),
+    /// Invalid register R{0}, must be R0 to R7
+    InvalidRegister(u8),
+    /// PC {0:#06X} is outside of the address space programs are executed in
+    PcOutOfRange(u16),
+    /// Framebuffer at {origin:#06X} with {columns}x{rows} cells does not fit into memory
+    FramebufferOutOfRange { origin: u16, columns: u16, rows: u16 },
+}
+impl Debug for StateAccessError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+impl Error for StateAccessError {}
+
+/// Possible errors reading a test script, see
+/// [`TestScript`](crate::test_script::TestScript).
+///
+/// `Display` and `Debug` provide all necessary details.
+#[rustfmt::skip]
+#[derive(Display, PartialEq, Eq)]
+pub enum TestScriptError {
+    /// Cannot read test script from file '{file}': {message}
+    NotLoadable { file: String, message: String },
+    /// Invalid TOML: {0}
+    InvalidToml(String),
+    /// Unknown key '{0}'
+    UnknownKey(String),
+    /// Invalid value of '{key}': {message}
+    InvalidValue { key: String, message: String },
+}
+impl Debug for TestScriptError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+impl Error for TestScriptError {}
@@ -0,0 +1,228 @@
+//! Comparison of program output against expected output stored in golden files.
+//!
+//! On mismatch the first divergence is reported with line, column and some lines of context,
+//! which keeps failures readable even for long outputs.
+//! Golden files can be (re-)generated from the actual output by [`GoldenMode::Update`], which
+//! [`GoldenMode::from_env`] selects if the environment variable `LC3_UPDATE_GOLDEN` is `1` or
+//! `true`.
+//!
+//! # Example
+//! ```no_run
+//! use lc3_emulator::golden::{self, GoldenMode};
+//!
+//! let output = "Hello World!\n";
+//! golden::check(output, "tests/golden/hello.txt", GoldenMode::from_env())
+//!     .unwrap_or_else(|e| panic!("{e}"));
+//! ```
+use displaydoc::Display;
+use std::error::Error;
+use std::fmt::{Debug, Formatter};
+use std::path::Path;
+use std::{env, fs};
+
+/// Lines shown before and after the first divergence.
+const CONTEXT_LINES: usize = 2;
+
+/// Whether golden files are compared against or overwritten.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GoldenMode {
+    Compare,
+    Update,
+}
+impl GoldenMode {
+    /// [`GoldenMode::Update`] if the environment variable `LC3_UPDATE_GOLDEN` is `1` or `true`,
+    /// [`GoldenMode::Compare`] otherwise.
+    #[must_use]
+    pub fn from_env() -> Self {
+        Self::from_update_golden(env::var("LC3_UPDATE_GOLDEN").ok().as_deref())
+    }
+    fn from_update_golden(value: Option<&str>) -> Self {
+        match value {
+            Some("1" | "true") => Self::Update,
+            _ => Self::Compare,
+        }
+    }
+}
+
+/// Location and surroundings of the first difference between expected and actual output.
+#[derive(PartialEq, Eq)]
+pub struct Divergence {
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column number in characters
+    pub column: usize,
+    /// Expected lines around the divergence, starting at line `first_context_line`
+    pub expected_context: Vec<String>,
+    /// Actual lines around the divergence, starting at line `first_context_line`
+    pub actual_context: Vec<String>,
+    /// 1-based line number of the first context line
+    pub first_context_line: usize,
+}
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Output differs at line {}, column {}",
+            self.line, self.column
+        )?;
+        for (label, lines) in [
+            ("expected", &self.expected_context),
+            ("actual", &self.actual_context),
+        ] {
+            writeln!(f, "{label}:")?;
+            for (idx, line) in lines.iter().enumerate() {
+                let line_no = self.first_context_line + idx;
+                let marker = if line_no == self.line { '>' } else { ' ' };
+                writeln!(f, "{marker}{line_no:5} | {line:?}")?;
+            }
+        }
+        Ok(())
+    }
+}
+impl Debug for Divergence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
+}
+
+/// Possible errors checking against golden files.
+#[rustfmt::skip]
+#[derive(Display, PartialEq, Eq)]
+pub enum GoldenError {
+    /// {0}
+    Mismatch(Divergence),
+    /// Cannot access golden file '{file}': {message}
+    GoldenFileNotAccessible { file: String, message: String },
+}
+impl Debug for GoldenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
+}
+impl Error for GoldenError {}
+
+/// Finds the first position where `actual` differs from `expected`, `None` if they are equal.
+#[must_use]
+pub fn first_divergence(expected: &str, actual: &str) -> Option<Divergence> {
+    if expected == actual {
+        return None;
+    }
+    let expected_lines: Vec<&str> = expected.split('\n').collect();
+    let actual_lines: Vec<&str> = actual.split('\n').collect();
+    let line_idx = expected_lines
+        .iter()
+        .zip(&actual_lines)
+        .position(|(e, a)| e != a)
+        .unwrap_or_else(|| expected_lines.len().min(actual_lines.len()));
+    let expected_line = expected_lines.get(line_idx).copied().unwrap_or_default();
+    let actual_line = actual_lines.get(line_idx).copied().unwrap_or_default();
+    let column_idx = expected_line
+        .chars()
+        .zip(actual_line.chars())
+        .position(|(e, a)| e != a)
+        .unwrap_or_else(|| {
+            expected_line
+                .chars()
+                .count()
+                .min(actual_line.chars().count())
+        });
+    let first = line_idx.saturating_sub(CONTEXT_LINES);
+    let context = |lines: &[&str]| {
+        lines
+            .iter()
+            .skip(first)
+            .take(line_idx - first + CONTEXT_LINES + 1)
+            .map(|l| (*l).to_owned())
+            .collect()
+    };
+    Some(Divergence {
+        line: line_idx + 1,
+        column: column_idx + 1,
+        expected_context: context(&expected_lines),
+        actual_context: context(&actual_lines),
+        first_context_line: first + 1,
+    })
+}
+
+/// Compares `actual` against the contents of the golden file at `path`
+/// or overwrites the golden file with `actual` in [`GoldenMode::Update`].
+///
+/// # Errors
+/// - See [`GoldenError`]
+pub fn check(actual: &str, path: impl AsRef<Path>, mode: GoldenMode) -> Result<(), GoldenError> {
+    let path = path.as_ref();
+    let not_accessible = |e: std::io::Error| GoldenError::GoldenFileNotAccessible {
+        file: path.display().to_string(),
+        message: e.to_string(),
+    };
+    match mode {
+        GoldenMode::Update => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(not_accessible)?;
+            }
+            fs::write(path, actual).map_err(not_accessible)
+        }
+        GoldenMode::Compare => {
+            let expected = fs::read_to_string(path).map_err(not_accessible)?;
+            first_divergence(&expected, actual).map_or(Ok(()), |d| Err(GoldenError::Mismatch(d)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+
+    #[gtest]
+    fn test_equal_output_has_no_divergence() {
+        expect_that!(first_divergence("a\nb\n", "a\nb\n"), none());
+    }
+    #[gtest]
+    fn test_divergence_line_column_and_context() {
+        let expected = "one\ntwo\nthree\nfour\nfive\nsix\n";
+        let actual = "one\ntwo\nthree\nfoul\nfive\nsix\n";
+        let d = first_divergence(expected, actual).unwrap();
+        expect_that!(d.line, eq(4));
+        expect_that!(d.column, eq(4));
+        expect_that!(d.first_context_line, eq(2));
+        expect_that!(
+            d.expected_context,
+            elements_are![eq("two"), eq("three"), eq("four"), eq("five"), eq("six")]
+        );
+        expect_that!(d.actual_context[2], eq("foul"));
+        expect_that!(d.to_string(), contains_substring(">    4 | \"foul\""));
+    }
+    #[gtest]
+    fn test_divergence_on_truncated_output() {
+        let d = first_divergence("Hello\nWorld", "Hello\nWo").unwrap();
+        expect_that!((d.line, d.column), eq((2, 3)));
+        let d = first_divergence("Hello", "Hello\n").unwrap();
+        expect_that!((d.line, d.column), eq((2, 1)));
+    }
+    #[gtest]
+    fn test_check_update_then_compare() {
+        let path = env::temp_dir().join(format!("lc3-golden-{}.txt", std::process::id()));
+        check("Program halted\n", &path, GoldenMode::Update).unwrap();
+        expect_that!(
+            check("Program halted\n", &path, GoldenMode::Compare),
+            ok(eq(&()))
+        );
+        let res = check("Program failed\n", &path, GoldenMode::Compare);
+        expect_that!(res, err(matches_pattern!(GoldenError::Mismatch(_))));
+        fs::remove_file(path).unwrap();
+    }
+    #[gtest]
+    fn test_update_golden_values() {
+        for (value, mode) in [
+            (None, GoldenMode::Compare),
+            (Some("1"), GoldenMode::Update),
+            (Some("true"), GoldenMode::Update),
+            (Some("0"), GoldenMode::Compare),
+            (Some("false"), GoldenMode::Compare),
+            (Some(""), GoldenMode::Compare),
+        ] {
+            expect_that!(GoldenMode::from_update_golden(value), eq(mode), "{value:?}");
+        }
+    }
+}
@@ -1,23 +1,66 @@
+use crate::random::SplitMix64;
+use crate::sync::lock;
+#[cfg(not(target_arch = "wasm32"))]
 use crossterm::event::{KeyModifiers, poll, read};
+use std::collections::VecDeque;
 use std::io;
+use std::sync::{Arc, Mutex};
+use std::task::Waker;
+use std::thread;
 use std::time::Duration;
 
+/// Pause of [`KeyboardInputProvider::wait_for_input`] by default.
+pub const INPUT_WAIT_INTERVAL: Duration = Duration::from_millis(10);
+
 /// Providing Keyboard Input independent of an implementation.
 pub trait KeyboardInputProvider {
     /// Checks if input is available, does not block.
+    ///
+    /// # Errors
+    /// - reading from the underlying input device failed
     fn check_input_available(&mut self) -> io::Result<bool>;
     /// Provides input if `check_input_available` returned `true`, panics otherwise.
     fn get_input_character(&mut self) -> char;
     /// True if CTRL-C was triggered
     fn is_interrupted(&self) -> bool;
+    /// Called by the emulator once it stopped because of CTRL-C, so that execution can be
+    /// resumed.
+    fn reset_interrupt(&mut self) {}
+    /// Called by the emulator after every executed instruction.
+    fn on_instruction_executed(&mut self) {}
+    /// Called by reads blocking until input is available each time none was, so that waiting
+    /// does not keep a CPU busy. Sleeps for [`INPUT_WAIT_INTERVAL`] by default.
+    fn wait_for_input(&mut self) {
+        thread::sleep(INPUT_WAIT_INTERVAL);
+    }
+    /// Called by [`Emulator::execute_async`](crate::emulator::Emulator::execute_async) awaiting
+    /// input, `waker` has to be woken once input may have become available or CTRL-C was
+    /// triggered.
+    ///
+    /// Wakes it right away by default, so that the provider is checked again on the next poll.
+    /// Providers receiving input from elsewhere, f.e. another thread, should wake it once it
+    /// arrives instead.
+    fn register_waker(&mut self, waker: &Waker) {
+        waker.wake_by_ref();
+    }
 }
 
+/// A keyboard input provider shared by the emulator and its memory, which can be moved to
+/// another thread together with the emulator.
+pub type SharedInputProvider = Arc<Mutex<dyn KeyboardInputProvider + Send>>;
+
 pub struct TerminalInputProvider {
     is_char_available: bool,
     available_char: Option<char>,
     is_interrupted: bool,
 }
+impl Default for TerminalInputProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl TerminalInputProvider {
+    #[must_use]
     pub const fn new() -> Self {
         Self {
             is_char_available: false,
@@ -27,7 +70,12 @@ impl TerminalInputProvider {
     }
 }
 impl KeyboardInputProvider for TerminalInputProvider {
+    #[cfg(not(target_arch = "wasm32"))]
     fn check_input_available(&mut self) -> io::Result<bool> {
+        // a key read by an earlier check stays available until the program reads it
+        if self.is_char_available {
+            return Ok(true);
+        }
         if poll(Duration::from_millis(100))?
             && let Some(event) = read()?.as_key_press_event()
             && let Some(c) = event.code.as_char()
@@ -42,11 +90,247 @@ impl KeyboardInputProvider for TerminalInputProvider {
         }
         Ok(false)
     }
+    /// There is no terminal on wasm32.
+    #[cfg(target_arch = "wasm32")]
+    fn check_input_available(&mut self) -> io::Result<bool> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
     fn get_input_character(&mut self) -> char {
+        self.is_char_available = false;
         self.available_char
             .unwrap_or_else(|| panic!("No input available"))
     }
     fn is_interrupted(&self) -> bool {
         self.is_interrupted
     }
+    fn reset_interrupt(&mut self) {
+        self.is_interrupted = false;
+    }
+    fn wait_for_input(&mut self) {
+        // checking for input already waits for a key for a while
+    }
+}
+
+/// Delivers scripted input characters as soon as the program asks for them.
+///
+/// Once all of the input was read no further character becomes available, so
+/// `ScriptedInputProvider::new("")` provides no input at all.
+#[derive(Debug, Default, Clone)]
+pub struct ScriptedInputProvider {
+    input_data: Vec<char>,
+    index: usize,
+}
+impl ScriptedInputProvider {
+    #[must_use]
+    pub fn new(input: &str) -> Self {
+        Self {
+            input_data: input.chars().collect(),
+            index: 0,
+        }
+    }
+}
+impl KeyboardInputProvider for ScriptedInputProvider {
+    fn check_input_available(&mut self) -> io::Result<bool> {
+        Ok(self.index < self.input_data.len())
+    }
+    fn get_input_character(&mut self) -> char {
+        let Some(c) = self.input_data.get(self.index).copied() else {
+            panic!("No input available");
+        };
+        self.index += 1;
+        c
+    }
+    fn is_interrupted(&self) -> bool {
+        false
+    }
+    fn register_waker(&mut self, _waker: &Waker) {
+        // all input is available right away, so none arrives while waiting
+    }
+}
+
+/// Delivers scripted input characters only after a random number of executed instructions.
+///
+/// Each character becomes available after `0..=max_delay` instructions (or keyboard status
+/// polls) determined by a PRNG seeded with `seed`, which exercises KBSR polling loops of the
+/// program under test with varying timing. A failing run can be reproduced by the same seed.
+pub struct ChaosInputProvider {
+    input_data: Vec<char>,
+    index: usize,
+    seed: u64,
+    max_delay: u64,
+    countdown: u64,
+    rng: SplitMix64,
+}
+impl ChaosInputProvider {
+    /// Default for the maximum number of instructions before the next character is available.
+    pub const DEFAULT_MAX_DELAY: u64 = 1000;
+
+    #[must_use]
+    pub fn new(input: &str, seed: u64) -> Self {
+        Self::with_max_delay(input, seed, Self::DEFAULT_MAX_DELAY)
+    }
+    #[must_use]
+    pub fn with_max_delay(input: &str, seed: u64, max_delay: u64) -> Self {
+        let mut rng = SplitMix64::new(seed);
+        let countdown = rng.next_up_to(max_delay);
+        Self {
+            input_data: input.chars().collect(),
+            index: 0,
+            seed,
+            max_delay,
+            countdown,
+            rng,
+        }
+    }
+    /// The seed used to randomize the input timing.
+    #[must_use]
+    pub const fn seed(&self) -> u64 {
+        self.seed
+    }
+    const fn tick(&mut self) {
+        self.countdown = self.countdown.saturating_sub(1);
+    }
+}
+impl KeyboardInputProvider for ChaosInputProvider {
+    fn check_input_available(&mut self) -> io::Result<bool> {
+        let available = self.countdown == 0 && self.index < self.input_data.len();
+        // polling the keyboard takes time as well
+        self.tick();
+        Ok(available)
+    }
+    fn get_input_character(&mut self) -> char {
+        let Some(c) = self.input_data.get(self.index).copied() else {
+            panic!("No input available");
+        };
+        self.index += 1;
+        self.countdown = self.rng.next_up_to(self.max_delay);
+        c
+    }
+    fn is_interrupted(&self) -> bool {
+        false
+    }
+    fn on_instruction_executed(&mut self) {
+        self.tick();
+    }
+    fn wait_for_input(&mut self) {
+        // the delay of pending input passes by polling
+        if self.index >= self.input_data.len() {
+            thread::sleep(INPUT_WAIT_INTERVAL);
+        }
+    }
+    fn register_waker(&mut self, waker: &Waker) {
+        // no more input arrives once all of it was read
+        if self.index < self.input_data.len() {
+            waker.wake_by_ref();
+        }
+    }
+}
+
+/// Characters queued by the embedder, see
+/// [`Emulator::queue_input`](crate::emulator::Emulator::queue_input).
+#[derive(Default)]
+pub(crate) struct InputQueue {
+    chars: VecDeque<char>,
+    /// Woken when characters are queued, registered by a run awaiting input
+    waker: Option<Waker>,
+}
+impl InputQueue {
+    pub(crate) fn extend(&mut self, input: impl IntoIterator<Item = char>) {
+        self.chars.extend(input);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+    /// Characters not yet delivered.
+    pub(crate) fn pending(&self) -> String {
+        self.chars.iter().collect()
+    }
+    #[cfg(feature = "async")]
+    pub(crate) fn register_waker(&mut self, waker: &Waker) {
+        self.waker = Some(waker.clone());
+    }
+    #[cfg(feature = "async")]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+}
+
+/// Delivers queued characters before falling back to another provider, see
+/// [`Emulator::queue_input`](crate::emulator::Emulator::queue_input).
+pub(crate) struct QueuedInputProvider {
+    queue: Arc<Mutex<InputQueue>>,
+    fallback: SharedInputProvider,
+}
+impl QueuedInputProvider {
+    pub(crate) fn new(queue: Arc<Mutex<InputQueue>>, fallback: SharedInputProvider) -> Self {
+        Self { queue, fallback }
+    }
+}
+impl KeyboardInputProvider for QueuedInputProvider {
+    fn check_input_available(&mut self) -> io::Result<bool> {
+        if lock(&self.queue).chars.is_empty() {
+            lock(&self.fallback).check_input_available()
+        } else {
+            Ok(true)
+        }
+    }
+    fn get_input_character(&mut self) -> char {
+        let queued = lock(&self.queue).chars.pop_front();
+        queued.unwrap_or_else(|| lock(&self.fallback).get_input_character())
+    }
+    fn is_interrupted(&self) -> bool {
+        lock(&self.fallback).is_interrupted()
+    }
+    fn reset_interrupt(&mut self) {
+        lock(&self.fallback).reset_interrupt();
+    }
+    fn on_instruction_executed(&mut self) {
+        lock(&self.fallback).on_instruction_executed();
+    }
+    fn wait_for_input(&mut self) {
+        lock(&self.fallback).wait_for_input();
+    }
+    fn register_waker(&mut self, waker: &Waker) {
+        lock(&self.fallback).register_waker(waker);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+
+    fn drain(provider: &mut ChaosInputProvider) -> (String, u64) {
+        let mut res = String::new();
+        let mut polls = 0;
+        while res.len() < provider.input_data.len() {
+            polls += 1;
+            if provider.check_input_available().unwrap() {
+                res.push(provider.get_input_character());
+            }
+        }
+        (res, polls)
+    }
+
+    #[gtest]
+    fn test_chaos_input_delivers_all_characters_in_order() {
+        let mut provider = ChaosInputProvider::with_max_delay("abc", 42, 50);
+        let (res, polls) = drain(&mut provider);
+        expect_that!(res, eq("abc"));
+        expect_that!(polls, ge(3));
+    }
+    #[gtest]
+    fn test_scripted_input_delivers_characters_then_none() {
+        let mut provider = ScriptedInputProvider::new("ab");
+        expect_that!(provider.check_input_available().unwrap(), eq(true));
+        expect_that!(provider.get_input_character(), eq('a'));
+        expect_that!(provider.get_input_character(), eq('b'));
+        expect_that!(provider.check_input_available().unwrap(), eq(false));
+    }
+    #[gtest]
+    fn test_chaos_input_is_reproducible_by_seed() {
+        let (_, polls_a) = drain(&mut ChaosInputProvider::new("hello", 7));
+        let (_, polls_b) = drain(&mut ChaosInputProvider::new("hello", 7));
+        expect_that!(polls_a, eq(polls_b));
+    }
 }
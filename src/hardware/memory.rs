@@ -1,23 +1,108 @@
 use crate::errors::LoadProgramError;
-use crate::hardware::keyboard::KeyboardInputProvider;
-use std::cell::RefCell;
+use crate::hardware::keyboard::SharedInputProvider;
+use crate::hardware::serial::SerialDevice;
+use crate::random::SplitMix64;
+use crate::sync::lock;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeSet;
 use std::fmt::{Debug, Formatter};
-use std::ops::{Index, IndexMut};
-use std::rc::Rc;
+use std::ops::{Index, IndexMut, Range};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
 pub const PROGRAM_SECTION_START: u16 = 0x3000;
 pub const PROGRAM_SECTION_END: u16 = 0xFDFF;
 pub const PROGRAM_SECTION_MAX_INSTRUCTION_COUNT: u16 =
     PROGRAM_SECTION_END - PROGRAM_SECTION_START + 1;
-const MEMORY_SIZE_U16: u16 = PROGRAM_SECTION_START + PROGRAM_SECTION_MAX_INSTRUCTION_COUNT; // TODO
+/// Number of words of the whole address space.
+const MEMORY_SIZE: usize = 1 << 16;
+/// Every `u16` at its own index, so that generated values can be returned by reference.
+static U16_VALUES: [u16; MEMORY_SIZE] = {
+    let mut values = [0; MEMORY_SIZE];
+    let mut idx = 0;
+    while idx < MEMORY_SIZE {
+        #[expect(clippy::cast_possible_truncation, reason = "idx is below 1 << 16")]
+        {
+            values[idx] = idx as u16;
+        }
+        idx += 1;
+    }
+    values
+};
+
+/// Areas of the LC-3 memory map, see `Memory::region_of`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryRegion {
+    /// `x0000`-`x00FF`, start addresses of the trap service routines
+    TrapVectorTable,
+    /// `x0100`-`x01FF`, start addresses of interrupt and exception handlers
+    InterruptVectorTable,
+    /// From `x0200` up to the user space, code and data of the operating system
+    OperatingSystem,
+    /// Programs and their data, `x3000`-`xFDFF` by default
+    UserSpace,
+    /// Memory mapped device registers, `xFE00`-`xFFFF` by default
+    DeviceRegisters,
+}
+
+/// Memory map of the emulated machine, the one of the standard LC-3 by default.
+///
+/// Research variants of the LC-3 with different layouts can be simulated by changing the
+/// regions, see [`EmulatorBuilder::with_config`](crate::emulator::EmulatorBuilder::with_config).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmulatorConfig {
+    /// Addresses available to user programs, which are loaded at its start
+    pub user_space: Range<u16>,
+    /// Addresses where the memory mapped device registers are accessible
+    pub device_region: Range<u16>,
+    /// Seed of the pseudo-random number register at `xFE08`, which yields a new value each read.
+    /// The register is only mapped if a seed is set.
+    pub random_seed: Option<u64>,
+}
+impl Default for EmulatorConfig {
+    fn default() -> Self {
+        Self {
+            user_space: PROGRAM_SECTION_START
+                ..PROGRAM_SECTION_START + PROGRAM_SECTION_MAX_INSTRUCTION_COUNT,
+            device_region: PROGRAM_SECTION_END + 1..u16::MAX,
+            random_seed: None,
+        }
+    }
+}
 
 /// An abstraction for the LC-3 memory including application but excluding registers.
 pub struct Memory {
-    /// Index equals memory address
-    data: Vec<u16>,
+    /// Index equals memory address, shared copy-on-write between instances created from the
+    /// same [`MemorySnapshot`]
+    data: Arc<Vec<u16>>,
     instruction_count: u16,
-    keyboard_input_provider: Rc<RefCell<dyn KeyboardInputProvider>>,
+    keyboard_input_provider: SharedInputProvider,
     u8_val_table: [u16; 256],
+    non_executable: Vec<Range<u16>>,
+    regions: Vec<LoadedRegion>,
+    watchpoints: Vec<(u16, WatchKind)>,
+    /// First watched access since the last call of [`Memory::take_watchpoint_hit`]
+    watchpoint_hit: Cell<Option<MemoryAccess>>,
+    /// Writes since the last call of [`Memory::take_writes`] if logging is enabled
+    write_log: Option<Vec<MemoryAccess>>,
+    /// Program addresses written since loading if tracking is enabled
+    code_writes: Option<BTreeSet<u16>>,
+    observers: RefCell<Vec<Box<dyn MemoryObserver + Send>>>,
+    /// Characters written to the display data register since the last call of
+    /// [`Memory::take_display_output`]
+    display_output: String,
+    /// Machine control register, the clock runs while its bit 15 is set
+    mcr: u16,
+    /// Interrupt enable bit of the keyboard status register
+    keyboard_interrupt_enabled: bool,
+    /// Generator of the random number register
+    random: RefCell<SplitMix64>,
+    serial: Option<RefCell<Box<dyn SerialDevice + Send>>>,
+    /// Interrupt enable bit of the serial receive status register
+    serial_interrupt_enabled: bool,
+    /// Set when the embedder cancels the execution, ends reads waiting for input
+    cancel_requested: Arc<AtomicBool>,
+    config: EmulatorConfig,
 }
 
 impl Debug for Memory {
@@ -30,6 +115,132 @@ impl Debug for Memory {
         )
     }
 }
+/// Immutable copy of memory contents which new [`Memory`] instances can be created from cheaply.
+#[derive(Clone)]
+pub struct MemorySnapshot {
+    data: Arc<Vec<u16>>,
+    instruction_count: u16,
+    non_executable: Vec<Range<u16>>,
+    regions: Vec<LoadedRegion>,
+    config: EmulatorConfig,
+}
+
+impl MemorySnapshot {
+    /// Address, old and new value of every cell differing in `other`.
+    pub fn changed_cells<'a>(
+        &'a self,
+        other: &'a Self,
+    ) -> impl Iterator<Item = (u16, u16, u16)> + 'a {
+        // shared data cannot differ
+        let len = if Arc::ptr_eq(&self.data, &other.data) {
+            0
+        } else {
+            self.data.len()
+        };
+        self.data
+            .iter()
+            .zip(other.data.iter())
+            .take(len)
+            .enumerate()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(address, (old, new))| {
+                (
+                    u16::try_from(address).expect("memory addresses fit into u16"),
+                    *old,
+                    *new,
+                )
+            })
+    }
+}
+
+/// A memory region occupied by loaded data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadedRegion {
+    /// First address
+    pub origin: u16,
+    /// Number of words
+    pub length: u16,
+    /// File the region was loaded from, if any
+    pub source: Option<String>,
+    /// Whether the program may write to this region
+    pub writable: bool,
+    /// Whether instructions may be fetched from this region
+    pub executable: bool,
+}
+impl LoadedRegion {
+    /// Address after the last word.
+    #[must_use]
+    pub const fn end(&self) -> u32 {
+        self.origin as u32 + self.length as u32
+    }
+}
+impl std::fmt::Display for LoadedRegion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "x{:04X}-x{:04X} {:6} words r{}{} {}",
+            self.origin,
+            self.end() - 1,
+            self.length,
+            if self.writable { 'w' } else { '-' },
+            if self.executable { 'x' } else { '-' },
+            self.source.as_deref().unwrap_or("<memory>")
+        )
+    }
+}
+
+/// Kinds of accesses a watchpoint reacts to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+impl WatchKind {
+    const fn matches(self, kind: AccessKind) -> bool {
+        matches!(
+            (self, kind),
+            (Self::ReadWrite, _)
+                | (Self::Read, AccessKind::Read)
+                | (Self::Write, AccessKind::Write)
+        )
+    }
+}
+
+/// Whether memory was read or written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+/// A single access of a memory cell by an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub address: u16,
+    pub kind: AccessKind,
+    /// Value before the access
+    pub old: u16,
+    /// Value after the access, equal to `old` for reads
+    pub new: u16,
+}
+
+/// Instrumentation notified about every memory access of an instruction, f.e. for a cache
+/// simulation. Accesses of trap routines and the instruction fetch are not reported.
+pub trait MemoryObserver {
+    fn on_read(&mut self, _address: u16, _value: u16) {}
+    fn on_write(&mut self, _address: u16, _old: u16, _new: u16) {}
+}
+/// Allows keeping a handle to inspect the observer after attaching it.
+impl<T: MemoryObserver> MemoryObserver for Arc<Mutex<T>> {
+    fn on_read(&mut self, address: u16, value: u16) {
+        lock(self).on_read(address, value);
+    }
+    fn on_write(&mut self, address: u16, old: u16, new: u16) {
+        lock(self).on_write(address, old, new);
+    }
+}
+
 /// Memory regions mapped to IO functionality.
 #[repr(u16)]
 #[derive(enumn::N)]
@@ -38,50 +249,109 @@ pub enum MemoryMappedIOLocations {
     Kbsr = 0xFE00,
     /// Keyboard Data Register
     Kbdr = 0xFE02,
+    /// Display Status Register
+    Dsr = 0xFE04,
+    /// Display Data Register
+    Ddr = 0xFE06,
+    /// Random Number Register, see [`EmulatorConfig::random_seed`]
+    Rnr = 0xFE08,
+    /// Serial Receive Status Register
+    Srsr = 0xFE0A,
+    /// Serial Receive Data Register
+    Srdr = 0xFE0C,
+    /// Serial Transmit Status Register
+    Stsr = 0xFE0E,
+    /// Serial Transmit Data Register
+    Stdr = 0xFE10,
+    /// Machine Control Register
+    Mcr = 0xFFFE,
 }
 impl Index<u16> for Memory {
     type Output = u16;
     fn index(&self, index: u16) -> &Self::Output {
-        MemoryMappedIOLocations::n(index).map_or_else(
-            || {
-                self.assert_valid_access(index);
-                &self.data[usize::from(index)]
-            },
+        self.device_at(index).map_or_else(
+            || &self.data[usize::from(index)],
             |mapped_io_loc| match mapped_io_loc {
                 MemoryMappedIOLocations::Kbsr => {
-                    if self
-                        .keyboard_input_provider
-                        .borrow_mut()
+                    let ready = lock(&self.keyboard_input_provider)
                         .check_input_available()
-                        .unwrap_or(false)
-                    {
-                        &Self::KEYBOARD_STATUS_REGISTER_SET
-                    } else {
-                        &Self::KEYBOARD_STATUS_REGISTER_UNSET
-                    }
+                        .unwrap_or(false);
+                    &Self::STATUS_REGISTER_VALUES
+                        [usize::from(ready) | usize::from(self.keyboard_interrupt_enabled) << 1]
                 }
                 MemoryMappedIOLocations::Kbdr => {
-                    let res = self
-                        .keyboard_input_provider
-                        .borrow_mut()
-                        .get_input_character();
+                    let res = lock(&self.keyboard_input_provider).get_input_character();
                     &self.u8_val_table[res as usize]
                 }
+                // the display is always ready
+                MemoryMappedIOLocations::Dsr => &Self::STATUS_REGISTER_READY,
+                MemoryMappedIOLocations::Mcr => &self.mcr,
+                // data registers for output read as 0
+                MemoryMappedIOLocations::Ddr | MemoryMappedIOLocations::Stdr => &0,
+                MemoryMappedIOLocations::Srsr => {
+                    let ready = self.is_serial_ready();
+                    &Self::STATUS_REGISTER_VALUES
+                        [usize::from(ready) | usize::from(self.serial_interrupt_enabled) << 1]
+                }
+                MemoryMappedIOLocations::Srdr => {
+                    let byte = self
+                        .serial
+                        .as_ref()
+                        .map_or(0, |serial| serial.borrow_mut().receive());
+                    &self.u8_val_table[usize::from(byte)]
+                }
+                MemoryMappedIOLocations::Stsr => {
+                    let ready = self
+                        .serial
+                        .as_ref()
+                        .is_some_and(|serial| serial.borrow_mut().can_transmit());
+                    if ready {
+                        &Self::STATUS_REGISTER_READY
+                    } else {
+                        &0
+                    }
+                }
+                MemoryMappedIOLocations::Rnr => {
+                    #[expect(clippy::cast_possible_truncation, reason = "lower 16 bits are used")]
+                    let value = self.random.borrow_mut().next_u64() as u16;
+                    &U16_VALUES[usize::from(value)]
+                }
             },
         )
     }
 }
 impl IndexMut<u16> for Memory {
     fn index_mut(&mut self, index: u16) -> &mut Self::Output {
-        self.assert_valid_access(index);
-        &mut self.data[usize::from(index)]
+        &mut Arc::make_mut(&mut self.data)[usize::from(index)]
     }
 }
 impl Memory {
-    const KEYBOARD_STATUS_REGISTER_SET: u16 = 1 << 15;
-    const KEYBOARD_STATUS_REGISTER_UNSET: u16 = 0;
-    pub fn new(keyboard_input_provider: Rc<RefCell<dyn KeyboardInputProvider>>) -> Self {
-        let data = vec![0x0u16; usize::from(MEMORY_SIZE_U16)];
+    const INTERRUPT_ENABLE: u16 = 1 << 14;
+    /// Status register values indexed by the ready bit and the interrupt enable bit shifted left
+    /// by one
+    const STATUS_REGISTER_VALUES: [u16; 4] = [
+        0,
+        1 << 15,
+        Self::INTERRUPT_ENABLE,
+        1 << 15 | Self::INTERRUPT_ENABLE,
+    ];
+    const STATUS_REGISTER_READY: u16 = 1 << 15;
+    pub fn new(keyboard_input_provider: SharedInputProvider) -> Self {
+        Self::with_config(keyboard_input_provider, EmulatorConfig::default())
+    }
+    /// Creates memory laid out according to `config`.
+    pub fn with_config(
+        keyboard_input_provider: SharedInputProvider,
+        config: EmulatorConfig,
+    ) -> Self {
+        let data = Arc::new(vec![0x0u16; MEMORY_SIZE]);
+        Self::with_data(data, keyboard_input_provider, config)
+    }
+    fn with_data(
+        data: Arc<Vec<u16>>,
+        keyboard_input_provider: SharedInputProvider,
+        config: EmulatorConfig,
+    ) -> Self {
         let mut u8_val_table: [u16; 256] = [0; 256];
         for (idx, b) in u8_val_table.iter_mut().enumerate() {
             #[expect(clippy::cast_possible_truncation)]
@@ -94,42 +364,605 @@ impl Memory {
             instruction_count: 0,
             keyboard_input_provider,
             u8_val_table,
+            non_executable: Vec::new(),
+            regions: Vec::new(),
+            watchpoints: Vec::new(),
+            watchpoint_hit: Cell::new(None),
+            write_log: None,
+            code_writes: None,
+            observers: RefCell::new(Vec::new()),
+            display_output: String::new(),
+            mcr: Self::STATUS_REGISTER_READY,
+            keyboard_interrupt_enabled: false,
+            random: RefCell::new(SplitMix64::new(config.random_seed.unwrap_or_default())),
+            serial: None,
+            serial_interrupt_enabled: false,
+            cancel_requested: Arc::default(),
+            config,
         }
     }
-    #[inline]
-    fn assert_valid_access(&self, index: u16) {
-        assert!(
-            (PROGRAM_SECTION_START..=PROGRAM_SECTION_END).contains(&index),
-            "Address {:#06X} is not in program space when indexing, valid range: {:#06X}..{:#06X}",
-            index,
-            PROGRAM_SECTION_START,
-            PROGRAM_SECTION_START + self.instruction_count
+    /// The memory map this memory is laid out by.
+    #[must_use]
+    pub const fn config(&self) -> &EmulatorConfig {
+        &self.config
+    }
+    /// The area of the memory map `address` belongs to.
+    #[must_use]
+    pub fn region_of(&self, address: u16) -> MemoryRegion {
+        let user_space = &self.config.user_space;
+        match address {
+            _ if user_space.contains(&address) => MemoryRegion::UserSpace,
+            _ if self.config.device_region.contains(&address) || address >= user_space.end => {
+                MemoryRegion::DeviceRegisters
+            }
+            0x0000..0x0100 => MemoryRegion::TrapVectorTable,
+            0x0100..0x0200 => MemoryRegion::InterruptVectorTable,
+            _ => MemoryRegion::OperatingSystem,
+        }
+    }
+    /// The device register at `address` if it is inside the device region.
+    fn device_at(&self, address: u16) -> Option<MemoryMappedIOLocations> {
+        MemoryMappedIOLocations::n(address).filter(|location| {
+            self.config.device_region.contains(&address)
+                && match location {
+                    MemoryMappedIOLocations::Rnr => self.config.random_seed.is_some(),
+                    MemoryMappedIOLocations::Srsr
+                    | MemoryMappedIOLocations::Srdr
+                    | MemoryMappedIOLocations::Stsr
+                    | MemoryMappedIOLocations::Stdr => self.serial.is_some(),
+                    _ => true,
+                }
+        })
+    }
+    /// Creates memory with the contents of `snapshot` without copying them until written to.
+    pub fn from_snapshot(
+        snapshot: &MemorySnapshot,
+        keyboard_input_provider: SharedInputProvider,
+    ) -> Self {
+        let mut res = Self::with_data(
+            snapshot.data.clone(),
+            keyboard_input_provider,
+            snapshot.config.clone(),
+        );
+        res.instruction_count = snapshot.instruction_count;
+        res.non_executable.clone_from(&snapshot.non_executable);
+        res.regions.clone_from(&snapshot.regions);
+        res
+    }
+    /// Copies the memory including watchpoints and device state, the contents stay shared
+    /// until either copy is written to.
+    ///
+    /// Observers and the serial device are not copied, the copy reads from
+    /// `keyboard_input_provider`.
+    pub fn fork(&self, keyboard_input_provider: SharedInputProvider) -> Self {
+        let mut res = Self::with_data(
+            self.data.clone(),
+            keyboard_input_provider,
+            self.config.clone(),
         );
+        res.instruction_count = self.instruction_count;
+        res.non_executable.clone_from(&self.non_executable);
+        res.regions.clone_from(&self.regions);
+        res.watchpoints.clone_from(&self.watchpoints);
+        res.write_log = self.write_log.as_ref().map(|_| Vec::new());
+        res.code_writes.clone_from(&self.code_writes);
+        res.mcr = self.mcr;
+        res.keyboard_interrupt_enabled = self.keyboard_interrupt_enabled;
+        res.random.clone_from(&self.random);
+        res
+    }
+    /// Replaces the contents with the ones of `snapshot`, keeping watchpoints and observers.
+    pub fn restore_snapshot(&mut self, snapshot: &MemorySnapshot) {
+        self.data = snapshot.data.clone();
+        self.instruction_count = snapshot.instruction_count;
+        self.non_executable.clone_from(&snapshot.non_executable);
+        self.regions.clone_from(&snapshot.regions);
+        self.config.clone_from(&snapshot.config);
+    }
+    /// Captures the current contents, which stay shared until either side is written to.
+    pub fn snapshot(&self) -> MemorySnapshot {
+        MemorySnapshot {
+            data: self.data.clone(),
+            instruction_count: self.instruction_count,
+            non_executable: self.non_executable.clone(),
+            regions: self.regions.clone(),
+            config: self.config.clone(),
+        }
     }
     /// Loads a program without an `.ORIG` header into the memory section
-    /// starting from address `_PROGRAM_SECTION_START_BYTES`
+    /// starting at the start of the user space, `x3000` by default,
     /// and returns an iterator over the loaded instructions.
     ///
     /// # Errors
     /// - Program too long
     pub fn load_program(&mut self, data: &[u16]) -> Result<(), LoadProgramError> {
-        if data.len() > usize::from(PROGRAM_SECTION_MAX_INSTRUCTION_COUNT) {
+        let start = self.config.user_space.start;
+        let maximum_instructions = self.config.user_space.end - start;
+        if data.len() > usize::from(maximum_instructions) {
             return Err(LoadProgramError::ProgramTooLong {
                 actual_instructions: data.len(),
-                maximum_instructions: PROGRAM_SECTION_MAX_INSTRUCTION_COUNT,
+                maximum_instructions,
             });
         }
         self.instruction_count = u16::try_from(data.len()).expect("instruction count too long");
-        let program_slice = &mut self.data[usize::from(PROGRAM_SECTION_START)
-            ..usize::from(PROGRAM_SECTION_START + self.instruction_count)];
+        let program_slice = &mut Arc::make_mut(&mut self.data)
+            [usize::from(start)..usize::from(start + self.instruction_count)];
         program_slice.copy_from_slice(data);
+        self.regions = vec![LoadedRegion {
+            origin: start,
+            length: self.instruction_count,
+            source: None,
+            writable: true,
+            executable: true,
+        }];
+        Ok(())
+    }
+    /// Loads an operating system image, f.e. `lc3os.obj`, at `origin` below the program
+    /// section, which provides the trap vector table and the trap service routines.
+    ///
+    /// Must be called after [`Memory::load_program`], which replaces all regions.
+    ///
+    /// # Errors
+    /// - The image does not fit below the program section or overlaps an already loaded image
+    pub fn load_os(&mut self, origin: u16, data: &[u16]) -> Result<(), LoadProgramError> {
+        let end = usize::from(origin) + data.len();
+        if end > usize::from(self.config.user_space.start) {
+            return Err(LoadProgramError::OsImageOutOfRange {
+                origin,
+                length: data.len(),
+            });
+        }
+        if self
+            .regions
+            .iter()
+            .any(|r| usize::from(r.origin) < end && r.end() > u32::from(origin))
+        {
+            return Err(LoadProgramError::RegionOverlaps(origin));
+        }
+        Arc::make_mut(&mut self.data)[usize::from(origin)..end].copy_from_slice(data);
+        self.regions.push(LoadedRegion {
+            origin,
+            length: u16::try_from(data.len()).expect("checked to fit below the program section"),
+            source: None,
+            writable: true,
+            executable: true,
+        });
+        Ok(())
+    }
+    /// Loads `data` at `origin` in the user space next to the program, f.e. a lookup table.
+    ///
+    /// The region is marked as not executable. Must be called after [`Memory::load_program`].
+    ///
+    /// # Errors
+    /// - The region exceeds the user space or overlaps an already loaded region
+    pub fn load_region(&mut self, origin: u16, data: &[u16]) -> Result<(), LoadProgramError> {
+        self.load_user_region(origin, data, false)
+    }
+    /// Loads instructions at `origin` in the user space next to the program, f.e. a library of
+    /// subroutines the program calls, otherwise like [`Memory::load_region`].
+    ///
+    /// # Errors
+    /// - The region exceeds the user space or overlaps an already loaded region
+    pub fn load_code_region(&mut self, origin: u16, data: &[u16]) -> Result<(), LoadProgramError> {
+        self.load_user_region(origin, data, true)
+    }
+    fn load_user_region(
+        &mut self,
+        origin: u16,
+        data: &[u16],
+        executable: bool,
+    ) -> Result<(), LoadProgramError> {
+        let end = u32::from(origin) + u32::try_from(data.len()).unwrap_or(u32::MAX);
+        if origin < self.config.user_space.start || end > u32::from(self.config.user_space.end) {
+            return Err(LoadProgramError::RegionOutOfRange {
+                origin,
+                length: data.len(),
+            });
+        }
+        if self
+            .regions
+            .iter()
+            .any(|r| u32::from(r.origin) < end && r.end() > u32::from(origin))
+        {
+            return Err(LoadProgramError::RegionOverlaps(origin));
+        }
+        let length = u16::try_from(data.len()).expect("checked to fit into the user space");
+        Arc::make_mut(&mut self.data)[usize::from(origin)..usize::from(origin + length)]
+            .copy_from_slice(data);
+        self.regions.push(LoadedRegion {
+            origin,
+            length,
+            source: None,
+            writable: true,
+            executable,
+        });
+        if !executable {
+            self.set_non_executable(origin..origin + length);
+        }
         Ok(())
     }
+    /// `true` if an operating system image was loaded by [`Memory::load_os`].
+    #[must_use]
+    pub fn is_os_loaded(&self) -> bool {
+        self.regions
+            .iter()
+            .any(|r| r.origin < self.config.user_space.start)
+    }
+    /// Records the file the most recently loaded region was read from.
+    pub fn set_source_of_last_region(&mut self, source: &str) {
+        if let Some(region) = self.regions.last_mut() {
+            region.source = Some(source.to_owned());
+        }
+    }
+    /// All occupied regions ordered by address, split where parts are marked as not executable.
+    #[must_use]
+    pub fn layout(&self) -> Vec<LoadedRegion> {
+        let mut res = Vec::with_capacity(self.regions.len());
+        for region in &self.regions {
+            let end = region.end();
+            let mut bounds: Vec<u32> = self
+                .non_executable
+                .iter()
+                .flat_map(|r| [u32::from(r.start), u32::from(r.end)])
+                .filter(|b| (u32::from(region.origin) + 1..end).contains(b))
+                .collect();
+            bounds.push(u32::from(region.origin));
+            bounds.push(end);
+            bounds.sort_unstable();
+            bounds.dedup();
+            for part in bounds.windows(2) {
+                let origin = u16::try_from(part[0]).expect("region start within memory");
+                let length = u16::try_from(part[1] - part[0]).expect("region length within memory");
+                res.push(LoadedRegion {
+                    origin,
+                    length,
+                    executable: region.executable && self.is_executable(origin),
+                    ..region.clone()
+                });
+            }
+        }
+        res.sort_by_key(|r| r.origin);
+        res
+    }
+    /// Marks the addresses in `range` as not executable.
+    pub fn set_non_executable(&mut self, range: Range<u16>) {
+        if !range.is_empty() {
+            self.non_executable.push(range);
+        }
+    }
+    /// `true` if `address` lies in a loaded region instructions are fetched from, the program,
+    /// an operating system or a region loaded by [`Memory::load_code_region`].
+    #[must_use]
+    pub fn in_code_region(&self, address: u16) -> bool {
+        self.regions
+            .iter()
+            .any(|r| r.executable && (u32::from(r.origin)..r.end()).contains(&u32::from(address)))
+    }
+    /// `false` if `address` was marked as not executable.
+    #[must_use]
+    pub fn is_executable(&self, address: u16) -> bool {
+        !self.non_executable.iter().any(|r| r.contains(&address))
+    }
+    /// `true` if the program set the interrupt enable bit of the keyboard status register.
+    #[must_use]
+    pub const fn is_keyboard_interrupt_enabled(&self) -> bool {
+        self.keyboard_interrupt_enabled
+    }
+    /// Reads the keyboard registers from `provider` from now on.
+    pub fn set_keyboard_input_provider(&mut self, provider: SharedInputProvider) {
+        self.keyboard_input_provider = provider;
+    }
+    /// `true` if a key is available to be read from the keyboard data register.
+    pub fn is_keyboard_ready(&self) -> bool {
+        lock(&self.keyboard_input_provider)
+            .check_input_available()
+            .unwrap_or(false)
+    }
+    /// `true` if the user interrupted the program via the keyboard, f.e. by CTRL-C.
+    pub fn is_keyboard_interrupted(&self) -> bool {
+        lock(&self.keyboard_input_provider).is_interrupted()
+    }
+    /// Lets the keyboard input provider wait a while for input, see
+    /// [`KeyboardInputProvider::wait_for_input`](crate::hardware::keyboard::KeyboardInputProvider::wait_for_input).
+    pub(crate) fn wait_for_input(&self) {
+        lock(&self.keyboard_input_provider).wait_for_input();
+    }
+    /// Shares the cancellation request of the emulator, see [`Memory::is_cancel_requested`].
+    pub(crate) fn set_cancel_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.cancel_requested = flag;
+    }
+    /// `true` if the embedder requested to stop the execution, which ends reads waiting for
+    /// input like a keyboard interrupt.
+    pub fn is_cancel_requested(&self) -> bool {
+        self.cancel_requested.load(Ordering::Relaxed)
+    }
+    /// Returns whether the user interrupted the program and resets the interruption.
+    pub fn take_keyboard_interrupt(&self) -> bool {
+        let mut provider = lock(&self.keyboard_input_provider);
+        let interrupted = provider.is_interrupted();
+        if interrupted {
+            provider.reset_interrupt();
+        }
+        interrupted
+    }
+    /// Maps the serial device registers to `device`.
+    pub fn attach_serial(&mut self, device: Box<dyn SerialDevice + Send>) {
+        self.serial = Some(RefCell::new(device));
+    }
+    /// `true` if a byte can be read from the serial receive data register.
+    pub fn is_serial_ready(&self) -> bool {
+        self.serial
+            .as_ref()
+            .is_some_and(|serial| serial.borrow_mut().check_received())
+    }
+    #[must_use]
+    pub const fn is_serial_interrupt_enabled(&self) -> bool {
+        self.serial_interrupt_enabled
+    }
+    /// Content of `address` without side effects, device registers are not read.
+    #[must_use]
+    pub fn peek(&self, address: u16) -> u16 {
+        self.data.get(usize::from(address)).copied().unwrap_or(0)
+    }
+    /// Contents of `range` without side effects, device registers are not read.
+    #[must_use]
+    pub fn cells(&self, range: Range<u16>) -> &[u16] {
+        &self.data[usize::from(range.start)..usize::from(range.end)]
+    }
+    /// Reads `address` on behalf of an instruction, which is observed by watchpoints.
+    pub fn read(&self, address: u16) -> u16 {
+        let value = self[address];
+        self.observe(MemoryAccess {
+            address,
+            kind: AccessKind::Read,
+            old: value,
+            new: value,
+        });
+        value
+    }
+    /// Writes `value` to `address` on behalf of an instruction, which is observed by watchpoints.
+    pub fn write(&mut self, address: u16, value: u16) {
+        let old = match self.device_at(address) {
+            Some(MemoryMappedIOLocations::Kbsr) => {
+                let old = self[address];
+                self.keyboard_interrupt_enabled = value & Self::INTERRUPT_ENABLE != 0;
+                old
+            }
+            Some(MemoryMappedIOLocations::Ddr) => {
+                #[expect(clippy::cast_possible_truncation, reason = "characters are 8 bits")]
+                self.display_output.push(char::from(value as u8));
+                0
+            }
+            Some(MemoryMappedIOLocations::Mcr) => std::mem::replace(&mut self.mcr, value),
+            Some(MemoryMappedIOLocations::Srsr) => {
+                let old = self[address];
+                self.serial_interrupt_enabled = value & Self::INTERRUPT_ENABLE != 0;
+                old
+            }
+            Some(MemoryMappedIOLocations::Stdr) => {
+                if let Some(serial) = self.serial.as_ref() {
+                    serial.borrow_mut().transmit(value.to_le_bytes()[0]);
+                }
+                0
+            }
+            _ => std::mem::replace(&mut self[address], value),
+        };
+        let access = MemoryAccess {
+            address,
+            kind: AccessKind::Write,
+            old,
+            new: value,
+        };
+        if let Some(log) = self.write_log.as_mut() {
+            log.push(access);
+        }
+        if (self.program_start()..self.program_end()).contains(&address)
+            && let Some(code_writes) = self.code_writes.as_mut()
+        {
+            code_writes.insert(address);
+        }
+        self.observe(access);
+    }
+    /// Returns and clears the characters written to the display data register.
+    pub fn take_display_output(&mut self) -> String {
+        std::mem::take(&mut self.display_output)
+    }
+    /// Clears the clock enable bit of the machine control register like the `HALT` routine of
+    /// the operating system, which stops execution after the current instruction.
+    pub const fn stop_clock(&mut self) {
+        self.mcr &= !Self::STATUS_REGISTER_READY;
+    }
+    /// Returns whether the machine control register stopped the clock and restarts it, so that
+    /// execution can be resumed.
+    pub const fn take_clock_stopped(&mut self) -> bool {
+        let stopped = self.mcr & Self::STATUS_REGISTER_READY == 0;
+        self.mcr |= Self::STATUS_REGISTER_READY;
+        stopped
+    }
+    /// Enables or disables recording of writes into the loaded program for
+    /// [`Memory::is_modified_code`].
+    pub fn set_code_write_tracking(&mut self, enabled: bool) {
+        self.code_writes = enabled.then(BTreeSet::new);
+    }
+    /// `true` if an instruction wrote to the program at `address` while tracking code writes.
+    #[must_use]
+    pub fn is_modified_code(&self, address: u16) -> bool {
+        self.code_writes
+            .as_ref()
+            .is_some_and(|code_writes| code_writes.contains(&address))
+    }
+    /// Enables or disables recording of writes for [`Memory::take_writes`].
+    pub fn set_write_logging(&mut self, enabled: bool) {
+        self.write_log = enabled.then(Vec::new);
+    }
+    /// Sets `address` to `value` without being observed, f.e. to revert a write.
+    pub fn restore(&mut self, address: u16, value: u16) {
+        self[address] = value;
+    }
+    /// Returns and clears the writes recorded since the last call.
+    pub fn take_writes(&mut self) -> Vec<MemoryAccess> {
+        self.write_log
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+    fn observe(&self, access: MemoryAccess) {
+        for observer in self.observers.borrow_mut().iter_mut() {
+            match access.kind {
+                AccessKind::Read => observer.on_read(access.address, access.new),
+                AccessKind::Write => observer.on_write(access.address, access.old, access.new),
+            }
+        }
+        if self
+            .watchpoints
+            .iter()
+            .any(|(address, kind)| *address == access.address && kind.matches(access.kind))
+            && self.watchpoint_hit.get().is_none()
+        {
+            self.watchpoint_hit.set(Some(access));
+        }
+    }
+    /// Notifies `observer` about all following reads and writes of instructions.
+    pub fn add_observer(&mut self, observer: impl MemoryObserver + Send + 'static) {
+        self.observers.get_mut().push(Box::new(observer));
+    }
+    /// Watches accesses of `kind` to `address`, replacing an existing watchpoint there.
+    pub fn add_watchpoint(&mut self, address: u16, kind: WatchKind) {
+        self.remove_watchpoint(address);
+        self.watchpoints.push((address, kind));
+    }
+    /// Removes the watchpoint at `address` if there is one.
+    pub fn remove_watchpoint(&mut self, address: u16) {
+        self.watchpoints.retain(|(a, _)| *a != address);
+    }
+    /// Returns and resets the first watched access since the last call.
+    pub fn take_watchpoint_hit(&self) -> Option<MemoryAccess> {
+        self.watchpoint_hit.take()
+    }
+    pub const fn program_start(&self) -> u16 {
+        self.config.user_space.start
+    }
     pub const fn program_end(&self) -> u16 {
-        PROGRAM_SECTION_START + self.instruction_count
+        self.config.user_space.start + self.instruction_count
     }
     pub fn program_slice(&self) -> &[u16] {
-        &self.data[usize::from(PROGRAM_SECTION_START)
-            ..usize::from(PROGRAM_SECTION_START + self.instruction_count)]
+        &self.data[usize::from(self.program_start())..usize::from(self.program_end())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::test_helpers::FakeKeyboardInputProvider;
+    use googletest::prelude::*;
+
+    #[gtest]
+    fn test_observer() {
+        #[derive(Default)]
+        struct CountingObserver {
+            reads: Vec<(u16, u16)>,
+            writes: Vec<(u16, u16, u16)>,
+        }
+        impl MemoryObserver for CountingObserver {
+            fn on_read(&mut self, address: u16, value: u16) {
+                self.reads.push((address, value));
+            }
+            fn on_write(&mut self, address: u16, old: u16, new: u16) {
+                self.writes.push((address, old, new));
+            }
+        }
+        let mut mem = Memory::new(Arc::new(Mutex::new(FakeKeyboardInputProvider::new(""))));
+        mem.load_program(&[1, 2, 3]).unwrap();
+        let observer = Arc::new(Mutex::new(CountingObserver::default()));
+        mem.add_observer(observer.clone());
+        let _ = mem.read(0x3001);
+        mem.write(0x3002, 9);
+        let _ = mem[0x3000];
+        expect_that!(lock(&observer).reads, elements_are![eq(&(0x3001, 2))]);
+        expect_that!(lock(&observer).writes, elements_are![eq(&(0x3002, 3, 9))]);
+    }
+    #[gtest]
+    fn test_region_of() {
+        let mut mem = Memory::new(Arc::new(Mutex::new(FakeKeyboardInputProvider::new(""))));
+        expect_that!(mem.region_of(0x0025), eq(MemoryRegion::TrapVectorTable));
+        expect_that!(
+            mem.region_of(0x0180),
+            eq(MemoryRegion::InterruptVectorTable)
+        );
+        expect_that!(mem.region_of(0x2FFF), eq(MemoryRegion::OperatingSystem));
+        expect_that!(mem.region_of(0x3000), eq(MemoryRegion::UserSpace));
+        expect_that!(mem.region_of(0xFDFF), eq(MemoryRegion::UserSpace));
+        expect_that!(mem.region_of(0xFE00), eq(MemoryRegion::DeviceRegisters));
+        expect_that!(mem.region_of(0xFFFF), eq(MemoryRegion::DeviceRegisters));
+        // accessible without operating system
+        mem.write(0x0200, 0x1234);
+        expect_that!(mem.read(0x0200), eq(0x1234));
+        expect_that!(mem.read(0x0025), eq(0));
+    }
+    #[gtest]
+    fn test_random_number_register() {
+        let kip = Arc::new(Mutex::new(FakeKeyboardInputProvider::new("")));
+        let config = EmulatorConfig {
+            random_seed: Some(4711),
+            ..EmulatorConfig::default()
+        };
+        let a = Memory::with_config(kip.clone(), config.clone());
+        let b = Memory::with_config(kip.clone(), config);
+        let values: Vec<_> = (0..5).map(|_| a.read(0xFE08)).collect();
+        // reproducible from the seed, but a new value each read
+        expect_that!(
+            (0..5).map(|_| b.read(0xFE08)).collect::<Vec<_>>(),
+            eq(&values)
+        );
+        expect_that!(values.windows(2).all(|pair| pair[0] == pair[1]), eq(false));
+        let without_seed = Memory::new(kip);
+        expect_that!(without_seed.read(0xFE08), eq(0));
+    }
+    #[gtest]
+    fn test_watchpoints() {
+        let mut mem = Memory::new(Arc::new(Mutex::new(FakeKeyboardInputProvider::new(""))));
+        mem.load_program(&[0; 10]).unwrap();
+        mem.add_watchpoint(0x3001, WatchKind::Write);
+        mem.add_watchpoint(0x3002, WatchKind::Read);
+        let _ = mem.read(0x3001);
+        mem.write(0x3002, 7);
+        expect_that!(mem.take_watchpoint_hit(), none());
+        mem.write(0x3001, 5);
+        let _ = mem.read(0x3002);
+        expect_that!(
+            mem.take_watchpoint_hit(),
+            some(eq(MemoryAccess {
+                address: 0x3001,
+                kind: AccessKind::Write,
+                old: 0,
+                new: 5
+            }))
+        );
+        expect_that!(mem.take_watchpoint_hit(), none());
+        mem.remove_watchpoint(0x3001);
+        mem.write(0x3001, 6);
+        expect_that!(mem.take_watchpoint_hit(), none());
+    }
+    #[gtest]
+    fn test_layout_split_by_non_executable() {
+        let mut mem = Memory::new(Arc::new(Mutex::new(FakeKeyboardInputProvider::new(""))));
+        mem.load_program(&[0; 10]).unwrap();
+        mem.set_source_of_last_region("prog.obj");
+        mem.set_non_executable(0x3007..0x300A);
+        mem.set_non_executable(0x4000..0x4010);
+        let layout = mem.layout();
+        assert_that!(layout.len(), eq(2));
+        expect_that!(
+            (layout[0].origin, layout[0].length, layout[0].executable),
+            eq((0x3000, 7, true))
+        );
+        expect_that!(
+            (layout[1].origin, layout[1].length, layout[1].executable),
+            eq((0x3007, 3, false))
+        );
+        expect_that!(
+            layout[1].to_string(),
+            eq("x3007-x3009      3 words rw- prog.obj")
+        );
     }
 }
@@ -1,3 +1,8 @@
-pub(crate) mod keyboard;
+pub mod keyboard;
 pub(crate) mod memory;
 pub mod registers;
+pub mod serial;
+
+pub use memory::{
+    AccessKind, EmulatorConfig, MemoryAccess, MemoryObserver, MemoryRegion, WatchKind,
+};
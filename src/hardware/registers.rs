@@ -1,4 +1,5 @@
 use crate::hardware::memory;
+use crate::hardware::memory::EmulatorConfig;
 use crate::numbers;
 use std::fmt::{Debug, Formatter};
 
@@ -46,10 +47,27 @@ impl Debug for Register {
         )
     }
 }
+/// Initial Supervisor Stack Pointer, the stack grows down into the operating system space.
+const INITIAL_SSP: u16 = memory::PROGRAM_SECTION_START;
+
+/// Privilege mode of the processor, bit 15 of the PSR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Privilege {
+    Supervisor,
+    User,
+}
+
+#[derive(Clone)]
 pub struct Registers {
     general_purpose: [Register; 8],
     pc: Register,
     cond: ConditionFlag,
+    privilege: Privilege,
+    /// Priority level 0 to 7, bits 10 to 8 of the PSR
+    priority: u8,
+    /// Stack pointer of the mode not running, the current one is in R6
+    saved_ssp: u16,
+    saved_usp: u16,
 }
 impl Registers {
     #[must_use]
@@ -58,22 +76,29 @@ impl Registers {
             general_purpose: [Register(0); 8],
             pc: Register(memory::PROGRAM_SECTION_START),
             cond: ConditionFlag::Zero,
+            privilege: Privilege::User,
+            priority: 0,
+            saved_ssp: INITIAL_SSP,
+            saved_usp: 0,
+        }
+    }
+    /// Initial registers with the PC at the start of the user space of `config`.
+    #[must_use]
+    pub const fn with_config(config: &EmulatorConfig) -> Self {
+        Self {
+            pc: Register(config.user_space.start),
+            ..Self::new()
         }
     }
     #[must_use]
     pub const fn pc(&self) -> Register {
         self.pc
     }
-    pub fn inc_pc(&mut self) {
-        self.set_pc(self.pc.0 + 1);
+    pub const fn inc_pc(&mut self) {
+        self.set_pc(self.pc.0.wrapping_add(1));
     }
-    pub fn set_pc(&mut self, val: u16) {
-        debug_assert!(
-            // one behind valid addresses allowed since the PC is incremented
-            // before executing the current instruction
-            (memory::PROGRAM_SECTION_START..=(memory::PROGRAM_SECTION_END + 1)).contains(&val),
-            "Program Counter (PC) must be between 0x3000 and 0xFE00, but is: {val:#06X}"
-        );
+    /// Sets the PC to any address, the emulator checks jump targets before executing them.
+    pub const fn set_pc(&mut self, val: u16) {
         self.pc = Register::from_binary(val);
     }
     #[must_use]
@@ -99,6 +124,59 @@ impl Registers {
         let val = self.get(r);
         self.cond = ConditionFlag::from(val);
     }
+    #[must_use]
+    pub const fn privilege(&self) -> Privilege {
+        self.privilege
+    }
+    #[must_use]
+    pub const fn priority(&self) -> u8 {
+        self.priority
+    }
+    /// Processor Status Register with the privilege in bit 15, the priority in bits 10 to 8
+    /// and the condition codes in bits 2 to 0.
+    #[must_use]
+    pub const fn psr(&self) -> u16 {
+        let privilege = match self.privilege {
+            Privilege::Supervisor => 0,
+            Privilege::User => 1 << 15,
+        };
+        privilege | (self.priority as u16) << 8 | self.cond as u16
+    }
+    /// Sets the Processor Status Register, switching stacks if the privilege changes, see
+    /// [`Registers::switch_privilege`].
+    pub fn set_psr(&mut self, psr: u16) {
+        self.switch_privilege(if psr >> 15 == 1 {
+            Privilege::User
+        } else {
+            Privilege::Supervisor
+        });
+        self.priority = ((psr >> 8) & 0b111) as u8;
+        self.cond = match psr & 0b111 {
+            0b100 => ConditionFlag::Neg,
+            0b001 => ConditionFlag::Pos,
+            _ => ConditionFlag::Zero,
+        };
+    }
+    /// Changes the privilege mode, saving the stack pointer R6 of the old mode and loading the
+    /// one of the new mode.
+    pub fn switch_privilege(&mut self, privilege: Privilege) {
+        if privilege == self.privilege {
+            return;
+        }
+        let sp = self.general_purpose[6].0;
+        let new_sp = match privilege {
+            Privilege::Supervisor => {
+                self.saved_usp = sp;
+                self.saved_ssp
+            }
+            Privilege::User => {
+                self.saved_ssp = sp;
+                self.saved_usp
+            }
+        };
+        self.general_purpose[6] = Register(new_sp);
+        self.privilege = privilege;
+    }
 }
 impl Default for Registers {
     fn default() -> Self {
@@ -142,6 +220,22 @@ mod tests {
 
     use super::*;
     #[gtest]
+    fn test_psr_switches_stacks() {
+        let mut regs = Registers::new();
+        regs.set(6, from_binary(0x4000));
+        expect_that!(regs.psr(), eq(0x8002));
+        regs.set_psr(0x0401);
+        expect_that!(regs.privilege(), eq(Privilege::Supervisor));
+        expect_that!(regs.priority(), eq(4));
+        expect_that!(regs.get_conditional_register(), eq(ConditionFlag::Pos));
+        expect_that!(regs.get(6), eq(from_binary(INITIAL_SSP)));
+        regs.set(6, from_binary(0x2FFE));
+        regs.set_psr(0x8004);
+        expect_that!(regs.get(6), eq(from_binary(0x4000)));
+        regs.switch_privilege(Privilege::Supervisor);
+        expect_that!(regs.get(6), eq(from_binary(0x2FFE)));
+    }
+    #[gtest]
     fn test_condition_flag_values() {
         expect_that!(ConditionFlag::Pos as u8, eq(1));
         expect_that!(ConditionFlag::Zero as u8, eq(2));
@@ -0,0 +1,192 @@
+//! Serial line between two emulators or an emulator and the host, see
+//! [`Emulator::attach_serial`](crate::emulator::Emulator::attach_serial).
+//!
+//! Programs use four device registers:
+//! - `xFE0A` Serial Receive Status Register, bit 15 is set when a byte arrived and bit 14
+//!   enables the receive interrupt
+//! - `xFE0C` Serial Receive Data Register holding the received byte
+//! - `xFE0E` Serial Transmit Status Register, bit 15 is set when a byte can be sent
+//! - `xFE10` Serial Transmit Data Register, writing sends its low byte
+use crate::sync::lock;
+use std::collections::VecDeque;
+use std::io;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+
+/// One end of a serial line.
+pub trait SerialDevice {
+    /// Checks if a received byte is available, does not block.
+    fn check_received(&mut self) -> bool;
+    /// Takes the next received byte, `0` if none is available.
+    fn receive(&mut self) -> u8;
+    /// Checks if a byte can be sent without overflowing the transmit buffer.
+    fn can_transmit(&mut self) -> bool;
+    /// Sends `byte`, which is dropped if the transmit buffer is full.
+    fn transmit(&mut self, byte: u8);
+}
+
+/// End of a line created by [`link`], which buffers the bytes in transit.
+pub struct SerialEndpoint {
+    rx: Arc<Mutex<VecDeque<u8>>>,
+    tx: Arc<Mutex<VecDeque<u8>>>,
+    capacity: usize,
+}
+
+/// Connects two endpoints, each direction buffering up to `capacity` bytes.
+#[must_use]
+pub fn link(capacity: usize) -> (SerialEndpoint, SerialEndpoint) {
+    let a_to_b = Arc::new(Mutex::new(VecDeque::with_capacity(capacity)));
+    let b_to_a = Arc::new(Mutex::new(VecDeque::with_capacity(capacity)));
+    (
+        SerialEndpoint {
+            rx: b_to_a.clone(),
+            tx: a_to_b.clone(),
+            capacity,
+        },
+        SerialEndpoint {
+            rx: a_to_b,
+            tx: b_to_a,
+            capacity,
+        },
+    )
+}
+
+impl SerialDevice for SerialEndpoint {
+    fn check_received(&mut self) -> bool {
+        !lock(&self.rx).is_empty()
+    }
+    fn receive(&mut self) -> u8 {
+        lock(&self.rx).pop_front().unwrap_or_default()
+    }
+    fn can_transmit(&mut self) -> bool {
+        lock(&self.tx).len() < self.capacity
+    }
+    fn transmit(&mut self, byte: u8) {
+        if self.can_transmit() {
+            lock(&self.tx).push_back(byte);
+        }
+    }
+}
+
+/// Connects the line to a host `reader` and `writer`.
+///
+/// Checking for received bytes reads from `reader`, which blocks for terminals and sockets,
+/// so it is meant for files, pipes or buffers. Communication stops at the first error, which
+/// is available via [`HostSerial::error`].
+pub struct HostSerial<R: Read, W: Write> {
+    reader: R,
+    writer: W,
+    received: Option<u8>,
+    error: Option<io::Error>,
+}
+
+impl<R: Read, W: Write> HostSerial<R, W> {
+    pub const fn new(reader: R, writer: W) -> Self {
+        Self {
+            reader,
+            writer,
+            received: None,
+            error: None,
+        }
+    }
+    /// The error which stopped communication if there was one.
+    #[must_use]
+    pub const fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+    /// Returns the underlying reader and writer.
+    pub fn into_inner(self) -> (R, W) {
+        (self.reader, self.writer)
+    }
+}
+
+impl<R: Read, W: Write> SerialDevice for HostSerial<R, W> {
+    fn check_received(&mut self) -> bool {
+        if self.received.is_none() && self.error.is_none() {
+            let mut byte = [0];
+            match self.reader.read(&mut byte) {
+                Ok(1) => self.received = Some(byte[0]),
+                Ok(_) => {}
+                Err(e) => self.error = Some(e),
+            }
+        }
+        self.received.is_some()
+    }
+    fn receive(&mut self) -> u8 {
+        self.check_received();
+        self.received.take().unwrap_or_default()
+    }
+    fn can_transmit(&mut self) -> bool {
+        self.error.is_none()
+    }
+    fn transmit(&mut self, byte: u8) {
+        if self.error.is_none()
+            && let Err(e) = self.writer.write_all(&[byte])
+        {
+            self.error = Some(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::EmulatorBuilder;
+    use crate::emulator::test_helpers::StringWriter;
+    use crate::hardware::registers::from_binary;
+    use googletest::prelude::*;
+
+    #[gtest]
+    pub fn test_link_emulators() {
+        let sender = [
+            0x3000, // .ORIG x3000
+            0xA204, // WAIT: LDI R1, STSR
+            0x07FE, // BRzp WAIT
+            0x2004, // LD R0, CHAR
+            0xB002, // STI R0, STDR
+            0xF025, // HALT
+            0xFE0E, // STSR
+            0xFE10, // STDR
+            0x0058, // CHAR: 'X'
+        ];
+        let receiver = [
+            0x3000, // .ORIG x3000
+            0xA203, // WAIT: LDI R1, SRSR
+            0x07FE, // BRzp WAIT
+            0xA002, // LDI R0, SRDR
+            0xF025, // HALT
+            0xFE0A, // SRSR
+            0xFE0C, // SRDR
+        ];
+        let (a, b) = link(1);
+        let mut emu_a = EmulatorBuilder::new(&sender).build().unwrap();
+        let mut emu_b = EmulatorBuilder::new(&receiver).build().unwrap();
+        emu_a.attach_serial(a);
+        emu_b.attach_serial(b);
+        emu_a.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        emu_b.execute_with_stdout(&mut StringWriter::new()).unwrap();
+        expect_that!(emu_b.registers().get(0), eq(from_binary(0x58)));
+    }
+
+    #[gtest]
+    pub fn test_endpoint_buffer() {
+        let (mut a, mut b) = link(2);
+        a.transmit(1);
+        a.transmit(2);
+        expect_that!(a.can_transmit(), eq(false));
+        a.transmit(3);
+        expect_that!(b.check_received(), eq(true));
+        expect_that!([b.receive(), b.receive()], eq([1, 2]));
+        expect_that!(b.check_received(), eq(false));
+        expect_that!(a.check_received(), eq(false));
+    }
+
+    #[gtest]
+    pub fn test_host_serial() {
+        let mut host = HostSerial::new(&b"hi"[..], Vec::new());
+        expect_that!([host.receive(), host.receive()], eq([b'h', b'i']));
+        expect_that!(host.check_received(), eq(false));
+        host.transmit(b'!');
+        expect_that!(host.into_inner().1, eq(b"!"));
+    }
+}
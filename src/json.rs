@@ -0,0 +1,116 @@
+//! Minimal JSON writing for machine-readable output without further dependencies.
+use std::fmt::{Display, Write};
+
+/// Escapes `s` as JSON string including the surrounding quotes.
+pub fn string(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(res, "\\u{:04x}", u32::from(c));
+            }
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+/// Builds a JSON object field by field keeping the insertion order.
+pub struct Object {
+    buf: String,
+}
+impl Object {
+    pub fn new() -> Self {
+        Self {
+            buf: String::from("{"),
+        }
+    }
+    fn key(&mut self, key: &str) {
+        if self.buf.len() > 1 {
+            self.buf.push(',');
+        }
+        self.buf.push_str(&string(key));
+        self.buf.push(':');
+    }
+    /// Adds a string field.
+    pub fn str(mut self, key: &str, value: &str) -> Self {
+        self.key(key);
+        self.buf.push_str(&string(value));
+        self
+    }
+    /// Adds a string field, `null` for `None`.
+    pub fn opt_str(mut self, key: &str, value: Option<&str>) -> Self {
+        self.key(key);
+        self.buf
+            .push_str(&value.map_or_else(|| "null".to_owned(), string));
+        self
+    }
+    /// Adds an array of strings.
+    pub fn strs<'a>(mut self, key: &str, values: impl IntoIterator<Item = &'a str>) -> Self {
+        self.key(key);
+        self.buf.push('[');
+        for (i, value) in values.into_iter().enumerate() {
+            if i > 0 {
+                self.buf.push(',');
+            }
+            self.buf.push_str(&string(value));
+        }
+        self.buf.push(']');
+        self
+    }
+    /// Adds a number or boolean field.
+    pub fn num(mut self, key: &str, value: impl Display) -> Self {
+        self.key(key);
+        let _ = write!(self.buf, "{value}");
+        self
+    }
+    /// Adds an array of numbers.
+    pub fn nums<T: Display>(mut self, key: &str, values: impl IntoIterator<Item = T>) -> Self {
+        self.key(key);
+        self.buf.push('[');
+        for (i, value) in values.into_iter().enumerate() {
+            if i > 0 {
+                self.buf.push(',');
+            }
+            let _ = write!(self.buf, "{value}");
+        }
+        self.buf.push(']');
+        self
+    }
+    pub fn finish(mut self) -> String {
+        self.buf.push('}');
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+
+    #[gtest]
+    fn test_string_escaping() {
+        expect_that!(string("a\"b\\c\nd\u{1}"), eq(r#""a\"b\\c\nd\u0001""#));
+    }
+    #[gtest]
+    fn test_object() {
+        let json = Object::new()
+            .str("name", "x")
+            .num("n", 3)
+            .nums("a", [1, 2])
+            .opt_str("none", None)
+            .strs("s", ["a", "b"])
+            .finish();
+        expect_that!(
+            json,
+            eq(r#"{"name":"x","n":3,"a":[1,2],"none":null,"s":["a","b"]}"#)
+        );
+    }
+}
@@ -27,8 +27,20 @@
 //! # Errors
 //! - see [`LoadProgramError`](errors::LoadProgramError)
 //! - see [`ExecutionError`](errors::ExecutionError)
+//!
+//! # Stability
+//! The [`api`] module contains the curated, semver-stable surface of this crate.
+pub mod api;
+pub mod assembler;
+pub mod debugger;
 pub mod emulator;
 pub mod errors;
+pub mod golden;
 pub mod hardware;
+pub(crate) mod json;
 pub(crate) mod numbers;
+pub(crate) mod random;
+pub mod symbols;
+pub(crate) mod sync;
 mod terminal;
+pub mod test_script;
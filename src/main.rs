@@ -1,27 +1,1531 @@
+use clap::error::ErrorKind;
+use clap::{
+    ArgAction, ArgGroup, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum,
+};
+use clap_complete::Shell;
+use lc3_emulator::api::{AssembledProgram, EmulatorBuilder, EmulatorConfig};
+use lc3_emulator::assembler;
+use lc3_emulator::assembler::linker;
+use lc3_emulator::debugger::memory_editor::MemoryEditor;
 use lc3_emulator::emulator;
-use std::env;
+use lc3_emulator::emulator::breakpoints::Breakpoint;
+use lc3_emulator::emulator::disassembler;
+use lc3_emulator::emulator::image_format::ImageFormat;
+use lc3_emulator::emulator::snapshot::MachineState;
+use lc3_emulator::emulator::summary::RunSummary;
+use lc3_emulator::emulator::trace::{JsonTraceSink, TextTraceSink};
+use lc3_emulator::emulator::vcd::VcdTraceSink;
+use lc3_emulator::emulator::{Emulator, ExitStatus, HaltReason, StepResult, TrapMode};
+use lc3_emulator::errors::{AssembleProgramError, ExecutionError};
+use lc3_emulator::hardware::WatchKind;
+use lc3_emulator::hardware::keyboard::ScriptedInputProvider;
+use lc3_emulator::hardware::registers::ConditionFlag;
+use lc3_emulator::test_script::TestScript;
 use std::error::Error;
-use std::path::Path;
+use std::ffi::OsString;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, IsTerminal, Read, Write};
+use std::num::NonZeroU32;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Exit code of programs terminated by SIGINT, which CTRL-C sends in cooked mode
+const EXIT_CODE_INTERRUPTED: i32 = 130;
+/// Exit code of programs failing with an execution error or running past their end instead
+/// of executing `HALT`
+const EXIT_CODE_EXECUTION_ERROR: i32 = 2;
+/// Exit code of `test` if a case failed
+const EXIT_CODE_TEST_FAILED: i32 = 1;
+/// Exit code if the program, its symbol table or a test script cannot be loaded
+const EXIT_CODE_LOAD_ERROR: i32 = 3;
+/// Exit code of programs waiting for input after all input from a file or pipe was read
+const EXIT_CODE_INPUT_EXHAUSTED: i32 = 5;
+/// Exit code of programs stopped by `--timeout` or `--max-instructions`, like the one of the
+/// `timeout` command
+const EXIT_CODE_TIMEOUT: i32 = 124;
+/// File in the working directory with default options
+const CONFIG_FILE: &str = "lc3emu.toml";
+/// How often `--watch-source` checks whether the program changed
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Emulator for the Little Computer 3 (LC-3)
+#[derive(Parser)]
+#[command(
+    version,
+    after_help = "Programs are LC-3 obj files usually ending with .obj as output by the lc3as \
+                  assembler you can download from\n\
+                  https://highered.mheducation.com/sites/0072467509/student_view0/lc-3_simulator.html\n\
+                  Object files written by lc3tools are detected automatically.\n\
+                  Sources ending with .asm are assembled before loading.\n\
+                  Files ending with .hex are read as Intel HEX records.\n\
+                  Without a command the first argument is the program to run, f.e. \
+                  lc3-emulator prog.obj.\n\
+                  Default options are read from lc3emu.toml in the working directory, f.e. \
+                  plain = true in a table [run] for the run command or at the top level for \
+                  all commands having the option."
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// Ignores the default options of lc3emu.toml in the working directory
+    #[arg(long, global = true)]
+    no_config: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Executes a program
+    #[command(
+        after_help = "Keyboard input is read from stdin without a terminal if stdin is not \
+                      one, f.e. a pipe or a redirected file.\n\
+                      Exit codes: 0 if the program halted, R0 with --exit-with-r0, \
+                      2 if it failed or ran past its end instead of executing HALT, \
+                      3 if it could not be loaded, \
+                      5 if it waited for input after all input from a file or pipe was read, \
+                      124 if --timeout or --max-instructions stopped it, \
+                      130 if interrupted by CTRL-C."
+    )]
+    Run(Box<RunArgs>),
+    /// Executes a program step by step controlled by commands read from stdin
+    Debug(ImageArgs),
+    /// Prints the disassembly of the loaded regions of a program
+    Disasm(DisasmArgs),
+    /// Assembles a program into an obj file and a sym file next to it
+    ///
+    /// A single source must start with .ORIG. Several sources, or a single one with --origin,
+    /// are relocatable modules without .ORIG which are linked into one program.
+    Asm(AsmArgs),
+    /// Prints the address range, obvious data, strings, memory layout and load warnings of a
+    /// program
+    Inspect(ImageArgs),
+    /// Starts an empty machine executing instructions typed into stdin one at a time
+    Repl,
+    /// Runs a program repeatedly with virtualized I/O and reports its wall time and throughput
+    Bench(BenchArgs),
+    /// Runs a program once per case of a test script and reports the expectations not met
+    #[command(
+        after_help = "Example script, keys at the top level apply to all cases:\n\n\
+                      max_instructions = 100000\n\n\
+                      [[case]]\n\
+                      name = \"three times ten\"\n\
+                      input = \"3\"\n\
+                      output_contains = \"Result: 30\"\n\
+                      output_matches = ['Result: \\d+\\n']\n\
+                      registers = { R3 = 30, NZP = \"P\" }\n\
+                      memory = { RESULT = 30, x4000 = \"x001E\" }\n\n\
+                      Further keys are timeout in seconds and output with the exact output.\n\
+                      Exit codes: 0 if all cases passed, 1 if one failed, \
+                      3 if the program or script could not be loaded."
+    )]
+    Test(TestArgs),
+    /// Prints the completion script of a shell, f.e. for bash: source <(lc3-emulator
+    /// completions bash)
+    Completions(CompletionsArgs),
+    /// Debugs a program in a full-screen terminal UI
+    #[cfg(feature = "tui")]
+    Tui(ImageArgs),
+}
+
+#[derive(Args)]
+struct ImageArgs {
+    /// The program to load, optionally with further images loaded at their own origin, f.e.
+    /// os.obj prog.obj lib.obj: the first image at or above x3000 is the program, images
+    /// below are operating systems whose trap routines are used and the others further code
+    /// or data the program may call or read
+    #[arg(required = true, value_name = "FILE")]
+    files: Vec<String>,
+    /// Reads FILE as raw binary without .ORIG word loaded at ADDR
+    #[arg(long, value_name = "ADDR", value_parser = parse_origin)]
+    origin: Option<u16>,
+    /// Symbol table as output by lc3as, used for labels
+    #[arg(long, value_name = "SYM")]
+    symbols: Option<String>,
+    /// Starts execution at ADDR instead of the start of the program
+    #[arg(long, value_name = "ADDR", value_parser = parse_origin)]
+    entry: Option<u16>,
+}
+
+/// A file given on the command line, read up front to find the program among them.
+struct Image<'a> {
+    file: &'a str,
+    /// `None` if the file cannot be read, which loading it reports
+    origin: Option<u16>,
+    /// The program if the file is a source
+    assembled: Option<AssembledProgram>,
+}
+
+impl ImageArgs {
+    /// Loads the program, exits with [`EXIT_CODE_LOAD_ERROR`] if that fails.
+    fn load(&self) -> Emulator {
+        self.try_load().unwrap_or_else(|e| {
+            eprintln!("{e}");
+            process::exit(EXIT_CODE_LOAD_ERROR);
+        })
+    }
+    /// Loads the program and further images, assembling sources ending with .asm first, or
+    /// returns the message why that failed.
+    fn try_load(&self) -> Result<Emulator, String> {
+        if self.origin.is_some() && self.files.len() > 1 {
+            Cli::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "--origin requires a single FILE",
+                )
+                .exit();
+        }
+        let mut images = self
+            .files
+            .iter()
+            .map(|file| self.read_image(file))
+            .collect::<Result<Vec<_>, _>>()?;
+        let user_space = EmulatorConfig::default().user_space;
+        let program = images
+            .iter()
+            .position(|image| image.origin.is_some_and(|o| o >= user_space.start))
+            .unwrap_or(0);
+        let program = images.remove(program);
+        let mut emu = match program.assembled {
+            Some(assembled) => {
+                let mut emu = emulator::from_bytes(&assembled.to_bytes())
+                    .map_err(|e| format!("Error: {e}"))?;
+                emu.set_symbols(assembled.symbols);
+                emu
+            }
+            None => emulator::from_program_with_format(program.file, self.format(program.file))
+                .map_err(|e| format!("Error: {e}"))?,
+        };
+        for image in images {
+            match &image.assembled {
+                Some(assembled) => emu.load_image_bytes(&assembled.to_bytes()),
+                None => emu.load_image(image.file, self.format(image.file)),
+            }
+            .map_err(|e| format!("Error: {}: {e}", image.file))?;
+        }
+        if emu.memory().is_os_loaded() {
+            emu.set_trap_mode(TrapMode::Vectored);
+        }
+        if let Some(symbols) = &self.symbols {
+            emu.load_symbols(symbols)
+                .map_err(|e| format!("Error: {e}"))?;
+        }
+        if let Some(entry) = self.entry {
+            emu.set_start_pc(entry).map_err(|e| format!("Error: {e}"))?;
+        }
+        Ok(emu)
+    }
+    fn read_image<'a>(&self, file: &'a str) -> Result<Image<'a>, String> {
+        let path = Path::new(file);
+        let is_source = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("asm"));
+        if is_source && self.origin.is_none() {
+            let source = fs::read_to_string(path).map_err(|e| format!("Error: {file}: {e}"))?;
+            let program =
+                assembler::assemble(&source).map_err(|e| assemble_error_message(path, &e))?;
+            return Ok(Image {
+                file,
+                origin: program.words.first().copied(),
+                assembled: Some(program),
+            });
+        }
+        let origin = fs::read(path)
+            .ok()
+            .and_then(|data| self.format(file).to_obj_words(&data).ok())
+            .and_then(|words| words.first().copied());
+        Ok(Image {
+            file,
+            origin,
+            assembled: None,
+        })
+    }
+    fn format(&self, file: &str) -> ImageFormat {
+        self.origin.map_or_else(
+            || ImageFormat::from_extension(file),
+            |origin| ImageFormat::RawBinary { origin },
+        )
+    }
+    /// Loads the program with keyboard input only delivered via the `input` command, since
+    /// stdin is used for the commands of the debugger.
+    fn load_for_debugging(&self) -> Emulator {
+        let mut emu = self.load();
+        emu.set_input_provider(ScriptedInputProvider::new(""));
+        emu
+    }
+}
+
+#[derive(Args)]
+struct RunArgs {
+    #[command(flatten)]
+    image: ImageArgs,
+    /// Prints the occupied memory regions after loading
+    #[arg(long)]
+    layout: bool,
+    /// Describes every executed instruction in plain English on stderr
+    #[arg(long)]
+    explain: bool,
+    #[command(flatten)]
+    output: OutputArgs,
+    /// Limits execution to HZ clock cycles per second, one per instruction
+    #[arg(long, value_name = "HZ")]
+    clock: Option<NonZeroU32>,
+    /// Writes the keyboard input of the session to REPLAY
+    #[arg(long, value_name = "REPLAY")]
+    record: Option<PathBuf>,
+    /// Feeds the keyboard input recorded in REPLAY to the program
+    #[arg(long, value_name = "REPLAY", conflicts_with = "record")]
+    replay: Option<PathBuf>,
+    /// Writes the registers, PC and NZP per executed instruction to VCD
+    #[arg(long, value_name = "VCD")]
+    vcd: Option<PathBuf>,
+    /// Reads keyboard input from FILE instead of the terminal
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["record", "replay"])]
+    input: Option<PathBuf>,
+    /// Writes every executed instruction to stderr or the file given by --trace-file
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "text"
+    )]
+    trace: Option<TraceFormat>,
+    /// Writes the trace to FILE instead of stderr
+    #[arg(long, value_name = "FILE", requires = "trace")]
+    trace_file: Option<PathBuf>,
+    #[command(flatten)]
+    end: EndArgs,
+    #[command(flatten)]
+    dumps: DumpArgs,
+    /// Prints a summary of the run; json captures the program output into it, takes keyboard
+    /// input only from --input or a non-terminal stdin and ends the run at --break or --watch
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        default_value = "text",
+        conflicts_with_all = ["record", "replay"]
+    )]
+    format: ResultFormat,
+    /// Writes the JSON summary to FILE instead of stdout, f.e. /dev/fd/3, requires --format json
+    #[arg(long, value_name = "FILE")]
+    result_file: Option<PathBuf>,
+    /// Pauses before executing the instruction at ADDR, an address like x3010 or a label
+    #[arg(long = "break", value_name = "ADDR")]
+    breakpoints: Vec<String>,
+    /// Pauses after an instruction wrote to ADDR, an address like x4000 or a label
+    #[arg(long = "watch", value_name = "ADDR")]
+    watchpoints: Vec<String>,
+    /// Runs the program again whenever one of its files is saved, sources are assembled again,
+    /// and prints how the result differs from the previous run
+    #[arg(
+        long,
+        conflicts_with_all = ["record", "replay", "format", "breakpoints", "watchpoints", "dumps", "exit_with_r0"]
+    )]
+    watch_source: bool,
+}
+
+/// What is printed when the run ends.
+#[derive(Args)]
+#[command(group(ArgGroup::new("dumps").multiple(true)))]
+struct DumpArgs {
+    /// Prints the registers, condition flag and instruction count when the program ends
+    #[arg(id = "dump_state", long = "dump-state", group = "dumps")]
+    state: bool,
+    /// Prints a hex dump of the words from START up to excluding END when the program ends
+    #[arg(
+        id = "dump_memory",
+        long = "dump-memory",
+        value_name = "START:END",
+        value_parser = parse_range,
+        group = "dumps"
+    )]
+    memory: Option<Range<u16>>,
+    /// Writes the dumps to FILE instead of stdout
+    #[arg(
+        id = "dump_file",
+        long = "dump-file",
+        value_name = "FILE",
+        requires = "dumps"
+    )]
+    file: Option<PathBuf>,
+}
+
+/// When the run ends and how that is reported.
+#[derive(Args)]
+struct EndArgs {
+    /// Stops the program after executing N instructions
+    #[arg(long, value_name = "N")]
+    max_instructions: Option<u64>,
+    /// Stops the program after DURATION like 500ms, 5s or 2m
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    timeout: Option<Duration>,
+    /// Exits with the lowest 8 bits of R0 after the program halted
+    #[arg(long)]
+    exit_with_r0: bool,
+}
+
+/// How the program output is written.
+#[derive(Args)]
+struct OutputArgs {
+    /// Leaves out the "Input: " prompt of IN and "Program halted" of HALT from the output
+    #[arg(short, long)]
+    quiet: bool,
+    /// Writes the output unchanged without raw mode and cursor movement of the terminal,
+    /// keyboard input is then only delivered after pressing enter
+    #[arg(long)]
+    plain: bool,
+}
+
+impl RunArgs {
+    /// Rejects combinations of options clap cannot, since defaults count as given there.
+    fn validate(&self) -> Result<(), clap::Error> {
+        if self.result_file.is_some() && self.format != ResultFormat::Json {
+            return Err(Cli::command().error(
+                ErrorKind::ArgumentConflict,
+                "--result-file requires --format json",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ResultFormat {
+    /// Program output on stdout and messages on stderr
+    Text,
+    /// A single JSON object with halt reason, registers, instruction count, output and errors
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TraceFormat {
+    /// One line with disassembly and registers per instruction
+    Text,
+    /// One JSON object per instruction (JSON Lines)
+    Json,
+}
+
+#[derive(Args)]
+struct DisasmArgs {
+    #[command(flatten)]
+    image: ImageArgs,
+    /// Prints source which lc3as assembles to the same words again
+    #[arg(long)]
+    source: bool,
+}
+
+#[derive(Args)]
+struct AsmArgs {
+    /// The program, or modules placed one after the other in the given order
+    #[arg(required = true)]
+    sources: Vec<PathBuf>,
+    /// Address linked modules are loaded at [default: x3000]
+    #[arg(long, value_name = "ADDR", value_parser = parse_origin)]
+    origin: Option<u16>,
+    /// The obj file to write
+    #[arg(short, long, value_name = "OBJ")]
+    output: PathBuf,
+}
+
+#[derive(Args)]
+struct BenchArgs {
+    #[command(flatten)]
+    image: ImageArgs,
+    /// Number of measured runs
+    #[arg(short = 'n', long, value_name = "N", default_value_t = 10)]
+    iterations: u32,
+    /// Number of runs before the measured ones, f.e. to warm up caches
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    warmup: u32,
+    /// Reads keyboard input of every run from FILE, without it the program gets none
+    #[arg(long, value_name = "FILE")]
+    input: Option<PathBuf>,
+    /// Fails a run after it executed N instructions, f.e. for programs that never halt
+    #[arg(long, value_name = "N")]
+    max_instructions: Option<u64>,
+}
+
+#[derive(Args)]
+struct TestArgs {
+    #[command(flatten)]
+    image: ImageArgs,
+    /// The TOML test script declaring input, limits and expected output, registers and memory
+    #[arg(long, value_name = "TOML")]
+    script: PathBuf,
+}
+
+#[derive(Args)]
+struct CompletionsArgs {
+    /// The shell to complete commands and options in
+    shell: Shell,
+}
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let args = env::args().collect::<Vec<_>>();
-    if args.len() != 2 {
-        usage(args[0].as_str());
-        return Err("Exiting.".into());
-    }
-    let mut emu = emulator::from_program(args[1].as_str()).map_err(Box::<dyn Error>::from)?;
-    emu.execute().map_err(Box::<dyn Error>::from)
-}
-
-fn usage(program_name: &str) {
-    let program_name = Path::new(program_name).file_name().map_or_else(
-        || String::from(file!()),
-        |n| String::from_utf8_lossy(n.as_encoded_bytes()).to_string(),
-    );
-    eprintln!("Usage: {program_name} <FILE>");
-    eprintln!("\n<FILE> is a LC-3 obj file usually ending with .obj as output by the");
-    eprintln!("lc3as assembler you can download from");
-    eprintln!(
-        "https://highered.mheducation.com/sites/0072467509/student_view0/lc-3_simulator.html"
-    );
+    let mut command = Cli::command();
+    if !std::env::args_os().any(|arg| arg == "--no-config") {
+        command = apply_config(command);
+    }
+    let args = with_default_command(&command, std::env::args_os().collect());
+    let cli = Cli::from_arg_matches(&command.get_matches_from(args)).unwrap_or_else(|e| e.exit());
+    match cli.command {
+        Command::Run(args) => run(&args),
+        Command::Debug(args) => debug(&args),
+        Command::Disasm(args) => {
+            disasm(&args);
+            Ok(())
+        }
+        Command::Asm(args) => asm(&args),
+        Command::Inspect(args) => {
+            inspect(&args);
+            Ok(())
+        }
+        Command::Repl => repl(),
+        Command::Bench(args) => bench(&args),
+        Command::Test(args) => {
+            test(&args);
+            Ok(())
+        }
+        Command::Completions(args) => {
+            let mut command = Cli::command();
+            clap_complete::generate(
+                args.shell,
+                &mut command,
+                env!("CARGO_BIN_NAME"),
+                &mut io::stdout(),
+            );
+            Ok(())
+        }
+        #[cfg(feature = "tui")]
+        Command::Tui(args) => {
+            lc3_emulator::debugger::tui::run(&mut args.load_for_debugging())?;
+            Ok(())
+        }
+    }
+}
+
+/// `args` with the `run` command inserted if the first argument is a file instead of a
+/// command, so that `lc3-emulator prog.obj` runs the program as before there were commands.
+fn with_default_command(command: &clap::Command, mut args: Vec<OsString>) -> Vec<OsString> {
+    let first = (1..args.len()).find(|&idx| args[idx] != "--no-config");
+    if let Some(idx) = first {
+        let arg = args[idx].to_string_lossy();
+        if !arg.starts_with('-') && arg != "help" && command.find_subcommand(&*arg).is_none() {
+            args.insert(idx, "run".into());
+        }
+    }
+    args
+}
+
+/// Uses the options in [`CONFIG_FILE`] as defaults of `command`, exits if they are invalid.
+fn apply_config(mut command: clap::Command) -> clap::Command {
+    let Ok(text) = fs::read_to_string(CONFIG_FILE) else {
+        return command;
+    };
+    match with_config(command.clone(), &text) {
+        Ok(command) => command,
+        Err(e) => command
+            .error(ErrorKind::InvalidValue, format!("{CONFIG_FILE}: {e}"))
+            .exit(),
+    }
+}
+
+/// Sets the options at the top level of the TOML `text` as defaults of all subcommands having
+/// them and those in a table named like a subcommand as defaults of that one.
+fn with_config(mut command: clap::Command, text: &str) -> Result<clap::Command, String> {
+    let config: toml::Table = text
+        .parse()
+        .map_err(|e: toml::de::Error| e.to_string().trim_end().to_owned())?;
+    // options of a subcommand's table are set last to override the ones for all subcommands
+    let (tables, options): (Vec<_>, Vec<_>) = config.iter().partition(|(_, v)| v.is_table());
+    for (key, value) in options {
+        let values = config_values(key, value)?;
+        if !command
+            .get_subcommands()
+            .any(|sub| long_arg(sub, key).is_some())
+        {
+            return Err(format!("no command has the option --{key}"));
+        }
+        command = command.mut_subcommands(|sub| set_default(sub, key, &values));
+    }
+    for (name, table) in tables {
+        let sub = command
+            .find_subcommand(name)
+            .ok_or_else(|| format!("unknown command {name}"))?;
+        let mut sub = sub.clone();
+        for (key, value) in table.as_table().into_iter().flatten() {
+            if long_arg(&sub, key).is_none() {
+                return Err(format!("the {name} command has no option --{key}"));
+            }
+            sub = set_default(sub, key, &config_values(key, value)?);
+        }
+        command = command.mut_subcommand(name, |_| sub);
+    }
+    Ok(command)
+}
+
+/// The values of a config option as given on the command line.
+fn config_values(key: &str, value: &toml::Value) -> Result<Vec<String>, String> {
+    let scalar = |value: &toml::Value| match value {
+        toml::Value::String(s) => Ok(s.clone()),
+        toml::Value::Integer(n) => Ok(n.to_string()),
+        toml::Value::Float(n) => Ok(n.to_string()),
+        toml::Value::Boolean(b) => Ok(b.to_string()),
+        v => Err(format!("invalid value of {key}: {}", v.type_str())),
+    };
+    match value {
+        toml::Value::Array(values) => values.iter().map(scalar).collect(),
+        v => Ok(vec![scalar(v)?]),
+    }
+}
+
+fn long_arg<'a>(command: &'a clap::Command, long: &str) -> Option<&'a clap::Arg> {
+    command
+        .get_arguments()
+        .find(|arg| arg.get_long() == Some(long))
+}
+
+/// Uses `values` as default of the option `--long` of `command` if it has the option.
+fn set_default(command: clap::Command, long: &str, values: &[String]) -> clap::Command {
+    let Some(arg) = long_arg(&command, long) else {
+        return command;
+    };
+    let id = arg.get_id().clone();
+    let is_unset_flag = matches!(arg.get_action(), ArgAction::SetTrue) && values == ["false"];
+    if is_unset_flag {
+        return command;
+    }
+    command.mut_arg(id, |arg| arg.default_values(values))
+}
+
+fn run(args: &RunArgs) -> Result<(), Box<dyn Error>> {
+    args.validate().unwrap_or_else(|e| e.exit());
+    if args.watch_source {
+        return watch_source(args);
+    }
+    let mut emu = args.image.load();
+    if args.layout {
+        println!("Memory layout:");
+        for region in emu.layout() {
+            println!("  {region}");
+        }
+    }
+    if let Some(path) = &args.replay {
+        emu.replay(path)?;
+    }
+    if let Some(path) = &args.record {
+        emu.record_input(path)?;
+    }
+    configure(&mut emu, args)?;
+    add_stops(&mut emu, args);
+    let timed_out = start_timeout(&emu, args.end.timeout);
+    let res = match (read_scripted_input(args)?, args.format) {
+        (Some(input), ResultFormat::Json) => {
+            let mut output = Vec::new();
+            let res = emu.execute_scripted(&String::from_utf8_lossy(&input), &mut output);
+            let summary = RunSummary::new(&emu, &res, String::from_utf8_lossy(&output).into());
+            match &args.result_file {
+                Some(path) => fs::write(path, format!("{}\n", summary.to_json()))?,
+                None => println!("{}", summary.to_json()),
+            }
+            res
+        }
+        (Some(input), ResultFormat::Text) => {
+            let res = emu.execute_scripted(&String::from_utf8_lossy(&input), io::stdout());
+            pause_at_stops(&mut emu, res, |emu| emu.continue_scripted(io::stdout()))
+        }
+        (None, _) if args.output.plain => {
+            let res = emu.execute_plain();
+            pause_at_stops(&mut emu, res, Emulator::execute_plain)
+        }
+        (None, _) => {
+            let res = emu.execute();
+            pause_at_stops(&mut emu, res, Emulator::execute)
+        }
+    };
+    for diagnostic in emu.diagnostics() {
+        eprintln!("warning: {diagnostic}");
+    }
+    dump(&emu, &args.dumps)?;
+    if res.is_err() && !emu.call_stack().is_empty() {
+        eprintln!("Call stack, innermost first:");
+        for frame in emu.call_stack().iter().rev() {
+            eprintln!("  {frame}");
+        }
+    }
+    let Some(exit_code) = exit_code(&emu, res, &args.end, timed_out.load(Ordering::Relaxed)) else {
+        return Ok(());
+    };
+    // process::exit skips destructors, flushes trace files
+    drop(emu);
+    process::exit(exit_code);
+}
+
+/// Applies the options shared by single runs and `--watch-source`.
+fn configure(emu: &mut Emulator, args: &RunArgs) -> io::Result<()> {
+    if args.explain {
+        emu.set_explain(stderr());
+    }
+    emu.set_quiet(args.output.quiet);
+    if let Some(hz) = args.clock {
+        emu.set_clock(hz);
+    }
+    set_trace_sink(emu, args)?;
+    if let Some(max) = args.end.max_instructions {
+        emu.set_max_instructions(max);
+    }
+    Ok(())
+}
+
+/// Cancels the run of `emu` after `timeout`, the returned flag tells whether that happened.
+fn start_timeout(emu: &Emulator, timeout: Option<Duration>) -> Arc<AtomicBool> {
+    let timed_out = Arc::new(AtomicBool::new(false));
+    if let Some(timeout) = timeout {
+        let token = emu.cancel_token();
+        let timed_out = timed_out.clone();
+        thread::spawn(move || {
+            thread::sleep(timeout);
+            timed_out.store(true, Ordering::Relaxed);
+            token.cancel();
+        });
+    }
+    timed_out
+}
+
+/// Runs the program again whenever one of its files changes, printing how the run differs from
+/// the previous one, until interrupted.
+fn watch_source(args: &RunArgs) -> Result<(), Box<dyn Error>> {
+    let path = args.image.files.join(" ");
+    let mut modified = Vec::new();
+    let mut previous: Option<RunSummary> = None;
+    for run in 1.. {
+        eprintln!("Watching {path} for changes, press CTRL-C to stop");
+        loop {
+            let current: Vec<_> = args
+                .image
+                .files
+                .iter()
+                .map(|file| fs::metadata(file).and_then(|m| m.modified()).ok())
+                .collect();
+            if current != modified {
+                modified = current;
+                break;
+            }
+            thread::sleep(WATCH_INTERVAL);
+        }
+        eprintln!("=== Run {run} of {path}");
+        let mut emu = match args.image.try_load() {
+            Ok(emu) => emu,
+            Err(e) => {
+                eprintln!("{e}");
+                continue;
+            }
+        };
+        configure(&mut emu, args)?;
+        let input = match &args.input {
+            Some(input) => fs::read(input)?,
+            None => Vec::new(),
+        };
+        start_timeout(&emu, args.end.timeout);
+        let mut output = Vec::new();
+        let res = emu.execute_scripted(&String::from_utf8_lossy(&input), &mut output);
+        io::stdout().write_all(&output)?;
+        io::stdout().flush()?;
+        let summary = RunSummary::new(&emu, &res, String::from_utf8_lossy(&output).into());
+        for warning in &summary.warnings {
+            eprintln!("warning: {warning}");
+        }
+        match (&summary.error, summary.halt_reason) {
+            (Some(e), _) => eprintln!("Error: {e}"),
+            (None, HaltReason::InputExhausted) => {
+                eprintln!("Program waits for input after all input was read");
+            }
+            (None, _) => eprintln!("Ended after {} instructions", summary.instructions_executed),
+        }
+        if let Some(previous) = &previous {
+            let changes = summary.changes_since(previous);
+            if changes.is_empty() {
+                eprintln!("No changes since the previous run");
+            } else {
+                eprintln!("Changes since the previous run:");
+                for change in changes {
+                    eprintln!("  {change}");
+                }
+            }
+        }
+        previous = Some(summary);
+    }
+    Ok(())
+}
+
+/// The exit code of a run which ended with `res` other than 0, printing why it ended.
+fn exit_code(
+    emu: &Emulator,
+    res: Result<ExitStatus, ExecutionError>,
+    args: &EndArgs,
+    timed_out: bool,
+) -> Option<i32> {
+    // the terminal is not in raw mode anymore after returning from execute
+    let exit_code = match res {
+        Err(ExecutionError::Interrupted(pc)) if timed_out => {
+            eprintln!("Timed out at {pc:#06X}");
+            EXIT_CODE_TIMEOUT
+        }
+        Err(e @ ExecutionError::InstructionLimitExceeded { .. }) => {
+            eprintln!("Error: {e}");
+            EXIT_CODE_TIMEOUT
+        }
+        Err(ExecutionError::Interrupted(pc)) => {
+            eprintln!(
+                "Interrupted at {pc:#06X} after {} instructions",
+                emu.execution_summary().instructions_executed
+            );
+            EXIT_CODE_INTERRUPTED
+        }
+        Err(e) => {
+            eprintln!("Error: {e}");
+            EXIT_CODE_EXECUTION_ERROR
+        }
+        Ok(status) => match status.halt_reason {
+            HaltReason::HaltTrap | HaltReason::McrCleared if args.exit_with_r0 => {
+                i32::from(emu.state().register(0).as_binary() & 0xFF)
+            }
+            HaltReason::RanPastProgramEnd => EXIT_CODE_EXECUTION_ERROR,
+            HaltReason::InputExhausted => {
+                eprintln!("Program waits for input after all input was read");
+                EXIT_CODE_INPUT_EXHAUSTED
+            }
+            _ => return None,
+        },
+    };
+    Some(exit_code)
+}
+
+/// Keyboard input read up front from `--input` or stdin if it is no terminal, always for
+/// `--format json`.
+fn read_scripted_input(args: &RunArgs) -> io::Result<Option<Vec<u8>>> {
+    let mut input = Vec::new();
+    match &args.input {
+        Some(path) => input = fs::read(path)?,
+        None if !io::stdin().is_terminal() && args.replay.is_none() && args.record.is_none() => {
+            io::stdin().read_to_end(&mut input)?;
+        }
+        None if args.format == ResultFormat::Json => {}
+        None => return Ok(None),
+    }
+    Ok(Some(input))
+}
+
+/// Attaches the sink for `--vcd` or `--trace`.
+fn set_trace_sink(emu: &mut Emulator, args: &RunArgs) -> io::Result<()> {
+    if let Some(path) = &args.vcd {
+        emu.add_trace_sink(VcdTraceSink::new(BufWriter::new(File::create(path)?)));
+    }
+    if let Some(format) = args.trace {
+        let out: Box<dyn Write + Send> = match &args.trace_file {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => stderr(),
+        };
+        match format {
+            TraceFormat::Text => emu.add_trace_sink(TextTraceSink::new(out)),
+            TraceFormat::Json => emu.add_trace_sink(JsonTraceSink::new(out)),
+        }
+    }
+    Ok(())
+}
+
+/// Adds the breakpoints of `--break` and watchpoints of `--watch`, exits on unknown addresses.
+fn add_stops(emu: &mut Emulator, args: &RunArgs) {
+    let resolve = |emu: &Emulator, text: &str| {
+        resolve_address(emu, text)
+            .unwrap_or_else(|e| Cli::command().error(ErrorKind::InvalidValue, e).exit())
+    };
+    for text in &args.breakpoints {
+        let address = resolve(emu, text);
+        emu.add_breakpoint(Breakpoint::new(address));
+    }
+    for text in &args.watchpoints {
+        let address = resolve(emu, text);
+        emu.add_watchpoint(address, WatchKind::Write);
+    }
+}
+
+/// Prints the machine state while `res` is a stop at a breakpoint or watchpoint and offers to
+/// continue, step or quit. Without a terminal to read the choice from execution continues.
+fn pause_at_stops(
+    emu: &mut Emulator,
+    mut res: Result<ExitStatus, ExecutionError>,
+    resume: impl Fn(&mut Emulator) -> Result<ExitStatus, ExecutionError>,
+) -> Result<ExitStatus, ExecutionError> {
+    loop {
+        let _ = io::stdout().flush();
+        match res.as_ref().map(|status| status.halt_reason) {
+            Ok(HaltReason::Breakpoint(hit)) => eprintln!("Breakpoint at x{:04X}", hit.address),
+            Ok(HaltReason::Watchpoint(hit)) => eprintln!(
+                "Watchpoint x{:04X} written at x{:04X}: x{:04X} -> x{:04X}",
+                hit.access.address, hit.pc, hit.access.old, hit.access.new
+            ),
+            _ => return res,
+        }
+        loop {
+            let _ = write_registers(&mut io::stderr(), &emu.state());
+            let pc = emu.state().pc();
+            for line in emu.disassemble(pc..pc.saturating_add(1)) {
+                eprintln!("{line}");
+            }
+            if !io::stdin().is_terminal() {
+                res = resume(emu);
+                break;
+            }
+            let choice = read_pause_choice().unwrap_or('q');
+            if choice == 'q' {
+                return res;
+            }
+            let stepped = choice == 's'
+                && matches!(
+                    emu.run_until(1, &mut io::stdout(), |_| false),
+                    Ok(StepResult::BudgetExhausted
+                        | StepResult::Breakpoint(_)
+                        | StepResult::Watchpoint(_))
+                );
+            if !stepped {
+                // also after a step ending the run, which provides the exit status f.e. of a halt
+                res = resume(emu);
+                break;
+            }
+            let _ = io::stdout().flush();
+        }
+    }
+}
+
+/// Reads whether to continue, step or quit as `c`, `s` or `q` from stdin, `None` at its end.
+fn read_pause_choice() -> Option<char> {
+    loop {
+        eprint!("c(ontinue), s(tep) or q(uit)? ");
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+            return None;
+        }
+        match line.trim() {
+            "" | "c" | "continue" => return Some('c'),
+            "s" | "step" => return Some('s'),
+            "q" | "quit" => return Some('q'),
+            _ => {}
+        }
+    }
+}
+
+/// Writes the dumps requested by `--dump-state` and `--dump-memory`.
+fn dump(emu: &Emulator, args: &DumpArgs) -> io::Result<()> {
+    if !args.state && args.memory.is_none() {
+        return Ok(());
+    }
+    let mut out: Box<dyn Write> = match &args.file {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout()),
+    };
+    let state = emu.state();
+    if args.state {
+        write_registers(&mut out, &state)?;
+        writeln!(
+            out,
+            "Instructions executed: {}",
+            state.instructions_executed()
+        )?;
+    }
+    if let Some(range) = args.memory.clone() {
+        let words = state.memory_slice(range.clone());
+        for (address, line) in range.step_by(8).zip(words.chunks(8)) {
+            write!(out, "x{address:04X}:")?;
+            for word in line {
+                write!(out, " x{word:04X}")?;
+            }
+            writeln!(out)?;
+        }
+    }
+    out.flush()
+}
+
+fn write_registers(out: &mut impl Write, state: &MachineState) -> io::Result<()> {
+    for r in 0..8 {
+        let separator = if r == 0 { "" } else { "  " };
+        write!(
+            out,
+            "{separator}R{r} x{:04X}",
+            state.register(r).as_binary()
+        )?;
+    }
+    let nzp = match state.condition() {
+        ConditionFlag::Neg => 'N',
+        ConditionFlag::Zero => 'Z',
+        ConditionFlag::Pos => 'P',
+    };
+    writeln!(out, "\nPC x{:04X}  NZP {nzp}", state.pc())
+}
+
+/// Stderr for output next to the program output, like traces and explanations.
+fn stderr() -> Box<dyn Write + Send> {
+    if io::stderr().is_terminal() {
+        Box::new(RawTerminalWriter(io::stderr()))
+    } else {
+        Box::new(io::stderr())
+    }
+}
+
+/// Starts lines at the first column of terminals in raw mode, which the terminal is in while
+/// executing the program.
+struct RawTerminalWriter<W: Write>(W);
+
+impl<W: Write> Write for RawTerminalWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for (idx, line) in buf.split(|b| *b == b'\n').enumerate() {
+            if idx > 0 {
+                self.0.write_all(b"\r\n")?;
+            }
+            self.0.write_all(line)?;
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+const DEBUG_HELP: &str = "\
+s, step             executes the next instruction
+n, next             executes the next instruction, subroutine calls as a whole
+f, finish           executes until the current subroutine returns
+c, continue         executes until a breakpoint is reached or the program halts
+b, break <ADDR>     sets a breakpoint at an address like x3004 or a label
+r, registers        prints the registers
+l, list             prints the next instructions
+i, input <TEXT>     delivers TEXT followed by a newline as keyboard input
+q, quit             exits the debugger";
+
+fn debug(args: &ImageArgs) -> Result<(), Box<dyn Error>> {
+    let mut emu = args.load_for_debugging();
+    let mut stdout = io::stdout();
+    println!("Type help for a list of commands.");
+    print_next_instruction(&emu);
+    for line in io::stdin().lines() {
+        let line = line?;
+        let line = line.trim();
+        let (command, argument) = line
+            .split_once(' ')
+            .map_or((line, ""), |(c, a)| (c, a.trim()));
+        let res = match command {
+            "s" | "step" => emu.run_until(1, &mut stdout, |_| false),
+            "n" | "next" => emu.step_over(u64::MAX, &mut stdout),
+            "f" | "finish" => emu.step_out(u64::MAX, &mut stdout),
+            "c" | "continue" => emu.run_until(u64::MAX, &mut stdout, |_| false),
+            "q" | "quit" => break,
+            _ => {
+                inspect_in_debugger(&mut emu, command, argument);
+                continue;
+            }
+        };
+        stdout.flush()?;
+        match res {
+            Ok(StepResult::Halted) => println!("Program halted"),
+            Ok(StepResult::WaitingForInput) => {
+                println!("Program waits for keyboard input, deliver it via input <TEXT>");
+            }
+            Ok(StepResult::Breakpoint(hit)) => println!("Breakpoint at x{:04X}", hit.address),
+            Ok(_) => {}
+            Err(e) => println!("Error: {e}"),
+        }
+        print_next_instruction(&emu);
+    }
+    Ok(())
+}
+
+/// Executes the debugger commands not executing instructions.
+fn inspect_in_debugger(emu: &mut Emulator, command: &str, argument: &str) {
+    match command {
+        "" => {}
+        "b" | "break" => match resolve_address(emu, argument) {
+            Ok(address) => {
+                emu.add_breakpoint(Breakpoint::new(address));
+                println!("Breakpoint set at x{address:04X}");
+            }
+            Err(e) => println!("{e}"),
+        },
+        "r" | "registers" => {
+            if let Err(e) = write_registers(&mut io::stdout(), &emu.state()) {
+                println!("Error: {e}");
+            }
+        }
+        "l" | "list" => {
+            let pc = emu.state().pc();
+            for line in emu.disassemble(pc..pc.saturating_add(10)) {
+                println!("{line}");
+            }
+        }
+        "i" | "input" => emu.queue_input(&format!("{argument}\n")),
+        "h" | "help" => println!("{DEBUG_HELP}"),
+        _ => println!("Unknown command {command}, type help for a list of commands"),
+    }
+}
+
+/// The address `text` like x3004 or a label stands for.
+/// The address of the label `text` or the address `text` like x3004, a label like `FACE` is
+/// not taken for a hexadecimal address.
+fn resolve_address(emu: &Emulator, text: &str) -> Result<u16, String> {
+    emu.symbols()
+        .address_of(text)
+        .or_else(|| parse_origin(text).ok())
+        .ok_or_else(|| format!("Unknown address {text}"))
+}
+
+fn print_next_instruction(emu: &Emulator) {
+    let pc = emu.state().pc();
+    for line in emu.disassemble(pc..pc.saturating_add(1)) {
+        println!("{line}");
+    }
+}
+
+const REPL_HELP: &str = "\
+<INSTRUCTION>       assembles an instruction like ADD R1, R1, #5 at the PC and executes it
+<WORD>              stores a word like x1265 or #-1 at the PC and executes it
+pc <ADDR>           continues at an address like x3100
+i, input <TEXT>     delivers TEXT followed by a newline as keyboard input
+h, help             prints this help
+q, quit             exits";
+
+fn repl() -> Result<(), Box<dyn Error>> {
+    // The whole user space is loaded so every address the PC reaches is part of the program.
+    let user_space = EmulatorConfig::default().user_space;
+    let image: Vec<u16> = std::iter::once(user_space.start)
+        .chain(user_space.map(|_| 0))
+        .collect();
+    let mut emu = EmulatorBuilder::new(&image)
+        .with_input_provider(ScriptedInputProvider::new(""))
+        .build()?;
+    let mut editor = MemoryEditor::new();
+    let mut stdout = io::stdout();
+    println!("Type an instruction or a word to execute it at the PC, help for a list of commands.");
+    write_registers(&mut stdout, &emu.state())?;
+    for line in io::stdin().lines() {
+        let line = line?;
+        let line = line.trim();
+        let (command, argument) = line
+            .split_once(' ')
+            .map_or((line, ""), |(c, a)| (c, a.trim()));
+        let res = match command {
+            "" => continue,
+            "q" | "quit" => break,
+            "h" | "help" => {
+                println!("{REPL_HELP}");
+                continue;
+            }
+            "pc" => {
+                let res = resolve_address(&emu, argument)
+                    .and_then(|address| emu.set_pc(address).map_err(|e| e.to_string()));
+                if let Err(e) = res {
+                    println!("{e}");
+                }
+                write_registers(&mut stdout, &emu.state())?;
+                continue;
+            }
+            "i" | "input" => {
+                emu.queue_input(&format!("{argument}\n"));
+                emu.run_until(1, &mut stdout, |_| false)
+            }
+            _ => {
+                let pc = emu.state().pc();
+                if let Err(e) = editor.edit(&mut emu, pc, line) {
+                    println!("Error: {e}");
+                    continue;
+                }
+                // Resumes a halted machine
+                emu.set_pc(pc)?;
+                emu.run_until(1, &mut stdout, |_| false)
+            }
+        };
+        stdout.flush()?;
+        match res {
+            Ok(StepResult::WaitingForInput) => {
+                println!("Program waits for keyboard input, deliver it via input <TEXT>");
+            }
+            Ok(_) => {}
+            Err(e) => println!("Error: {e}"),
+        }
+        write_registers(&mut stdout, &emu.state())?;
+    }
+    Ok(())
+}
+
+fn disasm(args: &DisasmArgs) {
+    let emu = args.image.load();
+    for region in emu.layout() {
+        let end = u16::try_from(region.end()).unwrap_or(u16::MAX);
+        if args.source {
+            let words = emu.state().memory_slice(region.origin..end);
+            print!("{}", disassembler::disassemble(words, region.origin));
+        } else {
+            for line in emu.disassemble(region.origin..end) {
+                println!("{line}");
+            }
+        }
+    }
+}
+
+fn asm(args: &AsmArgs) -> Result<(), Box<dyn Error>> {
+    let sources = args
+        .sources
+        .iter()
+        .map(fs::read_to_string)
+        .collect::<Result<Vec<_>, _>>()?;
+    if let ([source], [path], None) = (sources.as_slice(), args.sources.as_slice(), args.origin) {
+        let program = assembler::assemble(source).unwrap_or_else(|e| {
+            eprintln!("{}", assemble_error_message(path, &e));
+            process::exit(1);
+        });
+        fs::write(&args.output, program.to_bytes())?;
+        fs::write(
+            args.output.with_extension("sym"),
+            program.symbols.to_string(),
+        )?;
+        return Ok(());
+    }
+    let modules: Vec<&str> = sources.iter().map(String::as_str).collect();
+    let program =
+        linker::link_program(&modules, args.origin.unwrap_or(0x3000)).unwrap_or_else(|e| {
+            match linker::locate_error(&modules, e) {
+                Ok((module, e)) => {
+                    eprintln!("{}", assemble_error_message(&args.sources[module], &e));
+                }
+                Err(e) => eprintln!("{e}"),
+            }
+            process::exit(1);
+        });
+    fs::write(&args.output, program.to_bytes())?;
+    fs::write(
+        args.output.with_extension("sym"),
+        program.symbols.to_string(),
+    )?;
+    Ok(())
+}
+
+/// `e` prefixed with `path` and, if it has one, its line and column like compilers do.
+fn assemble_error_message(path: &Path, e: &AssembleProgramError) -> String {
+    match e {
+        AssembleProgramError::Assemble { .. } | AssembleProgramError::MisplacedOrig { .. } => {
+            format!("{}:{e}", path.display())
+        }
+        _ => format!("{}: {e}", path.display()),
+    }
+}
+
+fn inspect(args: &ImageArgs) {
+    let emu = args.load();
+    let info = emu.image_info();
+    let range = info.address_range();
+    println!("Origin: x{:04X}", info.origin);
+    println!("Words: {}", info.length);
+    println!("Address range: x{:04X}-x{:04X}", range.start, range.end - 1);
+    if info.data_regions.is_empty() {
+        println!("No obvious data regions");
+    } else {
+        println!("Obvious data regions:");
+        for region in &info.data_regions {
+            println!("  x{:04X}-x{:04X}", region.start, region.end - 1);
+        }
+    }
+    if !info.strings.is_empty() {
+        println!("Strings:");
+        for string in &info.strings {
+            println!("  x{:04X} {:?}", string.address, string.text);
+        }
+    }
+    println!("Memory layout:");
+    for region in emu.layout() {
+        println!("  {region}");
+    }
+    let warnings = emu.validate();
+    if warnings.is_empty() {
+        println!("No load warnings");
+    } else {
+        println!("Load warnings:");
+        for warning in warnings {
+            println!("  {warning}");
+        }
+    }
+}
+
+fn bench(args: &BenchArgs) -> Result<(), Box<dyn Error>> {
+    let mut emu = args.image.load();
+    if let Some(max) = args.max_instructions {
+        emu.set_max_instructions(max);
+    }
+    let input = match &args.input {
+        Some(path) => String::from_utf8_lossy(&fs::read(path)?).into_owned(),
+        None => String::new(),
+    };
+    let report = emu
+        .bench(args.warmup, &input)
+        .and_then(|_| emu.bench(args.iterations, &input))
+        .unwrap_or_else(|e| {
+            eprintln!("Error: {e}");
+            process::exit(EXIT_CODE_EXECUTION_ERROR);
+        });
+    println!("{report}");
+    Ok(())
+}
+
+fn test(args: &TestArgs) {
+    let script = TestScript::load(&args.script).unwrap_or_else(|e| {
+        eprintln!("Error: {e}");
+        process::exit(EXIT_CODE_LOAD_ERROR);
+    });
+    let mut emu = args.image.load();
+    let reports = script.run(&mut emu);
+    for report in &reports {
+        if report.passed() {
+            println!("PASS {}", report.name);
+            continue;
+        }
+        println!("FAIL {}", report.name);
+        for failure in &report.failures {
+            for line in failure.lines() {
+                println!("  {line}");
+            }
+        }
+        if report.output.is_empty() {
+            println!("  No output");
+            continue;
+        }
+        println!("  Output:");
+        for line in report.output.lines() {
+            println!("  | {line}");
+        }
+    }
+    let passed = reports.iter().filter(|r| r.passed()).count();
+    println!("{passed} of {} cases passed", reports.len());
+    if passed < reports.len() {
+        process::exit(EXIT_CODE_TEST_FAILED);
+    }
+}
+
+/// Parses `500ms`, `5s`, `2m`, `1h` or `5`, which is seconds.
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    let unit_start = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(unit_start);
+    let seconds_per_unit = match unit {
+        "ms" => 0.001,
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => return Err(format!("unknown unit {unit}, expected ms, s, m or h")),
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .and_then(|n| Duration::try_from_secs_f64(n * seconds_per_unit).ok())
+        .filter(|duration| !duration.is_zero())
+        .ok_or_else(|| format!("{text} is not a positive duration"))
+}
+
+/// Parses `x4000:x4020` into the range of addresses from the first up to excluding the second.
+fn parse_range(text: &str) -> Result<Range<u16>, String> {
+    let (start, end) = text
+        .split_once(':')
+        .ok_or_else(|| format!("{text} is not of the form START:END"))?;
+    let start = parse_origin(start).map_err(|e| format!("invalid START {start}: {e}"))?;
+    let end = parse_origin(end).map_err(|e| format!("invalid END {end}: {e}"))?;
+    if start > end {
+        return Err(format!("START {start:#06X} is after END {end:#06X}"));
+    }
+    Ok(start..end)
+}
+
+/// Parses `x3000` or `0x3000`.
+fn parse_origin(text: &str) -> Result<u16, std::num::ParseIntError> {
+    let hex = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix(['x', 'X']))
+        .unwrap_or(text);
+    u16::from_str_radix(hex, 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+    use yare::parameterized;
+
+    fn parse(config: &str, args: &[&str]) -> Cli {
+        let command = with_config(Cli::command(), config).unwrap();
+        let matches = command
+            .try_get_matches_from(std::iter::once("lc3-emulator").chain(args.iter().copied()))
+            .unwrap();
+        Cli::from_arg_matches(&matches).unwrap()
+    }
+    fn run_args(config: &str, args: &[&str]) -> Box<RunArgs> {
+        let mut all = vec!["run", "prog.obj"];
+        all.extend(args);
+        match parse(config, &all).command {
+            Command::Run(args) => args,
+            _ => panic!("run command expected"),
+        }
+    }
+    fn bench_max_instructions(config: &str) -> Option<u64> {
+        match parse(config, &["bench", "prog.obj"]).command {
+            Command::Bench(args) => args.max_instructions,
+            _ => panic!("bench command expected"),
+        }
+    }
+
+    #[parameterized(
+        file = { &["prog.obj"], &["run", "prog.obj"] },
+        file_after_global_option = {
+            &["--no-config", "prog.obj", "--plain"], &["--no-config", "run", "prog.obj", "--plain"]
+        },
+        command = { &["disasm", "prog.obj"], &["disasm", "prog.obj"] },
+        option = { &["--version"], &["--version"] },
+        help = { &["help", "run"], &["help", "run"] },
+    )]
+    #[test_macro(gtest)]
+    fn test_default_command(args: &[&str], expected: &[&str]) {
+        let args = std::iter::once("lc3-emulator")
+            .chain(args.iter().copied())
+            .map(OsString::from)
+            .collect();
+        let res = with_default_command(&Cli::command(), args);
+        expect_that!(res[1..], eq(expected));
+    }
+    #[gtest]
+    pub fn test_config_top_level_applies_to_all_commands() {
+        let config = "max-instructions = 5";
+        expect_that!(run_args(config, &[]).end.max_instructions, some(eq(5)));
+        expect_that!(bench_max_instructions(config), some(eq(5)));
+    }
+    #[gtest]
+    pub fn test_config_command_table_overrides_top_level() {
+        let config = "max-instructions = 5\n[run]\nmax-instructions = 7";
+        expect_that!(run_args(config, &[]).end.max_instructions, some(eq(7)));
+        expect_that!(bench_max_instructions(config), some(eq(5)));
+    }
+    #[gtest]
+    pub fn test_config_command_line_overrides_config() {
+        let args = run_args("[run]\nmax-instructions = 7", &["--max-instructions", "9"]);
+        expect_that!(args.end.max_instructions, some(eq(9)));
+    }
+    #[gtest]
+    pub fn test_config_arrays() {
+        let args = run_args("[run]\nbreak = [\"x3004\", \"LOOP\"]", &[]);
+        expect_that!(args.breakpoints, elements_are![eq("x3004"), eq("LOOP")]);
+    }
+    #[gtest]
+    pub fn test_config_flags() {
+        expect_that!(run_args("[run]\nexplain = true", &[]).explain, eq(true));
+        expect_that!(run_args("[run]\nexplain = false", &[]).explain, eq(false));
+        // an unset flag in the config can still be set on the command line
+        expect_that!(
+            run_args("[run]\nexplain = false", &["--explain"]).explain,
+            eq(true)
+        );
+    }
+    #[parameterized(
+        unknown_option = { "nope = 1", "no command has the option --nope" },
+        unknown_command = { "[nope]\nexplain = true", "unknown command nope" },
+        unknown_command_option = {
+            "[bench]\nexplain = true", "the bench command has no option --explain"
+        },
+        invalid_value = { "[run]\nexplain = { a = 1 }", "invalid value of explain: table" },
+    )]
+    #[test_macro(gtest)]
+    fn test_config_errors(config: &str, expected: &str) {
+        expect_that!(
+            with_config(Cli::command(), config).map(|_| ()),
+            err(eq(expected))
+        );
+    }
+
+    #[gtest]
+    pub fn test_result_file_requires_json_format() {
+        let res = run_args("", &["--result-file", "result.json"]).validate();
+        expect_that!(
+            res.map_err(|e| e.kind()),
+            err(eq(ErrorKind::ArgumentConflict))
+        );
+        let res = run_args("", &["--format", "json", "--result-file", "result.json"]).validate();
+        expect_that!(res, ok(eq(&())));
+    }
+    #[gtest]
+    pub fn test_vcd_and_trace_are_both_written() {
+        let dir = std::env::temp_dir();
+        let vcd = dir.join(format!("lc3-cli-{}.vcd", process::id()));
+        let trace = dir.join(format!("lc3-cli-{}.trace", process::id()));
+        let args = run_args(
+            "",
+            &[
+                "--vcd",
+                vcd.to_str().unwrap(),
+                "--trace",
+                "--trace-file",
+                trace.to_str().unwrap(),
+            ],
+        );
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        configure(&mut emu, &args).unwrap();
+        emu.execute_scripted("", io::sink()).unwrap();
+        // flushes both files
+        drop(emu);
+        let (vcd_text, trace_text) = (
+            fs::read_to_string(&vcd).unwrap(),
+            fs::read_to_string(&trace).unwrap(),
+        );
+        let _ = (fs::remove_file(vcd), fs::remove_file(trace));
+        expect_that!(vcd_text, contains_substring("$enddefinitions"));
+        expect_that!(trace_text, contains_substring("HALT"));
+    }
+
+    #[parameterized(
+        milliseconds = { "500ms", Duration::from_millis(500) },
+        seconds = { "5s", Duration::from_secs(5) },
+        fraction = { "1.5s", Duration::from_millis(1500) },
+        minutes = { "2m", Duration::from_secs(120) },
+        hours = { "1h", Duration::from_secs(3600) },
+        no_unit = { "5", Duration::from_secs(5) },
+    )]
+    #[test_macro(gtest)]
+    fn test_parse_duration(text: &str, expected: Duration) {
+        expect_that!(parse_duration(text), ok(eq(&expected)));
+    }
+    #[parameterized(
+        unknown_unit = { "5d" },
+        zero = { "0s" },
+        empty = { "" },
+        negative = { "-1s" },
+    )]
+    #[test_macro(gtest)]
+    fn test_parse_duration_errors(text: &str) {
+        expect_that!(parse_duration(text), err(anything()));
+    }
+
+    #[gtest]
+    pub fn test_parse_range() {
+        expect_that!(parse_range("x4000:x4020"), ok(eq(&(0x4000..0x4020))));
+        expect_that!(parse_range("0x4000:4000"), ok(eq(&(0x4000..0x4000))));
+        expect_that!(
+            parse_range("x4000"),
+            err(eq("x4000 is not of the form START:END"))
+        );
+        expect_that!(
+            parse_range("x4020:x4000"),
+            err(eq("START 0x4020 is after END 0x4000"))
+        );
+        expect_that!(
+            parse_range("x4000:LOOP"),
+            err(starts_with("invalid END LOOP"))
+        );
+    }
 }
@@ -0,0 +1,52 @@
+//! Small, dependency free pseudo-random number generation.
+//!
+//! Not suitable for anything security related, the only goal is reproducibility from a seed.
+
+/// [SplitMix64](https://prng.di.unimi.it/splitmix64.c) generator.
+#[derive(Debug, Clone)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+    pub const fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    /// Returns a value in `0..=max`.
+    pub const fn next_up_to(&mut self, max: u64) -> u64 {
+        if max == u64::MAX {
+            self.next_u64()
+        } else {
+            self.next_u64() % (max + 1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+
+    #[gtest]
+    fn test_same_seed_same_sequence() {
+        let mut a = SplitMix64::new(4711);
+        let mut b = SplitMix64::new(4711);
+        for _ in 0..100 {
+            expect_that!(a.next_u64(), eq(b.next_u64()));
+        }
+    }
+    #[gtest]
+    fn test_next_up_to_in_range() {
+        let mut rng = SplitMix64::new(1);
+        for _ in 0..1000 {
+            expect_that!(rng.next_up_to(7), le(7));
+        }
+    }
+}
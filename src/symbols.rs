@@ -0,0 +1,188 @@
+//! Symbol tables as written by `lc3as` into `.sym` files next to the `.obj` file.
+//!
+//! Used to print labels like `LOOP` instead of raw addresses, f.e. in explain mode and traces.
+//! The format is:
+//! ```text
+//! // Symbol table
+//! // Scope level 0:
+//! //    Symbol Name       Page Address
+//! //    ----------------  ------------
+//! //    LOOP_START        3003
+//! ```
+use crate::errors::SymbolTableError;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::fs;
+
+/// Bidirectional mapping between labels and addresses.
+///
+/// Several labels may name the same address, the alphabetically first one is used to
+/// describe the address.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    by_name: BTreeMap<String, u16>,
+    by_address: BTreeMap<u16, BTreeSet<String>>,
+}
+
+impl SymbolTable {
+    /// Parses the contents of a `.sym` file.
+    ///
+    /// # Errors
+    /// - [`SymbolTableError::InvalidLine`] if a symbol line has no valid hex address
+    pub fn parse(content: &str) -> Result<Self, SymbolTableError> {
+        let mut res = Self::default();
+        for (idx, line) in content.lines().enumerate() {
+            let Some(entry) = line.trim().strip_prefix("//") else {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                return Err(invalid_line(idx, line));
+            };
+            let parts = entry.split_whitespace().collect::<Vec<_>>();
+            // skips header, scope and separator lines
+            let [name, address] = parts.as_slice() else {
+                continue;
+            };
+            if name.starts_with('-') || *name == "Symbol" {
+                continue;
+            }
+            let address = u16::from_str_radix(address, 16).map_err(|_| invalid_line(idx, line))?;
+            res.insert(name, address);
+        }
+        Ok(res)
+    }
+    /// Reads and parses a `.sym` file.
+    ///
+    /// # Errors
+    /// - [`SymbolTableError::NotLoadable`] if the file cannot be read
+    /// - see [`SymbolTable::parse`]
+    pub fn from_file(path: &str) -> Result<Self, SymbolTableError> {
+        let content = fs::read_to_string(path).map_err(|e| SymbolTableError::NotLoadable {
+            file: path.to_owned(),
+            message: e.to_string(),
+        })?;
+        Self::parse(&content)
+    }
+    /// Adds or moves the symbol `name`, other labels of `address` are kept.
+    pub fn insert(&mut self, name: &str, address: u16) {
+        if let Some(old) = self.by_name.insert(name.to_owned(), address)
+            && let Some(names) = self.by_address.get_mut(&old)
+        {
+            names.remove(name);
+            if names.is_empty() {
+                self.by_address.remove(&old);
+            }
+        }
+        self.by_address
+            .entry(address)
+            .or_default()
+            .insert(name.to_owned());
+    }
+    #[must_use]
+    pub fn address_of(&self, name: &str) -> Option<u16> {
+        self.by_name.get(name).copied()
+    }
+    /// The alphabetically first label of `address`.
+    #[must_use]
+    pub fn name_at(&self, address: u16) -> Option<&str> {
+        self.names_at(address).next()
+    }
+    /// All labels of `address` in alphabetical order.
+    pub fn names_at(&self, address: u16) -> impl Iterator<Item = &str> {
+        self.by_address
+            .get(&address)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+    /// All symbols ordered by address, labels of the same address alphabetically.
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.by_address
+            .iter()
+            .flat_map(|(address, names)| names.iter().map(|name| (*address, name.as_str())))
+    }
+    /// Formats `address` as hex followed by its label if there is one, f.e. `x3003 (LOOP)`.
+    #[must_use]
+    pub fn describe(&self, address: u16) -> String {
+        self.name_at(address).map_or_else(
+            || format!("x{address:04X}"),
+            |name| format!("x{address:04X} ({name})"),
+        )
+    }
+}
+
+impl Display for SymbolTable {
+    /// Writes the table in the format of `lc3as`, which [`SymbolTable::parse`] reads.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "// Symbol table")?;
+        writeln!(f, "// Scope level 0:")?;
+        writeln!(f, "//\tSymbol Name       Page Address")?;
+        writeln!(f, "//\t----------------  ------------")?;
+        let mut symbols = self.by_name.iter().collect::<Vec<_>>();
+        symbols.sort_by_key(|(_, address)| **address);
+        for (name, address) in symbols {
+            writeln!(f, "//\t{name:<16}  {address:04X}")?;
+        }
+        writeln!(f)
+    }
+}
+
+fn invalid_line(idx: usize, line: &str) -> SymbolTableError {
+    SymbolTableError::InvalidLine {
+        line: idx + 1,
+        content: line.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use googletest::prelude::*;
+
+    #[gtest]
+    pub fn test_parse_sym_file() {
+        let symbols = SymbolTable::from_file("examples/times_ten.sym").unwrap();
+        expect_that!(symbols.address_of("LOOP_START"), some(eq(0x3003)));
+        expect_that!(symbols.name_at(0x3009), some(eq("ZERO")));
+        expect_that!(symbols.iter().count(), eq(4));
+        expect_that!(symbols.describe(0x3008), eq("x3008 (LOOP_COUNT)"));
+        expect_that!(symbols.describe(0x3000), eq("x3000"));
+    }
+    #[gtest]
+    pub fn test_display_like_lc3as() {
+        let content = fs::read_to_string("examples/times_ten.sym").unwrap();
+        let symbols = SymbolTable::parse(&content).unwrap();
+        expect_that!(symbols.to_string(), eq(&content));
+    }
+    #[gtest]
+    pub fn test_aliases_of_an_address() {
+        let mut symbols = SymbolTable::default();
+        symbols.insert("START", 0x3000);
+        symbols.insert("MAIN", 0x3000);
+        symbols.insert("LOOP", 0x3000);
+        symbols.insert("LOOP", 0x3003);
+        expect_that!(symbols.name_at(0x3000), some(eq("MAIN")));
+        expect_that!(
+            symbols.names_at(0x3000).collect::<Vec<_>>(),
+            elements_are![eq(&"MAIN"), eq(&"START")]
+        );
+        expect_that!(symbols.address_of("START"), some(eq(0x3000)));
+        expect_that!(symbols.name_at(0x3003), some(eq("LOOP")));
+        expect_that!(symbols.iter().count(), eq(3));
+    }
+    #[gtest]
+    pub fn test_parse_invalid_address() {
+        let res = SymbolTable::parse("// Symbol table\n//\tLOOP  30G3\n");
+        assert_that!(
+            res,
+            err(eq(&SymbolTableError::InvalidLine {
+                line: 2,
+                content: "//\tLOOP  30G3".into()
+            }))
+        );
+    }
+}
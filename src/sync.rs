@@ -0,0 +1,9 @@
+//! Locking of state shared between the emulator and its embedder, which may run on different
+//! threads.
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Locks `mutex` even if a thread panicked while holding it, the shared state stays usable
+/// since it is only updated in single steps.
+pub fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
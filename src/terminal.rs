@@ -1,3 +1,4 @@
+#[cfg(not(target_arch = "wasm32"))]
 use crossterm::{ExecutableCommand, cursor, execute, terminal};
 use std::io;
 use std::io::Write;
@@ -6,6 +7,7 @@ use crate::emulator::stdout_helpers::CrosstermCompatibility;
 
 pub struct RawLock {}
 
+#[cfg(not(target_arch = "wasm32"))]
 impl Drop for RawLock {
     fn drop(&mut self) {
         // terminal stays in raw mode but no means to repair
@@ -21,6 +23,7 @@ pub enum EchoOptions {
     EchoOff,
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 fn handle_set_raw_error(e: &io::Error) {
     eprintln!("Could not set terminal to raw mode: {e}");
 }
@@ -28,6 +31,7 @@ fn handle_set_raw_error(e: &io::Error) {
 /// Set terminal to raw in best-effort mode, only log on failure, since it does not work for
 /// cargo doc tests and disabling does not work because of a
 /// [rust issue](https://github.com/rust-lang/rust/issues/67295).
+#[cfg(not(target_arch = "wasm32"))]
 pub fn set_terminal_raw(mut stdout: impl Write) -> RawLock {
     if let Err(e) =
         terminal::enable_raw_mode().and_then(|()| stdout.execute(terminal::EnableLineWrap))
@@ -37,21 +41,36 @@ pub fn set_terminal_raw(mut stdout: impl Write) -> RawLock {
     RawLock {}
 }
 
+/// There is no terminal on wasm32.
+#[cfg(target_arch = "wasm32")]
+pub fn set_terminal_raw(_stdout: impl Write) -> RawLock {
+    RawLock {}
+}
+
 fn can_query_size_or_position(stdout: &(impl Write + CrosstermCompatibility)) -> bool {
     !(*stdout).will_block_on_size_or_position_queries()
 }
 
 pub fn print(stdout: &mut (impl Write + CrosstermCompatibility), data: &str) -> io::Result<()> {
-    let (_column_count, row_count) = if can_query_size_or_position(stdout) {
-        terminal::size()?
-    } else {
-        (1, 1)
-    };
-    let (_column, mut row) = if can_query_size_or_position(stdout) {
-        cursor::position()?
-    } else {
-        (0, 0)
-    };
+    if !can_query_size_or_position(stdout) {
+        // not a terminal, f.e. a pipe or file, so newlines need no cursor movement
+        stdout.write_all(data.as_bytes())?;
+        return stdout.flush();
+    }
+    print_to_terminal(stdout, data)
+}
+
+/// There is no terminal on wasm32, so the output is written as is.
+#[cfg(target_arch = "wasm32")]
+fn print_to_terminal(stdout: &mut impl Write, data: &str) -> io::Result<()> {
+    stdout.write_all(data.as_bytes())?;
+    stdout.flush()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn print_to_terminal(stdout: &mut impl Write, data: &str) -> io::Result<()> {
+    let (_column_count, row_count) = terminal::size()?;
+    let (_column, mut row) = cursor::position()?;
     for (idx, part) in data.split('\n').enumerate() {
         row += 1;
         if idx > 0 {
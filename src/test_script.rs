@@ -0,0 +1,519 @@
+//! Expect-style tests of a program declared in a TOML script, f.e. for autograders.
+//!
+//! A case declares the keyboard input of a run, limits and what is expected once the program
+//! halted: substrings or regular expressions of the output, the exact output and the values of
+//! registers and memory cells. Cases are declared as `[[case]]`, keys at the top level apply to
+//! all of them and a script without cases is a single case.
+//!
+//! ```toml
+//! max_instructions = 100000
+//!
+//! [[case]]
+//! name = "three times ten"
+//! input = "3"
+//! output_contains = "Result: 30"
+//! output_matches = ['Result: \d+\n']
+//! registers = { R3 = 30, NZP = "P" }
+//! memory = { RESULT = 30, x4000 = "x001E" }
+//! ```
+//!
+//! Keys are `name`, `input`, `max_instructions`, `timeout` in seconds, `output` with the exact
+//! output, `output_contains` and `output_matches` with a string or an array of strings and the
+//! tables `registers` with the keys `R0` to `R7`, `PC` and `NZP` and `memory` with addresses or
+//! labels as keys. Values are numbers or strings like `x3000`, `#-1`, `b101` or a label.
+use crate::assembler;
+use crate::emulator::snapshot::MachineState;
+use crate::emulator::stdout_helpers::VirtualConsole;
+use crate::emulator::trace::condition_name;
+use crate::emulator::{Emulator, StepResult};
+use crate::errors::TestScriptError;
+use crate::golden;
+use crate::hardware::keyboard::ScriptedInputProvider;
+use crate::numbers::twos_complement_to_decimal;
+use regex_lite::Regex;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use toml::{Table, Value};
+
+/// Keys of a case, which at the top level of the script apply to all cases.
+const CASE_KEYS: [&str; 9] = [
+    "name",
+    "input",
+    "max_instructions",
+    "timeout",
+    "output",
+    "output_contains",
+    "output_matches",
+    "registers",
+    "memory",
+];
+
+/// The cases of a test script.
+#[derive(Debug, Clone)]
+pub struct TestScript {
+    cases: Vec<TestCase>,
+}
+
+impl TestScript {
+    /// Reads the script at `path`.
+    ///
+    /// # Errors
+    /// - See [`TestScriptError`]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TestScriptError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| TestScriptError::NotLoadable {
+            file: path.display().to_string(),
+            message: e.to_string(),
+        })?;
+        Self::parse(&text)
+    }
+    /// Parses the script `text`.
+    ///
+    /// # Errors
+    /// - See [`TestScriptError`]
+    pub fn parse(text: &str) -> Result<Self, TestScriptError> {
+        let script: Table = text.parse().map_err(|e: toml::de::Error| {
+            TestScriptError::InvalidToml(e.to_string().trim_end().to_owned())
+        })?;
+        check_keys(&script, true)?;
+        let cases = match script.get("case") {
+            None => vec![TestCase::parse(&script, &script, 1)?],
+            Some(Value::Array(cases)) => cases
+                .iter()
+                .zip(1..)
+                .map(|(case, number)| match case {
+                    Value::Table(case) => TestCase::parse(case, &script, number),
+                    _ => Err(invalid("case", "cases must be declared as [[case]]")),
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err(invalid("case", "cases must be declared as [[case]]")),
+        };
+        Ok(Self { cases })
+    }
+    #[must_use]
+    pub fn cases(&self) -> &[TestCase] {
+        &self.cases
+    }
+    /// Runs all cases one after the other on `emu`, see [`TestCase::run`].
+    pub fn run(&self, emu: &mut Emulator) -> Vec<CaseReport> {
+        self.cases.iter().map(|case| case.run(emu)).collect()
+    }
+}
+
+/// A run of the program with its input, limits and expectations.
+#[derive(Debug, Clone)]
+pub struct TestCase {
+    name: String,
+    input: String,
+    max_instructions: Option<u64>,
+    timeout: Option<Duration>,
+    output: Option<String>,
+    output_contains: Vec<String>,
+    output_matches: Vec<Regex>,
+    registers: Vec<(RegisterName, Word)>,
+    condition: Option<String>,
+    memory: Vec<(Word, Word)>,
+}
+
+impl TestCase {
+    /// Parses `case`, falling back to the keys of `script` for those it does not declare.
+    fn parse(case: &Table, script: &Table, number: usize) -> Result<Self, TestScriptError> {
+        check_keys(case, std::ptr::eq(case, script))?;
+        let get = |key: &str| case.get(key).or_else(|| script.get(key));
+        let mut res = Self {
+            name: get("name")
+                .map(|v| string("name", v))
+                .transpose()?
+                .unwrap_or_else(|| format!("case {number}")),
+            input: get("input")
+                .map(|v| string("input", v))
+                .transpose()?
+                .unwrap_or_default(),
+            max_instructions: get("max_instructions")
+                .map(|v| match v {
+                    Value::Integer(n) => u64::try_from(*n)
+                        .map_err(|_| invalid("max_instructions", "must not be negative")),
+                    v => Err(expected("max_instructions", "an integer", v)),
+                })
+                .transpose()?,
+            timeout: get("timeout").map(timeout).transpose()?,
+            output: get("output").map(|v| string("output", v)).transpose()?,
+            output_contains: get("output_contains")
+                .map(|v| strings("output_contains", v))
+                .transpose()?
+                .unwrap_or_default(),
+            output_matches: Vec::new(),
+            registers: Vec::new(),
+            condition: None,
+            memory: Vec::new(),
+        };
+        if let Some(patterns) = get("output_matches") {
+            for pattern in strings("output_matches", patterns)? {
+                let regex = Regex::new(&pattern)
+                    .map_err(|e| invalid("output_matches", &format!("{pattern}: {e}")))?;
+                res.output_matches.push(regex);
+            }
+        }
+        if let Some(registers) = get("registers") {
+            for (name, value) in table("registers", registers)? {
+                let key = format!("registers.{name}");
+                if name == "NZP" {
+                    let condition = string(&key, value)?;
+                    if !matches!(condition.as_str(), "N" | "Z" | "P") {
+                        return Err(invalid(&key, "must be N, Z or P"));
+                    }
+                    res.condition = Some(condition);
+                } else {
+                    let register = RegisterName::parse(name)
+                        .ok_or_else(|| invalid(&key, "registers are R0 to R7, PC and NZP"))?;
+                    res.registers.push((register, Word::parse(&key, value)?));
+                }
+            }
+        }
+        if let Some(memory) = get("memory") {
+            for (address, value) in table("memory", memory)? {
+                let key = format!("memory.{address}");
+                res.memory
+                    .push((Word::from_text(address), Word::parse(&key, value)?));
+            }
+        }
+        Ok(res)
+    }
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Resets `emu`, runs the program with the input and limits of the case and reports all
+    /// expectations which are not met.
+    ///
+    /// The limits replace the ones of `emu`, a program waiting for more input than declared
+    /// fails.
+    pub fn run(&self, emu: &mut Emulator) -> CaseReport {
+        emu.reset();
+        emu.set_max_instructions(self.max_instructions.unwrap_or(u64::MAX));
+        emu.set_input_provider(ScriptedInputProvider::new(&self.input));
+        let mut console = VirtualConsole(Vec::new());
+        let res = match self.timeout {
+            Some(timeout) => emu.run_for_duration(timeout, &mut console),
+            None => emu.run_budgeted(u64::MAX, &mut console),
+        };
+        let output = String::from_utf8_lossy(&console.0).into_owned();
+        let mut failures = Vec::new();
+        match res {
+            Ok(StepResult::Halted) => {}
+            Ok(StepResult::WaitingForInput) => {
+                failures.push("Program waits for input after all input was read".to_owned());
+            }
+            Ok(StepResult::BudgetExhausted) => failures.push(format!(
+                "Timed out after {:?}",
+                self.timeout.unwrap_or_default()
+            )),
+            Ok(stopped) => failures.push(format!("Program stopped: {stopped:?}")),
+            Err(e) => failures.push(format!("Error: {e}")),
+        }
+        self.check_output(&output, &mut failures);
+        self.check_state(&emu.state(), &mut failures);
+        CaseReport {
+            name: self.name.clone(),
+            output,
+            failures,
+        }
+    }
+    fn check_output(&self, output: &str, failures: &mut Vec<String>) {
+        if let Some(divergence) = self
+            .output
+            .as_ref()
+            .and_then(|expected| golden::first_divergence(expected, output))
+        {
+            failures.push(divergence.to_string().trim_end().to_owned());
+        }
+        for expected in &self.output_contains {
+            if !output.contains(expected.as_str()) {
+                failures.push(format!("Output does not contain {expected:?}"));
+            }
+        }
+        for regex in &self.output_matches {
+            if !regex.is_match(output) {
+                failures.push(format!("Output does not match /{regex}/"));
+            }
+        }
+    }
+    fn check_state(&self, state: &MachineState, failures: &mut Vec<String>) {
+        let mismatch = |expected: &Word, actual: u16| match expected.resolve(state) {
+            Ok(expected) if expected == actual => None,
+            Ok(expected) => Some(format!(
+                "expected {}, got {}",
+                describe(expected),
+                describe(actual)
+            )),
+            Err(e) => Some(e),
+        };
+        for (register, expected) in &self.registers {
+            let actual = match register {
+                RegisterName::General(r) => state.register(*r).as_binary(),
+                RegisterName::Pc => state.pc(),
+            };
+            if let Some(mismatch) = mismatch(expected, actual) {
+                failures.push(format!("{register}: {mismatch}"));
+            }
+        }
+        for (address, expected) in &self.memory {
+            if let Some(mismatch) = address
+                .resolve(state)
+                .map_or_else(Some, |resolved| mismatch(expected, state.memory(resolved)))
+            {
+                failures.push(format!("{address}: {mismatch}"));
+            }
+        }
+        if let Some(expected) = &self.condition {
+            let actual = condition_name(state.registers());
+            if expected != actual {
+                failures.push(format!("NZP: expected {expected}, got {actual}"));
+            }
+        }
+    }
+}
+
+/// Result of running a [`TestCase`].
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseReport {
+    pub name: String,
+    /// Everything the program wrote to the console
+    pub output: String,
+    /// One readable description per expectation which is not met
+    pub failures: Vec<String>,
+}
+
+impl CaseReport {
+    #[must_use]
+    pub const fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RegisterName {
+    General(u8),
+    Pc,
+}
+
+impl RegisterName {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "PC" => Some(Self::Pc),
+            _ => name
+                .strip_prefix('R')
+                .and_then(|r| r.parse().ok())
+                .filter(|&r| r < 8)
+                .map(Self::General),
+        }
+    }
+}
+
+impl Display for RegisterName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::General(r) => write!(f, "R{r}"),
+            Self::Pc => write!(f, "PC"),
+        }
+    }
+}
+
+/// An address or value, resolved when the case is run if it is a label.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Word {
+    Number(u16),
+    Label(String),
+}
+
+impl Word {
+    fn parse(key: &str, value: &Value) -> Result<Self, TestScriptError> {
+        match value {
+            Value::Integer(n) => i32::try_from(*n)
+                .ok()
+                .and_then(|n| assembler::to_word(n).ok())
+                .map(Self::Number)
+                .ok_or_else(|| invalid(key, &format!("{n} does not fit into 16 bits"))),
+            Value::String(text) => Ok(Self::from_text(text)),
+            v => Err(expected(key, "a number or a string", v)),
+        }
+    }
+    fn from_text(text: &str) -> Self {
+        assembler::parse_number(text)
+            .ok()
+            .and_then(|n| assembler::to_word(n).ok())
+            .map_or_else(|| Self::Label(text.to_owned()), Self::Number)
+    }
+    fn resolve(&self, state: &MachineState) -> Result<u16, String> {
+        match self {
+            Self::Number(n) => Ok(*n),
+            Self::Label(label) => state
+                .address_of(label)
+                .ok_or_else(|| format!("unknown label {label}")),
+        }
+    }
+}
+
+impl Display for Word {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(n) => write!(f, "x{n:04X}"),
+            Self::Label(label) => write!(f, "{label}"),
+        }
+    }
+}
+
+/// `value` in hex and signed decimal like `xFFFF (-1)`.
+fn describe(value: u16) -> String {
+    format!("x{value:04X} ({})", twos_complement_to_decimal(value))
+}
+
+fn check_keys(table: &Table, cases_allowed: bool) -> Result<(), TestScriptError> {
+    table
+        .keys()
+        .find(|key| !(CASE_KEYS.contains(&key.as_str()) || cases_allowed && *key == "case"))
+        .map_or(Ok(()), |key| Err(TestScriptError::UnknownKey(key.clone())))
+}
+
+fn string(key: &str, value: &Value) -> Result<String, TestScriptError> {
+    value
+        .as_str()
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| expected(key, "a string", value))
+}
+
+/// A single string or an array of strings.
+fn strings(key: &str, value: &Value) -> Result<Vec<String>, TestScriptError> {
+    match value {
+        Value::Array(values) => values.iter().map(|v| string(key, v)).collect(),
+        v => Ok(vec![string(key, v)?]),
+    }
+}
+
+fn table<'a>(key: &str, value: &'a Value) -> Result<&'a Table, TestScriptError> {
+    value
+        .as_table()
+        .ok_or_else(|| expected(key, "a table", value))
+}
+
+/// Seconds as integer or float.
+fn timeout(value: &Value) -> Result<Duration, TestScriptError> {
+    #[expect(
+        clippy::cast_precision_loss,
+        reason = "Timeouts beyond 2^52 seconds are not expected"
+    )]
+    let seconds = match value {
+        Value::Integer(n) => *n as f64,
+        Value::Float(n) => *n,
+        v => return Err(expected("timeout", "seconds", v)),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|e| invalid("timeout", &e.to_string()))
+}
+
+fn invalid(key: &str, message: &str) -> TestScriptError {
+    TestScriptError::InvalidValue {
+        key: key.to_owned(),
+        message: message.to_owned(),
+    }
+}
+
+fn expected(key: &str, what: &str, value: &Value) -> TestScriptError {
+    invalid(key, &format!("expected {what}, got {}", value.type_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator;
+    use googletest::prelude::*;
+
+    #[gtest]
+    pub fn test_passing_script() {
+        let script = TestScript::parse(
+            r##"
+            max_instructions = 1000
+            registers = { NZP = "Z" }
+
+            [[case]]
+            name = "times ten"
+            registers = { R3 = 30, R2 = "#0", PC = "x3007" }
+            memory = { ZERO = 0, x3008 = 10 }
+            "##,
+        )
+        .unwrap();
+        let mut emu = emulator::from_program("examples/times_ten.obj").unwrap();
+        let reports = script.run(&mut emu);
+        expect_that!(
+            reports,
+            elements_are![matches_pattern!(CaseReport {
+                name: eq("times ten"),
+                output: eq("\nProgram halted\n"),
+                failures: is_empty(),
+            })]
+        );
+    }
+    #[gtest]
+    pub fn test_failing_script() {
+        let script = TestScript::parse(
+            r#"
+            input = "a"
+            output_contains = "b"
+            output_matches = ['^a\n+Program halted', 'x']
+            registers = { R0 = -1, NZP = "N" }
+            memory = { x4000 = "x0001", MISSING = 0 }
+            "#,
+        )
+        .unwrap();
+        let mut emu = emulator::from_program("examples/getc_out.obj").unwrap();
+        let reports = script.run(&mut emu);
+        expect_that!(reports[0].name, eq("case 1"));
+        expect_that!(
+            reports[0].failures,
+            elements_are![
+                eq("Output does not contain \"b\""),
+                eq("Output does not match /x/"),
+                eq("R0: expected xFFFF (-1), got x0061 (97)"),
+                eq("MISSING: unknown label MISSING"),
+                eq("x4000: expected x0001 (1), got x0000 (0)"),
+                eq("NZP: expected N, got Z"),
+            ]
+        );
+        let reports = TestScript::parse("input = 'a'\nmax_instructions = 2")
+            .unwrap()
+            .run(&mut emu);
+        expect_that!(
+            reports[0].failures,
+            elements_are![starts_with("Error: Instruction limit exceeded after 2")]
+        );
+    }
+    #[gtest]
+    pub fn test_invalid_scripts() {
+        expect_that!(
+            TestScript::parse("inptu = \"a\"").unwrap_err(),
+            eq(&TestScriptError::UnknownKey("inptu".into()))
+        );
+        expect_that!(
+            TestScript::parse("registers = { R8 = 1 }")
+                .unwrap_err()
+                .to_string(),
+            eq("Invalid value of 'registers.R8': registers are R0 to R7, PC and NZP")
+        );
+        expect_that!(
+            TestScript::parse("case = 1").unwrap_err().to_string(),
+            eq("Invalid value of 'case': cases must be declared as [[case]]")
+        );
+        expect_that!(
+            TestScript::parse("memory = { x3000 = 65536 }")
+                .unwrap_err()
+                .to_string(),
+            eq("Invalid value of 'memory.x3000': 65536 does not fit into 16 bits")
+        );
+        expect_that!(
+            TestScript::parse("output = 1").unwrap_err().to_string(),
+            eq("Invalid value of 'output': expected a string, got integer")
+        );
+    }
+}